@@ -0,0 +1,345 @@
+//! Annotate games automatically, by searching every position and comparing each move to the engine's choice.
+//!
+//! Moves are judged by how much they lower the player's winning probability, compared to the best move.
+//! Winning probabilities are the mean values of the moves in the search tree, from 0.0 to 1.0.
+
+use std::fmt::{self, Display};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use board_game_traits::{GameResult, Position as PositionTrait};
+use pgn_traits::PgnPosition;
+
+use crate::position::{Move, Position};
+use crate::ptn::{Game, PtnMove};
+use crate::search::time_management::Deadlines;
+use crate::search::{MctsSetting, MonteCarloTree};
+
+/// Visits the played move needs in the search of the previous position, for its score there to be used
+const MIN_PLAYED_MOVE_VISITS: u32 = 100;
+
+/// How long to search each position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchBudget {
+    Nodes(u32),
+    Time(Duration),
+}
+
+/// How good a move was, compared to the engine's best move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Judgement {
+    /// The only move that doesn't lose ground, found by the player
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Judgement {
+    /// The move annotation for the judgement
+    pub fn annotation(self) -> &'static str {
+        match self {
+            Judgement::Good => "!",
+            Judgement::Inaccuracy => "?!",
+            Judgement::Mistake => "?",
+            Judgement::Blunder => "??",
+        }
+    }
+}
+
+impl Display for Judgement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Judgement::Good => "Good move",
+            Judgement::Inaccuracy => "Inaccuracy",
+            Judgement::Mistake => "Mistake",
+            Judgement::Blunder => "Blunder",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The engine's opinion of a single move in a game.
+/// All winning probabilities are for the player who made the move
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveAnalysis<const S: usize> {
+    pub played_move: Move<S>,
+    /// Winning probability after the played move.
+    /// Taken from the same search as `best_score` if the move got enough visits there.
+    /// Otherwise, it comes from a separate search of the next position, and is less comparable to `best_score`
+    pub played_score: f32,
+    pub best_move: Move<S>,
+    /// Winning probability after the best move
+    pub best_score: f32,
+    /// The principal variation, starting with `best_move`
+    pub best_line: Vec<Move<S>>,
+    /// Winning probability after the second best move, if there is one
+    pub second_best_score: Option<f32>,
+    pub judgement: Option<Judgement>,
+}
+
+impl<const S: usize> MoveAnalysis<S> {
+    /// How much winning probability the played move lost, compared to the best move
+    pub fn score_loss(&self) -> f32 {
+        (self.best_score - self.played_score).max(0.0)
+    }
+}
+
+/// Searches every position in a game, and judges the moves by how much winning probability they lose
+#[derive(Clone, Debug)]
+pub struct Annotator<const S: usize> {
+    settings: MctsSetting<S>,
+    budget: SearchBudget,
+    inaccuracy_threshold: f32,
+    mistake_threshold: f32,
+    blunder_threshold: f32,
+    max_line_length: usize,
+}
+
+impl<const S: usize> Annotator<S> {
+    pub fn new(budget: SearchBudget) -> Self {
+        Annotator {
+            settings: MctsSetting::default(),
+            budget,
+            inaccuracy_threshold: 0.05,
+            mistake_threshold: 0.10,
+            blunder_threshold: 0.20,
+            max_line_length: 6,
+        }
+    }
+
+    /// Search with these settings. The arena size is set from the search budget
+    pub fn settings(mut self, settings: MctsSetting<S>) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// The winning probability a move must lose to be an inaccuracy, mistake or blunder.
+    /// A move is also marked as good if every other move would have been a mistake
+    pub fn thresholds(mut self, inaccuracy: f32, mistake: f32, blunder: f32) -> Self {
+        assert!(0.0 < inaccuracy && inaccuracy <= mistake && mistake <= blunder);
+        self.inaccuracy_threshold = inaccuracy;
+        self.mistake_threshold = mistake;
+        self.blunder_threshold = blunder;
+        self
+    }
+
+    /// Show at most this many moves of the best line in comments and variations
+    pub fn max_line_length(mut self, max_line_length: usize) -> Self {
+        self.max_line_length = max_line_length;
+        self
+    }
+
+    /// Analyze every move in the game. `progress` is called after each move is analyzed
+    pub fn analyze_game<F: FnMut(&MoveAnalysis<S>)>(
+        &self,
+        game: &Game<Position<S>>,
+        mut progress: F,
+    ) -> Vec<MoveAnalysis<S>> {
+        let mut position = game.start_position.clone();
+        let mut analysis = vec![];
+        // The best move and its score in the current position, which is searched before the move is played
+        let mut current_search = self.search(&position);
+
+        for PtnMove { mv, .. } in game.moves.iter() {
+            let Some(SearchResult {
+                best_move,
+                best_score,
+                best_line,
+                second_best_score,
+                move_scores,
+            }) = current_search
+            else {
+                // The game has already ended
+                break;
+            };
+            position.do_move(*mv);
+
+            // Prefer the played move's score from the same search as the best move, so that the two are comparable.
+            // If the search barely looked at the move, fall back to searching the next position
+            current_search = self.search(&position);
+            let parent_score = move_scores
+                .iter()
+                .find(|(played_move, _, visits)| {
+                    played_move == mv && *visits >= MIN_PLAYED_MOVE_VISITS
+                })
+                .map(|(_, score, _)| *score);
+            let played_score = match position.game_result() {
+                Some(GameResult::Draw) => 0.5,
+                Some(result) if result == GameResult::win_by(!position.side_to_move()) => 1.0,
+                Some(_) => 0.0,
+                None if *mv == best_move => best_score,
+                None => parent_score.unwrap_or_else(|| {
+                    current_search
+                        .as_ref()
+                        .map_or(0.5, |result| 1.0 - result.best_score)
+                }),
+            };
+
+            let mut move_analysis = MoveAnalysis {
+                played_move: *mv,
+                played_score,
+                best_move,
+                best_score,
+                best_line,
+                second_best_score,
+                judgement: None,
+            };
+            move_analysis.judgement = self.judge(&move_analysis, position.game_result().is_some());
+            progress(&move_analysis);
+            analysis.push(move_analysis);
+        }
+        analysis
+    }
+
+    /// A copy of the game with the analysis added to each move.
+    /// Every analyzed move gets a comment with its winning probability, and the best line if another move was better.
+    /// Inaccuracies, mistakes and blunders also get the best line as a variation.
+    /// Existing comments and variations are kept, but previous `!` and `?` annotations are replaced
+    pub fn annotate_game(
+        &self,
+        game: &Game<Position<S>>,
+        analysis: &[MoveAnalysis<S>],
+    ) -> Game<Position<S>> {
+        let mut annotated_game = game.clone();
+        let mut position = game.start_position.clone();
+
+        for (ptn_move, move_analysis) in annotated_game.moves.iter_mut().zip(analysis) {
+            ptn_move
+                .annotations
+                .retain(|annotation| !annotation.contains(['!', '?']));
+            if let Some(judgement) = move_analysis.judgement {
+                ptn_move
+                    .annotations
+                    .push(judgement.annotation().to_string());
+            }
+
+            let mut comment = format!("{:.1}%", move_analysis.played_score * 100.0);
+            if move_analysis.played_move != move_analysis.best_move {
+                let mut line_position = position.clone();
+                let best_line: Vec<String> = move_analysis
+                    .best_line
+                    .iter()
+                    .take(self.max_line_length)
+                    .map(|mv| {
+                        let move_string = line_position.move_to_san(mv);
+                        line_position.do_move(*mv);
+                        move_string
+                    })
+                    .collect();
+                comment = format!(
+                    "{}, best {} ({:.1}%)",
+                    comment,
+                    best_line.join(" "),
+                    move_analysis.best_score * 100.0
+                );
+            }
+            if let Some(judgement) = move_analysis.judgement {
+                comment = format!("{}. {}", judgement, comment);
+            }
+            ptn_move.comments.push(comment);
+
+            if matches!(
+                move_analysis.judgement,
+                Some(Judgement::Inaccuracy | Judgement::Mistake | Judgement::Blunder)
+            ) {
+                ptn_move.variations.push(
+                    move_analysis
+                        .best_line
+                        .iter()
+                        .take(self.max_line_length)
+                        .map(|mv| PtnMove::new(*mv))
+                        .collect(),
+                );
+            }
+            position.do_move(move_analysis.played_move);
+        }
+        annotated_game
+    }
+
+    fn judge(&self, analysis: &MoveAnalysis<S>, ends_game: bool) -> Option<Judgement> {
+        let score_loss = analysis.score_loss();
+        if analysis.played_move != analysis.best_move {
+            if score_loss >= self.blunder_threshold {
+                Some(Judgement::Blunder)
+            } else if score_loss >= self.mistake_threshold {
+                Some(Judgement::Mistake)
+            } else if score_loss >= self.inaccuracy_threshold {
+                Some(Judgement::Inaccuracy)
+            } else {
+                None
+            }
+        } else if !ends_game
+            && analysis.second_best_score.is_some_and(|second_best_score| {
+                analysis.best_score - second_best_score >= self.mistake_threshold
+            })
+        {
+            Some(Judgement::Good)
+        } else {
+            None
+        }
+    }
+
+    /// Search the position, or return `None` if the game is over
+    fn search(&self, position: &Position<S>) -> Option<SearchResult<S>> {
+        if position.game_result().is_some() {
+            return None;
+        }
+        let tree = match self.budget {
+            SearchBudget::Nodes(nodes) => {
+                let mut tree = MonteCarloTree::new(
+                    position.clone(),
+                    self.settings.clone().arena_size_for_nodes(nodes),
+                );
+                if let Err(err) = tree.search_nodes(nodes.max(2) as u64) {
+                    eprintln!("Warning: {err}");
+                }
+                tree
+            }
+            SearchBudget::Time(time) => {
+                // Give enough memory for roughly 200K nps
+                let max_nodes = (time.as_secs_f64().ceil() as u32).saturating_mul(200_000);
+                let mut tree = MonteCarloTree::new(
+                    position.clone(),
+                    self.settings.clone().arena_size(
+                        max_nodes
+                            .saturating_mul((S * S) as u32 * 3)
+                            .min(2_u32.pow(31)),
+                    ),
+                );
+                tree.search_until_deadlines(
+                    Deadlines::fixed(time),
+                    &AtomicBool::new(false),
+                    |_| {},
+                );
+                tree
+            }
+        };
+        let mut lines = tree.multi_pv(2).into_iter();
+        let best_line = lines.next()?;
+        let second_best_score = lines.next().map(|line| line.winning_probability);
+        let move_scores = tree
+            .shallow_edges()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|edge| Some((edge.mv, edge.winning_probability()?, edge.visits)))
+            .collect();
+        Some(SearchResult {
+            best_move: best_line.mv,
+            best_score: best_line.winning_probability,
+            best_line: best_line.pv,
+            second_best_score,
+            move_scores,
+        })
+    }
+}
+
+/// Results of searching a single position, with scores for the side to move
+struct SearchResult<const S: usize> {
+    best_move: Move<S>,
+    best_score: f32,
+    best_line: Vec<Move<S>>,
+    second_best_score: Option<f32>,
+    /// Every searched move, with its winning probability and visits
+    move_scores: Vec<(Move<S>, f32, u32)>,
+}
@@ -0,0 +1,211 @@
+//! Run the search described by an [`Event`], independently of how the event was received.
+//! Used by the AWS Lambda handler, and by the `serve` HTTP server.
+
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use board_game_traits::{GameResult, Position as EvalPosition};
+use pgn_traits::PgnPosition;
+
+use crate::aws::{Event, Line, MoveStats, Output, TimeControl};
+use crate::evaluation::weights::{Weights, WeightsKind};
+use crate::position::{Komi, Move, Position};
+use crate::search::time_management::{TimeManager, TimePolicy};
+use crate::search::{MctsSetting, MonteCarloTree};
+
+pub type Error = Box<dyn std::error::Error + Sync + Send>;
+
+/// Analyze the event's position, using at most `max_mem_usage` bytes for the search tree.
+/// `info` is called with the intermediate results at regular intervals during the search.
+/// The search returns early if `stop` is set, for example because the caller has gone away
+pub fn analyze<F: Fn(&Output)>(
+    event: Event,
+    max_mem_usage: usize,
+    stop: &AtomicBool,
+    info: F,
+) -> Result<Output, Error> {
+    match event.size {
+        4 => analyze_generic::<4, F>(event, max_mem_usage, stop, info),
+        5 => analyze_generic::<5, F>(event, max_mem_usage, stop, info),
+        6 => analyze_generic::<6, F>(event, max_mem_usage, stop, info),
+        7 => analyze_generic::<7, F>(event, max_mem_usage, stop, info),
+        8 => analyze_generic::<8, F>(event, max_mem_usage, stop, info),
+        s => Err(format!("Unsupported board size {}", s).into()),
+    }
+}
+
+pub fn analyze_generic<const S: usize, F: Fn(&Output)>(
+    e: Event,
+    max_mem_usage: usize,
+    stop: &AtomicBool,
+    info: F,
+) -> Result<Output, Error> {
+    let komi = Komi::try_from(e.komi)?;
+    let eval_komi = match e.eval_komi {
+        Some(komi_f64) => Komi::try_from(komi_f64)?,
+        None => komi,
+    };
+    let mut position = match e.tps {
+        Some(tps) => <Position<S>>::from_fen_with_komi(&tps, komi)?,
+        None => <Position<S>>::start_position_with_komi(komi),
+    };
+    for move_string in e.moves {
+        let mv = parse_legal_move(&position, &move_string)?;
+        position.do_move(mv);
+    }
+    let excluded_moves = e
+        .excluded_moves
+        .iter()
+        .map(|move_string| parse_legal_move(&position, move_string))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match position.game_result() {
+        Some(GameResult::Draw) => {
+            return Ok(Output {
+                score: 0.5,
+                ..Default::default()
+            })
+        }
+        Some(result) if result == GameResult::win_by(position.side_to_move()) => {
+            return Ok(Output {
+                score: 0.0,
+                ..Default::default()
+            })
+        }
+        Some(_) => {
+            return Ok(Output {
+                score: 1.0,
+                ..Default::default()
+            })
+        }
+        None => (),
+    }
+
+    let value_params = match e.value_params {
+        Some(params) => validate_params::<S>(WeightsKind::Value, eval_komi, params)?,
+        None => <Position<S>>::value_params(eval_komi).into(),
+    };
+    let policy_params = match e.policy_params {
+        Some(params) => validate_params::<S>(WeightsKind::Policy, eval_komi, params)?,
+        None => <Position<S>>::policy_params(eval_komi).into(),
+    };
+
+    let mut settings = if let Some(dirichlet) = e.dirichlet_noise {
+        MctsSetting::default().add_dirichlet(dirichlet)
+    } else {
+        MctsSetting::default()
+    }
+    .add_rollout_depth(e.rollout_depth)
+    .add_rollout_temperature(e.rollout_temperature)
+    .mem_usage(max_mem_usage)
+    .add_value_params(value_params)
+    .add_policy_params(policy_params)
+    .exclude_moves(excluded_moves);
+    if let Some(variance) = e.static_eval_variance {
+        settings = settings.add_static_eval_variance(variance);
+    }
+    let multi_pv = e.multi_pv.unwrap_or(1).max(1);
+
+    let start_time = Instant::now();
+    let plies = position.half_moves_played();
+    let mut tree = MonteCarloTree::try_new(position, settings)
+        .map_err(|err| format!("Failed to allocate the search tree: {}", err))?;
+
+    match e.time_control {
+        TimeControl::Time(_, _) => {
+            let deadlines = TimeManager::new(TimePolicy::Fraction {
+                time_divisor: 40,
+                increment_divisor: 3,
+            })
+            .opening_plies(4)
+            .max_move_time(Duration::from_secs(40))
+            .deadlines::<S>(&e.time_control, plies)
+            .unwrap();
+
+            tree.search_until_deadlines(deadlines, stop, |tree| {
+                info(&output(tree, start_time, multi_pv))
+            });
+        }
+        TimeControl::FixedNodes(nodes) => {
+            // Report progress at exponentially increasing intervals, but check for stops regularly
+            let mut nodes_left = nodes;
+            let mut batch_size = 1000;
+            while nodes_left > 0 && !stop.load(Ordering::Relaxed) {
+                let batch_nodes = nodes_left.min(batch_size);
+                if let Err(err) = tree.search_nodes(batch_nodes) {
+                    eprintln!("Warning: {err}");
+                    break;
+                }
+                nodes_left -= batch_nodes;
+                if nodes_left > 0 {
+                    info(&output(&tree, start_time, multi_pv));
+                }
+                batch_size = (batch_size * 2).min(100_000);
+            }
+        }
+    }
+    Ok(output(&tree, start_time, multi_pv))
+}
+
+fn parse_legal_move<const S: usize>(
+    position: &Position<S>,
+    move_string: &str,
+) -> Result<Move<S>, Error> {
+    let mv = position.move_from_san(move_string)?;
+    let mut legal_moves = vec![];
+    position.generate_moves(&mut legal_moves);
+    if !legal_moves.contains(&mv) {
+        return Err(format!("Illegal {}s move {}", S, move_string).into());
+    }
+    Ok(mv)
+}
+
+/// Validate parameters sent with an event
+fn validate_params<const S: usize>(
+    kind: WeightsKind,
+    komi: Komi,
+    params: Vec<f32>,
+) -> Result<Arc<[f32]>, Error> {
+    let weights = Weights::new(kind, S, komi, params);
+    weights.validate_as::<S>(kind)?;
+    Ok(weights.params.into())
+}
+
+/// The search result so far
+fn output<const S: usize>(
+    tree: &MonteCarloTree<S>,
+    start_time: Instant,
+    multi_pv: usize,
+) -> Output {
+    let mut edges = tree.shallow_edges().unwrap_or_default();
+    edges.sort_by_key(|edge| edge.visits);
+    edges.reverse();
+
+    Output {
+        pv: tree.pv().map(|mv| mv.to_string()).collect(),
+        score: tree.best_move().map_or(0.5, |(_, score)| 1.0 - score),
+        nodes: tree.visits(),
+        mem_usage: tree.mem_usage() as u64,
+        time_taken: start_time.elapsed(),
+        moves: edges
+            .iter()
+            .map(|edge| MoveStats {
+                mv: edge.mv.to_string(),
+                visits: edge.visits,
+                score: edge.winning_probability().map(|score| 1.0 - score),
+                policy: edge.policy(),
+            })
+            .collect(),
+        lines: tree
+            .multi_pv(multi_pv)
+            .into_iter()
+            .map(|line| Line {
+                pv: line.pv.iter().map(|mv| mv.to_string()).collect(),
+                score: 1.0 - line.winning_probability,
+                visits: line.visits,
+            })
+            .collect(),
+    }
+}
@@ -5,6 +5,7 @@ use serde::Serialize;
 
 use crate::search::TimeControl;
 
+pub mod analysis;
 #[cfg(feature = "aws-lambda-runtime")]
 pub mod server;
 
@@ -15,10 +16,24 @@ pub struct Event {
     pub moves: Vec<String>,
     pub time_control: TimeControl,
     pub komi: f64, // "Main" komi setting, used to determine the game result at terminal nodes
-    pub eval_komi: Option<f64>, // Komi used for heuristic evaluation. Default to the main komi, but not all komis are supported
+    pub eval_komi: Option<f64>, // Komi used for heuristic evaluation. Defaults to the main komi
     pub dirichlet_noise: Option<f32>,
     pub rollout_depth: u16,
     pub rollout_temperature: f64,
+    /// Moves that will not be searched, in PTN notation
+    #[serde(default)]
+    pub excluded_moves: Vec<String>,
+    /// Number of principal variations to return in `Output::lines`. Defaults to 1
+    #[serde(default)]
+    pub multi_pv: Option<usize>,
+    #[serde(default)]
+    pub static_eval_variance: Option<f32>,
+    /// Value parameters to use instead of the built-in parameters for the board size and eval komi
+    #[serde(default)]
+    pub value_params: Option<Vec<f32>>,
+    /// Policy parameters to use instead of the built-in parameters for the board size and eval komi
+    #[serde(default)]
+    pub policy_params: Option<Vec<f32>>,
 }
 
 #[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
@@ -28,4 +43,31 @@ pub struct Output {
     pub nodes: u32,
     pub mem_usage: u64,
     pub time_taken: time::Duration,
+    /// Statistics for every legal move in the position, most visited first
+    #[serde(default)]
+    pub moves: Vec<MoveStats>,
+    /// The best lines, up to `Event::multi_pv` of them
+    #[serde(default)]
+    pub lines: Vec<Line>,
+}
+
+/// Search statistics for a move in the analyzed position.
+/// Scores are from the same perspective as `Output::score`
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct MoveStats {
+    pub mv: String,
+    pub visits: u32,
+    /// `None` if the move was not searched
+    pub score: Option<f32>,
+    /// The policy's prior probability for the move
+    pub policy: f32,
+}
+
+/// A principal variation, starting with one of the moves in the analyzed position.
+/// Scores are from the same perspective as `Output::score`
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Line {
+    pub pv: Vec<String>,
+    pub score: f32,
+    pub visits: u32,
 }
@@ -0,0 +1,226 @@
+//! Annotate the games in a PTN file, marking inaccuracies, mistakes and blunders, and commenting each move with the engine's evaluation.
+//!
+//! The board size is read from the first `Size` tag in the file, unless `--size` is given.
+//! The annotated games are written as PTN to `--output`, or to stdout.
+
+use std::fs;
+use std::io::{self, Write};
+use std::process::exit;
+use std::str::FromStr;
+use std::time::Duration;
+
+use board_game_traits::{Color, Position as PositionTrait};
+use clap::{Arg, ArgMatches, Command};
+
+use tiltak::annotate::{Annotator, Judgement, SearchBudget};
+use tiltak::evaluation::weights::LoadedWeights;
+use tiltak::position::{Komi, Position};
+use tiltak::ptn::ptn_parser;
+use tiltak::search::MctsSetting;
+
+fn main() {
+    let matches = Command::new("Tiltak game annotator")
+        .version("0.1")
+        .author("Morten Lohne")
+        .arg(
+            Arg::new("input")
+                .help("PTN file with the games to annotate")
+                .required(true)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .help("Write the annotated games to this file, instead of to stdout")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("size")
+                .short('s')
+                .long("size")
+                .help("Board size. Defaults to the size in the PTN's Size tag")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64).range(4..=8)),
+        )
+        .arg(
+            Arg::new("nodes")
+                .long("nodes")
+                .help("Number of MCTS nodes to search in each position")
+                .default_value("1000000")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u32).range(2..)),
+        )
+        .arg(
+            Arg::new("time")
+                .long("time")
+                .help("Search each position for this many seconds, instead of a fixed number of nodes")
+                .num_args(1)
+                .conflicts_with("nodes")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            Arg::new("thresholds")
+                .long("thresholds")
+                .help("How many percentage points of winning probability a move must lose to be an inaccuracy, mistake or blunder")
+                .default_value("5,10,20")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("value-weights")
+                .long("value-weights")
+                .help("Search with the value parameters in this weights file, instead of the built-in parameters")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("policy-weights")
+                .long("policy-weights")
+                .help("Search with the policy parameters in this weights file, instead of the built-in parameters")
+                .num_args(1),
+        )
+        .get_matches();
+
+    if let Err(err) = run(&matches) {
+        eprintln!("Error: {}", err);
+        exit(1)
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let input = fs::read_to_string(matches.get_one::<String>("input").unwrap())?;
+    let size = match matches.get_one::<u64>("size") {
+        Some(size) => *size as usize,
+        None => size_from_tags(&input).ok_or("No Size tag in the PTN, use --size")?,
+    };
+    match size {
+        4 => annotate_games::<4>(matches, &input),
+        5 => annotate_games::<5>(matches, &input),
+        6 => annotate_games::<6>(matches, &input),
+        7 => annotate_games::<7>(matches, &input),
+        8 => annotate_games::<8>(matches, &input),
+        _ => Err(format!("Unsupported size {}", size).into()),
+    }
+}
+
+fn size_from_tags(input: &str) -> Option<usize> {
+    input.lines().find_map(|line| {
+        line.trim()
+            .strip_prefix("[Size \"")?
+            .strip_suffix("\"]")?
+            .parse()
+            .ok()
+    })
+}
+
+fn annotate_games<const S: usize>(
+    matches: &ArgMatches,
+    input: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let budget = match matches.get_one::<f64>("time") {
+        Some(seconds) => SearchBudget::Time(Duration::from_secs_f64(*seconds)),
+        None => SearchBudget::Nodes(*matches.get_one::<u32>("nodes").unwrap()),
+    };
+    let thresholds = matches
+        .get_one::<String>("thresholds")
+        .unwrap()
+        .split(',')
+        .map(|threshold| f32::from_str(threshold.trim()).map(|percent| percent / 100.0))
+        .collect::<Result<Vec<_>, _>>()?;
+    let [inaccuracy, mistake, blunder] = thresholds[..] else {
+        return Err("--thresholds must be three comma-separated percentages".into());
+    };
+    if !(0.0 < inaccuracy && inaccuracy <= mistake && mistake <= blunder) {
+        return Err("--thresholds must be positive and increasing".into());
+    }
+    let weights = LoadedWeights::load(
+        matches
+            .get_one::<String>("value-weights")
+            .map(String::as_str),
+        matches
+            .get_one::<String>("policy-weights")
+            .map(String::as_str),
+    )?;
+
+    let (games, errors) = ptn_parser::parse_ptn_lenient::<Position<S>>(input);
+    for err in errors {
+        eprintln!("Warning: Skipped game: {}", err);
+    }
+
+    let mut output: Box<dyn Write> = match matches.get_one::<String>("output") {
+        Some(path) => Box::new(io::BufWriter::new(fs::File::create(path)?)),
+        None => Box::new(io::stdout()),
+    };
+
+    for (i, game) in games.iter().enumerate() {
+        // Search with the game's komi, but write the game with its original start position,
+        // so that the komi doesn't show up as a custom start position
+        let mut komi_game = game.clone();
+        if let Some((_, komi_str)) = game
+            .tags
+            .iter()
+            .find(|(tag, _)| tag.eq_ignore_ascii_case("Komi"))
+        {
+            match Komi::from_str(komi_str) {
+                Ok(komi) => komi_game.start_position.set_komi(komi),
+                Err(err) => {
+                    eprintln!("Warning: Skipped game {}: Bad Komi tag: {}", i + 1, err);
+                    continue;
+                }
+            }
+        }
+        let settings = match MctsSetting::default()
+            .add_loaded_weights(&weights, komi_game.start_position.komi())
+        {
+            Ok(settings) => settings,
+            Err(err) => {
+                eprintln!("Warning: Skipped game {}: {}", i + 1, err);
+                continue;
+            }
+        };
+        let annotator = Annotator::<S>::new(budget)
+            .settings(settings)
+            .thresholds(inaccuracy, mistake, blunder);
+
+        // Count inaccuracies, mistakes and blunders for each player
+        let mut judgements = [[0; 3]; 2];
+        let mut ply = (game.start_position.side_to_move() == Color::Black) as usize;
+        let analysis = annotator.analyze_game(&komi_game, |move_analysis| {
+            let judgement_index = match move_analysis.judgement {
+                Some(Judgement::Inaccuracy) => Some(0),
+                Some(Judgement::Mistake) => Some(1),
+                Some(Judgement::Blunder) => Some(2),
+                Some(Judgement::Good) | None => None,
+            };
+            if let Some(index) = judgement_index {
+                judgements[ply % 2][index] += 1;
+            }
+            ply += 1;
+            eprint!(".");
+        });
+        eprintln!();
+
+        for (player, [inaccuracies, mistakes, blunders]) in
+            ["Player1", "Player2"].iter().zip(judgements)
+        {
+            let name = game
+                .tags
+                .iter()
+                .find(|(tag, _)| tag.eq_ignore_ascii_case(player))
+                .map_or(*player, |(_, name)| name.as_str());
+            eprintln!(
+                "Game {}, {}: {} inaccuracies, {} mistakes, {} blunders",
+                i + 1,
+                name,
+                inaccuracies,
+                mistakes,
+                blunders
+            );
+        }
+
+        annotator
+            .annotate_game(game, &analysis)
+            .game_to_ptn(&mut output)?;
+        output.flush()?;
+    }
+    Ok(())
+}
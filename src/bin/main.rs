@@ -4,7 +4,8 @@ use std::io::{Read, Write};
 use std::str::FromStr;
 #[cfg(feature = "constant-tuning")]
 use std::sync::atomic::{self, AtomicU64};
-use std::{fs, io, time};
+use std::sync::Arc;
+use std::{env, fs, io, time};
 
 use board_game_traits::Position as PositionTrait;
 use board_game_traits::{Color, GameResult};
@@ -13,9 +14,11 @@ use pgn_traits::PgnPosition;
 #[cfg(feature = "constant-tuning")]
 use rayon::prelude::*;
 
+use tiltak::book::OpeningBook;
 use tiltak::evaluation::parameters::{
     self, IncrementalPolicy, PolicyIndexes, Value, ValueApplier, ValueIndexes,
 };
+use tiltak::evaluation::weights::{LoadedWeights, WeightsError};
 #[cfg(feature = "sqlite")]
 use tiltak::policy_sqlite;
 use tiltak::position::Role;
@@ -24,6 +27,7 @@ use tiltak::position::{
 };
 use tiltak::position::{Position, Stack};
 use tiltak::ptn::{Game, PtnMove};
+use tiltak::search::solver::{Proof, Solver};
 use tiltak::search::{cp_to_win_percentage, MctsSetting};
 use tiltak::{minmax, ptn};
 use tiltak::{position, search};
@@ -32,14 +36,33 @@ use tiltak::{position, search};
 mod tests;
 
 fn main() {
+    let args: Vec<String> = env::args().collect();
+    let arg_value = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|i| args.get(i + 1))
+            .map(String::as_str)
+    };
+    let weights =
+        match LoadedWeights::load(arg_value("--value-weights"), arg_value("--policy-weights")) {
+            Ok(weights) => weights,
+            Err(err) => {
+                println!("Failed to load weights: {}", err);
+                return;
+            }
+        };
+
     println!("play: Play against the engine through the command line");
     println!("aimatch: Watch the engine play against a very simple minmax implementation");
-    println!("analyze <size>: Analyze a given position, provided from a PTN or a simple move list");
-    println!("tps <size>: Analyze a given position, provided from a tps string");
+    println!("analyze <size> [komi] [lines]: Analyze a given position, provided from a PTN or a simple move list");
+    println!("tps <size> [komi] [lines]: Analyze a given position, provided from a tps string");
     println!("game <size>: Analyze a whole game, provided from a PTN or a simple move list");
     println!(
         "perft <size>: Generate perft numbers of a given position, provided from a tps string"
     );
+    println!("export_tree <size> [komi] [nodes] [min visits] [max depth]: Search a position from a tps string, and write the search tree to tree.json and tree.dot");
+    println!("solve <size> [komi] [plies]: Search for a forced road win or loss in a position from a tps string");
+    println!("build_book <size> <komi> <plies> <book file> <input files...>: Build an opening book from .ptn game files and analyze_openings output files");
     #[cfg(feature = "sqlite")]
     println!("test_policy: Test how well policy scores find immediate wins in real games");
     loop {
@@ -56,6 +79,9 @@ fn main() {
             .get(2)
             .map(|komi_str| Komi::from_str(komi_str).unwrap())
             .unwrap_or_default();
+        let lines = words
+            .get(3)
+            .map(|lines_str| usize::from_str(lines_str).unwrap());
         match words[0] {
             "play" => {
                 let position = Position::default();
@@ -67,22 +93,22 @@ fn main() {
                 }
             }
             "analyze" => match words.get(1) {
-                Some(&"4") => analyze_position_from_ptn::<4>(komi),
-                Some(&"5") => analyze_position_from_ptn::<5>(komi),
-                Some(&"6") => analyze_position_from_ptn::<6>(komi),
-                Some(&"7") => analyze_position_from_ptn::<7>(komi),
-                Some(&"8") => analyze_position_from_ptn::<8>(komi),
+                Some(&"4") => analyze_position_from_ptn::<4>(komi, lines, &weights),
+                Some(&"5") => analyze_position_from_ptn::<5>(komi, lines, &weights),
+                Some(&"6") => analyze_position_from_ptn::<6>(komi, lines, &weights),
+                Some(&"7") => analyze_position_from_ptn::<7>(komi, lines, &weights),
+                Some(&"8") => analyze_position_from_ptn::<8>(komi, lines, &weights),
                 Some(s) => println!("Unsupported size {}", s),
-                None => analyze_position_from_ptn::<5>(komi),
+                None => analyze_position_from_ptn::<5>(komi, lines, &weights),
             },
             "tps" => match words.get(1) {
-                Some(&"4") => analyze_position_from_tps::<4>(komi),
-                Some(&"5") => analyze_position_from_tps::<5>(komi),
-                Some(&"6") => analyze_position_from_tps::<6>(komi),
-                Some(&"7") => analyze_position_from_tps::<7>(komi),
-                Some(&"8") => analyze_position_from_tps::<8>(komi),
+                Some(&"4") => analyze_position_from_tps::<4>(komi, lines, &weights),
+                Some(&"5") => analyze_position_from_tps::<5>(komi, lines, &weights),
+                Some(&"6") => analyze_position_from_tps::<6>(komi, lines, &weights),
+                Some(&"7") => analyze_position_from_tps::<7>(komi, lines, &weights),
+                Some(&"8") => analyze_position_from_tps::<8>(komi, lines, &weights),
                 Some(s) => println!("Unsupported size {}", s),
-                None => analyze_position_from_tps::<5>(komi),
+                None => analyze_position_from_tps::<5>(komi, lines, &weights),
             },
             "perft" => match words.get(1) {
                 Some(&"3") => perft_from_tps::<3>(),
@@ -94,6 +120,54 @@ fn main() {
                 Some(s) => println!("Unsupported size {}", s),
                 None => perft_from_tps::<5>(),
             },
+            "export_tree" => {
+                let nodes = words.get(3).map_or(100_000, |s| s.parse().unwrap());
+                let min_visits = words.get(4).map_or(100, |s| s.parse().unwrap());
+                let max_depth = words.get(5).map_or(4, |s| s.parse().unwrap());
+                match words.get(1) {
+                    Some(&"4") => export_tree::<4>(komi, nodes, min_visits, max_depth, &weights),
+                    Some(&"5") => export_tree::<5>(komi, nodes, min_visits, max_depth, &weights),
+                    Some(&"6") => export_tree::<6>(komi, nodes, min_visits, max_depth, &weights),
+                    Some(&"7") => export_tree::<7>(komi, nodes, min_visits, max_depth, &weights),
+                    Some(&"8") => export_tree::<8>(komi, nodes, min_visits, max_depth, &weights),
+                    Some(s) => println!("Unsupported size {}", s),
+                    None => export_tree::<5>(komi, nodes, min_visits, max_depth, &weights),
+                }
+            }
+            "solve" => {
+                let plies = words.get(3).map_or(5, |s| s.parse().unwrap());
+                match words.get(1) {
+                    Some(&"4") => solve_from_tps::<4>(komi, plies),
+                    Some(&"5") => solve_from_tps::<5>(komi, plies),
+                    Some(&"6") => solve_from_tps::<6>(komi, plies),
+                    Some(&"7") => solve_from_tps::<7>(komi, plies),
+                    Some(&"8") => solve_from_tps::<8>(komi, plies),
+                    Some(s) => println!("Unsupported size {}", s),
+                    None => solve_from_tps::<5>(komi, plies),
+                }
+            }
+            "build_book" => {
+                if words.len() < 6 {
+                    println!("Error: format is 'build_book <size> <komi> <plies> <book file> <input files...>'");
+                    continue;
+                }
+                let plies = words[3].parse().unwrap();
+                let (output, inputs) = (words[4], &words[5..]);
+                let result = match words[1] {
+                    "4" => build_book::<4>(komi, plies, output, inputs),
+                    "5" => build_book::<5>(komi, plies, output, inputs),
+                    "6" => build_book::<6>(komi, plies, output, inputs),
+                    "7" => build_book::<7>(komi, plies, output, inputs),
+                    "8" => build_book::<8>(komi, plies, output, inputs),
+                    s => {
+                        println!("Unsupported size {}", s);
+                        continue;
+                    }
+                };
+                if let Err(err) = result {
+                    println!("Failed to build opening book: {}", err);
+                }
+            }
             #[cfg(feature = "constant-tuning")]
             "openings" => {
                 let depth = 4;
@@ -118,7 +192,7 @@ fn main() {
                         }
                         let result = (opening, search::mcts(position, 100_000));
                         let total = evaled.fetch_add(1, atomic::Ordering::Relaxed);
-                        if total % 1000 == 0 {
+                        if total.is_multiple_of(1000) {
                             eprintln!(
                                 "Evaluted {} openings in {}s",
                                 total,
@@ -154,7 +228,7 @@ fn main() {
                 match words.get(1) {
                     Some(&"6") => {
                         io::stdin().read_to_string(&mut input).unwrap();
-                        let games = tiltak::ptn::ptn_parser::parse_ptn(&input).unwrap();
+                        let games = parse_games_lenient(&input);
                         if games.is_empty() {
                             continue;
                         }
@@ -164,7 +238,7 @@ fn main() {
                     }
                     None | Some(&"5") => {
                         io::stdin().read_to_string(&mut input).unwrap();
-                        let games = tiltak::ptn::ptn_parser::parse_ptn(&input).unwrap();
+                        let games = parse_games_lenient(&input);
                         if games.is_empty() {
                             println!("Couldn't parse any games");
                             continue;
@@ -190,9 +264,11 @@ fn main() {
                 }
                 let komi = Komi::from_str(words[2]).unwrap();
                 match words[1] {
-                    "4" => value_params::<4>(komi),
-                    "5" => value_params::<5>(komi),
-                    "6" => value_params::<6>(komi),
+                    "4" => value_params::<4>(komi, &weights),
+                    "5" => value_params::<5>(komi, &weights),
+                    "6" => value_params::<6>(komi, &weights),
+                    "7" => value_params::<7>(komi, &weights),
+                    "8" => value_params::<8>(komi, &weights),
                     s => panic!("Unsupported size {}", s),
                 }
             }
@@ -203,9 +279,11 @@ fn main() {
                 }
                 let komi = Komi::from_str(words[2]).unwrap();
                 match words[1] {
-                    "4" => value_features::<4>(Position::start_position_with_komi(komi)),
-                    "5" => value_features::<5>(Position::start_position_with_komi(komi)),
-                    "6" => value_features::<6>(Position::start_position_with_komi(komi)),
+                    "4" => value_features::<4>(Position::start_position_with_komi(komi), &weights),
+                    "5" => value_features::<5>(Position::start_position_with_komi(komi), &weights),
+                    "6" => value_features::<6>(Position::start_position_with_komi(komi), &weights),
+                    "7" => value_features::<7>(Position::start_position_with_komi(komi), &weights),
+                    "8" => value_features::<8>(Position::start_position_with_komi(komi), &weights),
                     s => panic!("Unsupported size {}", s),
                 }
             }
@@ -216,9 +294,11 @@ fn main() {
                 }
                 let komi = Komi::from_str(words[2]).unwrap();
                 match words[1] {
-                    "4" => policy_params::<4>(komi),
-                    "5" => policy_params::<5>(komi),
-                    "6" => policy_params::<6>(komi),
+                    "4" => policy_params::<4>(komi, &weights),
+                    "5" => policy_params::<5>(komi, &weights),
+                    "6" => policy_params::<6>(komi, &weights),
+                    "7" => policy_params::<7>(komi, &weights),
+                    "8" => policy_params::<8>(komi, &weights),
                     s => panic!("Unsupported size {}", s),
                 }
             }
@@ -227,15 +307,40 @@ fn main() {
     }
 }
 
-fn value_features<const S: usize>(position: Position<S>) {
+/// The value parameters loaded from a weights file, or the built-in parameters if none were loaded for this size.
+/// Fails if the loaded weights are for a different komi
+fn eval_value_params<const S: usize>(
+    komi: Komi,
+    weights: &LoadedWeights,
+) -> Result<Arc<[f32]>, WeightsError> {
+    weights.check_komi(S, komi)?;
+    Ok(weights
+        .value_params::<S>()
+        .unwrap_or_else(|| <Position<S>>::value_params(komi).into()))
+}
+
+/// The policy parameters loaded from a weights file, or the built-in parameters if none were loaded for this size.
+/// Fails if the loaded weights are for a different komi
+fn eval_policy_params<const S: usize>(
+    komi: Komi,
+    weights: &LoadedWeights,
+) -> Result<Arc<[f32]>, WeightsError> {
+    weights.check_komi(S, komi)?;
+    Ok(weights
+        .policy_params::<S>()
+        .unwrap_or_else(|| <Position<S>>::policy_params(komi).into()))
+}
+
+fn value_features<const S: usize>(position: Position<S>, weights: &LoadedWeights) {
     let indexes: ValueIndexes<S> = parameters::value_indexes();
     let indexes_string = format!("{:?}", indexes);
 
-    let params = match S {
-        4 => parameters::value_features_4s(position.komi()).as_slice(),
-        5 => parameters::value_features_5s(position.komi()).as_slice(),
-        6 => parameters::value_features_6s(position.komi()).as_slice(),
-        _ => panic!("Unsupported size {}", S),
+    let params = match eval_value_params::<S>(position.komi(), weights) {
+        Ok(params) => params,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
     };
 
     let black_start_index = params.len() / 2;
@@ -304,15 +409,16 @@ fn value_features<const S: usize>(position: Position<S>) {
     }
 }
 
-fn value_params<const S: usize>(komi: Komi) {
+fn value_params<const S: usize>(komi: Komi, weights: &LoadedWeights) {
     let indexes: ValueIndexes<S> = parameters::value_indexes();
     let indexes_string = format!("{:?}", indexes);
 
-    let params = match S {
-        4 => parameters::value_features_4s(komi).as_slice(),
-        5 => parameters::value_features_5s(komi).as_slice(),
-        6 => parameters::value_features_6s(komi).as_slice(),
-        _ => panic!("Unsupported size {}", S),
+    let params = match eval_value_params::<S>(komi, weights) {
+        Ok(params) => params,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
     };
     let black_start_index = params.len() / 2;
 
@@ -342,15 +448,16 @@ fn value_params<const S: usize>(komi: Komi) {
     }
 }
 
-fn policy_params<const S: usize>(komi: Komi) {
+fn policy_params<const S: usize>(komi: Komi, weights: &LoadedWeights) {
     let indexes: PolicyIndexes<S> = parameters::policy_indexes();
     let indexes_string = format!("{:?}", indexes);
 
-    let params = match S {
-        4 => parameters::policy_features_4s(komi).as_slice(),
-        5 => parameters::policy_features_5s(komi).as_slice(),
-        6 => parameters::policy_features_6s(komi).as_slice(),
-        _ => panic!("Unsupported size {}", S),
+    let params = match eval_policy_params::<S>(komi, weights) {
+        Ok(params) => params,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
     };
 
     println!("\nPolicy features:\n");
@@ -694,7 +801,20 @@ fn mcts_vs_minmax(minmax_depth: u16, mcts_nodes: u64) {
     println!("\n{:?}\nResult: {:?}", position, position.game_result());
 }
 
-fn analyze_position_from_ptn<const S: usize>(komi: Komi) {
+/// Parse the games in the input, printing the errors in any games that couldn't be parsed
+fn parse_games_lenient<const S: usize>(input: &str) -> Vec<Game<Position<S>>> {
+    let (games, errors) = tiltak::ptn::ptn_parser::parse_ptn_lenient(input);
+    for err in errors {
+        println!("Skipped game: {}", err);
+    }
+    games
+}
+
+fn analyze_position_from_ptn<const S: usize>(
+    komi: Komi,
+    lines: Option<usize>,
+    weights: &LoadedWeights,
+) {
     println!("Enter move list or a full PTN, then press enter followed by CTRL+D");
 
     let mut input = String::new();
@@ -711,36 +831,45 @@ fn analyze_position_from_ptn<const S: usize>(komi: Komi) {
     for PtnMove { mv, .. } in games[0].moves.clone() {
         position.do_move(mv);
     }
-    analyze_position(&position)
+    analyze_position(&position, lines, weights)
 }
 
-fn analyze_position_from_tps<const S: usize>(komi: Komi) {
+fn analyze_position_from_tps<const S: usize>(
+    komi: Komi,
+    lines: Option<usize>,
+    weights: &LoadedWeights,
+) {
     println!("Enter TPS");
     let mut input = String::new();
     io::stdin().read_line(&mut input).unwrap();
     let position = <Position<S>>::from_fen_with_komi(&input, komi).unwrap();
-    analyze_position(&position)
+    analyze_position(&position, lines, weights)
 }
 
-fn analyze_position<const S: usize>(position: &Position<S>) {
+/// Analyze a position indefinitely. If `lines` is set, show that many of the best moves with their own principal variations
+fn analyze_position<const S: usize>(
+    position: &Position<S>,
+    lines: Option<usize>,
+    weights: &LoadedWeights,
+) {
+    let (value_params, policy_params) = match eval_value_params::<S>(position.komi(), weights)
+        .and_then(|value_params| {
+            Ok((
+                value_params,
+                eval_policy_params::<S>(position.komi(), weights)?,
+            ))
+        }) {
+        Ok(params) => params,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+
     println!("TPS {}", position.to_fen());
     println!("{:?}", position);
     println!("Komi: {}", position.komi());
 
-    // Change which sets of eval parameters to use in search
-    // Eval komi other than 0 or 2 will crash, since parameters are only trained for those two komi
-    // The raw search still uses the "real" komi to determine the game result at terminal nodes
-    let eval_komi = Komi::from_half_komi(match position.komi().half_komi() {
-        -1 => 0,
-        0 => 0,
-        1 => 0,
-        3 => 4,
-        4 => 4,
-        5 => 4,
-        _ => panic!("No static eval params for komi {}", position.komi()),
-    })
-    .unwrap();
-
     assert_eq!(position.game_result(), None, "Cannot analyze finished game");
 
     let mut simple_moves = vec![];
@@ -752,15 +881,15 @@ fn analyze_position<const S: usize>(position: &Position<S>) {
         &mut simple_moves,
         &mut moves,
         &mut fcd_per_move,
-        <Position<S>>::policy_params(eval_komi),
+        &policy_params,
         &mut vec![],
     );
     moves.sort_by(|(_mv, score1), (_, score2)| score1.partial_cmp(score2).unwrap().reverse());
 
     let settings: MctsSetting<S> = search::MctsSetting::default()
         .arena_size(2_u32.pow(30) * 3)
-        .add_policy_params(<Position<S>>::policy_params(eval_komi))
-        .add_value_params(<Position<S>>::value_params(eval_komi))
+        .add_policy_params(policy_params)
+        .add_value_params(value_params.clone())
         // .add_rollout_depth(1000)
         .exclude_moves(vec![]);
     let start_time = time::Instant::now();
@@ -772,8 +901,7 @@ fn analyze_position<const S: usize>(position: &Position<S>) {
             return;
         };
         if i % 100_000 == 0 {
-            let static_eval = position
-                .static_eval_with_params(<Position<S>>::value_params(eval_komi))
+            let static_eval = position.static_eval_with_params(&value_params)
                 * position.side_to_move().multiplier() as f32;
             println!(
                 "{} visits, eval: {:.2}%, Wilem-style eval: {:+.2}, static eval: {:.4}, static winning probability: {:.2}%, {:.2}s",
@@ -784,7 +912,10 @@ fn analyze_position<const S: usize>(position: &Position<S>) {
                 search::cp_to_win_percentage(static_eval) * 100.0,
                 start_time.elapsed().as_secs_f64()
             );
-            tree.print_info();
+            match lines {
+                Some(num_lines) => print_multi_pv(&tree, num_lines),
+                None => tree.print_info(),
+            }
             if let Some((mv, value)) = tree.best_move() {
                 println!("Best move: ({}, {})", mv, value);
             }
@@ -792,6 +923,146 @@ fn analyze_position<const S: usize>(position: &Position<S>) {
     }
 }
 
+/// Search a position for a fixed number of nodes, and write the search tree as JSON and Graphviz DOT
+fn export_tree<const S: usize>(
+    komi: Komi,
+    nodes: u32,
+    min_visits: u32,
+    max_depth: usize,
+    weights: &LoadedWeights,
+) {
+    println!("Enter TPS (or leave empty for initial)");
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).unwrap();
+    let position = if input.trim().is_empty() {
+        <Position<S>>::start_position_with_komi(komi)
+    } else {
+        <Position<S>>::from_fen_with_komi(&input, komi).unwrap()
+    };
+
+    let settings = match MctsSetting::default()
+        .arena_size_for_nodes(nodes)
+        .add_loaded_weights(weights, komi)
+    {
+        Ok(settings) => settings,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+    let mut tree = search::MonteCarloTree::new(position, settings);
+    if let Err(err) = tree.search_nodes(nodes as u64) {
+        println!("{err}");
+        return;
+    }
+
+    let exported = tree.export_tree(max_depth, min_visits);
+    fs::write("tree.json", exported.to_json()).unwrap();
+    fs::write("tree.dot", exported.to_dot()).unwrap();
+    println!(
+        "Wrote {} nodes to tree.json and tree.dot. Render with 'dot -Tsvg tree.dot -o tree.svg'",
+        exported.num_nodes()
+    );
+}
+
+/// Search for a forced win or loss of at most `max_plies` plies
+fn solve_from_tps<const S: usize>(komi: Komi, max_plies: u8) {
+    println!("Enter TPS (or leave empty for initial)");
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).unwrap();
+    let mut position = if input.trim().is_empty() {
+        <Position<S>>::start_position_with_komi(komi)
+    } else {
+        <Position<S>>::from_fen_with_komi(&input, komi).unwrap()
+    };
+
+    let mut solver = Solver::new(100_000_000);
+    let start_time = time::Instant::now();
+    let proof = solver.solve(&mut position, max_plies);
+    let elapsed = start_time.elapsed();
+    match proof {
+        Proof::Win { mv, plies } => println!(
+            "{} wins in {} plies with {}",
+            position.side_to_move(),
+            plies,
+            position.move_to_san(&mv)
+        ),
+        Proof::Loss { plies } => println!("{} loses in {} plies", position.side_to_move(), plies),
+        Proof::Unknown => println!("No forced result found in {} plies", max_plies),
+    }
+    println!(
+        "{} nodes in {:.2}s, {:.0} nps",
+        solver.nodes(),
+        elapsed.as_secs_f32(),
+        solver.nodes() as f32 / elapsed.as_secs_f32()
+    );
+}
+
+/// Build an opening book from the first `plies` moves of the games in `.ptn` files,
+/// and from `analyze_openings` output in any other files.
+/// Analysis files are read last, so that their scores replace the scores from games
+fn build_book<const S: usize>(
+    komi: Komi,
+    plies: usize,
+    output: &str,
+    inputs: &[&str],
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut book = OpeningBook::new(S, komi);
+    let (ptn_files, analysis_files): (Vec<&str>, Vec<&str>) =
+        inputs.iter().partition(|input| input.ends_with(".ptn"));
+
+    for path in ptn_files {
+        let games = ptn::ptn_parser::parse_ptn::<Position<S>>(&fs::read_to_string(path)?)?;
+        let mut num_added = 0;
+        for mut game in games {
+            if let Some((_, komi_str)) = game
+                .tags
+                .iter()
+                .find(|(tag, _)| tag.eq_ignore_ascii_case("Komi"))
+            {
+                game.start_position.set_komi(Komi::from_str(komi_str)?);
+            }
+            if game.start_position.komi() == komi && game.game_result().is_some() {
+                book.add_game(&game, plies);
+                num_added += 1;
+            }
+        }
+        println!("Added {} games from {}", num_added, path);
+    }
+    for path in analysis_files {
+        for line in fs::read_to_string(path)?.lines() {
+            if !line.trim().is_empty() {
+                book.add_analysis_line::<S>(line)?;
+            }
+        }
+        println!("Added openings from {}", path);
+    }
+
+    book.sort_moves();
+    book.to_file(output)?;
+    println!("Wrote {} positions to {}", book.len(), output);
+    Ok(())
+}
+
+fn print_multi_pv<const S: usize>(tree: &search::MonteCarloTree<S>, num_lines: usize) {
+    let position = tree.position();
+    for (i, line) in tree.multi_pv(num_lines).iter().enumerate() {
+        println!(
+            "{}: {}, {} visits, {:.2}% winning, {:.2}% policy, pv: {}",
+            i + 1,
+            position.move_to_san(&line.mv),
+            line.visits,
+            line.winning_probability * 100.0,
+            line.policy * 100.0,
+            line.pv
+                .iter()
+                .map(|mv| position.move_to_san(mv))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+    }
+}
+
 fn perft_from_tps<const S: usize>() {
     println!("Enter TPS (or leave empty for initial)");
     let mut input = String::new();
@@ -820,8 +1091,7 @@ fn perft<const S: usize>(position: &mut Position<S>) {
 
 fn analyze_game<const S: usize>(game: Game<Position<S>>) {
     let mut position = game.start_position.clone();
-    let mut ply_number = 2;
-    for PtnMove { mv, .. } in game.moves {
+    for (ply_number, PtnMove { mv, .. }) in (2_usize..).zip(game.moves) {
         position.do_move(mv);
         if let Some(game_result) = position.game_result() {
             let result_string = match game_result {
@@ -856,7 +1126,6 @@ fn analyze_game<const S: usize>(game: Game<Position<S>>) {
                 );
             }
         }
-        ply_number += 1;
     }
 }
 
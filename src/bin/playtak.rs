@@ -2,7 +2,9 @@ use std::convert::Infallible;
 use std::io::{BufRead, Result, Write};
 use std::net::TcpStream;
 use std::str::FromStr;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{io, net, thread};
 
 use board_game_traits::{Color, GameResult, Position as PositionTrait};
@@ -15,14 +17,18 @@ use pgn_traits::PgnPosition;
 
 use rand::seq::SliceRandom;
 use rand::Rng;
+use tiltak::book::OpeningBook;
+use tiltak::evaluation::weights::LoadedWeights;
 use tiltak::position;
 use tiltak::position::{squares_iterator, Move, Role, Square};
 use tiltak::position::{Komi, Position};
 use tiltak::ptn::{Game, PtnMove};
 use tiltak::search;
-use tiltak::search::MctsSetting;
+use tiltak::search::strength::StrengthLevel;
+use tiltak::search::time_management::{Deadlines, TimeManager, TimePolicy};
+use tiltak::search::{MctsSetting, TimeControl};
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct PlaytakSettings {
     default_seek_size: usize,
     default_seek_color: Option<Color>,
@@ -40,13 +46,28 @@ pub struct PlaytakSettings {
     komi: Komi,
     opening_value_variance: Option<f32>,
     middlegame_value_variance: Option<f32>,
+    num_threads: usize,
+    ponder: bool,
+    strength: Option<StrengthLevel>,
+    weights: LoadedWeights,
+    book: Option<OpeningBook>,
 }
 
 impl PlaytakSettings {
-    pub fn to_mcts_setting<const S: usize>(&self, ply_number: usize) -> MctsSetting<S> {
-        let mut settings = MctsSetting::default()
+    /// The search settings for a move. Loaded weights are only used if they are for the game's komi
+    pub fn to_mcts_setting<const S: usize>(&self, komi: Komi, ply_number: usize) -> MctsSetting<S> {
+        let settings = MctsSetting::default()
             .add_rollout_depth(self.rollout_depth)
-            .add_rollout_temperature(self.rollout_temperature);
+            .add_rollout_temperature(self.rollout_temperature)
+            .num_threads(self.num_threads);
+        let mut settings = settings
+            .clone()
+            .add_loaded_weights(&self.weights, komi)
+            .unwrap_or(settings);
+        // Apply the level first, so that explicit noise settings take precedence
+        if let Some(strength) = self.strength {
+            settings = settings.strength(strength);
+        }
 
         if let Some(dirichlet) = self.dirichlet_noise {
             settings = settings.add_dirichlet(dirichlet);
@@ -64,6 +85,91 @@ impl PlaytakSettings {
     }
 }
 
+/// A search of the current position, running in a background thread on the opponent's time
+struct Ponder<const S: usize> {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<search::MonteCarloTree<S>>>,
+    settings: MctsSetting<S>,
+    start_time: Instant,
+    root_visits: u32,
+    edge_visits: Vec<(Move<S>, u32)>,
+}
+
+impl<const S: usize> Ponder<S> {
+    fn start(mut tree: search::MonteCarloTree<S>, settings: MctsSetting<S>) -> Self {
+        let edge_visits = tree
+            .shallow_edges()
+            .unwrap_or_default()
+            .iter()
+            .map(|edge| (edge.mv, edge.visits))
+            .collect();
+        let root_visits = tree.visits();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let handle = thread::spawn(move || {
+            if let Err(err) = tree.search_until_stopped(&thread_stop) {
+                warn!("Stopped pondering early: {}", err);
+            }
+            tree
+        });
+        Ponder {
+            stop,
+            handle: Some(handle),
+            settings,
+            start_time: Instant::now(),
+            root_visits,
+            edge_visits,
+        }
+    }
+
+    /// Stop pondering, and return the tree along with its settings.
+    /// Also returns the part of the pondering time that was spent on `mv`,
+    /// which is the time saved if the opponent played that move.
+    fn stop(mut self, mv: Move<S>) -> (search::MonteCarloTree<S>, MctsSetting<S>, Duration) {
+        self.stop.store(true, Ordering::Relaxed);
+        let tree = self.handle.take().unwrap().join().unwrap();
+        let ponder_time = self.start_time.elapsed();
+
+        let nodes_pondered = tree.visits() - self.root_visits;
+        let edge_visits_before = self
+            .edge_visits
+            .iter()
+            .find(|(edge_move, _)| *edge_move == mv)
+            .map(|(_, visits)| *visits)
+            .unwrap_or_default();
+        let edge_visits_after = tree
+            .shallow_edges()
+            .unwrap_or_default()
+            .iter()
+            .find(|edge| edge.mv == mv)
+            .map(|edge| edge.visits)
+            .unwrap_or_default();
+
+        let time_saved = if nodes_pondered == 0 {
+            Duration::ZERO
+        } else {
+            ponder_time.mul_f64(
+                edge_visits_after.saturating_sub(edge_visits_before) as f64 / nodes_pondered as f64,
+            )
+        };
+        info!(
+            "Pondered {} nodes in {:.1}s, {:.1}s on {}",
+            nodes_pondered,
+            ponder_time.as_secs_f32(),
+            time_saved.as_secs_f32(),
+            mv.to_string()
+        );
+        (tree, self.settings.clone(), time_saved)
+    }
+}
+
+impl<const S: usize> Drop for Ponder<S> {
+    fn drop(&mut self) {
+        // If the game ends while pondering, the search thread is stopped without waiting for it
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
 pub fn main() -> Result<()> {
     let app = Command::new("Tiltak playtak client")
         .version("0.1")
@@ -92,7 +198,7 @@ pub fn main() -> Result<()> {
                 .short('s')
                 .long("size")
                 .env("SIZE")
-                .help("Board size")
+                .help("Board size. 7s and 8s use untrained placeholder parameters, transferred from 6s")
                 .num_args(1)
                 .default_value("5")
                 .value_parser(clap::value_parser!(u64).range(4..=8)),
@@ -185,6 +291,26 @@ pub fn main() -> Result<()> {
             .env("FIXED_NODES")
             .help("Normally, the bot will search a variable number of nodes, depending on hardware on time control. This option overrides that to calculate a fixed amount of nodes each move")
             .num_args(1))
+        .arg(Arg::new("level")
+            .long("level")
+            .env("LEVEL")
+            .help("Play at a handicap level from 1 to 10, with calibrated node limits and noise. Level 10 is full strength. Cannot be combined with the noise, fixed node or ponder settings.")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u8).range(1..=10))
+            .conflicts_with_all(["policyNoise", "valueNoise", "fixedNodes", "ponder"]))
+        .arg(Arg::new("threads")
+            .long("threads")
+            .env("THREADS")
+            .help("Number of threads to search with")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u16).range(1..=255))
+            .default_value("1"))
+        .arg(Arg::new("ponder")
+            .long("ponder")
+            .env("PONDER")
+            .help("Keep searching on the opponent's time")
+            .action(ArgAction::SetTrue)
+            .num_args(0))
         .arg(Arg::new("komi")
             .long("komi")
             .env("KOMI")
@@ -223,7 +349,22 @@ pub fn main() -> Result<()> {
             .help("Network port to connect to")
             .num_args(1)
             .default_value("10000")
-            .value_parser(clap::value_parser!(u16)));
+            .value_parser(clap::value_parser!(u16)))
+        .arg(Arg::new("valueWeights")
+            .long("value-weights")
+            .env("VALUE_WEIGHTS")
+            .help("Load value parameters from a weights file, instead of using the built-in parameters. Only used for games of the same size as the weights.")
+            .num_args(1))
+        .arg(Arg::new("policyWeights")
+            .long("policy-weights")
+            .env("POLICY_WEIGHTS")
+            .help("Load policy parameters from a weights file, instead of using the built-in parameters. Only used for games of the same size as the weights.")
+            .num_args(1))
+        .arg(Arg::new("book")
+            .long("book")
+            .env("BOOK")
+            .help("Play moves from an opening book file instead of searching, while the game is in the book. Only used for games of the same size and komi as the book.")
+            .num_args(1));
 
     let matches = app.get_matches();
 
@@ -310,6 +451,8 @@ pub fn main() -> Result<()> {
         .get_one::<String>("fixedNodes")
         .map(|v| v.parse().unwrap());
 
+    let num_threads = *matches.get_one::<u16>("threads").unwrap() as usize;
+
     let tc = matches.get_one::<String>("tc").map(|tc| parse_tc(tc));
 
     let target_move_time: Option<Duration> = matches
@@ -328,6 +471,44 @@ pub fn main() -> Result<()> {
 
     let seek_unrated = matches.get_flag("seekUnrated");
 
+    let ponder = matches.get_flag("ponder");
+
+    let strength = matches
+        .get_one::<u8>("level")
+        .map(|level| StrengthLevel::from_level(*level).unwrap());
+
+    let weights = LoadedWeights::load(
+        matches
+            .get_one::<String>("valueWeights")
+            .map(String::as_str),
+        matches
+            .get_one::<String>("policyWeights")
+            .map(String::as_str),
+    )
+    .and_then(|weights| weights.check_komi(size, komi).map(|()| weights))
+    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    if weights.sizes().any(|weights_size| weights_size != size) {
+        warn!(
+            "Loaded weights are not for {}s, and will only be used for games of their own size",
+            size
+        );
+    }
+
+    let book: Option<OpeningBook> = matches
+        .get_one::<String>("book")
+        .map(OpeningBook::from_file)
+        .transpose()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    if let Some(book) = &book {
+        info!("Loaded opening book with {} positions", book.len());
+        if book.size != size || book.komi != komi {
+            warn!(
+                "Opening book is for {}s with {} komi, and will only be used for games of that size and komi",
+                book.size, book.komi
+            );
+        }
+    }
+
     let playtak_base_url = matches.get_one::<String>("playtakBaseUrl").unwrap();
     let playtak_port = *matches.get_one::<u16>("playtakPort").unwrap();
 
@@ -350,6 +531,11 @@ pub fn main() -> Result<()> {
         komi,
         opening_value_variance: opening_value_noise,
         middlegame_value_variance: middlegame_value_noise,
+        num_threads,
+        ponder,
+        strength,
+        weights,
+        book,
     };
 
     loop {
@@ -379,9 +565,11 @@ pub fn main() -> Result<()> {
         let error = match matches.get_one::<String>("playBot") {
             Some(bot_name) => {
                 match match size {
-                    4 => session.accept_seek::<4>(playtak_settings, bot_name),
-                    5 => session.accept_seek::<5>(playtak_settings, bot_name),
-                    6 => session.accept_seek::<6>(playtak_settings, bot_name),
+                    4 => session.accept_seek::<4>(&playtak_settings, bot_name),
+                    5 => session.accept_seek::<5>(&playtak_settings, bot_name),
+                    6 => session.accept_seek::<6>(&playtak_settings, bot_name),
+                    7 => session.accept_seek::<7>(&playtak_settings, bot_name),
+                    8 => session.accept_seek::<8>(&playtak_settings, bot_name),
                     s => panic!("Unsupported size {}", s),
                 } {
                     Ok(_game) => return Ok(()),
@@ -389,7 +577,7 @@ pub fn main() -> Result<()> {
                 }
             }
             None => match size {
-                4..=6 => session.seek_playtak_games(playtak_settings),
+                4..=8 => session.seek_playtak_games(&playtak_settings),
                 s => panic!("Unsupported size {}", s),
             }
             .unwrap_err(),
@@ -490,11 +678,13 @@ impl<'a> ChatCommand<'a> {
             Some("4") => 4,
             Some("5") => 5,
             Some("6") => 6,
+            Some("7") => 7,
+            Some("8") => 8,
             s => {
                 self.respond(
                     session,
                     &format!(
-                        "Unsupported size {}. Must be 4, 5 or 6",
+                        "Unsupported size {}. Must be 4, 5, 6, 7 or 8",
                         s.unwrap_or_default()
                     ),
                 )?;
@@ -638,7 +828,7 @@ impl PlaytakSession {
 
     fn send_seek(
         &mut self,
-        playtak_settings: PlaytakSettings,
+        playtak_settings: &PlaytakSettings,
         size: usize,
         color: Option<Color>,
     ) -> Result<()> {
@@ -679,7 +869,7 @@ impl PlaytakSession {
         }
     }
 
-    fn seek_playtak_games(&mut self, playtak_settings: PlaytakSettings) -> io::Result<Infallible> {
+    fn seek_playtak_games(&mut self, playtak_settings: &PlaytakSettings) -> io::Result<Infallible> {
         let mut restoring_previous_session = true;
         let mut next_seek_color = playtak_settings.default_seek_color;
         let mut next_seek_size = playtak_settings.default_seek_size;
@@ -712,6 +902,16 @@ impl PlaytakSession {
                             playtak_settings,
                             restoring_previous_session,
                         )?,
+                        7 => self.play_game::<7>(
+                            playtak_game,
+                            playtak_settings,
+                            restoring_previous_session,
+                        )?,
+                        8 => self.play_game::<8>(
+                            playtak_game,
+                            playtak_settings,
+                            restoring_previous_session,
+                        )?,
                         s => panic!("Unsupported size {}", s),
                     };
                     restoring_previous_session = false;
@@ -765,7 +965,7 @@ impl PlaytakSession {
 
     pub fn accept_seek<const S: usize>(
         &mut self,
-        playtak_settings: PlaytakSettings,
+        playtak_settings: &PlaytakSettings,
         bot_name: &str,
     ) -> io::Result<()> {
         // The server doesn't send increment when the game starts
@@ -808,7 +1008,7 @@ impl PlaytakSession {
     fn play_game<const S: usize>(
         &mut self,
         game: PlaytakGame,
-        playtak_settings: PlaytakSettings,
+        playtak_settings: &PlaytakSettings,
         mut restoring_previous_session: bool,
     ) -> io::Result<(usize, Option<Color>)> {
         info!(
@@ -821,11 +1021,19 @@ impl PlaytakSession {
             game.increment.as_secs_f32(),
             game.komi
         );
+        if let Err(err) = playtak_settings.weights.check_komi(S, game.komi) {
+            warn!("Using the built-in parameters for this game: {}", err);
+        }
         let mut next_seek_size = playtak_settings.default_seek_size;
         let mut next_seek_color = playtak_settings.default_seek_color;
         let mut position = <Position<S>>::start_position_with_komi(game.komi);
         let mut moves = vec![];
         let mut our_time_left = game.time_left;
+        // The search tree from our previous move, along with the settings it was created with, ignoring arena size
+        let mut previous_tree: Option<(search::MonteCarloTree<S>, MctsSetting<S>)> = None;
+        let mut ponder: Option<Ponder<S>> = None;
+        // Time spent pondering on the move the opponent actually played, which is subtracted from our next move
+        let mut time_saved_pondering = Duration::ZERO;
         'gameloop: loop {
             if position.game_result().is_some() {
                 // Double check that the game is still over, if we remove information about move repetitions
@@ -838,78 +1046,126 @@ impl PlaytakSession {
                 }
             }
             if position.side_to_move() == game.our_color && !restoring_previous_session {
-                let (best_move, score) =
-                    // On the very first move, always place instantly in a random corner
-                    if squares_iterator::<S>().all(|square| position.stack_heights()[square] == 0) {
-                        let mut rng = rand::thread_rng();
-                        let corner_placements: Vec<Move<S>> = Square::corners().into_iter().map(|square| Move::placement(Role::Flat, square)).collect();
-
-                        (*corner_placements.choose(&mut rng).unwrap(), 0.0)
-                    } else if let Some(fixed_nodes) = playtak_settings.fixed_nodes {
-                        let settings =
-                            playtak_settings.to_mcts_setting(position.half_moves_played())
-                            .arena_size_for_nodes(fixed_nodes as u32);
-                        let mut tree = search::MonteCarloTree::new(position.clone(), settings);
-                        for _ in 0..fixed_nodes {
-                            if let Err(err) = tree.select() {
-                                eprintln!("Warning: {err}");
-                                break;
-                            }
-                        }
-
-                        // Wait for a bit
-                        let mut rng = rand::thread_rng();
-                        let sleep_duration = Duration::from_millis(rng.gen_range(1000..2500));
-                        thread::sleep(sleep_duration);
-
-                        tree.best_move().unwrap()
-                    } else {
-                        {
-                            let maximum_time = if let Some(target_move_time) =  playtak_settings.target_move_time {
-                                if let Some((trigger_move, _)) = playtak_settings.extra_time {
-                                    if position.half_moves_played() / 2 > trigger_move as usize {
-                                        (our_time_left / 6 + game.increment / 2).min(6 * target_move_time)
-                                    } else {
-                                        (our_time_left / 6 + game.increment / 2).min(2 * target_move_time)
-                                    }
-                                } else {
-                                (our_time_left / 6 + game.increment / 2).min(2 * target_move_time)
-                                }
-                            } else {
-                                our_time_left / 6 + game.increment / 2
-                            };
+                let (best_move, score) = if let Some(book_move) = playtak_settings
+                    .book
+                    .as_ref()
+                    .and_then(|book| book.choose_move(&position, &mut rand::thread_rng()))
+                {
+                    info!("Playing book move {}", position.move_to_san(&book_move));
+                    (book_move, 0.0)
+                }
+                // On the very first move, always place instantly in a random corner
+                else if squares_iterator::<S>()
+                    .all(|square| position.stack_heights()[square] == 0)
+                {
+                    let mut rng = rand::thread_rng();
+                    let corner_placements: Vec<Move<S>> = Square::corners()
+                        .into_iter()
+                        .map(|square| Move::placement(Role::Flat, square))
+                        .collect();
+
+                    (*corner_placements.choose(&mut rng).unwrap(), 0.0)
+                } else if let Some(fixed_nodes) = playtak_settings.fixed_nodes {
+                    let settings = playtak_settings
+                        .to_mcts_setting(position.komi(), position.half_moves_played())
+                        .arena_size_for_nodes(fixed_nodes as u32);
+                    let mut tree = search::MonteCarloTree::new(position.clone(), settings);
+                    if let Err(err) = tree.search_nodes(fixed_nodes) {
+                        eprintln!("Warning: {err}");
+                    }
 
-                            // Give enough memory for a CPU calculating at roughly 200K nps.
-                            let max_nodes = (maximum_time.as_secs() as u32).saturating_mul(200_000);
+                    // Wait for a bit
+                    let mut rng = rand::thread_rng();
+                    let sleep_duration = Duration::from_millis(rng.gen_range(1000..2500));
+                    thread::sleep(sleep_duration);
 
-                            // For 6s, the toughest position I've found required 40 elements/node searched
-                            // This formula gives 72, which is hopefully plenty
-                            let max_arena_size = if playtak_settings.rollout_depth < 10 {
-                                max_nodes.saturating_mul((S * S) as u32 * 2)
+                    tree.best_move().unwrap()
+                } else {
+                    {
+                        let mut time_manager = TimeManager::new(TimePolicy::Fraction {
+                            time_divisor: 6,
+                            increment_divisor: 2,
+                        });
+                        if let Some(target_move_time) = playtak_settings.target_move_time {
+                            let is_extra_time =
+                                playtak_settings
+                                    .extra_time
+                                    .is_some_and(|(trigger_move, _)| {
+                                        position.half_moves_played() / 2 > trigger_move as usize
+                                    });
+                            time_manager = time_manager.max_move_time(if is_extra_time {
+                                6 * target_move_time
                             } else {
-                                // Give SlateBot a smaller tree size, because its nps is much lower
-                                max_nodes.saturating_mul(S as u32 * 2)
-                            };
-
-                            let settings =
-                                playtak_settings.to_mcts_setting(position.half_moves_played())
-                                .arena_size(max_arena_size.min(2_u32.pow(31)));
-
-                            search::play_move_time(position.clone(), maximum_time, settings)
+                                2 * target_move_time
+                            });
                         }
-                    };
+                        let deadlines = time_manager
+                            .deadlines::<S>(
+                                &TimeControl::Time(our_time_left, game.increment),
+                                position.half_moves_played(),
+                            )
+                            .unwrap();
+
+                        // Give enough memory for a CPU calculating at roughly 200K nps per thread.
+                        let max_nodes = (deadlines.hard.as_secs() as u32)
+                            .saturating_mul(200_000)
+                            .saturating_mul(playtak_settings.num_threads as u32);
+
+                        // For 6s, the toughest position I've found required 40 elements/node searched
+                        // This formula gives 72, which is hopefully plenty
+                        let max_arena_size = if playtak_settings.rollout_depth < 10 {
+                            max_nodes.saturating_mul((S * S) as u32 * 2)
+                        } else {
+                            // Give SlateBot a smaller tree size, because its nps is much lower
+                            max_nodes.saturating_mul(S as u32 * 2)
+                        };
+
+                        let deadlines = Deadlines {
+                            soft: deadlines.soft.saturating_sub(time_saved_pondering),
+                            hard: deadlines.hard.saturating_sub(time_saved_pondering),
+                        };
+
+                        let base_settings = playtak_settings
+                            .to_mcts_setting(position.komi(), position.half_moves_played());
+
+                        // Keep searching the previous tree, unless the settings have changed since
+                        if previous_tree.as_ref().is_some_and(|(tree, tree_settings)| {
+                            *tree_settings != base_settings || *tree.position() != position
+                        }) {
+                            previous_tree = None;
+                        }
+                        let mut tree = match previous_tree.take() {
+                            Some((tree, _)) => tree,
+                            None => search::MonteCarloTree::new(
+                                position.clone(),
+                                base_settings
+                                    .clone()
+                                    .arena_size(max_arena_size.min(2_u32.pow(31))),
+                            ),
+                        };
+                        tree.search_until_deadlines(deadlines, &AtomicBool::new(false), |_| {});
+                        let (best_move, score) = tree.best_move().unwrap();
+                        previous_tree = Some((tree, base_settings));
+
+                        (best_move, score)
+                    }
+                };
 
                 position.do_move(best_move);
                 moves.push(PtnMove {
-                    mv: best_move,
-                    annotations: vec![],
-                    comment: score.to_string(),
+                    comments: vec![score.to_string()],
+                    ..PtnMove::new(best_move)
                 });
 
                 let output_string =
                     format!("Game#{} {}", game.game_no, best_move.to_string_playtak());
                 self.send_line(&output_string)?;
 
+                time_saved_pondering = Duration::ZERO;
+                if let Some((tree, _)) = previous_tree.as_mut() {
+                    tree.advance(best_move);
+                }
+
                 // Say "Tak" whenever there is a threat to win
                 // Only do this vs Shigewara
                 if game.white_player == "shigewara" || game.black_player == "shigewara" {
@@ -931,6 +1187,11 @@ impl PlaytakSession {
                     }
                 }
             } else {
+                if playtak_settings.ponder && ponder.is_none() && !restoring_previous_session {
+                    if let Some((tree, settings)) = previous_tree.take() {
+                        ponder = Some(Ponder::start(tree, settings));
+                    }
+                }
                 // Wait for the opponent's move. The server may send other messages in the meantime
                 loop {
                     let line = self.read_line()?;
@@ -973,11 +1234,18 @@ impl PlaytakSession {
                             "P" | "M" => {
                                 let move_string = words[1..].join(" ");
                                 let move_played = Move::from_string_playtak(&move_string);
+                                if let Some(ponder) = ponder.take() {
+                                    let (tree, settings, time_saved) = ponder.stop(move_played);
+                                    previous_tree = Some((tree, settings));
+                                    time_saved_pondering = time_saved;
+                                }
+                                if let Some((tree, _)) = previous_tree.as_mut() {
+                                    tree.advance(move_played);
+                                }
                                 position.do_move(move_played);
                                 moves.push(PtnMove {
-                                    mv: move_played,
-                                    annotations: vec![],
-                                    comment: "0.0".to_string(),
+                                    comments: vec!["0.0".to_string()],
+                                    ..PtnMove::new(move_played)
                                 });
                                 break;
                             }
@@ -1023,6 +1291,7 @@ impl PlaytakSession {
             moves: moves.clone(),
             game_result_str: position.pgn_game_result(),
             tags,
+            leading_comments: vec![],
         };
 
         let mut ptn = Vec::new();
@@ -0,0 +1,218 @@
+//! Extract puzzles from PTN files or playtak.com SQLite databases: positions where exactly one move forces a road win.
+//!
+//! Each puzzle is written on one line, as `<tps>;<plies>;<solution>;<source>`.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::process::exit;
+use std::thread;
+
+use clap::{Arg, ArgMatches, Command};
+
+use tiltak::position::Position;
+use tiltak::ptn::{ptn_parser, Game};
+use tiltak::puzzles::{Puzzle, PuzzleFinder};
+#[cfg(feature = "sqlite")]
+use tiltak::sqlite::GameQuery;
+
+type DynError = Box<dyn std::error::Error + Send + Sync>;
+
+fn main() {
+    let matches = Command::new("Tiltak puzzle extractor")
+        .version("0.1")
+        .author("Morten Lohne")
+        .arg(
+            Arg::new("inputs")
+                .help("PTN files, or playtak.com SQLite databases, to extract puzzles from")
+                .required(true)
+                .num_args(1..),
+        )
+        .arg(
+            Arg::new("size")
+                .short('s')
+                .long("size")
+                .help("Board size")
+                .required(true)
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64).range(4..=8)),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .help("Write the puzzles to this file, instead of to stdout")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("plies")
+                .long("plies")
+                .help("Maximum length of the forced win, in plies. Must be odd. Longer wins usually need a higher --max-nodes to be verified")
+                .default_value("3")
+                .num_args(1)
+                .value_parser(|input: &str| match input.parse::<u8>() {
+                    Ok(plies) if plies % 2 == 1 => Ok(plies),
+                    _ => Err("must be an odd number".to_string()),
+                }),
+        )
+        .arg(
+            Arg::new("min-plies")
+                .long("min-plies")
+                .help("Minimum length of the forced win, in plies. The default skips wins in one move")
+                .default_value("3")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u8)),
+        )
+        .arg(
+            Arg::new("max-nodes")
+                .long("max-nodes")
+                .help("Give up on a position after searching this many nodes")
+                .default_value("1000000")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("min-rating")
+                .long("min-rating")
+                .help("Only read games where both players are rated at least this high, from SQLite databases")
+                .num_args(1)
+                .value_parser(clap::value_parser!(i64)),
+        )
+        .get_matches();
+
+    let result = match *matches.get_one::<u64>("size").unwrap() {
+        4 => extract_puzzles::<4>(&matches),
+        5 => extract_puzzles::<5>(&matches),
+        6 => extract_puzzles::<6>(&matches),
+        7 => extract_puzzles::<7>(&matches),
+        8 => extract_puzzles::<8>(&matches),
+        _ => unreachable!(),
+    };
+    if let Err(err) = result {
+        eprintln!("Error: {}", err);
+        exit(1)
+    }
+}
+
+fn extract_puzzles<const S: usize>(matches: &ArgMatches) -> Result<(), DynError> {
+    let max_plies = *matches.get_one::<u8>("plies").unwrap();
+    let min_plies = *matches.get_one::<u8>("min-plies").unwrap();
+    let max_nodes = *matches.get_one::<u64>("max-nodes").unwrap();
+
+    let mut games = vec![];
+    for path in matches.get_many::<String>("inputs").unwrap() {
+        let games_in_file = read_games::<S>(path, matches)?;
+        eprintln!("Read {} games from {}", games_in_file.len(), path);
+        games.extend(games_in_file);
+    }
+
+    // Search the games on all cores, with one puzzle finder per thread
+    let num_threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = games.len().div_ceil(num_threads).max(1);
+    let puzzles: Vec<Puzzle<S>> = thread::scope(|scope| {
+        let handles: Vec<_> = games
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut finder = PuzzleFinder::<S>::new(max_plies)
+                        .min_plies(min_plies)
+                        .max_nodes(max_nodes);
+                    chunk
+                        .iter()
+                        .flat_map(|(game, source)| finder.puzzles_from_game(game, source))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    let mut output: Box<dyn Write> = match matches.get_one::<String>("output") {
+        Some(path) => Box::new(io::BufWriter::new(fs::File::create(path)?)),
+        None => Box::new(io::stdout()),
+    };
+    // The same position often occurs in several games
+    let mut seen_positions = HashSet::new();
+    let mut num_puzzles = 0;
+    for puzzle in puzzles {
+        if seen_positions.insert(puzzle.position.clone()) {
+            writeln!(output, "{}", puzzle)?;
+            num_puzzles += 1;
+        }
+    }
+    output.flush()?;
+    eprintln!("Found {} puzzles in {} games", num_puzzles, games.len());
+    Ok(())
+}
+
+/// Read the games in a PTN file or SQLite database, each with a description of where it came from
+fn read_games<const S: usize>(
+    path: &str,
+    matches: &ArgMatches,
+) -> Result<Vec<(Game<Position<S>>, String)>, DynError> {
+    let games = if path.ends_with(".ptn") {
+        let (games, errors) =
+            ptn_parser::parse_ptn_lenient::<Position<S>>(&fs::read_to_string(path)?);
+        for err in errors {
+            eprintln!("Warning: Skipped game in {}: {}", path, err);
+        }
+        games
+    } else {
+        read_database_games::<S>(path, matches)?
+    };
+
+    Ok(games
+        .into_iter()
+        .enumerate()
+        .map(|(i, game)| {
+            let tag = |name: &str| {
+                game.tags
+                    .iter()
+                    .find(|(tag, _)| tag.eq_ignore_ascii_case(name))
+                    .map_or("?", |(_, value)| value.as_str())
+                    .to_string()
+            };
+            let event = match tag("Event").as_str() {
+                "?" => format!("game {}", i + 1),
+                event => event.to_string(),
+            };
+            // Semicolons separate the fields of the puzzle format
+            let source = format!(
+                "{} {}, {} vs {}",
+                path,
+                event,
+                tag("Player1"),
+                tag("Player2")
+            )
+            .replace(';', ",");
+            (game, source)
+        })
+        .collect())
+}
+
+#[cfg(feature = "sqlite")]
+fn read_database_games<const S: usize>(
+    path: &str,
+    matches: &ArgMatches,
+) -> Result<Vec<Game<Position<S>>>, DynError> {
+    let mut query = GameQuery::new();
+    if let Some(min_rating) = matches.get_one::<i64>("min-rating") {
+        query = query.min_rating(*min_rating);
+    }
+    Ok(query.read_games::<S, _>(path)?)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn read_database_games<const S: usize>(
+    path: &str,
+    _matches: &ArgMatches,
+) -> Result<Vec<Game<Position<S>>>, DynError> {
+    Err(format!(
+        "{} is not a .ptn file, and reading SQLite databases requires the sqlite feature",
+        path
+    )
+    .into())
+}
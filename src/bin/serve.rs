@@ -0,0 +1,182 @@
+//! Analyze positions over HTTP, using the same JSON `Event` and `Output` format as the AWS Lambda handler.
+//!
+//! `POST /analyze` with an `Event` as the body responds with the finished `Output`.
+//! `POST /analyze?stream=true` responds with newline-delimited JSON instead,
+//! with an `{"info": <Output>}` line for each intermediate result, followed by `{"output": <Output>}` or `{"error": <message>}`.
+
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use clap::{Arg, Command};
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use tiltak::aws::analysis::{self, Error};
+use tiltak::aws::{Event, Output};
+
+/// Requests larger than this are rejected, since events are only a few kilobytes
+const MAX_REQUEST_SIZE: u64 = 1024 * 1024;
+
+fn main() {
+    let matches = Command::new("Tiltak analysis server")
+        .version("0.1")
+        .author("Morten Lohne")
+        .arg(
+            Arg::new("address")
+                .long("address")
+                .env("TILTAK_ADDRESS")
+                .help("Address to listen on")
+                .default_value("127.0.0.1:8080")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("workers")
+                .long("workers")
+                .env("TILTAK_WORKERS")
+                .help("Maximum number of requests to handle concurrently. Defaults to the number of CPU cores")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("max-mem-mb")
+                .long("max-mem-mb")
+                .env("TILTAK_MAX_MEM_MB")
+                .help("Maximum memory for each search tree, in megabytes")
+                .default_value("1024")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64).range(1..65536)),
+        )
+        .get_matches();
+
+    let address = matches.get_one::<String>("address").unwrap();
+    let workers = matches.get_one::<u64>("workers").map_or_else(
+        || thread::available_parallelism().map_or(1, |n| n.get()),
+        |workers| *workers as usize,
+    );
+    let max_mem_usage = *matches.get_one::<u64>("max-mem-mb").unwrap() as usize * 1024 * 1024;
+
+    let server = match Server::http(address) {
+        Ok(server) => Arc::new(server),
+        Err(err) => {
+            eprintln!("Error: Failed to listen on {}: {}", address, err);
+            exit(1)
+        }
+    };
+    println!(
+        "Listening on {} with {} workers, {}MB per search",
+        address,
+        workers,
+        max_mem_usage / (1024 * 1024)
+    );
+
+    let handles: Vec<_> = (0..workers)
+        .map(|_| {
+            let server = server.clone();
+            thread::spawn(move || loop {
+                match server.recv() {
+                    Ok(request) => {
+                        if let Err(err) = handle_request(request, max_mem_usage) {
+                            eprintln!("Warning: Failed to send response: {}", err);
+                        }
+                    }
+                    Err(err) => eprintln!("Warning: Failed to receive request: {}", err),
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+fn handle_request(mut request: Request, max_mem_usage: usize) -> io::Result<()> {
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    if path != "/analyze" {
+        return request.respond(error_response(404, "Not found"));
+    }
+    if *request.method() != Method::Post {
+        return request.respond(error_response(405, "Use POST to analyze a position"));
+    }
+    let stream = query.split('&').any(|param| param == "stream=true");
+
+    let mut body = String::new();
+    if let Err(err) = request
+        .as_reader()
+        .take(MAX_REQUEST_SIZE)
+        .read_to_string(&mut body)
+    {
+        return request.respond(error_response(400, &format!("Invalid request: {}", err)));
+    }
+    let event: Event = match serde_json::from_str(&body) {
+        Ok(event) => event,
+        Err(err) => {
+            return request.respond(error_response(400, &format!("Invalid event: {}", err)))
+        }
+    };
+
+    if stream {
+        stream_analysis(request, event, max_mem_usage)
+    } else {
+        match run_analysis(event, max_mem_usage, &AtomicBool::new(false), |_| {}) {
+            Ok(output) => request.respond(json_response(200, &json!(output))),
+            Err(err) => request.respond(error_response(400, &err.to_string())),
+        }
+    }
+}
+
+/// Respond with one JSON line per intermediate result, sent as soon as it is available.
+/// The chunked response is written directly to the connection, because tiny_http buffers chunked responses
+fn stream_analysis(request: Request, event: Event, max_mem_usage: usize) -> io::Result<()> {
+    let writer = RefCell::new(request.into_writer());
+    write!(
+        writer.borrow_mut(),
+        "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nTransfer-Encoding: chunked\r\n\r\n"
+    )?;
+    let write_chunk = |chunk: &str| -> io::Result<()> {
+        let mut writer = writer.borrow_mut();
+        write!(writer, "{:x}\r\n{}\r\n", chunk.len(), chunk)?;
+        writer.flush()
+    };
+
+    // Stop searching if the client disconnects
+    let stop = AtomicBool::new(false);
+    let result = run_analysis(event, max_mem_usage, &stop, |output| {
+        if write_chunk(&format!("{}\n", json!({ "info": output }))).is_err() {
+            stop.store(true, Ordering::Relaxed);
+        }
+    });
+    match result {
+        Ok(output) => write_chunk(&format!("{}\n", json!({ "output": output })))?,
+        Err(err) => write_chunk(&format!("{}\n", json!({ "error": err.to_string() })))?,
+    }
+    // An empty chunk ends the response
+    write_chunk("")
+}
+
+/// Run the analysis, turning panics into errors so that the worker thread survives them
+fn run_analysis<F: Fn(&Output)>(
+    event: Event,
+    max_mem_usage: usize,
+    stop: &AtomicBool,
+    info: F,
+) -> Result<Output, Error> {
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        analysis::analyze(event, max_mem_usage, stop, info)
+    }))
+    .unwrap_or_else(|_| Err("Internal error during search".into()))
+}
+
+fn json_response(status_code: u16, value: &serde_json::Value) -> Response<io::Cursor<Vec<u8>>> {
+    Response::from_string(value.to_string())
+        .with_status_code(status_code)
+        .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap())
+}
+
+fn error_response(status_code: u16, message: &str) -> Response<io::Cursor<Vec<u8>>> {
+    json_response(status_code, &json!({ "error": message }))
+}
@@ -1,233 +1,770 @@
 use board_game_traits::{Color, Position as PositionTrait};
 use pgn_traits::PgnPosition;
-use std::io::{BufRead, BufReader};
+use std::any::Any;
+use std::io::{self, BufRead};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use std::{env, io};
+use std::{env, process, thread};
+use tiltak::book::OpeningBook;
+use tiltak::evaluation::weights::LoadedWeights;
 use tiltak::position::{Komi, Position};
+use tiltak::search::strength::StrengthLevel;
+use tiltak::search::time_management::{Deadlines, TimeManager, TimePolicy};
+use tiltak::search::{MctsSetting, MonteCarloTree, TimeControl};
 
-use std::any::Any;
-use tiltak::search::{self, MctsSetting, MonteCarloTree};
+/// The time policy tei has always used, a fifth of the remaining time plus half the increment
+const DEFAULT_TIME_POLICY: TimePolicy = TimePolicy::Fraction {
+    time_divisor: 5,
+    increment_divisor: 2,
+};
 
 pub fn main() {
-    let is_slatebot = env::args().any(|arg| arg == "--slatebot");
+    let args: Vec<String> = env::args().collect();
+    let is_slatebot = args.iter().any(|arg| arg == "--slatebot");
+    let weights = match LoadedWeights::load(
+        arg_value(&args, "--value-weights"),
+        arg_value(&args, "--policy-weights"),
+    ) {
+        Ok(weights) => weights,
+        Err(err) => {
+            eprintln!("Failed to load weights: {}", err);
+            process::exit(1)
+        }
+    };
+    let book = match arg_value(&args, "--book").map(OpeningBook::from_file) {
+        None => None,
+        Some(Ok(book)) => Some(book),
+        Some(Err(err)) => {
+            eprintln!("Failed to load opening book: {}", err);
+            process::exit(1)
+        }
+    };
 
-    loop {
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        if input.trim() == "tei" {
-            break;
+    let mut lines = io::stdin().lock().lines();
+
+    for line in lines.by_ref() {
+        match line {
+            Ok(line) if line.trim() == "tei" => break,
+            Ok(_) => (),
+            Err(_) => return,
         }
     }
 
+    let mut engine = Engine::new(is_slatebot, weights, book);
+
     println!("id name Tiltak");
     println!("id author Morten Lohne");
-    println!("option name HalfKomi type spin default 0 min -10 max 10");
+    engine.print_options();
     println!("teiok");
 
+    for line in lines {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim() == "quit" {
+            break;
+        }
+        if let Err(err) = engine.handle_command(&line) {
+            println!("info string Error: {}", err);
+        }
+    }
+    engine.stop_search();
+}
+
+/// The value following `flag` in the command line arguments
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+/// Flags set by the input thread, to control a running search
+#[derive(Default)]
+struct SearchSignals {
+    stop: AtomicBool,
+    ponderhit: AtomicBool,
+}
+
+/// A search running in a background thread.
+/// The thread prints its own `info` and `bestmove` output,
+/// and returns the search tree when it finishes, so that it can be reused in the next search
+struct RunningSearch {
+    signals: Arc<SearchSignals>,
+    handle: thread::JoinHandle<Box<dyn Any + Send>>,
+}
+
+struct Engine {
+    size: Option<usize>,
+    komi: Komi,
+    num_threads: usize,
+    hash_mb: usize,
+    rollout_depth: u16,
+    rollout_temperature: Option<f64>,
+    dirichlet: Option<f32>,
+    static_eval_variance: Option<f32>,
+    level: u8,
+    c_puct_init: f32,
+    c_puct_base: f32,
+    initial_mean_action_value: f32,
+    multi_pv: usize,
+    transpositions: bool,
+    time_policy: TimePolicy,
+    weights: LoadedWeights,
+    book: Option<OpeningBook>,
     // Position stored in a `dyn Any` variable, because it can be any size
-    let mut position: Option<Box<dyn Any>> = None;
-    let mut size: Option<usize> = None;
-    let mut komi = Komi::default();
+    position: Option<Box<dyn Any>>,
+    // The tree from the previous search, which can be reused if the next position follows from it
+    previous_tree: Option<Box<dyn Any + Send>>,
+    search: Option<RunningSearch>,
+}
 
-    for line in BufReader::new(io::stdin()).lines().map(Result::unwrap) {
+impl Engine {
+    fn new(is_slatebot: bool, weights: LoadedWeights, book: Option<OpeningBook>) -> Self {
+        // The search parameters don't depend on the board size
+        let default_settings = <MctsSetting<5>>::default();
+        Engine {
+            size: None,
+            komi: Komi::default(),
+            num_threads: 1,
+            hash_mb: default_settings.arena_size_bytes() / (1024 * 1024),
+            rollout_depth: if is_slatebot { 200 } else { 0 },
+            rollout_temperature: None,
+            dirichlet: None,
+            static_eval_variance: None,
+            level: 10,
+            c_puct_init: default_settings.c_puct_init(),
+            c_puct_base: default_settings.c_puct_base(),
+            initial_mean_action_value: default_settings.initial_mean_action_value(),
+            multi_pv: 1,
+            transpositions: false,
+            time_policy: DEFAULT_TIME_POLICY,
+            weights,
+            book,
+            position: None,
+            previous_tree: None,
+            search: None,
+        }
+    }
+
+    fn handle_command(&mut self, line: &str) -> Result<(), String> {
         let mut words = line.split_whitespace();
-        match words.next().unwrap() {
-            "quit" => break,
+        let Some(command) = words.next() else {
+            return Ok(());
+        };
+
+        // Collect the tree from the last search, if it finished on its own
+        if self
+            .search
+            .as_ref()
+            .is_some_and(|search| search.handle.is_finished())
+        {
+            self.stop_search();
+        }
+
+        match command {
             "isready" => println!("readyok"),
-            "setoption" => {
-                if [
-                    words.next().unwrap_or_default(),
-                    words.next().unwrap_or_default(),
-                    words.next().unwrap_or_default(),
-                ]
-                .join(" ")
-                    == "name HalfKomi value"
-                {
-                    if let Some(k) = words
-                        .next()
-                        .and_then(|komi_string| komi_string.parse::<i8>().ok())
-                        .and_then(Komi::from_half_komi)
-                    {
-                        komi = k;
-                    } else {
-                        panic!("Invalid komi setting \"{}\"", line);
-                    }
-                } else {
-                    panic!("Invalid setoption string \"{}\"", line);
-                }
+            "stop" => self.stop_search(),
+            "ponderhit" => match self.search.as_ref() {
+                Some(search) => search.signals.ponderhit.store(true, Ordering::SeqCst),
+                None => return Err("Received ponderhit without a search running".to_string()),
+            },
+            _ if self.search.is_some() => {
+                return Err(format!(
+                    "Cannot process \"{}\" while searching, send stop first",
+                    line
+                ))
             }
+            "setoption" => self.set_option(line)?,
             "teinewgame" => {
-                let size_string = words.next();
-                size = size_string.and_then(|s| usize::from_str(s).ok());
-                position = None;
-
+                let size = words
+                    .next()
+                    .and_then(|s| usize::from_str(s).ok())
+                    .ok_or_else(|| format!("Invalid teinewgame command \"{}\"", line))?;
                 match size {
-                    Some(4) | Some(5) | Some(6) => (),
-                    _ => panic!("Error: Unsupported size {}", size.unwrap_or_default()),
+                    4..=8 => self.size = Some(size),
+                    _ => return Err(format!("Unsupported size {}", size)),
+                }
+                if self
+                    .weights
+                    .sizes()
+                    .any(|weights_size| weights_size != size)
+                {
+                    println!(
+                        "info string Loaded weights are not for {}s, using built-in parameters instead",
+                        size
+                    );
+                }
+                if self.book.as_ref().is_some_and(|book| book.size != size) {
+                    println!(
+                        "info string Opening book is not for {}s, and will not be used",
+                        size
+                    );
                 }
+                self.position = None;
+                self.previous_tree = None;
             }
             "position" => {
-                position = match size {
-                    None => panic!("Received position without receiving teinewgame string"),
-                    Some(4) => Some(Box::new(parse_position_string::<4>(&line, komi))),
-                    Some(5) => Some(Box::new(parse_position_string::<5>(&line, komi))),
-                    Some(6) => Some(Box::new(parse_position_string::<6>(&line, komi))),
-                    Some(s) => panic!("Unsupported size {}", s),
+                self.position = Some(match self.size {
+                    None => {
+                        return Err("Received position without receiving teinewgame".to_string())
+                    }
+                    Some(4) => Box::new(parse_position_string::<4>(line, self.komi)?),
+                    Some(5) => Box::new(parse_position_string::<5>(line, self.komi)?),
+                    Some(6) => Box::new(parse_position_string::<6>(line, self.komi)?),
+                    Some(7) => Box::new(parse_position_string::<7>(line, self.komi)?),
+                    Some(8) => Box::new(parse_position_string::<8>(line, self.komi)?),
+                    Some(s) => return Err(format!("Unsupported size {}", s)),
+                })
+            }
+            "go" => {
+                let go_options = GoOptions::parse(line)?;
+                match self.size {
+                    Some(4) => self.start_search::<4>(go_options)?,
+                    Some(5) => self.start_search::<5>(go_options)?,
+                    Some(6) => self.start_search::<6>(go_options)?,
+                    Some(7) => self.start_search::<7>(go_options)?,
+                    Some(8) => self.start_search::<8>(go_options)?,
+                    Some(s) => return Err(format!("Unsupported size {}", s)),
+                    None => return Err("Received go without receiving teinewgame".to_string()),
                 }
             }
-            "go" => match size {
-                Some(4) => parse_go_string::<4>(
-                    &line,
-                    position.as_ref().and_then(|p| p.downcast_ref()).unwrap(),
-                    is_slatebot,
-                ),
-                Some(5) => parse_go_string::<5>(
-                    &line,
-                    position.as_ref().and_then(|p| p.downcast_ref()).unwrap(),
-                    is_slatebot,
-                ),
-                Some(6) => parse_go_string::<6>(
-                    &line,
-                    position.as_ref().and_then(|p| p.downcast_ref()).unwrap(),
-                    is_slatebot,
-                ),
-                Some(s) => panic!("Error: Unsupported size {}", s),
-                None => panic!("Error: Received go without receiving teinewgame string"),
-            },
-            s => panic!("Unknown command \"{}\"", s),
+            s => return Err(format!("Unknown command \"{}\"", s)),
+        }
+        Ok(())
+    }
+
+    fn print_options(&self) {
+        let optional_string =
+            |value: Option<f32>| value.map_or("<empty>".to_string(), |v| v.to_string());
+
+        println!("option name HalfKomi type spin default 0 min -10 max 10");
+        println!("option name Threads type spin default 1 min 1 max 255");
+        println!(
+            "option name Hash type spin default {} min 1 max 65535",
+            self.hash_mb
+        );
+        println!(
+            "option name RolloutDepth type spin default {} min 0 max 1000",
+            self.rollout_depth
+        );
+        println!(
+            "option name RolloutTemperature type string default {}",
+            optional_string(self.rollout_temperature.map(|t| t as f32))
+        );
+        println!(
+            "option name DirichletNoise type string default {}",
+            optional_string(self.dirichlet)
+        );
+        println!(
+            "option name StaticEvalVariance type string default {}",
+            optional_string(self.static_eval_variance)
+        );
+        println!("option name Level type spin default 10 min 1 max 10");
+        println!(
+            "option name CPuctInit type string default {}",
+            self.c_puct_init
+        );
+        println!(
+            "option name CPuctBase type string default {}",
+            self.c_puct_base
+        );
+        println!(
+            "option name InitialMeanActionValue type string default {}",
+            self.initial_mean_action_value
+        );
+        println!("option name MultiPV type spin default 1 min 1 max 256");
+        println!("option name Transpositions type check default false");
+        println!("option name TimePolicy type combo default Fraction var Fraction var MovesLeft");
+    }
+
+    fn set_option(&mut self, line: &str) -> Result<(), String> {
+        let mut words = line.split_whitespace().skip(1);
+        if words.next() != Some("name") {
+            return Err(format!("Invalid setoption string \"{}\"", line));
+        }
+        let name: Vec<&str> = words.by_ref().take_while(|word| *word != "value").collect();
+        let value: Vec<&str> = words.collect();
+        let value = value.join(" ");
+
+        match name.join(" ").as_str() {
+            "HalfKomi" => {
+                self.komi = value
+                    .parse::<i8>()
+                    .ok()
+                    .and_then(Komi::from_half_komi)
+                    .ok_or_else(|| format!("Invalid komi setting \"{}\"", line))?;
+            }
+            "Threads" => {
+                self.num_threads = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=255).contains(n))
+                    .ok_or_else(|| format!("Invalid threads setting \"{}\"", line))?;
+            }
+            "Hash" => {
+                self.hash_mb = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|mb| (1..=65535).contains(mb))
+                    .ok_or_else(|| format!("Invalid hash setting \"{}\"", line))?;
+            }
+            "RolloutDepth" => {
+                self.rollout_depth = value
+                    .parse::<u16>()
+                    .ok()
+                    .filter(|depth| *depth <= 1000)
+                    .ok_or_else(|| format!("Invalid rollout depth \"{}\"", line))?;
+            }
+            "RolloutTemperature" => {
+                self.rollout_temperature = parse_optional_float(&value)
+                    .ok_or_else(|| format!("Invalid rollout temperature \"{}\"", line))?
+                    .map(|t| t as f64);
+            }
+            "DirichletNoise" => {
+                self.dirichlet = parse_optional_float(&value)
+                    .ok_or_else(|| format!("Invalid dirichlet noise \"{}\"", line))?;
+            }
+            "StaticEvalVariance" => {
+                self.static_eval_variance = parse_optional_float(&value)
+                    .ok_or_else(|| format!("Invalid static eval variance \"{}\"", line))?;
+            }
+            "Level" => {
+                self.level = value
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|level| StrengthLevel::from_level(*level).is_some())
+                    .ok_or_else(|| format!("Invalid level \"{}\"", line))?;
+            }
+            "CPuctInit" => {
+                self.c_puct_init = parse_float(&value)
+                    .ok_or_else(|| format!("Invalid cpuct init \"{}\"", line))?;
+            }
+            "CPuctBase" => {
+                self.c_puct_base = parse_float(&value)
+                    .ok_or_else(|| format!("Invalid cpuct base \"{}\"", line))?;
+            }
+            "InitialMeanActionValue" => {
+                self.initial_mean_action_value = parse_float(&value)
+                    .filter(|value| *value <= 1.0)
+                    .ok_or_else(|| format!("Invalid initial mean action value \"{}\"", line))?;
+            }
+            "MultiPV" => {
+                self.multi_pv = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=256).contains(n))
+                    .ok_or_else(|| format!("Invalid MultiPV setting \"{}\"", line))?;
+            }
+            "Transpositions" => {
+                self.transpositions = value
+                    .parse::<bool>()
+                    .map_err(|_| format!("Invalid transpositions setting \"{}\"", line))?;
+            }
+            "TimePolicy" => {
+                self.time_policy = match value.as_str() {
+                    "Fraction" => DEFAULT_TIME_POLICY,
+                    "MovesLeft" => TimePolicy::MovesLeft,
+                    _ => return Err(format!("Invalid time policy \"{}\"", line)),
+                };
+            }
+            _ => return Err(format!("Unknown option in \"{}\"", line)),
+        }
+        Ok(())
+    }
+
+    fn mcts_settings<const S: usize>(&self, komi: Komi) -> Result<MctsSetting<S>, String> {
+        let mut settings = MctsSetting::default()
+            .num_threads(self.num_threads)
+            .mem_usage(self.hash_mb * 1024 * 1024)
+            .add_rollout_depth(self.rollout_depth)
+            .use_transpositions(self.transpositions)
+            .add_loaded_weights(&self.weights, komi)
+            .map_err(|err| format!("Cannot use the loaded weights: {}", err))?
+            .add_search_params(
+                vec![
+                    self.c_puct_init,
+                    self.c_puct_base,
+                    self.initial_mean_action_value,
+                ]
+                .into_boxed_slice(),
+            )
+            // Apply the level first, so that explicit noise settings take precedence
+            .strength(StrengthLevel::from_level(self.level).unwrap());
+        if let Some(temperature) = self.rollout_temperature {
+            settings = settings.add_rollout_temperature(temperature);
+        }
+        if let Some(alpha) = self.dirichlet {
+            settings = settings.add_dirichlet(alpha);
+        }
+        if let Some(variance) = self.static_eval_variance {
+            settings = settings.add_static_eval_variance(variance);
+        }
+        Ok(settings)
+    }
+
+    fn start_search<const S: usize>(&mut self, go_options: GoOptions) -> Result<(), String> {
+        let position: Position<S> = self
+            .position
+            .as_ref()
+            .and_then(|position| position.downcast_ref::<Position<S>>())
+            .ok_or_else(|| "Received go without receiving position".to_string())?
+            .clone();
+        if position.game_result().is_some() {
+            return Err("Cannot search, the game is already over".to_string());
+        }
+
+        // Play book moves instantly, unless we are asked to keep searching until stopped
+        if !go_options.infinite && !go_options.ponder {
+            if let Some(mv) = self
+                .book
+                .as_ref()
+                .and_then(|book| book.choose_move(&position, &mut rand::thread_rng()))
+            {
+                println!("info string Book move");
+                println!("bestmove {}", position.move_to_san(&mv));
+                return Ok(());
+            }
+        }
+
+        let mcts_settings = self.mcts_settings(position.komi())?;
+        let tree = reuse_tree(&mut self.previous_tree, &position, mcts_settings);
+
+        let multi_pv = self.multi_pv;
+        let time_manager = TimeManager::new(self.time_policy);
+        let signals = Arc::new(SearchSignals::default());
+        let thread_signals = signals.clone();
+        let handle = thread::spawn(move || {
+            Box::new(run_search(
+                tree,
+                go_options,
+                &time_manager,
+                multi_pv,
+                &thread_signals,
+            )) as Box<dyn Any + Send>
+        });
+        self.search = Some(RunningSearch { signals, handle });
+        Ok(())
+    }
+
+    /// Stop the running search, if any, and wait for it to print its best move
+    fn stop_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.signals.stop.store(true, Ordering::SeqCst);
+            match search.handle.join() {
+                Ok(tree) => self.previous_tree = Some(tree),
+                Err(_) => println!("info string Error: Search thread panicked"),
+            }
+        }
+    }
+}
+
+/// Parse a non-negative float option
+fn parse_float(value: &str) -> Option<f32> {
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|value| value.is_finite() && *value >= 0.0)
+}
+
+/// Parse a float option that can be disabled, by setting it to an empty string
+fn parse_optional_float(value: &str) -> Option<Option<f32>> {
+    if value.is_empty() || value == "<empty>" || value.eq_ignore_ascii_case("none") {
+        Some(None)
+    } else {
+        parse_float(value).filter(|value| *value > 0.0).map(Some)
+    }
+}
+
+/// The parameters of a `go` command
+#[derive(Debug, Default)]
+struct GoOptions {
+    white_time: Option<Duration>,
+    black_time: Option<Duration>,
+    white_inc: Duration,
+    black_inc: Duration,
+    movetime: Option<Duration>,
+    nodes: Option<u64>,
+    infinite: bool,
+    ponder: bool,
+}
+
+impl GoOptions {
+    fn parse(line: &str) -> Result<Self, String> {
+        let mut go_options = GoOptions::default();
+        let mut words = line.split_whitespace().skip(1);
+
+        let parse_number = |word: Option<&str>| {
+            word.and_then(|w| w.parse::<u64>().ok())
+                .ok_or_else(|| format!("Invalid go command \"{}\"", line))
+        };
+
+        while let Some(word) = words.next() {
+            match word {
+                "wtime" => {
+                    go_options.white_time = Some(Duration::from_millis(parse_number(words.next())?))
+                }
+                "btime" => {
+                    go_options.black_time = Some(Duration::from_millis(parse_number(words.next())?))
+                }
+                "winc" => go_options.white_inc = Duration::from_millis(parse_number(words.next())?),
+                "binc" => go_options.black_inc = Duration::from_millis(parse_number(words.next())?),
+                "movetime" => {
+                    go_options.movetime = Some(Duration::from_millis(parse_number(words.next())?))
+                }
+                "nodes" => go_options.nodes = Some(parse_number(words.next())?),
+                "infinite" => go_options.infinite = true,
+                "ponder" => go_options.ponder = true,
+                _ => return Err(format!("Invalid go command \"{}\"", line)),
+            }
+        }
+        Ok(go_options)
+    }
+
+    /// Deadlines for the move, if we are playing on a clock
+    fn clock_deadlines<const S: usize>(
+        &self,
+        time_manager: &TimeManager,
+        position: &Position<S>,
+    ) -> Option<Deadlines> {
+        let (time, increment) = match position.side_to_move() {
+            Color::White => (self.white_time?, self.white_inc),
+            Color::Black => (self.black_time?, self.black_inc),
+        };
+        time_manager.deadlines::<S>(
+            &TimeControl::Time(time, increment),
+            position.half_moves_played(),
+        )
+    }
+}
+
+/// Search the position until the limits set by the `go` command are reached, or the search is stopped, and print the best move
+fn run_search<const S: usize>(
+    mut tree: MonteCarloTree<S>,
+    go_options: GoOptions,
+    time_manager: &TimeManager,
+    multi_pv: usize,
+    signals: &SearchSignals,
+) -> MonteCarloTree<S> {
+    let mut start_time = Instant::now();
+    // Handicap levels search a limited number of nodes per move, however the search is started
+    let level_max_visits = |tree: &MonteCarloTree<S>| {
+        tree.settings()
+            .node_limit()
+            .map(|max_nodes| tree.visits() as u64 + max_nodes)
+    };
+
+    if go_options.ponder {
+        // Search until the opponent plays the move we're pondering on, or we are stopped
+        let max_visits = level_max_visits(&tree);
+        search_until(&mut tree, start_time, max_visits, multi_pv, || {
+            signals.stop.load(Ordering::SeqCst) || signals.ponderhit.load(Ordering::SeqCst)
+        });
+        wait_for(|| {
+            signals.stop.load(Ordering::SeqCst) || signals.ponderhit.load(Ordering::SeqCst)
+        });
+        if signals.stop.load(Ordering::SeqCst) {
+            print_best_move(&tree);
+            return tree;
+        }
+        // The opponent played the expected move, so our clock starts now
+        start_time = Instant::now();
+    }
+
+    let clock_deadlines = go_options.clock_deadlines(time_manager, tree.position());
+
+    if go_options.infinite
+        || (clock_deadlines.is_none()
+            && go_options.movetime.is_none()
+            && go_options.nodes.is_none())
+    {
+        let max_visits = level_max_visits(&tree);
+        search_until(&mut tree, start_time, max_visits, multi_pv, || {
+            signals.stop.load(Ordering::SeqCst)
+        });
+        // Even if the search ends early, the best move must not be sent before we are stopped
+        wait_for(|| signals.stop.load(Ordering::SeqCst));
+    } else if let (Some(deadlines), None, None) =
+        (clock_deadlines, go_options.movetime, go_options.nodes)
+    {
+        let start_visits = tree.visits();
+        tree.search_until_deadlines(deadlines, &signals.stop, |tree| {
+            print_info(tree, start_time, start_visits, multi_pv)
+        });
+    } else {
+        let max_time = [
+            go_options.movetime.map(|movetime| movetime.mul_f64(0.7)),
+            clock_deadlines.map(|deadlines| deadlines.soft),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        let max_visits = [
+            go_options.nodes.map(|nodes| tree.visits() as u64 + nodes),
+            level_max_visits(&tree),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        search_until(&mut tree, start_time, max_visits, multi_pv, || {
+            signals.stop.load(Ordering::SeqCst)
+                || max_time.is_some_and(|max_time| start_time.elapsed() > max_time)
+        });
+    }
+
+    print_best_move(&tree);
+    tree
+}
+
+/// Search until `should_stop` returns true, or the tree reaches `max_visits` visits.
+/// Prints search info at exponentially increasing intervals
+fn search_until<const S: usize>(
+    tree: &mut MonteCarloTree<S>,
+    start_time: Instant,
+    max_visits: Option<u64>,
+    multi_pv: usize,
+    should_stop: impl Fn() -> bool,
+) {
+    let start_visits = tree.visits();
+    let mut next_info_visits = start_visits as u64 + 200;
+    loop {
+        let visits = tree.visits() as u64;
+        let nodes = max_visits.map_or(1000, |max_visits| {
+            max_visits.saturating_sub(visits).min(1000)
+        });
+        // Searching a proven position does not change the result
+        if nodes == 0 || should_stop() || tree.proven_plies().is_some() {
+            break;
+        }
+        if let Err(err) = tree.search_nodes(nodes) {
+            println!("info string Warning: {}", err);
+            break;
+        }
+        if tree.visits() as u64 >= next_info_visits {
+            print_info(tree, start_time, start_visits, multi_pv);
+            next_info_visits = (tree.visits() as f64 * 1.26) as u64;
+        }
+    }
+    print_info(tree, start_time, start_visits, multi_pv);
+}
+
+fn wait_for(condition: impl Fn() -> bool) {
+    while !condition() {
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Print search info for the `multi_pv` best moves. Nodes per second only counts the nodes searched since `start_visits`,
+/// since the tree may have been reused from an earlier search
+fn print_info<const S: usize>(
+    tree: &MonteCarloTree<S>,
+    start_time: Instant,
+    start_visits: u32,
+    multi_pv: usize,
+) {
+    let position = tree.position();
+    for (i, line) in tree.multi_pv(multi_pv).iter().enumerate() {
+        let multi_pv_string = if multi_pv > 1 {
+            format!(" multipv {}", i + 1)
+        } else {
+            String::new()
+        };
+        // Proven lines are reported in moves, not plies. Negative if we are getting mated
+        let score_string = match line.proven_plies {
+            Some(plies) => format!("mate {}", plies.signum() * (plies.abs() + 1) / 2),
+            None => format!("cp {}", (line.winning_probability * 200.0 - 100.0) as i64),
+        };
+        println!(
+            "info depth {} seldepth {}{} nodes {} score {} time {} nps {:.0} pv {}",
+            ((tree.visits() as f64 / 10.0).log2()) as u64,
+            line.pv.len(),
+            multi_pv_string,
+            tree.visits(),
+            score_string,
+            start_time.elapsed().as_millis(),
+            (tree.visits() - start_visits) as f32 / start_time.elapsed().as_secs_f32(),
+            line.pv
+                .iter()
+                .map(|mv| position.move_to_san(mv))
+                .collect::<Vec<String>>()
+                .join(" ")
+        );
+    }
+}
+
+fn print_best_move<const S: usize>(tree: &MonteCarloTree<S>) {
+    match tree.best_move() {
+        Some((best_move, _)) => {
+            println!("bestmove {}", tree.position().move_to_san(&best_move))
         }
+        None => println!("info string Error: Search did not find any moves"),
     }
 }
 
-fn parse_position_string<const S: usize>(line: &str, komi: Komi) -> Position<S> {
+fn parse_position_string<const S: usize>(line: &str, komi: Komi) -> Result<Position<S>, String> {
     let mut words_iter = line.split_whitespace();
     words_iter.next(); // position
     let mut position = match words_iter.next() {
         Some("startpos") => Position::start_position_with_komi(komi),
         Some("tps") => {
             let tps: String = (&mut words_iter).take(3).collect::<Vec<_>>().join(" ");
-            <Position<S>>::from_fen_with_komi(&tps, komi).unwrap()
+            <Position<S>>::from_fen_with_komi(&tps, komi)
+                .map_err(|err| format!("Invalid tps \"{}\": {}", tps, err))?
+        }
+        _ => {
+            return Err(format!(
+                "Expected \"startpos\" or \"tps\" to specify position in \"{}\"",
+                line
+            ))
         }
-        _ => panic!("Expected \"startpos\" or \"tps\" to specify position."),
     };
 
     match words_iter.next() {
         Some("moves") => {
             for move_string in words_iter {
-                position.do_move(position.move_from_san(move_string).unwrap());
+                let mv = position
+                    .move_from_san(move_string)
+                    .map_err(|err| format!("Invalid move \"{}\": {}", move_string, err))?;
+                if !position.move_is_legal(mv) || position.game_result().is_some() {
+                    return Err(format!("Illegal move \"{}\"", move_string));
+                }
+                position.do_move(mv);
             }
         }
-        Some(s) => panic!("Expected \"moves\" in \"{}\", got \"{}\".", line, s),
+        Some(s) => {
+            return Err(format!(
+                "Expected \"moves\" in \"{}\", got \"{}\".",
+                line, s
+            ))
+        }
         None => (),
     }
-    position
+    Ok(position)
 }
 
-fn parse_go_string<const S: usize>(line: &str, position: &Position<S>, is_slatebot: bool) {
-    let mut words = line.split_whitespace();
-    words.next(); // go
-
-    let mcts_settings = if is_slatebot {
-        MctsSetting::default().add_rollout_depth(200)
-    } else {
-        MctsSetting::default()
-    };
-
-    match words.next() {
-        Some("movetime") => {
-            let msecs = words.next().unwrap();
-            let movetime = Duration::from_millis(u64::from_str(msecs).unwrap());
-            let start_time = Instant::now();
-            let mut tree = search::MonteCarloTree::new(position.clone(), mcts_settings);
-
-            for i in 0.. {
-                let nodes_to_search = (200.0 * f64::powf(1.26, i as f64)) as u64;
-                let mut oom = false;
-                for _ in 0..nodes_to_search {
-                    if let Err(err) = tree.select() {
-                        eprintln!("Warning: {err}");
-                        oom = true;
-                        break;
-                    }
-                }
-                let (best_move, best_score) = tree.best_move().unwrap();
-                let pv: Vec<_> = tree.pv().collect();
-                println!(
-                    "info depth {} seldepth {} nodes {} score cp {} time {} nps {:.0} pv {}",
-                    ((tree.visits() as f64 / 10.0).log2()) as u64,
-                    pv.len(),
-                    tree.visits(),
-                    (best_score * 200.0 - 100.0) as i64,
-                    start_time.elapsed().as_millis(),
-                    tree.visits() as f32 / start_time.elapsed().as_secs_f32(),
-                    pv.iter()
-                        .map(|mv| position.move_to_san(mv))
-                        .collect::<Vec<String>>()
-                        .join(" ")
-                );
-                if oom || start_time.elapsed().as_secs_f64() > movetime.as_secs_f64() * 0.7 {
-                    println!("bestmove {}", position.move_to_san(&best_move));
-                    break;
-                }
+/// Continue from the previous search tree, if the new position can be reached from it.
+/// Otherwise, start a new tree
+fn reuse_tree<const S: usize>(
+    previous_tree: &mut Option<Box<dyn Any + Send>>,
+    position: &Position<S>,
+    mcts_settings: MctsSetting<S>,
+) -> MonteCarloTree<S> {
+    if let Some(mut tree) = previous_tree
+        .take()
+        .and_then(|tree| tree.downcast::<MonteCarloTree<S>>().ok())
+    {
+        let old_moves = tree.position().moves();
+        if *tree.settings() == mcts_settings && position.moves().starts_with(old_moves) {
+            let new_moves = position.moves()[old_moves.len()..].to_vec();
+            for mv in new_moves {
+                tree.advance(mv);
             }
-        }
-        Some("wtime") | Some("btime") | Some("winc") | Some("binc") => {
-            let parse_time = |s: Option<&str>| {
-                Duration::from_millis(
-                    s.and_then(|w| w.parse().ok())
-                        .unwrap_or_else(|| panic!("Incorrect go command {}", line)),
-                )
-            };
-            let mut words = line.split_whitespace().skip(1).peekable();
-            let mut white_time = Duration::default();
-            let mut white_inc = Duration::default();
-            let mut black_time = Duration::default();
-            let mut black_inc = Duration::default();
-
-            while let Some(word) = words.next() {
-                match word {
-                    "wtime" => white_time = parse_time(words.next()),
-                    "winc" => white_inc = parse_time(words.next()),
-                    "btime" => black_time = parse_time(words.next()),
-                    "binc" => black_inc = parse_time(words.next()),
-                    _ => (),
-                }
+            if tree.position() == position {
+                return *tree;
             }
-
-            println!("{:?}, {:?}", white_time, black_time);
-
-            let max_time = match position.side_to_move() {
-                Color::White => white_time / 5 + white_inc / 2,
-                Color::Black => black_time / 5 + black_inc / 2,
-            };
-
-            let start_time = Instant::now();
-
-            let mut tree = MonteCarloTree::new(position.clone(), mcts_settings);
-            tree.search_for_time(max_time, |tree| {
-                let best_score = tree.best_move().unwrap().1;
-                let pv: Vec<_> = tree.pv().collect();
-                println!(
-                    "info depth {} seldepth {} nodes {} score cp {} time {} nps {:.0} pv {}",
-                    ((tree.visits() as f64 / 10.0).log2()) as u64,
-                    pv.len(),
-                    tree.visits(),
-                    (best_score * 200.0 - 100.0) as i64,
-                    start_time.elapsed().as_millis(),
-                    tree.visits() as f32 / start_time.elapsed().as_secs_f32(),
-                    pv.iter()
-                        .map(|mv| position.move_to_san(mv))
-                        .collect::<Vec<String>>()
-                        .join(" ")
-                );
-            });
-            let best_move = tree.best_move().unwrap().0;
-
-            println!("bestmove {}", position.move_to_san(&best_move));
-        }
-        Some(_) | None => {
-            panic!("Invalid go command \"{}\"", line);
         }
     }
+    MonteCarloTree::new(position.clone(), mcts_settings)
 }
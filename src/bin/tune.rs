@@ -1,13 +1,26 @@
+use std::array;
+use std::fs;
+use std::io;
 use std::path::Path;
 use std::process::exit;
+use std::time::Duration;
 
-use clap::{Arg, Command};
+use clap::{Arg, ArgMatches, Command};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 use tiltak::evaluation::parameters::{
     self, NUM_POLICY_FEATURES_4S, NUM_POLICY_FEATURES_5S, NUM_POLICY_FEATURES_6S,
-    NUM_VALUE_FEATURES_4S, NUM_VALUE_FEATURES_5S, NUM_VALUE_FEATURES_6S,
+    NUM_POLICY_FEATURES_7S, NUM_POLICY_FEATURES_8S, NUM_VALUE_FEATURES_4S, NUM_VALUE_FEATURES_5S,
+    NUM_VALUE_FEATURES_6S, NUM_VALUE_FEATURES_7S, NUM_VALUE_FEATURES_8S,
 };
+use tiltak::evaluation::weights::{LoadedWeights, Weights, WeightsKind};
 use tiltak::position::Komi;
+use tiltak::search::strength::StrengthLevel;
+use tiltak::search::{MctsSetting, TimeControl};
+use tiltak::tune::calibration;
+use tiltak::tune::engine_match::{self, MatchPlayer, Sprt};
+use tiltak::tune::openings::openings_from_file;
 use tiltak::tune::training::TrainingOptions;
 use tiltak::tune::{spsa, training};
 
@@ -20,9 +33,9 @@ fn main() {
                 .global(true)
                 .short('s')
                 .long("size")
-                .help("Board size")
+                .help("Board size. 7s and 8s use untrained placeholder parameters, transferred from 6s")
                 .num_args(1)
-                .value_parser(clap::value_parser!(u64).range(4..=6)))
+                .value_parser(clap::value_parser!(u64).range(4..=8)))
         .arg(
             Arg::new("komi")
                 .global(true)
@@ -32,6 +45,18 @@ fn main() {
                 .value_parser(|input: &str| {
                     input.parse::<Komi>()
                 }))
+        .arg(
+            Arg::new("value-weights")
+                .global(true)
+                .long("value-weights")
+                .help("Start selfplay training from the value parameters in this weights file, instead of the built-in parameters.")
+                .num_args(1))
+        .arg(
+            Arg::new("policy-weights")
+                .global(true)
+                .long("policy-weights")
+                .help("Start selfplay training from the policy parameters in this weights file, instead of the built-in parameters.")
+                .num_args(1))
         .subcommand(Command::new("selfplay")
             .about("Tune value and policy constants by playing against itself. Will write the games to text files in the working directory.")
             .arg(
@@ -104,6 +129,9 @@ fn main() {
                     .required(true)
                     .value_name("move_scores.txt"))
         )
+        .subcommand(human_games_command())
+        .subcommand(match_command())
+        .subcommand(calibrate_command())
         .subcommand(Command::new("spsa")
             .about("Tune exploration parameters using SPSA. Starting values are hard-coded.")
             .arg(Arg::new("book")
@@ -152,8 +180,16 @@ fn main() {
                         >(
                             options,
                             *komi,
-                            *parameters::value_features_4s(*komi),
-                            *parameters::policy_features_4s(*komi),
+                            initial_params::<4, _>(
+                                &matches,
+                                WeightsKind::Value,
+                                parameters::value_features_4s(*komi),
+                            ),
+                            initial_params::<4, _>(
+                                &matches,
+                                WeightsKind::Policy,
+                                parameters::policy_features_4s(*komi),
+                            ),
                             vec![],
                             vec![],
                             0,
@@ -166,8 +202,16 @@ fn main() {
                         >(
                             options,
                             *komi,
-                            *parameters::value_features_5s(*komi),
-                            *parameters::policy_features_5s(*komi),
+                            initial_params::<5, _>(
+                                &matches,
+                                WeightsKind::Value,
+                                parameters::value_features_5s(*komi),
+                            ),
+                            initial_params::<5, _>(
+                                &matches,
+                                WeightsKind::Policy,
+                                parameters::policy_features_5s(*komi),
+                            ),
                             vec![],
                             vec![],
                             0,
@@ -180,8 +224,60 @@ fn main() {
                         >(
                             options,
                             *komi,
-                            *parameters::value_features_6s(*komi),
-                            *parameters::policy_features_6s(*komi),
+                            initial_params::<6, _>(
+                                &matches,
+                                WeightsKind::Value,
+                                parameters::value_features_6s(*komi),
+                            ),
+                            initial_params::<6, _>(
+                                &matches,
+                                WeightsKind::Policy,
+                                parameters::policy_features_6s(*komi),
+                            ),
+                            vec![],
+                            vec![],
+                            0,
+                        )
+                        .unwrap(),
+                        7 => training::train_perpetually::<
+                            7,
+                            NUM_VALUE_FEATURES_7S,
+                            NUM_POLICY_FEATURES_7S,
+                        >(
+                            options,
+                            *komi,
+                            initial_params::<7, _>(
+                                &matches,
+                                WeightsKind::Value,
+                                parameters::value_features_7s(*komi),
+                            ),
+                            initial_params::<7, _>(
+                                &matches,
+                                WeightsKind::Policy,
+                                parameters::policy_features_7s(*komi),
+                            ),
+                            vec![],
+                            vec![],
+                            0,
+                        )
+                        .unwrap(),
+                        8 => training::train_perpetually::<
+                            8,
+                            NUM_VALUE_FEATURES_8S,
+                            NUM_POLICY_FEATURES_8S,
+                        >(
+                            options,
+                            *komi,
+                            initial_params::<8, _>(
+                                &matches,
+                                WeightsKind::Value,
+                                parameters::value_features_8s(*komi),
+                            ),
+                            initial_params::<8, _>(
+                                &matches,
+                                WeightsKind::Policy,
+                                parameters::policy_features_8s(*komi),
+                            ),
                             vec![],
                             vec![],
                             0,
@@ -226,6 +322,18 @@ fn main() {
                             NUM_POLICY_FEATURES_6S,
                         >(options, *komi)
                         .unwrap(),
+                        7 => training::train_from_scratch::<
+                            7,
+                            NUM_VALUE_FEATURES_7S,
+                            NUM_POLICY_FEATURES_7S,
+                        >(options, *komi)
+                        .unwrap(),
+                        8 => training::train_from_scratch::<
+                            8,
+                            NUM_VALUE_FEATURES_8S,
+                            NUM_POLICY_FEATURES_8S,
+                        >(options, *komi)
+                        .unwrap(),
                         _ => panic!("Size {} not supported.", size),
                     }
                     break;
@@ -247,19 +355,86 @@ fn main() {
             match size {
                 4 => {
                     training::continue_training::<4, NUM_VALUE_FEATURES_4S, NUM_POLICY_FEATURES_4S>(
-                        options, *komi,
+                        options,
+                        *komi,
+                        initial_params::<4, _>(
+                            &matches,
+                            WeightsKind::Value,
+                            parameters::value_features_4s(*komi),
+                        ),
+                        initial_params::<4, _>(
+                            &matches,
+                            WeightsKind::Policy,
+                            parameters::policy_features_4s(*komi),
+                        ),
                     )
                     .unwrap()
                 }
                 5 => {
                     training::continue_training::<5, NUM_VALUE_FEATURES_5S, NUM_POLICY_FEATURES_5S>(
-                        options, *komi,
+                        options,
+                        *komi,
+                        initial_params::<5, _>(
+                            &matches,
+                            WeightsKind::Value,
+                            parameters::value_features_5s(*komi),
+                        ),
+                        initial_params::<5, _>(
+                            &matches,
+                            WeightsKind::Policy,
+                            parameters::policy_features_5s(*komi),
+                        ),
                     )
                     .unwrap()
                 }
                 6 => {
                     training::continue_training::<6, NUM_VALUE_FEATURES_6S, NUM_POLICY_FEATURES_6S>(
-                        options, *komi,
+                        options,
+                        *komi,
+                        initial_params::<6, _>(
+                            &matches,
+                            WeightsKind::Value,
+                            parameters::value_features_6s(*komi),
+                        ),
+                        initial_params::<6, _>(
+                            &matches,
+                            WeightsKind::Policy,
+                            parameters::policy_features_6s(*komi),
+                        ),
+                    )
+                    .unwrap()
+                }
+                7 => {
+                    training::continue_training::<7, NUM_VALUE_FEATURES_7S, NUM_POLICY_FEATURES_7S>(
+                        options,
+                        *komi,
+                        initial_params::<7, _>(
+                            &matches,
+                            WeightsKind::Value,
+                            parameters::value_features_7s(*komi),
+                        ),
+                        initial_params::<7, _>(
+                            &matches,
+                            WeightsKind::Policy,
+                            parameters::policy_features_7s(*komi),
+                        ),
+                    )
+                    .unwrap()
+                }
+                8 => {
+                    training::continue_training::<8, NUM_VALUE_FEATURES_8S, NUM_POLICY_FEATURES_8S>(
+                        options,
+                        *komi,
+                        initial_params::<8, _>(
+                            &matches,
+                            WeightsKind::Value,
+                            parameters::value_features_8s(*komi),
+                        ),
+                        initial_params::<8, _>(
+                            &matches,
+                            WeightsKind::Policy,
+                            parameters::policy_features_8s(*komi),
+                        ),
                     )
                     .unwrap()
                 }
@@ -290,6 +465,20 @@ fn main() {
                     .unwrap();
                     println!("{:?}", value_params);
                 }
+                7 => {
+                    let value_params = training::tune_value_from_file::<7, NUM_VALUE_FEATURES_7S>(
+                        file_name, *komi,
+                    )
+                    .unwrap();
+                    println!("{:?}", value_params);
+                }
+                8 => {
+                    let value_params = training::tune_value_from_file::<8, NUM_VALUE_FEATURES_8S>(
+                        file_name, *komi,
+                    )
+                    .unwrap();
+                    println!("{:?}", value_params);
+                }
                 _ => panic!("Size {} not supported.", size),
             }
         }
@@ -330,9 +519,85 @@ fn main() {
                     println!("Value: {:?}", value_params);
                     println!("Policy: {:?}", policy_params);
                 }
+                7 => {
+                    let (value_params, policy_params) =
+                        training::tune_value_and_policy_from_file::<
+                            7,
+                            NUM_VALUE_FEATURES_7S,
+                            NUM_POLICY_FEATURES_7S,
+                        >(value_file_name, policy_file_name, *komi)
+                        .unwrap();
+                    println!("Value: {:?}", value_params);
+                    println!("Policy: {:?}", policy_params);
+                }
+                8 => {
+                    let (value_params, policy_params) =
+                        training::tune_value_and_policy_from_file::<
+                            8,
+                            NUM_VALUE_FEATURES_8S,
+                            NUM_POLICY_FEATURES_8S,
+                        >(value_file_name, policy_file_name, *komi)
+                        .unwrap();
+                    println!("Value: {:?}", value_params);
+                    println!("Policy: {:?}", policy_params);
+                }
                 _ => panic!("Size {} not supported.", size),
             }
         }
+        Some(("human-games", arg)) => match size {
+            4 => train_from_human_games::<4, NUM_VALUE_FEATURES_4S, NUM_POLICY_FEATURES_4S>(
+                &matches,
+                arg,
+                *komi,
+                parameters::value_features_4s(*komi),
+                parameters::policy_features_4s(*komi),
+            ),
+            5 => train_from_human_games::<5, NUM_VALUE_FEATURES_5S, NUM_POLICY_FEATURES_5S>(
+                &matches,
+                arg,
+                *komi,
+                parameters::value_features_5s(*komi),
+                parameters::policy_features_5s(*komi),
+            ),
+            6 => train_from_human_games::<6, NUM_VALUE_FEATURES_6S, NUM_POLICY_FEATURES_6S>(
+                &matches,
+                arg,
+                *komi,
+                parameters::value_features_6s(*komi),
+                parameters::policy_features_6s(*komi),
+            ),
+            7 => train_from_human_games::<7, NUM_VALUE_FEATURES_7S, NUM_POLICY_FEATURES_7S>(
+                &matches,
+                arg,
+                *komi,
+                parameters::value_features_7s(*komi),
+                parameters::policy_features_7s(*komi),
+            ),
+            8 => train_from_human_games::<8, NUM_VALUE_FEATURES_8S, NUM_POLICY_FEATURES_8S>(
+                &matches,
+                arg,
+                *komi,
+                parameters::value_features_8s(*komi),
+                parameters::policy_features_8s(*komi),
+            ),
+            _ => panic!("Size {} not supported.", size),
+        },
+        Some(("match", arg)) => match size {
+            4 => run_engine_match::<4>(arg, *komi),
+            5 => run_engine_match::<5>(arg, *komi),
+            6 => run_engine_match::<6>(arg, *komi),
+            7 => run_engine_match::<7>(arg, *komi),
+            8 => run_engine_match::<8>(arg, *komi),
+            _ => panic!("Size {} not supported.", size),
+        },
+        Some(("calibrate", arg)) => match size {
+            4 => run_calibration::<4>(arg, *komi),
+            5 => run_calibration::<5>(arg, *komi),
+            6 => run_calibration::<6>(arg, *komi),
+            7 => run_calibration::<7>(arg, *komi),
+            8 => run_calibration::<8>(arg, *komi),
+            _ => panic!("Size {} not supported.", size),
+        },
         Some(("spsa", arg)) => {
             let mut variables = vec![
                 spsa::Variable {
@@ -367,6 +632,16 @@ fn main() {
                     arg.get_one::<String>("book").map(|s| s.as_ref()),
                     *komi,
                 ),
+                7 => spsa::tune::<7>(
+                    &mut variables,
+                    arg.get_one::<String>("book").map(|s| s.as_ref()),
+                    *komi,
+                ),
+                8 => spsa::tune::<8>(
+                    &mut variables,
+                    arg.get_one::<String>("book").map(|s| s.as_ref()),
+                    *komi,
+                ),
                 _ => panic!("Size {} not supported.", size),
             }
         }
@@ -376,3 +651,467 @@ fn main() {
         }
     }
 }
+
+/// The parameters from the `--value-weights` or `--policy-weights` file, or the built-in parameters if the option is not set
+fn initial_params<const S: usize, const N: usize>(
+    matches: &ArgMatches,
+    kind: WeightsKind,
+    built_in_params: &[f32; N],
+) -> [f32; N] {
+    let arg = match kind {
+        WeightsKind::Value => "value-weights",
+        WeightsKind::Policy => "policy-weights",
+    };
+    let Some(path) = matches.get_one::<String>(arg) else {
+        return *built_in_params;
+    };
+    let weights = Weights::from_file(path)
+        .and_then(|weights| weights.validate_as::<S>(kind).map(|()| weights))
+        .unwrap_or_else(|err| {
+            eprintln!("Error: Failed to load {}: {}", path, err);
+            exit(1)
+        });
+    weights.params.try_into().unwrap()
+}
+
+fn human_games_command() -> Command {
+    let command = Command::new("human-games")
+        .about("Tune value and policy constants from human games, read from PTN files or the playtak.com games database. Writes the tuned parameters to weights files in the working directory.")
+        .arg(Arg::new("ptn-files")
+            .index(1)
+            .num_args(0..)
+            .value_name("games.ptn"))
+        .arg(Arg::new("min-rating")
+            .long("min-rating")
+            .help("Only train on games where both players are rated at least this high. Games from PTN files need Rating1 and Rating2 tags.")
+            .num_args(1)
+            .value_parser(clap::value_parser!(i64)))
+        .arg(Arg::new("relabel-nodes")
+            .long("relabel-nodes")
+            .help("Label each position with a search of this many nodes, instead of with the game result and the played move.")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u32)))
+        .arg(Arg::new("from-scratch")
+            .long("from-scratch")
+            .help("Start from randomly initialized parameters, instead of the built-in parameters or the --value-weights and --policy-weights files.")
+            .action(clap::ArgAction::SetTrue))
+        .arg(Arg::new("output-prefix")
+            .long("output-prefix")
+            .help("Prefix of the written weights files.")
+            .default_value("human")
+            .num_args(1));
+    #[cfg(feature = "sqlite")]
+    let command = command.arg(
+        Arg::new("sqlite")
+            .long("sqlite")
+            .help("Read games from the playtak.com games database, in addition to the PTN files.")
+            .num_args(1)
+            .value_name("games_anon.db"),
+    );
+    command
+}
+
+/// Read human games from the files given to the `human-games` subcommand, tune on them, and write the new weights files
+fn train_from_human_games<const S: usize, const N: usize, const M: usize>(
+    matches: &ArgMatches,
+    arg: &ArgMatches,
+    komi: Komi,
+    built_in_value_params: &[f32; N],
+    built_in_policy_params: &[f32; M],
+) {
+    let min_rating = arg.get_one::<i64>("min-rating").copied();
+    let mut games = vec![];
+    for file_name in arg.get_many::<String>("ptn-files").into_iter().flatten() {
+        match training::read_human_games_from_ptn::<S>(file_name, komi, min_rating) {
+            Ok(mut file_games) => games.append(&mut file_games),
+            Err(err) => {
+                eprintln!("Error: Failed to read {}: {}", file_name, err);
+                exit(1)
+            }
+        }
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(database) = arg.get_one::<String>("sqlite") {
+        let mut query = tiltak::sqlite::GameQuery::new().komi(komi);
+        if let Some(min_rating) = min_rating {
+            query = query.min_rating(min_rating);
+        }
+        match query.read_games::<S, _>(database) {
+            Ok(mut database_games) => {
+                println!("Read {} games from {}", database_games.len(), database);
+                games.append(&mut database_games);
+            }
+            Err(err) => {
+                eprintln!("Error: Failed to read {}: {}", database, err);
+                exit(1)
+            }
+        }
+    }
+    if games.is_empty() {
+        eprintln!("Error: No games to train on");
+        exit(1)
+    }
+
+    let (initial_value_params, initial_policy_params) = if arg.get_flag("from-scratch") {
+        let mut rng = StdRng::from_seed(Default::default());
+        (
+            array::from_fn(|_| rng.gen_range(-0.01..0.01)),
+            array::from_fn(|_| rng.gen_range(-0.01..0.01)),
+        )
+    } else {
+        (
+            initial_params::<S, N>(matches, WeightsKind::Value, built_in_value_params),
+            initial_params::<S, M>(matches, WeightsKind::Policy, built_in_policy_params),
+        )
+    };
+
+    let (value_params, policy_params) = training::tune_from_human_games::<S, N, M>(
+        &games,
+        arg.get_one::<u32>("relabel-nodes").copied(),
+        &initial_value_params,
+        &initial_policy_params,
+    )
+    .unwrap();
+
+    let prefix = arg.get_one::<String>("output-prefix").unwrap();
+    for (kind, params) in [
+        (WeightsKind::Value, value_params.to_vec()),
+        (WeightsKind::Policy, policy_params.to_vec()),
+    ] {
+        let file_name = format!("{}_{}_{}s.weights", prefix, kind, S);
+        Weights::new(kind, S, komi, params)
+            .to_file(&file_name)
+            .unwrap();
+        println!("Wrote {} parameters to {}", kind, file_name);
+    }
+}
+
+fn match_command() -> Command {
+    let mut command = Command::new("match")
+        .about("Play a match between two engine configurations, and report the Elo difference. Each opening is played with both colors. Writes the games to a PTN file.")
+        .arg(Arg::new("games")
+            .long("games")
+            .help("Maximum number of games to play.")
+            .default_value("1000")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u64)))
+        .arg(Arg::new("book")
+            .long("book")
+            .help("Openings for the games, either one opening per line, or an opening book file, which gives every line of the book up to 6 plies. Defaults to a different random opening of four flat placements for each pair of games.")
+            .num_args(1)
+            .value_name("book.txt"))
+        .arg(Arg::new("sprt")
+            .long("sprt")
+            .help("Stop the match early with a sequential probability ratio test, of whether engine 1 is elo0 or elo1 Elo stronger than engine 2.")
+            .num_args(1)
+            .value_name("elo0,elo1")
+            .allow_hyphen_values(true)
+            .value_parser(parse_sprt_bounds))
+        .arg(Arg::new("alpha")
+            .long("alpha")
+            .help("SPRT false positive rate.")
+            .default_value("0.05")
+            .num_args(1)
+            .value_parser(clap::value_parser!(f64)))
+        .arg(Arg::new("beta")
+            .long("beta")
+            .help("SPRT false negative rate.")
+            .default_value("0.05")
+            .num_args(1)
+            .value_parser(clap::value_parser!(f64)))
+        .arg(Arg::new("output")
+            .long("output")
+            .help("File to write the games to.")
+            .default_value("match.ptn")
+            .num_args(1));
+    // Clap needs static argument names
+    for (player, [name, default_name, nodes, tc, value_weights, policy_weights, search_params]) in [
+        (
+            "1",
+            [
+                "name1",
+                "tiltak1",
+                "nodes1",
+                "tc1",
+                "value-weights1",
+                "policy-weights1",
+                "search-params1",
+            ],
+        ),
+        (
+            "2",
+            [
+                "name2",
+                "tiltak2",
+                "nodes2",
+                "tc2",
+                "value-weights2",
+                "policy-weights2",
+                "search-params2",
+            ],
+        ),
+    ] {
+        command = command
+            .arg(Arg::new(name)
+                .long(name)
+                .help(format!("Name of engine {} in the PTN output.", player))
+                .default_value(default_name)
+                .num_args(1))
+            .arg(Arg::new(nodes)
+                .long(nodes)
+                .help(format!("Nodes per move for engine {}.", player))
+                .conflicts_with(tc)
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64)))
+            .arg(Arg::new(tc)
+                .long(tc)
+                .help(format!("Time control for engine {}, as seconds per game plus seconds of increment. Defaults to 10000 nodes per move if neither this nor the node count are set.", player))
+                .num_args(1)
+                .value_name("60+0.6")
+                .value_parser(parse_time_control))
+            .arg(Arg::new(value_weights)
+                .long(value_weights)
+                .help(format!("Value weights file for engine {}, instead of the built-in parameters.", player))
+                .num_args(1))
+            .arg(Arg::new(policy_weights)
+                .long(policy_weights)
+                .help(format!("Policy weights file for engine {}, instead of the built-in parameters.", player))
+                .num_args(1))
+            .arg(Arg::new(search_params)
+                .long(search_params)
+                .help(format!("Comma-separated search parameters for engine {}, instead of the built-in parameters.", player))
+                .num_args(1)
+                .value_name("1.5,2200,0.61")
+                .value_parser(parse_search_params));
+    }
+    command
+}
+
+fn parse_sprt_bounds(input: &str) -> Result<(f64, f64), String> {
+    let (elo0, elo1) = input
+        .split_once(',')
+        .ok_or_else(|| "Expected two comma-separated Elo values".to_string())?;
+    let parse = |elo: &str| elo.trim().parse::<f64>().map_err(|err| err.to_string());
+    Ok((parse(elo0)?, parse(elo1)?))
+}
+
+fn parse_time_control(input: &str) -> Result<TimeControl, String> {
+    let (time, increment) = input.split_once('+').unwrap_or((input, "0"));
+    let parse = |seconds: &str| {
+        seconds
+            .trim()
+            .parse::<f64>()
+            .map_err(|err| err.to_string())
+            .and_then(|seconds| Duration::try_from_secs_f64(seconds).map_err(|err| err.to_string()))
+    };
+    Ok(TimeControl::Time(parse(time)?, parse(increment)?))
+}
+
+fn parse_search_params(input: &str) -> Result<Vec<f32>, String> {
+    let params = input
+        .split(',')
+        .map(|param| param.trim().parse::<f32>().map_err(|err| err.to_string()))
+        .collect::<Result<Vec<f32>, String>>()?;
+    if params.len() != 3 {
+        return Err(format!(
+            "Expected 3 search parameters, got {}",
+            params.len()
+        ));
+    }
+    Ok(params)
+}
+
+/// Build one side of a match from its command line options, where `player` is "1" or "2"
+fn match_player<const S: usize>(arg: &ArgMatches, player: &str, komi: Komi) -> MatchPlayer<S> {
+    let value_path = arg.get_one::<String>(&format!("value-weights{}", player));
+    let policy_path = arg.get_one::<String>(&format!("policy-weights{}", player));
+    let weights = LoadedWeights::load(
+        value_path.map(|path| path.as_str()),
+        policy_path.map(|path| path.as_str()),
+    )
+    .unwrap_or_else(|err| {
+        eprintln!(
+            "Error: Failed to load weights for engine {}: {}",
+            player, err
+        );
+        exit(1)
+    });
+    if weights.sizes().any(|size| size != S) {
+        eprintln!("Error: Weights for engine {} are not for {}s", player, S);
+        exit(1)
+    }
+
+    let time_control = match (
+        arg.get_one::<u64>(&format!("nodes{}", player)),
+        arg.get_one::<TimeControl>(&format!("tc{}", player)),
+    ) {
+        (Some(nodes), _) => TimeControl::FixedNodes(*nodes),
+        (None, Some(time_control)) => time_control.clone(),
+        (None, None) => TimeControl::FixedNodes(10_000),
+    };
+    let mut settings = MctsSetting::default()
+        .add_loaded_weights(&weights, komi)
+        .unwrap_or_else(|err| {
+            eprintln!(
+                "Error: Weights for engine {} are not usable: {}",
+                player, err
+            );
+            exit(1)
+        });
+    if let TimeControl::FixedNodes(nodes) = time_control {
+        settings = settings.arena_size_for_nodes(nodes as u32);
+    }
+    if let Some(search_params) = arg.get_one::<Vec<f32>>(&format!("search-params{}", player)) {
+        settings = settings.add_search_params(search_params.clone().into_boxed_slice());
+    }
+
+    MatchPlayer {
+        name: arg
+            .get_one::<String>(&format!("name{}", player))
+            .unwrap()
+            .clone(),
+        settings,
+        time_control,
+    }
+}
+
+/// Play the match given to the `match` subcommand, and print the result
+fn run_engine_match<const S: usize>(arg: &ArgMatches, komi: Komi) {
+    let player1 = match_player::<S>(arg, "1", komi);
+    let player2 = match_player::<S>(arg, "2", komi);
+    let openings = match arg.get_one::<String>("book") {
+        Some(path) => openings_from_file::<S>(path, komi).unwrap_or_else(|err| {
+            eprintln!("Error: Failed to read {}: {}", path, err);
+            exit(1)
+        }),
+        None => vec![],
+    };
+    let sprt = arg.get_one::<(f64, f64)>("sprt").map(|(elo0, elo1)| Sprt {
+        alpha: *arg.get_one::<f64>("alpha").unwrap(),
+        beta: *arg.get_one::<f64>("beta").unwrap(),
+        ..Sprt::new(*elo0, *elo1)
+    });
+    let output_path = arg.get_one::<String>("output").unwrap();
+    let output = fs::File::create(output_path).unwrap_or_else(|err| {
+        eprintln!("Error: Failed to create {}: {}", output_path, err);
+        exit(1)
+    });
+
+    let score = engine_match::run_match(
+        &player1,
+        &player2,
+        komi,
+        &openings,
+        *arg.get_one::<u64>("games").unwrap(),
+        sprt,
+        io::BufWriter::new(output),
+    )
+    .unwrap();
+
+    println!("{} vs {}: {}", player1.name, player2.name, score);
+    if let Some(sprt) = sprt {
+        match sprt.result(&score) {
+            engine_match::SprtResult::AcceptH0 => println!("SPRT: H0 accepted"),
+            engine_match::SprtResult::AcceptH1 => println!("SPRT: H1 accepted"),
+            engine_match::SprtResult::Continue => println!("SPRT: Inconclusive"),
+        }
+    }
+    println!("Wrote games to {}", output_path);
+}
+
+fn calibrate_command() -> Command {
+    Command::new("calibrate")
+        .about("Measure the Elo of the handicap levels, by playing matches between them. Writes the games to a PTN file.")
+        .arg(Arg::new("games")
+            .long("games")
+            .help("Number of games between each pair of levels.")
+            .default_value("100")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u64)))
+        .arg(Arg::new("levels")
+            .long("levels")
+            .help("Comma-separated levels to calibrate. Ratings are relative to the first level.")
+            .default_value("1,2,3,4,5,6,7,8,9,10")
+            .num_args(1)
+            .value_parser(parse_levels))
+        .arg(Arg::new("max-distance")
+            .long("max-distance")
+            .help("Only play matches between levels at most this far apart in the list of levels.")
+            .default_value("2")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u64).range(1..)))
+        .arg(Arg::new("full-strength-nodes")
+            .long("full-strength-nodes")
+            .help("Nodes per move for levels without a node limit.")
+            .default_value("100000")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u64)))
+        .arg(Arg::new("book")
+            .long("book")
+            .help("Openings for the games, either one opening per line, or an opening book file, which gives every line of the book up to 6 plies. Defaults to every opening of two flat placements.")
+            .num_args(1)
+            .value_name("book.txt"))
+        .arg(Arg::new("output")
+            .long("output")
+            .help("File to write the games to.")
+            .default_value("calibration.ptn")
+            .num_args(1))
+}
+
+fn parse_levels(input: &str) -> Result<Vec<StrengthLevel>, String> {
+    let levels = input
+        .split(',')
+        .map(|level| {
+            level
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .and_then(StrengthLevel::from_level)
+                .ok_or_else(|| format!("Invalid level \"{}\"", level))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if levels.len() < 2 {
+        return Err("Expected at least two levels".to_string());
+    }
+    Ok(levels)
+}
+
+/// Play the matches given to the `calibrate` subcommand, and print the fitted rating of each level
+fn run_calibration<const S: usize>(arg: &ArgMatches, komi: Komi) {
+    let levels = arg.get_one::<Vec<StrengthLevel>>("levels").unwrap();
+    let openings = match arg.get_one::<String>("book") {
+        Some(path) => openings_from_file::<S>(path, komi).unwrap_or_else(|err| {
+            eprintln!("Error: Failed to read {}: {}", path, err);
+            exit(1)
+        }),
+        None => calibration::two_ply_openings::<S>(),
+    };
+    let output_path = arg.get_one::<String>("output").unwrap();
+    let output = fs::File::create(output_path).unwrap_or_else(|err| {
+        eprintln!("Error: Failed to create {}: {}", output_path, err);
+        exit(1)
+    });
+
+    let ratings = calibration::calibrate_levels(
+        levels,
+        *arg.get_one::<u64>("full-strength-nodes").unwrap(),
+        komi,
+        &openings,
+        *arg.get_one::<u64>("games").unwrap(),
+        *arg.get_one::<u64>("max-distance").unwrap() as usize,
+        io::BufWriter::new(output),
+    )
+    .unwrap();
+
+    // Compare to the current ratings, shifted to be relative to the same level
+    let current_offset = levels[0].elo;
+    for (strength, rating) in levels.iter().zip(ratings) {
+        println!(
+            "Level {}: {:.0} Elo, currently {}",
+            strength.level,
+            rating,
+            strength.elo - current_offset
+        );
+    }
+    println!("Wrote games to {}", output_path);
+}
@@ -0,0 +1,386 @@
+//! Opening books, which let the engine play well-known openings instantly instead of searching.
+//!
+//! A book maps positions, keyed by their TPS string, to a list of book moves.
+//! Each move has a weight, which is how often it was played in the games the book was built from,
+//! and optionally a score, which is the expected result for the side playing the move, from 0.0 to 1.0.
+//!
+//! Books are built from PTN game collections, and from the output of the `analyze_openings` command.
+//! They are stored as a short plain text header, followed by one move per line:
+//! ```text
+//! tiltak-book 1
+//! size 5
+//! komi 0
+//! x,x,x,x,x/x,x,x,x,x/x,x,x,x,x/x,x,x,x,x/x,x,x,x,x 1 1;a1;120;0.52
+//! x,x,x,x,x/x,x,x,x,x/x,x,x,x,x/x,x,x,x,x/x,x,x,x,x 1 1;e1;80;-
+//! ...
+//! ```
+//! where a `-` means that the move has no score. Positions are written exactly as `Position::to_fen` writes them.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use board_game_traits::{Color, GameResult, Position as PositionTrait};
+use pgn_traits::PgnPosition;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::position::{Komi, Move, Position};
+use crate::ptn::Game;
+
+/// The version written in the header of new book files
+pub const BOOK_FORMAT_VERSION: u32 = 1;
+
+pub(crate) const MAGIC: &str = "tiltak-book";
+
+/// Moves whose score is this much lower than the best scored move in the position are never played from the book
+pub const MAX_SCORE_LOSS: f32 = 0.05;
+
+#[derive(Debug)]
+pub enum BookError {
+    Io(io::Error),
+    /// The file is not a book file, or is malformed
+    Parse(String),
+    UnsupportedVersion(u32),
+}
+
+impl Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BookError::Io(err) => write!(f, "IO error: {}", err),
+            BookError::Parse(err) => write!(f, "Invalid book file: {}", err),
+            BookError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported book file version {}, expected {}",
+                version, BOOK_FORMAT_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BookError {}
+
+impl From<io::Error> for BookError {
+    fn from(err: io::Error) -> Self {
+        BookError::Io(err)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BookMove {
+    /// The move in PTN notation
+    pub mv: String,
+    pub weight: u32,
+    /// Expected result for the player making the move, if known
+    pub score: Option<f32>,
+}
+
+/// An opening book for a single board size and komi
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpeningBook {
+    pub size: usize,
+    pub komi: Komi,
+    // A sorted map, so that written books are deterministic
+    positions: BTreeMap<String, Vec<BookMove>>,
+}
+
+impl OpeningBook {
+    pub fn new(size: usize, komi: Komi) -> Self {
+        OpeningBook {
+            size,
+            komi,
+            positions: BTreeMap::new(),
+        }
+    }
+
+    /// Number of positions in the book
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Whether the book can be used for games with this position's size and komi
+    pub fn is_for<const S: usize>(&self, position: &Position<S>) -> bool {
+        self.size == S && self.komi == position.komi()
+    }
+
+    /// The book moves for a position, or an empty slice if the position is not in the book
+    pub fn moves<const S: usize>(&self, position: &Position<S>) -> &[BookMove] {
+        if !self.is_for(position) {
+            return &[];
+        }
+        self.positions
+            .get(&position.to_fen())
+            .map_or(&[], |moves| moves.as_slice())
+    }
+
+    /// Get the entry for `mv` in the position, adding it with weight 0 if it is new
+    fn entry<const S: usize>(&mut self, position: &Position<S>, mv: Move<S>) -> &mut BookMove {
+        let move_string = position.move_to_san(&mv);
+        let moves = self.positions.entry(position.to_fen()).or_default();
+        let index = match moves
+            .iter()
+            .position(|book_move| book_move.mv == move_string)
+        {
+            Some(index) => index,
+            None => {
+                moves.push(BookMove {
+                    mv: move_string,
+                    weight: 0,
+                    score: None,
+                });
+                moves.len() - 1
+            }
+        };
+        &mut moves[index]
+    }
+
+    /// Add the first `max_plies` moves of a game.
+    /// Each move gets one more weight, and its score becomes the average result of the games it was played in.
+    /// Games with a different size or komi, or that are not finished, are ignored
+    pub fn add_game<const S: usize>(&mut self, game: &Game<Position<S>>, max_plies: usize) {
+        let Some(game_result) = game.game_result() else {
+            return;
+        };
+        let mut position = game.start_position.clone();
+        if !self.is_for(&position) {
+            return;
+        }
+        for ptn_move in game.moves.iter().take(max_plies) {
+            let score = match (game_result, position.side_to_move()) {
+                (GameResult::Draw, _) => 0.5,
+                (GameResult::WhiteWin, Color::White) | (GameResult::BlackWin, Color::Black) => 1.0,
+                _ => 0.0,
+            };
+            let book_move = self.entry(&position, ptn_move.mv);
+            let old_score = book_move.score.unwrap_or(score);
+            book_move.weight += 1;
+            book_move.score = Some(old_score + (score - old_score) / book_move.weight as f32);
+            position.do_move(ptn_move.mv);
+        }
+    }
+
+    /// Add a line of `analyze_openings` output, on the form `<moves>: <score>, <time>s, <pv>`.
+    /// The opening's last move gets the engine's score, replacing any score from games.
+    /// Moves of the opening that were not already in the book are added with weight 1
+    pub fn add_analysis_line<const S: usize>(&mut self, line: &str) -> Result<(), BookError> {
+        if S != self.size {
+            return Err(BookError::Parse(format!(
+                "Cannot add {}s opening to {}s book",
+                S, self.size
+            )));
+        }
+        let (moves_string, rest) = line
+            .split_once(':')
+            .ok_or_else(|| BookError::Parse(format!("Missing ':' in \"{}\"", line)))?;
+        let score_string = rest.split(',').next().unwrap_or_default().trim();
+        // The score is for the side to move after the opening, so the last move gets the opposite score
+        let score = 1.0
+            - score_string.parse::<f32>().map_err(|err| {
+                BookError::Parse(format!("Invalid score \"{}\": {}", score_string, err))
+            })?;
+
+        let mut position = <Position<S>>::start_position_with_komi(self.komi);
+        let move_strings: Vec<&str> = moves_string.split_whitespace().collect();
+        for (i, move_string) in move_strings.iter().enumerate() {
+            let mv = position
+                .move_from_san(move_string)
+                .ok()
+                .filter(|mv| position.move_is_legal(*mv))
+                .ok_or_else(|| BookError::Parse(format!("Illegal move \"{}\"", move_string)))?;
+            let book_move = self.entry(&position, mv);
+            book_move.weight = book_move.weight.max(1);
+            if i == move_strings.len() - 1 {
+                book_move.score = Some(score);
+            }
+            position.do_move(mv);
+        }
+        Ok(())
+    }
+
+    /// Remove moves with less than `min_weight` weight, and positions without any moves
+    pub fn prune(&mut self, min_weight: u32) {
+        self.positions.retain(|_, moves| {
+            moves.retain(|book_move| book_move.weight >= min_weight);
+            !moves.is_empty()
+        });
+    }
+
+    /// Choose a random book move for the position, weighted by the moves' weights.
+    /// Moves with weight 0, or with a score much lower than the best scored move, are never chosen.
+    /// Returns `None` if the position is not in the book
+    pub fn choose_move<const S: usize, R: Rng>(
+        &self,
+        position: &Position<S>,
+        rng: &mut R,
+    ) -> Option<Move<S>> {
+        let candidates = self.playable_moves(position);
+        let book_move = candidates
+            .choose_weighted(rng, |book_move| book_move.weight)
+            .ok()?;
+        position
+            .move_from_san(&book_move.mv)
+            .ok()
+            .filter(|mv| position.move_is_legal(*mv))
+    }
+
+    /// Every line of book moves from the start position, until the book runs out or the line is `max_plies` long.
+    /// Only moves that `choose_move` can play are followed
+    pub fn lines<const S: usize>(&self, max_plies: usize) -> Vec<Vec<Move<S>>> {
+        let mut lines = vec![];
+        let mut position = <Position<S>>::start_position_with_komi(self.komi);
+        self.add_lines(&mut position, &mut vec![], max_plies, &mut lines);
+        lines
+    }
+
+    fn add_lines<const S: usize>(
+        &self,
+        position: &mut Position<S>,
+        line: &mut Vec<Move<S>>,
+        max_plies: usize,
+        lines: &mut Vec<Vec<Move<S>>>,
+    ) {
+        let moves: Vec<Move<S>> = if line.len() < max_plies && position.game_result().is_none() {
+            self.playable_moves(position)
+                .into_iter()
+                .filter_map(|book_move| position.move_from_san(&book_move.mv).ok())
+                .filter(|mv| position.move_is_legal(*mv))
+                .collect()
+        } else {
+            vec![]
+        };
+        if moves.is_empty() {
+            if !line.is_empty() {
+                lines.push(line.clone());
+            }
+            return;
+        }
+        for mv in moves {
+            let reverse_move = position.do_move(mv);
+            line.push(mv);
+            self.add_lines(position, line, max_plies, lines);
+            line.pop();
+            position.reverse_move(reverse_move);
+        }
+    }
+
+    /// The moves that can be played from the book in the position.
+    /// Moves with weight 0, or with a score much lower than the best scored move, are excluded
+    fn playable_moves<const S: usize>(&self, position: &Position<S>) -> Vec<&BookMove> {
+        let moves = self.moves(position);
+        let best_score = moves
+            .iter()
+            .filter_map(|book_move| book_move.score)
+            .max_by(|a, b| a.total_cmp(b));
+        moves
+            .iter()
+            .filter(|book_move| {
+                book_move.weight > 0
+                    && best_score.is_none_or(|best_score| {
+                        book_move
+                            .score
+                            .is_none_or(|score| score >= best_score - MAX_SCORE_LOSS)
+                    })
+            })
+            .collect()
+    }
+
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, BookError> {
+        let mut lines = reader.lines();
+        let mut header_line = |key: &str| -> Result<String, BookError> {
+            let line = lines
+                .next()
+                .ok_or_else(|| BookError::Parse(format!("Missing {} header", key)))??;
+            match line.trim().split_once(' ') {
+                Some((line_key, value)) if line_key == key => Ok(value.trim().to_string()),
+                _ => Err(BookError::Parse(format!(
+                    "Expected {} header, got \"{}\"",
+                    key, line
+                ))),
+            }
+        };
+        let parse_number = |key: &str, value: String| {
+            value
+                .parse::<u32>()
+                .map_err(|err| BookError::Parse(format!("Invalid {} \"{}\": {}", key, value, err)))
+        };
+
+        let version = parse_number("version", header_line(MAGIC)?)?;
+        if version != BOOK_FORMAT_VERSION {
+            return Err(BookError::UnsupportedVersion(version));
+        }
+        let size = parse_number("size", header_line("size")?)? as usize;
+        let komi: Komi = header_line("komi")?.parse().map_err(BookError::Parse)?;
+        let mut book = OpeningBook::new(size, komi);
+
+        for line in lines {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(';').collect();
+            let [tps, mv, weight, score] = fields[..] else {
+                return Err(BookError::Parse(format!(
+                    "Expected 4 fields, got \"{}\"",
+                    line
+                )));
+            };
+            let score = match score {
+                "-" => None,
+                score => Some(score.parse::<f32>().map_err(|err| {
+                    BookError::Parse(format!("Invalid score \"{}\": {}", score, err))
+                })?),
+            };
+            book.positions
+                .entry(tps.to_string())
+                .or_default()
+                .push(BookMove {
+                    mv: mv.to_string(),
+                    weight: parse_number("weight", weight.to_string())?,
+                    score,
+                });
+        }
+        Ok(book)
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{} {}", MAGIC, BOOK_FORMAT_VERSION)?;
+        writeln!(writer, "size {}", self.size)?;
+        writeln!(writer, "komi {}", self.komi)?;
+        for (tps, moves) in self.positions.iter() {
+            for book_move in moves {
+                match book_move.score {
+                    Some(score) => writeln!(
+                        writer,
+                        "{};{};{};{}",
+                        tps, book_move.mv, book_move.weight, score
+                    )?,
+                    None => writeln!(writer, "{};{};{};-", tps, book_move.mv, book_move.weight)?,
+                }
+            }
+        }
+        writer.flush()
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, BookError> {
+        let file = fs::File::open(path)?;
+        Self::read(io::BufReader::new(file))
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(io::BufWriter::new(fs::File::create(path)?))
+    }
+
+    /// Sort each position's moves by weight, most played first
+    pub fn sort_moves(&mut self) {
+        for moves in self.positions.values_mut() {
+            moves.sort_by(|a, b| b.weight.cmp(&a.weight).then_with(|| a.mv.cmp(&b.mv)));
+        }
+    }
+}
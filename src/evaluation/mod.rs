@@ -1,3 +1,5 @@
 pub mod parameters;
 pub mod policy_eval;
+pub mod transfer;
 pub mod value_eval;
+pub mod weights;
@@ -1,9 +1,12 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 use std::{array, mem};
 
 use half::f16;
 
 use crate::{
-    evaluation::policy_eval::sigmoid,
+    evaluation::{policy_eval::sigmoid, transfer},
     position::{num_line_symmetries, num_square_symmetries, Komi},
 };
 
@@ -18,11 +21,19 @@ pub const NUM_POLICY_FEATURES_5S: usize = 216;
 pub const NUM_VALUE_FEATURES_6S: usize = 560;
 pub const NUM_POLICY_FEATURES_6S: usize = 224;
 
+pub const NUM_VALUE_FEATURES_7S: usize = 736;
+pub const NUM_POLICY_FEATURES_7S: usize = 256;
+
+pub const NUM_VALUE_FEATURES_8S: usize = 768;
+pub const NUM_POLICY_FEATURES_8S: usize = 264;
+
 const fn value_padding<const S: usize>() -> usize {
     match S {
         4 => 14,
         5 => 10,
         6 => 14,
+        7 => 0,
+        8 => 6,
         _ => unimplemented!(),
     }
 }
@@ -32,6 +43,8 @@ const fn policy_padding<const S: usize>() -> usize {
         4 => 3,
         5 => 7,
         6 => 5,
+        7 => 3,
+        8 => 1,
         _ => unimplemented!(),
     }
 }
@@ -54,6 +67,10 @@ impl IndexPair {
     pub fn as_mut_slice<'a, T>(&self, slice: &'a mut [T]) -> &'a mut [T] {
         &mut slice[self.start..self.start + self.length]
     }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.start + self.length
+    }
 }
 
 #[derive(Debug)]
@@ -239,6 +256,8 @@ impl<const S: usize> ValueIndexes<S> {
 pub const VALUE_INDEXES_4S: ValueIndexes<4> = ValueIndexes::new();
 pub const VALUE_INDEXES_5S: ValueIndexes<5> = ValueIndexes::new();
 pub const VALUE_INDEXES_6S: ValueIndexes<6> = ValueIndexes::new();
+pub const VALUE_INDEXES_7S: ValueIndexes<7> = ValueIndexes::new();
+pub const VALUE_INDEXES_8S: ValueIndexes<8> = ValueIndexes::new();
 
 impl<const S: usize> ValueIndexes<S> {
     pub const fn downcast_size<const N: usize>(self) -> ValueIndexes<N> {
@@ -255,6 +274,8 @@ pub const fn value_indexes<const S: usize>() -> ValueIndexes<S> {
         4 => VALUE_INDEXES_4S.downcast_size(),
         5 => VALUE_INDEXES_5S.downcast_size(),
         6 => VALUE_INDEXES_6S.downcast_size(),
+        7 => VALUE_INDEXES_7S.downcast_size(),
+        8 => VALUE_INDEXES_8S.downcast_size(),
         _ => panic!(),
     }
 }
@@ -327,6 +348,8 @@ pub struct PolicyIndexes<const S: usize> {
 pub const POLICY_INDEXES_4S: PolicyIndexes<4> = PolicyIndexes::new();
 pub const POLICY_INDEXES_5S: PolicyIndexes<5> = PolicyIndexes::new();
 pub const POLICY_INDEXES_6S: PolicyIndexes<6> = PolicyIndexes::new();
+pub const POLICY_INDEXES_7S: PolicyIndexes<7> = PolicyIndexes::new();
+pub const POLICY_INDEXES_8S: PolicyIndexes<8> = PolicyIndexes::new();
 
 impl<const S: usize> PolicyIndexes<S> {
     pub const fn downcast_size<const N: usize>(self) -> PolicyIndexes<N> {
@@ -343,6 +366,8 @@ pub const fn policy_indexes<const S: usize>() -> PolicyIndexes<S> {
         4 => POLICY_INDEXES_4S.downcast_size(),
         5 => POLICY_INDEXES_5S.downcast_size(),
         6 => POLICY_INDEXES_6S.downcast_size(),
+        7 => POLICY_INDEXES_7S.downcast_size(),
+        8 => POLICY_INDEXES_8S.downcast_size(),
         _ => panic!(),
     }
 }
@@ -481,20 +506,20 @@ impl<const S: usize> PolicyIndexes<S> {
     }
 }
 
-pub trait ValueApplier {
-    fn new(parameters: &'static [f32]) -> Self;
+pub trait ValueApplier<'a> {
+    fn new(parameters: &'a [f32]) -> Self;
     fn eval(&mut self, index_pair: IndexPair, index: usize, val: f16);
     fn finish(&mut self) -> f32;
 }
 
 #[derive(Debug, Clone)]
-pub struct Value<const S: usize> {
+pub struct Value<'a, const S: usize> {
     pub features: Vec<f16>,
-    parameters: &'static [f32],
+    parameters: &'a [f32],
 }
 
-impl<const S: usize> ValueApplier for Value<S> {
-    fn new(parameters: &'static [f32]) -> Self {
+impl<'a, const S: usize> ValueApplier<'a> for Value<'a, S> {
+    fn new(parameters: &'a [f32]) -> Self {
         Value {
             features: vec![f16::ZERO; num_value_features::<S>() / 2],
             parameters,
@@ -526,13 +551,13 @@ impl<const S: usize> ValueApplier for Value<S> {
 }
 
 #[derive(Debug, Clone)]
-pub struct IncrementalValue<const S: usize> {
+pub struct IncrementalValue<'a, const S: usize> {
     val: f32,
-    parameters: &'static [f32],
+    parameters: &'a [f32],
 }
 
-impl<const S: usize> ValueApplier for IncrementalValue<S> {
-    fn new(parameters: &'static [f32]) -> Self {
+impl<'a, const S: usize> ValueApplier<'a> for IncrementalValue<'a, S> {
+    fn new(parameters: &'a [f32]) -> Self {
         IncrementalValue {
             val: 0.0,
             parameters,
@@ -551,39 +576,40 @@ impl<const S: usize> ValueApplier for IncrementalValue<S> {
     }
 }
 
+/// Policy features for a single move.
+/// The parameters are passed in with every feature instead of being stored,
+/// so that the same feature sets can be re-used between positions, regardless of where the parameters come from
 pub trait PolicyApplier {
-    fn new(parameters: &'static [f32]) -> Self;
-    fn eval(&mut self, index_pair: IndexPair, index: usize, val: f16);
-    fn eval_one(&mut self, index_pair: IndexPair, index: usize) {
-        self.eval_i8(index_pair, index, 1)
+    fn new() -> Self;
+    fn eval(&mut self, parameters: &[f32], index_pair: IndexPair, index: usize, val: f16);
+    fn eval_one(&mut self, parameters: &[f32], index_pair: IndexPair, index: usize) {
+        self.eval_i8(parameters, index_pair, index, 1)
     }
-    fn eval_i8(&mut self, index_pair: IndexPair, index: usize, val: i8) {
-        self.eval(index_pair, index, f16::from(val))
+    fn eval_i8(&mut self, parameters: &[f32], index_pair: IndexPair, index: usize, val: i8) {
+        self.eval(parameters, index_pair, index, f16::from(val))
     }
-    fn eval_f32(&mut self, index_pair: IndexPair, index: usize, val: f32) {
-        self.eval(index_pair, index, f16::from_f32(val))
+    fn eval_f32(&mut self, parameters: &[f32], index_pair: IndexPair, index: usize, val: f32) {
+        self.eval(parameters, index_pair, index, f16::from_f32(val))
     }
     fn set_immediate_win(&mut self);
     fn has_immediate_win(&self) -> bool;
-    fn finish(&mut self, num_moves: usize) -> f16;
+    fn finish(&mut self, parameters: &[f32], num_moves: usize) -> f16;
 }
 
 #[derive(Debug, Clone)]
 pub struct Policy<const S: usize> {
     pub features: Vec<f16>,
-    parameters: &'static [f32],
     has_immediate_win: bool,
 }
 
 impl<const S: usize> PolicyApplier for Policy<S> {
-    fn new(parameters: &'static [f32]) -> Self {
+    fn new() -> Self {
         Policy {
             features: vec![f16::ZERO; num_policy_features::<S>()],
-            parameters,
             has_immediate_win: false,
         }
     }
-    fn eval(&mut self, index_pair: IndexPair, index: usize, val: f16) {
+    fn eval(&mut self, _parameters: &[f32], index_pair: IndexPair, index: usize, val: f16) {
         index_pair.as_mut_slice(&mut self.features)[index] += val
     }
 
@@ -595,7 +621,7 @@ impl<const S: usize> PolicyApplier for Policy<S> {
         self.has_immediate_win
     }
 
-    fn finish(&mut self, num_moves: usize) -> f16 {
+    fn finish(&mut self, parameters: &[f32], num_moves: usize) -> f16 {
         if num_moves < 2 {
             eprintln!("Warning: Got {} legal moves", num_moves,);
         }
@@ -603,12 +629,12 @@ impl<const S: usize> PolicyApplier for Policy<S> {
 
         const SIMD_WIDTH: usize = 8;
         assert_eq!(self.features.len() % SIMD_WIDTH, 0);
-        assert_eq!(self.features.len(), self.parameters.len());
+        assert_eq!(self.features.len(), parameters.len());
 
         let partial_sums: [f32; SIMD_WIDTH] = self
             .features
             .chunks_exact(SIMD_WIDTH)
-            .zip(self.parameters.chunks_exact(SIMD_WIDTH))
+            .zip(parameters.chunks_exact(SIMD_WIDTH))
             .fold([0.0; SIMD_WIDTH], |acc, (c, p)| {
                 array::from_fn(|i| acc[i] + c[i].to_f32() * p[i])
             });
@@ -625,28 +651,26 @@ impl<const S: usize> PolicyApplier for Policy<S> {
 #[derive(Debug, Clone)]
 pub struct IncrementalPolicy<const S: usize> {
     val: f32,
-    parameters: &'static [f32],
     has_immediate_win: bool,
 }
 
 impl<const S: usize> PolicyApplier for IncrementalPolicy<S> {
-    fn new(parameters: &'static [f32]) -> Self {
+    fn new() -> Self {
         IncrementalPolicy {
             val: 0.0,
-            parameters,
             has_immediate_win: false,
         }
     }
-    fn eval(&mut self, index_pair: IndexPair, index: usize, val: f16) {
-        self.val += index_pair.as_slice(self.parameters)[index] * val.to_f32()
+    fn eval(&mut self, parameters: &[f32], index_pair: IndexPair, index: usize, val: f16) {
+        self.val += index_pair.as_slice(parameters)[index] * val.to_f32()
     }
 
-    fn eval_i8(&mut self, index_pair: IndexPair, index: usize, val: i8) {
-        self.val += index_pair.as_slice(self.parameters)[index] * val as f32
+    fn eval_i8(&mut self, parameters: &[f32], index_pair: IndexPair, index: usize, val: i8) {
+        self.val += index_pair.as_slice(parameters)[index] * val as f32
     }
 
-    fn eval_f32(&mut self, index_pair: IndexPair, index: usize, val: f32) {
-        self.val += index_pair.as_slice(self.parameters)[index] * val
+    fn eval_f32(&mut self, parameters: &[f32], index_pair: IndexPair, index: usize, val: f32) {
+        self.val += index_pair.as_slice(parameters)[index] * val
     }
 
     fn set_immediate_win(&mut self) {
@@ -657,7 +681,7 @@ impl<const S: usize> PolicyApplier for IncrementalPolicy<S> {
         self.has_immediate_win
     }
 
-    fn finish(&mut self, num_moves: usize) -> f16 {
+    fn finish(&mut self, _parameters: &[f32], num_moves: usize) -> f16 {
         if num_moves < 2 {
             eprintln!("Warning: Got {} legal moves", num_moves,);
         }
@@ -676,6 +700,8 @@ pub const fn num_value_features<const S: usize>() -> usize {
         4 => NUM_VALUE_FEATURES_4S,
         5 => NUM_VALUE_FEATURES_5S,
         6 => NUM_VALUE_FEATURES_6S,
+        7 => NUM_VALUE_FEATURES_7S,
+        8 => NUM_VALUE_FEATURES_8S,
         _ => unimplemented!(),
     }
 }
@@ -685,56 +711,162 @@ pub const fn num_policy_features<const S: usize>() -> usize {
         4 => NUM_POLICY_FEATURES_4S,
         5 => NUM_POLICY_FEATURES_5S,
         6 => NUM_POLICY_FEATURES_6S,
+        7 => NUM_POLICY_FEATURES_7S,
+        8 => NUM_POLICY_FEATURES_8S,
         _ => unimplemented!(),
     }
 }
 
-pub fn value_features_4s(komi: Komi) -> &'static [f32; NUM_VALUE_FEATURES_4S] {
+/// Parameters are only trained for 0 and 2 komi.
+/// Other komi use a linear interpolation of the two sets, which is computed once and then cached, one for each half-komi from -10 to 10.
+/// Komi outside that range, such as 2.5 or 3 komi, extrapolate the difference between the two sets,
+/// with a warning the first time each komi is used
+fn komi_params<const N: usize>(
+    komi: Komi,
+    params_0komi: &'static [f32; N],
+    params_2komi: &'static [f32; N],
+    interpolated: &'static [OnceLock<[f32; N]>; 21],
+) -> &'static [f32; N] {
     match komi.half_komi() {
-        0 => &VALUE_PARAMS_4S_0KOMI,
-        4 => &VALUE_PARAMS_4S_2KOMI,
-        _ => unimplemented!("4s is not supported for 0 komi."),
+        0 => params_0komi,
+        4 => params_2komi,
+        half_komi => {
+            if !(0..4).contains(&half_komi) {
+                warn_untrained_komi(komi);
+            }
+            interpolated[(half_komi + 10) as usize].get_or_init(|| {
+                let weight = half_komi as f32 / 4.0;
+                array::from_fn(|i| params_0komi[i] * (1.0 - weight) + params_2komi[i] * weight)
+            })
+        }
     }
 }
 
-pub fn policy_features_4s(komi: Komi) -> &'static [f32; NUM_POLICY_FEATURES_4S] {
-    match komi.half_komi() {
-        0 => &POLICY_PARAMS_4S_0KOMI,
-        4 => &POLICY_PARAMS_4S_2KOMI,
-        _ => unimplemented!("4s is not supported for 0 komi."),
+fn warn_untrained_komi(komi: Komi) {
+    // One flag for each half-komi from -10 to 10
+    static WARNED: [AtomicBool; 21] = [const { AtomicBool::new(false) }; 21];
+    if !WARNED[(komi.half_komi() + 10) as usize].swap(true, Ordering::Relaxed) {
+        eprintln!(
+            "Warning: No trained evaluation parameters for {} komi, extrapolating from the parameters for 0 and 2 komi",
+            komi
+        );
     }
 }
 
+pub fn value_features_4s(komi: Komi) -> &'static [f32; NUM_VALUE_FEATURES_4S] {
+    static INTERPOLATED: [OnceLock<[f32; NUM_VALUE_FEATURES_4S]>; 21] =
+        [const { OnceLock::new() }; 21];
+    komi_params(
+        komi,
+        &VALUE_PARAMS_4S_0KOMI,
+        &VALUE_PARAMS_4S_2KOMI,
+        &INTERPOLATED,
+    )
+}
+
+pub fn policy_features_4s(komi: Komi) -> &'static [f32; NUM_POLICY_FEATURES_4S] {
+    static INTERPOLATED: [OnceLock<[f32; NUM_POLICY_FEATURES_4S]>; 21] =
+        [const { OnceLock::new() }; 21];
+    komi_params(
+        komi,
+        &POLICY_PARAMS_4S_0KOMI,
+        &POLICY_PARAMS_4S_2KOMI,
+        &INTERPOLATED,
+    )
+}
+
 pub fn value_features_5s(komi: Komi) -> &'static [f32; NUM_VALUE_FEATURES_5S] {
-    match komi.half_komi() {
-        0 => &VALUE_PARAMS_5S_0KOMI,
-        4 => &VALUE_PARAMS_5S_2KOMI,
-        _ => unimplemented!("5s is not supported for 0 komi."),
-    }
+    static INTERPOLATED: [OnceLock<[f32; NUM_VALUE_FEATURES_5S]>; 21] =
+        [const { OnceLock::new() }; 21];
+    komi_params(
+        komi,
+        &VALUE_PARAMS_5S_0KOMI,
+        &VALUE_PARAMS_5S_2KOMI,
+        &INTERPOLATED,
+    )
 }
 
 pub fn policy_features_5s(komi: Komi) -> &'static [f32; NUM_POLICY_FEATURES_5S] {
-    match komi.half_komi() {
-        0 => &POLICY_PARAMS_5S_0KOMI,
-        4 => &POLICY_PARAMS_5S_2KOMI,
-        _ => unimplemented!("5s is not supported for 0 komi."),
-    }
+    static INTERPOLATED: [OnceLock<[f32; NUM_POLICY_FEATURES_5S]>; 21] =
+        [const { OnceLock::new() }; 21];
+    komi_params(
+        komi,
+        &POLICY_PARAMS_5S_0KOMI,
+        &POLICY_PARAMS_5S_2KOMI,
+        &INTERPOLATED,
+    )
 }
 
 pub fn value_features_6s(komi: Komi) -> &'static [f32; NUM_VALUE_FEATURES_6S] {
-    match komi.half_komi() {
-        0 => &VALUE_PARAMS_6S_0KOMI,
-        4 => &VALUE_PARAMS_6S_2KOMI,
-        _ => unimplemented!("6s is not supported for 0 komi."),
-    }
+    static INTERPOLATED: [OnceLock<[f32; NUM_VALUE_FEATURES_6S]>; 21] =
+        [const { OnceLock::new() }; 21];
+    komi_params(
+        komi,
+        &VALUE_PARAMS_6S_0KOMI,
+        &VALUE_PARAMS_6S_2KOMI,
+        &INTERPOLATED,
+    )
 }
 
 pub fn policy_features_6s(komi: Komi) -> &'static [f32; NUM_POLICY_FEATURES_6S] {
-    match komi.half_komi() {
-        0 => &POLICY_PARAMS_6S_0KOMI,
-        4 => &POLICY_PARAMS_6S_2KOMI,
-        _ => unimplemented!("6s is not supported for 0 komi."),
-    }
+    static INTERPOLATED: [OnceLock<[f32; NUM_POLICY_FEATURES_6S]>; 21] =
+        [const { OnceLock::new() }; 21];
+    komi_params(
+        komi,
+        &POLICY_PARAMS_6S_0KOMI,
+        &POLICY_PARAMS_6S_2KOMI,
+        &INTERPOLATED,
+    )
+}
+
+/// There are no trained parameters for 7s and 8s yet, so placeholders are transferred from the 6s parameters for the same komi.
+/// The transferred parameters are computed once and then cached, one for each half-komi from -10 to 10
+fn transferred_params<const N: usize>(
+    komi: Komi,
+    transfer: impl FnOnce() -> Vec<f32>,
+    transferred: &'static [OnceLock<[f32; N]>; 21],
+) -> &'static [f32; N] {
+    transferred[(komi.half_komi() + 10) as usize].get_or_init(|| transfer().try_into().unwrap())
+}
+
+pub fn value_features_7s(komi: Komi) -> &'static [f32; NUM_VALUE_FEATURES_7S] {
+    static TRANSFERRED: [OnceLock<[f32; NUM_VALUE_FEATURES_7S]>; 21] =
+        [const { OnceLock::new() }; 21];
+    transferred_params(
+        komi,
+        || transfer::value_params::<7>(value_features_6s(komi)),
+        &TRANSFERRED,
+    )
+}
+
+pub fn policy_features_7s(komi: Komi) -> &'static [f32; NUM_POLICY_FEATURES_7S] {
+    static TRANSFERRED: [OnceLock<[f32; NUM_POLICY_FEATURES_7S]>; 21] =
+        [const { OnceLock::new() }; 21];
+    transferred_params(
+        komi,
+        || transfer::policy_params::<7>(policy_features_6s(komi)),
+        &TRANSFERRED,
+    )
+}
+
+pub fn value_features_8s(komi: Komi) -> &'static [f32; NUM_VALUE_FEATURES_8S] {
+    static TRANSFERRED: [OnceLock<[f32; NUM_VALUE_FEATURES_8S]>; 21] =
+        [const { OnceLock::new() }; 21];
+    transferred_params(
+        komi,
+        || transfer::value_params::<8>(value_features_6s(komi)),
+        &TRANSFERRED,
+    )
+}
+
+pub fn policy_features_8s(komi: Komi) -> &'static [f32; NUM_POLICY_FEATURES_8S] {
+    static TRANSFERRED: [OnceLock<[f32; NUM_POLICY_FEATURES_8S]>; 21] =
+        [const { OnceLock::new() }; 21];
+    transferred_params(
+        komi,
+        || transfer::policy_params::<8>(policy_features_6s(komi)),
+        &TRANSFERRED,
+    )
 }
 
 #[allow(clippy::unreadable_literal)]
@@ -61,7 +61,7 @@ impl<const S: usize> Position<S> {
         P: PolicyApplier,
     >(
         &self,
-        params_for_color: &'static [f32],
+        params_for_color: &[f32],
         group_data: &GroupData<S>,
         simple_moves: &mut Vec<Move<S>>,
         fcd_per_move: &mut Vec<i8>,
@@ -71,17 +71,23 @@ impl<const S: usize> Position<S> {
         let num_moves = simple_moves.len();
 
         while policy_feature_sets.len() < num_moves {
-            policy_feature_sets.push(P::new(params_for_color));
+            policy_feature_sets.push(P::new());
         }
 
-        self.features_for_moves(policy_feature_sets, simple_moves, fcd_per_move, group_data);
+        self.features_for_moves(
+            params_for_color,
+            policy_feature_sets,
+            simple_moves,
+            fcd_per_move,
+            group_data,
+        );
 
         moves.extend(
             simple_moves
                 .drain(..)
                 .zip(policy_feature_sets)
                 .map(|(mv, features)| {
-                    let eval = features.finish(num_moves);
+                    let eval = features.finish(params_for_color, num_moves);
 
                     (mv, eval)
                 }),
@@ -100,6 +106,7 @@ impl<const S: usize> Position<S> {
 
     pub fn features_for_moves<P: PolicyApplier>(
         &self,
+        params: &[f32],
         policies: &mut [P],
         moves: &[Move<S>],
         fcd_per_move: &mut Vec<i8>,
@@ -130,16 +137,24 @@ impl<const S: usize> Position<S> {
         }
 
         for (policy, (mv, &mut fcd)) in policies.iter_mut().zip(moves.iter().zip(fcd_per_move)) {
-            self.features_for_move(policy, mv, fcd, group_data);
+            self.features_for_move(params, policy, mv, fcd, group_data);
 
             // FCD bonus for all movements
             if let ExpMove::Move(square, _, _) = mv.expand() {
                 if fcd >= highest_fcd {
-                    policy.eval_one(indexes.fcd_highest_board, fcd.clamp(1, 6) as usize - 1);
+                    policy.eval_one(
+                        params,
+                        indexes.fcd_highest_board,
+                        fcd.clamp(1, 6) as usize - 1,
+                    );
                 } else if fcd >= highest_fcd_per_square[square] {
-                    policy.eval_one(indexes.fcd_highest_stack, (fcd.clamp(-1, 4) + 1) as usize);
+                    policy.eval_one(
+                        params,
+                        indexes.fcd_highest_stack,
+                        (fcd.clamp(-1, 4) + 1) as usize,
+                    );
                 } else {
-                    policy.eval_one(indexes.fcd_other, (fcd.clamp(-3, 4) + 3) as usize);
+                    policy.eval_one(params, indexes.fcd_other, (fcd.clamp(-3, 4) + 3) as usize);
                 }
             }
 
@@ -150,7 +165,7 @@ impl<const S: usize> Position<S> {
         if immediate_win_exists {
             for policy in policies.iter_mut().take(moves.len()) {
                 if !policy.has_immediate_win() {
-                    policy.eval_one(indexes.decline_win, 0)
+                    policy.eval_one(params, indexes.decline_win, 0)
                 }
             }
         }
@@ -158,6 +173,7 @@ impl<const S: usize> Position<S> {
 
     fn features_for_move<P: PolicyApplier>(
         &self,
+        params: &[f32],
         policy: &mut P,
         mv: &Move<S>,
         fcd: i8,
@@ -165,10 +181,10 @@ impl<const S: usize> Position<S> {
     ) {
         match self.side_to_move() {
             Color::White => features_for_move_colortr::<WhiteTr, BlackTr, P, S>(
-                self, policy, mv, fcd, group_data,
+                self, params, policy, mv, fcd, group_data,
             ),
             Color::Black => features_for_move_colortr::<BlackTr, WhiteTr, P, S>(
-                self, policy, mv, fcd, group_data,
+                self, params, policy, mv, fcd, group_data,
             ),
         }
     }
@@ -196,6 +212,7 @@ fn their_last_placement<const S: usize>(position: &Position<S>) -> Option<(Role,
 
 fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const S: usize>(
     position: &Position<S>,
+    params: &[f32],
     policy: &mut P,
     mv: &Move<S>,
     fcd: i8,
@@ -222,6 +239,7 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
             {
                 check_flat_win::<Us, P, S>(
                     position,
+                    params,
                     our_flatcount_after_move,
                     their_flatcount,
                     policy,
@@ -233,6 +251,7 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
             {
                 check_flat_win_next_move::<Us, P, S>(
                     position,
+                    params,
                     our_flatcount_after_move,
                     their_flatcount,
                     policy,
@@ -240,11 +259,16 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
             }
             // TODO: These two bonuses don't take komi into account, but they should
             else if Us::stones_left(position) == 2 && Us::caps_left(position) == 0 {
-                policy.eval_one(indexes.two_flats_left, 0);
-                policy.eval_i8(indexes.two_flats_left, 1, our_flat_lead_after_move);
+                policy.eval_one(params, indexes.two_flats_left, 0);
+                policy.eval_i8(params, indexes.two_flats_left, 1, our_flat_lead_after_move);
             } else if Us::stones_left(position) == 3 && Us::caps_left(position) == 0 {
-                policy.eval_one(indexes.three_flats_left, 0);
-                policy.eval_i8(indexes.three_flats_left, 1, our_flat_lead_after_move);
+                policy.eval_one(params, indexes.three_flats_left, 0);
+                policy.eval_i8(
+                    params,
+                    indexes.three_flats_left,
+                    1,
+                    our_flat_lead_after_move,
+                );
             }
 
             let their_open_critical_squares =
@@ -253,26 +277,32 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
             // Apply PSQT
             match (role, Us::color()) {
                 (Flat, Color::White) => policy.eval_one(
+                    params,
                     indexes.flat_psqt_white,
                     lookup_square_symmetries::<S>(square),
                 ),
                 (Flat, Color::Black) => policy.eval_one(
+                    params,
                     indexes.flat_psqt_black,
                     lookup_square_symmetries::<S>(square),
                 ),
                 (Wall, Color::White) => policy.eval_one(
+                    params,
                     indexes.wall_psqt_white,
                     lookup_square_symmetries::<S>(square),
                 ),
                 (Wall, Color::Black) => policy.eval_one(
+                    params,
                     indexes.wall_psqt_black,
                     lookup_square_symmetries::<S>(square),
                 ),
                 (Cap, Color::White) => policy.eval_one(
+                    params,
                     indexes.cap_psqt_white,
                     lookup_square_symmetries::<S>(square),
                 ),
                 (Cap, Color::Black) => policy.eval_one(
+                    params,
                     indexes.cap_psqt_black,
                     lookup_square_symmetries::<S>(square),
                 ),
@@ -288,10 +318,12 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                 let our_line_score = (Us::road_stones(group_data) & line).count();
                 let their_line_score = (Them::road_stones(group_data) & line).count();
                 policy.eval_one(
+                    params,
                     indexes.our_road_stones_in_line,
                     S * role_id + our_line_score as usize,
                 );
                 policy.eval_one(
+                    params,
                     indexes.their_road_stones_in_line,
                     S * role_id + their_line_score as usize,
                 );
@@ -330,9 +362,10 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                     let (group_size, group_connection) =
                         group_data.amount_in_group[*group_id as usize];
                     if (group_connection & connection) != connection {
-                        policy.eval_one(indexes.anchor_group_base, role_id);
+                        policy.eval_one(params, indexes.anchor_group_base, role_id);
                         // Divide by 10, as large values confuse the tuner
                         policy.eval_f32(
+                            params,
                             indexes.anchor_group_linear,
                             role_id,
                             group_size as f32 / 10.0,
@@ -343,9 +376,10 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                     let (group_size, group_connection) =
                         group_data.amount_in_group[*group_id as usize];
                     if (group_connection & connection) != connection {
-                        policy.eval_one(indexes.block_anchoring_group_base, role_id);
+                        policy.eval_one(params, indexes.block_anchoring_group_base, role_id);
                         // Divide by 10, as large values confuse the tuner
                         policy.eval_f32(
+                            params,
                             indexes.block_anchoring_group_linear,
                             role_id,
                             group_size as f32 / 10.0,
@@ -360,9 +394,10 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                     .map(|(_, group_id)| group_data.amount_in_group[*group_id as usize].0)
                     .sum();
 
-                policy.eval_one(indexes.merge_two_groups_base, role_id);
+                policy.eval_one(params, indexes.merge_two_groups_base, role_id);
                 // Divide by 10, as large values confuse the tuner
                 policy.eval_f32(
+                    params,
                     indexes.merge_two_groups_linear,
                     role_id,
                     total_neighbours_group_size as f32 / 10.0,
@@ -375,9 +410,10 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                     .map(|(_, group_id)| group_data.amount_in_group[*group_id as usize].0)
                     .sum();
 
-                policy.eval_one(indexes.block_merger_base, role_id);
+                policy.eval_one(params, indexes.block_merger_base, role_id);
                 // Divide by 10, as large values confuse the tuner
                 policy.eval_f32(
+                    params,
                     indexes.block_merger_linear,
                     role_id,
                     total_neighbours_group_size as f32 / 10.0,
@@ -387,9 +423,10 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                 let group_id = our_unique_neighbor_groups[0].1;
                 let amount_in_group = group_data.amount_in_group[group_id as usize].0;
 
-                policy.eval_one(indexes.extend_single_group_base, role_id);
+                policy.eval_one(params, indexes.extend_single_group_base, role_id);
                 // Divide by 10, as large values confuse the tuner
                 policy.eval_f32(
+                    params,
                     indexes.extend_single_group_linear,
                     role_id,
                     amount_in_group as f32 / 10.0,
@@ -403,8 +440,13 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                         .filter(|sq| group_data.groups[*sq] == group_id)
                         .all(|sq| sq.rank() != square.rank())
                 {
-                    policy.eval_one(indexes.extend_single_group_to_new_line_base, role_id);
+                    policy.eval_one(
+                        params,
+                        indexes.extend_single_group_to_new_line_base,
+                        role_id,
+                    );
                     policy.eval_f32(
+                        params,
                         indexes.extend_single_group_to_new_line_linear,
                         role_id,
                         amount_in_group as f32 / 10.0,
@@ -414,13 +456,13 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
 
             if role == Flat || role == Cap {
                 if Us::is_critical_square(group_data, square) {
-                    policy.eval_one(indexes.place_our_critical_square, 0);
+                    policy.eval_one(params, indexes.place_our_critical_square, 0);
                     policy.set_immediate_win();
                 } else if !their_open_critical_squares.is_empty() {
                     if their_open_critical_squares == BitBoard::empty().set_square(square) {
-                        policy.eval_one(indexes.place_their_critical_square, 0);
+                        policy.eval_one(params, indexes.place_their_critical_square, 0);
                     } else {
-                        policy.eval_one(indexes.ignore_their_critical_square, 0);
+                        policy.eval_one(params, indexes.ignore_their_critical_square, 0);
                     }
                 }
 
@@ -428,11 +470,11 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                 if let Some((last_role, last_square)) = our_last_placement(position) {
                     if last_role == Flat || last_role == Cap {
                         if square.neighbors_bitboard().get_square(last_square) {
-                            policy.eval_one(indexes.next_to_our_last_stone, 0);
+                            policy.eval_one(params, indexes.next_to_our_last_stone, 0);
                         } else if square.rank().abs_diff(last_square.rank()) == 1
                             && square.file().abs_diff(last_square.file()) == 1
                         {
-                            policy.eval_one(indexes.diagonal_to_our_last_stone, 0);
+                            policy.eval_one(params, indexes.diagonal_to_our_last_stone, 0);
                         }
                     }
                 }
@@ -441,11 +483,11 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                 if let Some((last_role, last_square)) = their_last_placement(position) {
                     if last_role == Flat {
                         if square.neighbors_bitboard().get_square(last_square) {
-                            policy.eval_one(indexes.next_to_their_last_stone, 0);
+                            policy.eval_one(params, indexes.next_to_their_last_stone, 0);
                         } else if square.rank().abs_diff(last_square.rank()) == 1
                             && square.file().abs_diff(last_square.file()) == 1
                         {
-                            policy.eval_one(indexes.diagonal_to_their_last_stone, 0);
+                            policy.eval_one(params, indexes.diagonal_to_their_last_stone, 0);
                         }
                     }
                 }
@@ -461,7 +503,12 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                             .file::<S>(neighbor.file())
                             .count();
                     if our_road_stones >= 2 {
-                        policy.eval_i8(indexes.attack_strong_flats, 0, our_road_stones as i8 - 1);
+                        policy.eval_i8(
+                            params,
+                            indexes.attack_strong_flats,
+                            0,
+                            our_road_stones as i8 - 1,
+                        );
                     }
                 }
             }
@@ -469,20 +516,20 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
             if role == Wall {
                 if !their_open_critical_squares.is_empty() {
                     if their_open_critical_squares == BitBoard::empty().set_square(square) {
-                        policy.eval_one(indexes.place_their_critical_square, 1);
+                        policy.eval_one(params, indexes.place_their_critical_square, 1);
                     } else {
-                        policy.eval_one(indexes.ignore_their_critical_square, 0);
+                        policy.eval_one(params, indexes.ignore_their_critical_square, 0);
                     }
                 }
             } else if role == Cap {
                 if Us::is_critical_square(group_data, square) {
-                    policy.eval_one(indexes.place_our_critical_square, 0);
+                    policy.eval_one(params, indexes.place_our_critical_square, 0);
                     policy.set_immediate_win();
                 } else if !their_open_critical_squares.is_empty() {
                     if their_open_critical_squares == BitBoard::empty().set_square(square) {
-                        policy.eval_one(indexes.place_their_critical_square, 2);
+                        policy.eval_one(params, indexes.place_their_critical_square, 2);
                     } else {
-                        policy.eval_one(indexes.ignore_their_critical_square, 0);
+                        policy.eval_one(params, indexes.ignore_their_critical_square, 0);
                     }
                 }
             }
@@ -498,7 +545,11 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                             .map(Them::is_our_road_piece)
                             .unwrap_or_default()
                     {
-                        policy.eval_one(indexes.blocking_stone_blocks_extensions_of_two_flats, 0);
+                        policy.eval_one(
+                            params,
+                            indexes.blocking_stone_blocks_extensions_of_two_flats,
+                            0,
+                        );
                     }
 
                     if position.stack_heights()[neighbour] > 2
@@ -520,11 +571,13 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                             Flat => unreachable!(),
                             Wall => {
                                 policy.eval_i8(
+                                    params,
                                     indexes.attack_strong_stack_with_wall,
                                     index,
                                     captives as i8,
                                 );
                                 policy.eval_i8(
+                                    params,
                                     indexes.attack_strong_stack_with_wall,
                                     index + 1,
                                     reserves as i8,
@@ -532,11 +585,13 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                             }
                             Cap => {
                                 policy.eval_i8(
+                                    params,
                                     indexes.attack_strong_stack_with_cap,
                                     index,
                                     captives as i8,
                                 );
                                 policy.eval_i8(
+                                    params,
                                     indexes.attack_strong_stack_with_cap,
                                     index + 1,
                                     reserves as i8,
@@ -550,8 +605,18 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                         }) = group_data.last_movement()
                         {
                             if neighbour == destination {
-                                policy.eval_i8(indexes.attack_last_movement, 0, captives as i8);
-                                policy.eval_i8(indexes.attack_last_movement, 1, reserves as i8);
+                                policy.eval_i8(
+                                    params,
+                                    indexes.attack_last_movement,
+                                    0,
+                                    captives as i8,
+                                );
+                                policy.eval_i8(
+                                    params,
+                                    indexes.attack_last_movement,
+                                    1,
+                                    reserves as i8,
+                                );
                             }
                         }
                     }
@@ -565,7 +630,7 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
             }) = group_data.last_movement()
             {
                 if square == origin {
-                    policy.eval_one(indexes.place_last_movement, role_id);
+                    policy.eval_one(params, indexes.place_last_movement, role_id);
                 }
             }
         }
@@ -577,8 +642,8 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                 Cap => 2,
             };
             match Us::color() {
-                Color::White => policy.eval_one(indexes.move_role_bonus_white, role_id),
-                Color::Black => policy.eval_one(indexes.move_role_bonus_black, role_id),
+                Color::White => policy.eval_one(params, indexes.move_role_bonus_white, role_id),
+                Color::Black => policy.eval_one(params, indexes.move_role_bonus_black, role_id),
             }
 
             if stack_movement.len() == 1
@@ -588,14 +653,16 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                 if let Some(piece) = position.top_stones()[square.go_direction(direction).unwrap()]
                 {
                     match (piece.role(), piece.color() == Us::color()) {
-                        (Flat, true) => policy.eval_one(indexes.simple_self_capture, role_id),
-                        (Flat, false) => policy.eval_one(indexes.simple_capture, role_id),
-                        (Wall, true) => policy.eval_one(indexes.simple_self_capture, 3),
-                        (Wall, false) => policy.eval_one(indexes.simple_capture, 3),
+                        (Flat, true) => {
+                            policy.eval_one(params, indexes.simple_self_capture, role_id)
+                        }
+                        (Flat, false) => policy.eval_one(params, indexes.simple_capture, role_id),
+                        (Wall, true) => policy.eval_one(params, indexes.simple_self_capture, 3),
+                        (Wall, false) => policy.eval_one(params, indexes.simple_capture, 3),
                         _ => unreachable!(),
                     }
                 } else {
-                    policy.eval_one(indexes.simple_movement, role_id);
+                    policy.eval_one(params, indexes.simple_movement, role_id);
                 }
             }
 
@@ -609,7 +676,7 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
             // Bonus for moving the piece we placed on our last turn
             if let Some((role, last_square)) = our_last_placement(position) {
                 if square == last_square && position.stack_heights()[destination_square] != 0 {
-                    policy.eval_one(indexes.move_last_placement, role.disc());
+                    policy.eval_one(params, indexes.move_last_placement, role.disc());
                 }
             }
 
@@ -729,12 +796,17 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                     };
                     let road_piece_count = destination_line.count() as usize;
                     if road_piece_count > 2 {
-                        policy.eval_one(indexes.move_cap_onto_strong_line, road_piece_count - 3);
+                        policy.eval_one(
+                            params,
+                            indexes.move_cap_onto_strong_line,
+                            road_piece_count - 3,
+                        );
                         if !(destination_square.neighbors_bitboard()
                             & Us::critical_squares(group_data))
                         .is_empty()
                         {
                             policy.eval_one(
+                                params,
                                 indexes.move_cap_onto_strong_line_with_critical_square,
                                 road_piece_count - 3,
                             );
@@ -751,6 +823,7 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                     if piece.color() != destination_top_stone.color() {
                         if Us::is_our_piece(piece) {
                             policy.eval_i8(
+                                params,
                                 indexes.stack_captured_by_movement,
                                 0,
                                 destination_stack_height as i8,
@@ -758,6 +831,7 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                             their_pieces_captured += 1;
                         } else {
                             policy.eval_i8(
+                                params,
                                 indexes.stack_captured_by_movement,
                                 0,
                                 -(destination_stack_height as i8),
@@ -786,12 +860,14 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                         if our_road_stones > 2 {
                             if piece.role() == Cap {
                                 policy.eval_i8(
+                                    params,
                                     indexes.stack_capture_in_strong_line_cap,
                                     our_road_stones - 3,
                                     color_factor * destination_stack_height as i8,
                                 );
                             } else {
                                 policy.eval_i8(
+                                    params,
                                     indexes.stack_capture_in_strong_line,
                                     our_road_stones - 3,
                                     color_factor * destination_stack_height as i8,
@@ -814,6 +890,7 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                 // It's possible that the spread that board fills also makes them a road
                 check_flat_win::<Us, P, S>(
                     position,
+                    params,
                     our_flatcount_after_move,
                     their_flatcount,
                     policy,
@@ -821,6 +898,7 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
             } else if num_squares_covered == S as u8 * S as u8 - 1 {
                 check_flat_win_next_move::<Us, P, S>(
                     position,
+                    params,
                     our_flatcount_after_move,
                     their_flatcount,
                     policy,
@@ -828,9 +906,9 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
             }
 
             if their_pieces == 0 {
-                policy.eval_one(indexes.pure_spread, 0);
+                policy.eval_one(params, indexes.pure_spread, 0);
             } else {
-                policy.eval_one(indexes.pure_spread, 1);
+                policy.eval_one(params, indexes.pure_spread, 1);
             }
 
             // Continue spreading the stack (the piece, that is) we spread last turn, if any
@@ -840,16 +918,16 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
             }) = group_data.second_to_last_movement()
             {
                 if destination == square {
-                    policy.eval_one(indexes.continue_spread, role_id);
+                    policy.eval_one(params, indexes.continue_spread, role_id);
                 }
             }
 
             // Recapture the stack they moved on their last move
             if let Some(role) = stack_recaptured_with {
                 if their_pieces == 0 {
-                    policy.eval_one(indexes.recapture_stack_pure, role as u16 as usize);
+                    policy.eval_one(params, indexes.recapture_stack_pure, role as u16 as usize);
                 } else {
-                    policy.eval_one(indexes.recapture_stack_impure, role as u16 as usize);
+                    policy.eval_one(params, indexes.recapture_stack_impure, role as u16 as usize);
                 }
             }
 
@@ -859,10 +937,11 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
             if !their_open_critical_squares.is_empty() {
                 if their_pieces_captured == 0 && captures_their_critical_square.is_none() {
                     // Move ignores their critical threat, but might win for us
-                    policy.eval_one(indexes.ignore_their_critical_square, 1)
+                    policy.eval_one(params, indexes.ignore_their_critical_square, 1)
                 } else {
                     // Move captures at least one stack, which might save us
                     policy.eval_i8(
+                        params,
                         indexes.place_their_critical_square,
                         3,
                         their_pieces_captured,
@@ -880,7 +959,7 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                         && (direction == North || direction == South)
                 {
                     // Only this option is a guaranteed win:
-                    policy.eval_one(indexes.move_onto_critical_square, 0);
+                    policy.eval_one(params, indexes.move_onto_critical_square, 0);
                     policy.set_immediate_win();
                 } else {
                     // Check if reaching the critical square still wins, in case our
@@ -898,7 +977,7 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
 
                     if edge_connection.is_winning() {
                         // Only this option is a guaranteed win:
-                        policy.eval_one(indexes.move_onto_critical_square, 0);
+                        policy.eval_one(params, indexes.move_onto_critical_square, 0);
                         policy.set_immediate_win();
                     }
                     // If the critical square has two neighbours of the same group,
@@ -917,10 +996,10 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
                             > 1
                         && position.top_stones()[critical_square].map(Piece::role) != Some(Wall)
                     {
-                        policy.eval_one(indexes.move_onto_critical_square, 1);
+                        policy.eval_one(params, indexes.move_onto_critical_square, 1);
                         policy.set_immediate_win();
                     } else {
-                        policy.eval_one(indexes.move_onto_critical_square, 2)
+                        policy.eval_one(params, indexes.move_onto_critical_square, 2)
                     }
                 }
             }
@@ -932,7 +1011,7 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
             }
 
             if group_edge_connection.is_winning() {
-                policy.eval_one(indexes.spread_that_connects_groups_to_win, 0);
+                policy.eval_one(params, indexes.spread_that_connects_groups_to_win, 0);
                 policy.set_immediate_win();
             }
         }
@@ -941,6 +1020,7 @@ fn features_for_move_colortr<Us: ColorTr, Them: ColorTr, P: PolicyApplier, const
 
 fn check_flat_win_next_move<Us: ColorTr, P: PolicyApplier, const S: usize>(
     position: &Position<S>,
+    params: &[f32],
     our_flatcount_after_move: i8,
     their_flatcount: i8,
     policy: &mut P,
@@ -951,24 +1031,33 @@ fn check_flat_win_next_move<Us: ColorTr, P: PolicyApplier, const S: usize>(
             .komi()
             .game_result_with_flatcounts(our_flatcount_after_move, their_flatcount + 1)
         {
-            GameResult::WhiteWin => policy.eval_one(indexes.place_to_allow_opponent_to_end, 2),
-            GameResult::BlackWin => policy.eval_one(indexes.place_to_allow_opponent_to_end, 0),
-            GameResult::Draw => policy.eval_one(indexes.place_to_allow_opponent_to_end, 1),
+            GameResult::WhiteWin => {
+                policy.eval_one(params, indexes.place_to_allow_opponent_to_end, 2)
+            }
+            GameResult::BlackWin => {
+                policy.eval_one(params, indexes.place_to_allow_opponent_to_end, 0)
+            }
+            GameResult::Draw => policy.eval_one(params, indexes.place_to_allow_opponent_to_end, 1),
         }
     } else {
         match position
             .komi()
             .game_result_with_flatcounts(their_flatcount + 1, our_flatcount_after_move)
         {
-            GameResult::WhiteWin => policy.eval_one(indexes.place_to_allow_opponent_to_end, 0),
-            GameResult::BlackWin => policy.eval_one(indexes.place_to_allow_opponent_to_end, 2),
-            GameResult::Draw => policy.eval_one(indexes.place_to_allow_opponent_to_end, 1),
+            GameResult::WhiteWin => {
+                policy.eval_one(params, indexes.place_to_allow_opponent_to_end, 0)
+            }
+            GameResult::BlackWin => {
+                policy.eval_one(params, indexes.place_to_allow_opponent_to_end, 2)
+            }
+            GameResult::Draw => policy.eval_one(params, indexes.place_to_allow_opponent_to_end, 1),
         }
     }
 }
 
 fn check_flat_win<Us: ColorTr, P: PolicyApplier, const S: usize>(
     position: &Position<S>,
+    params: &[f32],
     our_flatcount_after_move: i8,
     their_flatcount: i8,
     policy: &mut P,
@@ -980,23 +1069,23 @@ fn check_flat_win<Us: ColorTr, P: PolicyApplier, const S: usize>(
             .game_result_with_flatcounts(our_flatcount_after_move, their_flatcount)
         {
             GameResult::WhiteWin => {
-                policy.eval_one(indexes.place_to_win, 0);
+                policy.eval_one(params, indexes.place_to_win, 0);
                 policy.set_immediate_win();
             }
-            GameResult::BlackWin => policy.eval_one(indexes.place_to_loss, 0),
-            GameResult::Draw => policy.eval_one(indexes.place_to_draw, 0),
+            GameResult::BlackWin => policy.eval_one(params, indexes.place_to_loss, 0),
+            GameResult::Draw => policy.eval_one(params, indexes.place_to_draw, 0),
         }
     } else {
         match position
             .komi()
             .game_result_with_flatcounts(their_flatcount, our_flatcount_after_move)
         {
-            GameResult::WhiteWin => policy.eval_one(indexes.place_to_loss, 0),
+            GameResult::WhiteWin => policy.eval_one(params, indexes.place_to_loss, 0),
             GameResult::BlackWin => {
-                policy.eval_one(indexes.place_to_win, 0);
+                policy.eval_one(params, indexes.place_to_win, 0);
                 policy.set_immediate_win();
             }
-            GameResult::Draw => policy.eval_one(indexes.place_to_draw, 0),
+            GameResult::Draw => policy.eval_one(params, indexes.place_to_draw, 0),
         }
     }
 }
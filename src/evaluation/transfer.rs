@@ -0,0 +1,241 @@
+//! Parameters for board sizes without their own trained parameters, created by mapping every feature of the 6s parameters onto the larger board.
+//! These are untrained placeholders until real parameters are trained for those sizes. Their strength has not been measured,
+//! and they are mainly a starting point for tuning.
+
+use crate::evaluation::parameters::{
+    num_policy_features, num_value_features, policy_indexes, value_indexes, IndexPair,
+    PolicyIndexes, ValueIndexes,
+};
+use crate::position::{lookup_square_symmetries, num_square_symmetries, Square};
+
+/// The size of the parameters that are transferred to other sizes
+const SOURCE_SIZE: usize = 6;
+
+/// How a feature's values are laid out, which determines how they are mapped to another size
+#[derive(Clone, Copy, Debug)]
+enum Layout {
+    /// The feature has the same length for all sizes
+    Same,
+    /// One value per square symmetry
+    Squares,
+    /// Indexed by the number of ranks or files occupied
+    LinesOccupied,
+    /// Indexed by the number of our pieces in the line, plus the line's symmetry times the size
+    LineControl,
+    /// Indexed by the number of our pieces in the line, plus the role of the piece times the size
+    RoadStonesInLine,
+    /// Indexed by the number of our pieces in the line, minus 3
+    StrongLine,
+    Padding,
+}
+
+/// Create value parameters for size `S` from 6s value parameters
+pub fn value_params<const S: usize>(source_params: &[f32]) -> Vec<f32> {
+    assert_eq!(source_params.len(), num_value_features::<SOURCE_SIZE>());
+    let source_layouts = value_layouts(&value_indexes::<SOURCE_SIZE>());
+    let layouts = value_layouts(&value_indexes::<S>());
+
+    // The parameters have one half for each color, with identical layouts
+    let (source_white, source_black) = source_params.split_at(source_params.len() / 2);
+    let mut params = vec![0.0; num_value_features::<S>()];
+    let (white, black) = params.split_at_mut(num_value_features::<S>() / 2);
+    transfer_features::<S>(source_white, white, &source_layouts, &layouts);
+    transfer_features::<S>(source_black, black, &source_layouts, &layouts);
+    params
+}
+
+/// Create policy parameters for size `S` from 6s policy parameters
+pub fn policy_params<const S: usize>(source_params: &[f32]) -> Vec<f32> {
+    assert_eq!(source_params.len(), num_policy_features::<SOURCE_SIZE>());
+    let source_layouts = policy_layouts(&policy_indexes::<SOURCE_SIZE>());
+    let layouts = policy_layouts(&policy_indexes::<S>());
+
+    let mut params = vec![0.0; num_policy_features::<S>()];
+    transfer_features::<S>(source_params, &mut params, &source_layouts, &layouts);
+    params
+}
+
+fn transfer_features<const S: usize>(
+    source_params: &[f32],
+    params: &mut [f32],
+    source_layouts: &[(IndexPair, Layout)],
+    layouts: &[(IndexPair, Layout)],
+) {
+    let square_mapping = square_symmetry_mapping::<S>();
+
+    for (&(source_indexes, layout), &(indexes, _)) in source_layouts.iter().zip(layouts) {
+        let source = source_indexes.as_slice(source_params);
+        let target = indexes.as_mut_slice(params);
+        for (i, param) in target.iter_mut().enumerate() {
+            *param = match layout {
+                Layout::Same => source[i],
+                Layout::Squares => source[square_mapping[i]],
+                Layout::LinesOccupied => source[(i * SOURCE_SIZE + S / 2) / S],
+                Layout::LineControl => {
+                    let (symmetry, count) = (i / S, i % S);
+                    source[symmetry.min(2) * SOURCE_SIZE + map_line_count::<S>(count)]
+                }
+                Layout::RoadStonesInLine => {
+                    let (role, count) = (i / S, i % S);
+                    source[role * SOURCE_SIZE + map_line_count::<S>(count)]
+                }
+                Layout::StrongLine => source[map_line_count::<S>(i + 3).max(3) - 3],
+                Layout::Padding => 0.0,
+            };
+        }
+    }
+}
+
+/// Map the number of our pieces in a line to the 6s line with the same number of empty squares,
+/// except that a line with any of our pieces never maps to an empty line
+fn map_line_count<const S: usize>(count: usize) -> usize {
+    if count == 0 {
+        0
+    } else {
+        (count + SOURCE_SIZE).saturating_sub(S).max(1)
+    }
+}
+
+/// For each square symmetry in size `S`, the 6s square symmetry at the same relative position on the board
+fn square_symmetry_mapping<const S: usize>() -> Vec<usize> {
+    let mut mapping = vec![0; num_square_symmetries::<S>()];
+    let scale = |i: u8| ((i as usize * (SOURCE_SIZE - 1) + (S - 1) / 2) / (S - 1)) as u8;
+    for rank in 0..S as u8 {
+        for file in 0..S as u8 {
+            let source_square = Square::<SOURCE_SIZE>::from_rank_file(scale(rank), scale(file));
+            mapping[lookup_square_symmetries::<S>(Square::from_rank_file(rank, file))] =
+                lookup_square_symmetries::<SOURCE_SIZE>(source_square);
+        }
+    }
+    mapping
+}
+
+fn value_layouts<const S: usize>(indexes: &ValueIndexes<S>) -> Vec<(IndexPair, Layout)> {
+    use Layout::*;
+    vec![
+        (indexes.first_ply, Same),
+        (indexes.second_ply, Squares),
+        (indexes.flat_psqt_opening, Squares),
+        (indexes.flat_psqt_middlegame, Squares),
+        (indexes.flat_psqt_endgame, Squares),
+        (indexes.wall_psqt_opening, Squares),
+        (indexes.wall_psqt_middlegame, Squares),
+        (indexes.wall_psqt_endgame, Squares),
+        (indexes.cap_psqt_opening, Squares),
+        (indexes.cap_psqt_middlegame, Squares),
+        (indexes.cap_psqt_endgame, Squares),
+        (indexes.supports_psqt_opening, Squares),
+        (indexes.supports_psqt_middlegame, Squares),
+        (indexes.supports_psqt_endgame, Squares),
+        (indexes.captives_psqt_opening, Squares),
+        (indexes.captives_psqt_middlegame, Squares),
+        (indexes.captives_psqt_endgame, Squares),
+        (indexes.flat_win_this_ply, Same),
+        (indexes.flat_win_next_ply, Same),
+        (indexes.flat_win_two_ply, Same),
+        (indexes.flat_win_three_ply, Same),
+        (indexes.one_reserve_left_us, Same),
+        (indexes.one_reserve_left_them, Same),
+        (indexes.shallow_supports_per_piece, Same),
+        (indexes.shallow_supports_per_piece_mobility, Same),
+        (indexes.shallow_supports_per_piece_mob_scaled, Same),
+        (indexes.deep_supports_per_piece, Same),
+        (indexes.shallow_captives_per_piece, Same),
+        (indexes.shallow_captives_per_piece_mobility, Same),
+        (indexes.shallow_captives_per_piece_mob_scaled, Same),
+        (indexes.deep_captives_per_piece, Same),
+        (indexes.to_move_opening_flatstone_lead, Same),
+        (indexes.to_move_middlegame_flatstone_lead, Same),
+        (indexes.to_move_endgame_flatstone_lead, Same),
+        (indexes.i_number_of_groups, Same),
+        (indexes.critical_squares, Same),
+        (indexes.critical_square_cap_attack, Same),
+        (indexes.winning_spread_to_move, Same),
+        (indexes.winning_flat_spread_not_to_move, Same),
+        (indexes.winning_cap_spread_not_to_move, Same),
+        (indexes.flat_next_to_our_stack, Same),
+        (indexes.wall_next_to_our_stack, Same),
+        (indexes.cap_next_to_our_stack, Same),
+        (indexes.num_lines_occupied, LinesOccupied),
+        (indexes.line_control_empty, LineControl),
+        (indexes.line_control_their_blocking_piece, LineControl),
+        (indexes.line_control_other, LineControl),
+        (indexes.line_control_guarded_flat, Same),
+        (indexes.line_control_guarded_wall, Same),
+        (indexes.line_control_guarded_cap, Same),
+        (indexes.sidelined_cap, Same),
+        (indexes.fully_isolated_cap, Same),
+        (indexes.semi_isolated_cap, Same),
+        (indexes.padding, Padding),
+    ]
+}
+
+fn policy_layouts<const S: usize>(indexes: &PolicyIndexes<S>) -> Vec<(IndexPair, Layout)> {
+    use Layout::*;
+    vec![
+        (indexes.flat_psqt_white, Squares),
+        (indexes.flat_psqt_black, Squares),
+        (indexes.wall_psqt_white, Squares),
+        (indexes.wall_psqt_black, Squares),
+        (indexes.cap_psqt_white, Squares),
+        (indexes.cap_psqt_black, Squares),
+        (indexes.move_role_bonus_white, Same),
+        (indexes.move_role_bonus_black, Same),
+        (indexes.decline_win, Same),
+        (indexes.place_to_win, Same),
+        (indexes.place_to_draw, Same),
+        (indexes.place_to_loss, Same),
+        (indexes.place_to_allow_opponent_to_end, Same),
+        (indexes.two_flats_left, Same),
+        (indexes.three_flats_left, Same),
+        (indexes.our_road_stones_in_line, RoadStonesInLine),
+        (indexes.their_road_stones_in_line, RoadStonesInLine),
+        (indexes.extend_single_group_base, Same),
+        (indexes.extend_single_group_linear, Same),
+        (indexes.extend_single_group_to_new_line_base, Same),
+        (indexes.extend_single_group_to_new_line_linear, Same),
+        (indexes.merge_two_groups_base, Same),
+        (indexes.merge_two_groups_linear, Same),
+        (indexes.block_merger_base, Same),
+        (indexes.block_merger_linear, Same),
+        (indexes.anchor_group_base, Same),
+        (indexes.anchor_group_linear, Same),
+        (indexes.block_anchoring_group_base, Same),
+        (indexes.block_anchoring_group_linear, Same),
+        (indexes.place_our_critical_square, Same),
+        (indexes.place_their_critical_square, Same),
+        (indexes.ignore_their_critical_square, Same),
+        (indexes.next_to_our_last_stone, Same),
+        (indexes.next_to_their_last_stone, Same),
+        (indexes.diagonal_to_our_last_stone, Same),
+        (indexes.diagonal_to_their_last_stone, Same),
+        (indexes.attack_strong_flats, Same),
+        (indexes.blocking_stone_blocks_extensions_of_two_flats, Same),
+        (indexes.attack_strong_stack_with_wall, Same),
+        (indexes.attack_strong_stack_with_cap, Same),
+        (indexes.attack_last_movement, Same),
+        (indexes.place_last_movement, Same),
+        (indexes.simple_movement, Same),
+        (indexes.simple_capture, Same),
+        (indexes.simple_self_capture, Same),
+        (indexes.pure_spread, Same),
+        (indexes.fcd_highest_board, Same),
+        (indexes.fcd_highest_stack, Same),
+        (indexes.fcd_other, Same),
+        (indexes.stack_captured_by_movement, Same),
+        (indexes.stack_capture_in_strong_line, StrongLine),
+        (indexes.stack_capture_in_strong_line_cap, StrongLine),
+        (indexes.move_cap_onto_strong_line, StrongLine),
+        (
+            indexes.move_cap_onto_strong_line_with_critical_square,
+            StrongLine,
+        ),
+        (indexes.recapture_stack_pure, Same),
+        (indexes.recapture_stack_impure, Same),
+        (indexes.move_last_placement, Same),
+        (indexes.continue_spread, Same),
+        (indexes.move_onto_critical_square, Same),
+        (indexes.spread_that_connects_groups_to_win, Same),
+        (indexes.padding, Padding),
+    ]
+}
@@ -27,7 +27,7 @@ pub fn sigmoid_derived(x: f32) -> f32 {
     1.0 / (PI * (1.0 + x.powi(2)))
 }
 
-pub fn static_eval_game_phase<const S: usize, V: ValueApplier>(
+pub fn static_eval_game_phase<'a, const S: usize, V: ValueApplier<'a>>(
     position: &Position<S>,
     group_data: &GroupData<S>,
     white_value: &mut V,
@@ -617,7 +617,7 @@ pub fn static_eval_game_phase<const S: usize, V: ValueApplier>(
     );
 }
 
-fn flat_win<Us: ColorTr, Them: ColorTr, V: ValueApplier, const S: usize>(
+fn flat_win<'a, Us: ColorTr, Them: ColorTr, V: ValueApplier<'a>, const S: usize>(
     position: &Position<S>,
     white_flat_count: i8,
     black_flat_count: i8,
@@ -697,7 +697,7 @@ fn flat_win<Us: ColorTr, Them: ColorTr, V: ValueApplier, const S: usize>(
     }
 }
 
-fn cap_activity<Us: ColorTr, Them: ColorTr, V: ValueApplier, const S: usize>(
+fn cap_activity<'a, Us: ColorTr, Them: ColorTr, V: ValueApplier<'a>, const S: usize>(
     position: &Position<S>,
     square: Square<S>,
     our_value: &mut V,
@@ -799,7 +799,7 @@ fn pure_winning_spread_to<const S: usize>(
 }
 
 /// Give bonus for our critical squares
-fn critical_squares_eval<Us: ColorTr, Them: ColorTr, V: ValueApplier, const S: usize>(
+fn critical_squares_eval<'a, Us: ColorTr, Them: ColorTr, V: ValueApplier<'a>, const S: usize>(
     position: &Position<S>,
     group_data: &GroupData<S>,
     critical_square: Square<S>,
@@ -824,17 +824,13 @@ fn critical_squares_eval<Us: ColorTr, Them: ColorTr, V: ValueApplier, const S: u
     let rank = critical_square.rank();
     let file = critical_square.file();
 
-    let capstone_square_in_line = {
-        let capstone_in_rank = BitBoard::full().rank::<S>(rank) & Us::caps(group_data);
-        let capstone_in_file = BitBoard::full().file::<S>(file) & Us::caps(group_data);
-        capstone_in_rank
-            .occupied_square()
-            .or(capstone_in_file.occupied_square())
-    };
+    // Several capstones can be in line on boards with more than one capstone
+    let capstones_in_line = (BitBoard::full().rank::<S>(rank) | BitBoard::full().file::<S>(file))
+        & Us::caps(group_data);
 
     // Bonuses when our capstone can spread to the critical square
     // TODO: Don't give bonuses if walls/caps block the spread
-    if let Some(capstone_square) = capstone_square_in_line {
+    for capstone_square in capstones_in_line.into_iter() {
         let distance =
             file.abs_diff(capstone_square.file()) + rank.abs_diff(capstone_square.rank());
         let cap_stack = position.get_stack(capstone_square);
@@ -874,7 +870,7 @@ fn critical_squares_eval<Us: ColorTr, Them: ColorTr, V: ValueApplier, const S: u
 }
 
 #[inline(always)] // Force-inlining gives a 1.5% performance boost
-fn line_score<Us: ColorTr, Them: ColorTr, V: ValueApplier, const S: usize>(
+fn line_score<'a, Us: ColorTr, Them: ColorTr, V: ValueApplier<'a>, const S: usize>(
     position: &Position<S>,
     group_data: &GroupData<S>,
     line: BitBoard,
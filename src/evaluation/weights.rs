@@ -0,0 +1,354 @@
+//! Evaluation parameters stored in a file, so that newly tuned parameters can be used without recompiling.
+//!
+//! A weights file is a short plain text header, followed by one parameter per line:
+//! ```text
+//! tiltak-weights 1
+//! kind value
+//! size 6
+//! komi 2
+//! features 560
+//! 0.14389691
+//! ...
+//! ```
+//!
+//! Loaded parameters are reference counted, so that every search can share them without copying.
+
+use std::fmt::{self, Display};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::{fs, str::FromStr};
+
+use crate::evaluation::parameters::{
+    num_policy_features, num_value_features, policy_indexes, value_indexes,
+};
+use crate::position::Komi;
+
+/// The version written in the header of new weights files
+pub const WEIGHTS_FORMAT_VERSION: u32 = 1;
+
+const MAGIC: &str = "tiltak-weights";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeightsKind {
+    Value,
+    Policy,
+}
+
+impl Display for WeightsKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WeightsKind::Value => write!(f, "value"),
+            WeightsKind::Policy => write!(f, "policy"),
+        }
+    }
+}
+
+impl FromStr for WeightsKind {
+    type Err = WeightsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "value" => Ok(WeightsKind::Value),
+            "policy" => Ok(WeightsKind::Policy),
+            _ => Err(WeightsError::Parse(format!(
+                "Unknown weights kind \"{}\"",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WeightsError {
+    Io(io::Error),
+    /// The file is not a weights file, or is malformed
+    Parse(String),
+    UnsupportedVersion(u32),
+    WrongKind {
+        expected: WeightsKind,
+        actual: WeightsKind,
+    },
+    WrongSize {
+        expected: usize,
+        actual: usize,
+    },
+    UnsupportedSize(usize),
+    /// The weights were tuned for a different komi than the game's
+    WrongKomi {
+        expected: Komi,
+        actual: Komi,
+    },
+    /// The number of features does not match the feature layout for the board size
+    WrongFeatureCount {
+        expected: usize,
+        actual: usize,
+    },
+    NonFinite {
+        index: usize,
+    },
+}
+
+impl Display for WeightsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WeightsError::Io(err) => write!(f, "IO error: {}", err),
+            WeightsError::Parse(err) => write!(f, "Invalid weights file: {}", err),
+            WeightsError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported weights file version {}, expected {}",
+                version, WEIGHTS_FORMAT_VERSION
+            ),
+            WeightsError::WrongKind { expected, actual } => {
+                write!(f, "Expected {} weights, got {} weights", expected, actual)
+            }
+            WeightsError::WrongSize { expected, actual } => {
+                write!(f, "Expected weights for {}s, got {}s", expected, actual)
+            }
+            WeightsError::UnsupportedSize(size) => write!(f, "Unsupported size {}", size),
+            WeightsError::WrongKomi { expected, actual } => write!(
+                f,
+                "Expected weights for {} komi, got weights for {} komi",
+                expected, actual
+            ),
+            WeightsError::WrongFeatureCount { expected, actual } => write!(
+                f,
+                "Expected {} features, got {}. The weights were probably written by a different version of the evaluation",
+                expected, actual
+            ),
+            WeightsError::NonFinite { index } => write!(f, "Parameter {} is not finite", index),
+        }
+    }
+}
+
+impl std::error::Error for WeightsError {}
+
+impl From<io::Error> for WeightsError {
+    fn from(err: io::Error) -> Self {
+        WeightsError::Io(err)
+    }
+}
+
+/// Value or policy parameters for a single board size
+#[derive(Clone, Debug, PartialEq)]
+pub struct Weights {
+    pub kind: WeightsKind,
+    pub size: usize,
+    /// The komi the parameters were tuned for
+    pub komi: Komi,
+    pub params: Vec<f32>,
+}
+
+impl Weights {
+    pub fn new(kind: WeightsKind, size: usize, komi: Komi, params: Vec<f32>) -> Self {
+        Weights {
+            kind,
+            size,
+            komi,
+            params,
+        }
+    }
+
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, WeightsError> {
+        let mut lines = reader.lines();
+        let mut header_line = |key: &str| -> Result<String, WeightsError> {
+            let line = lines
+                .next()
+                .ok_or_else(|| WeightsError::Parse(format!("Missing {} header", key)))??;
+            match line.trim().split_once(' ') {
+                Some((line_key, value)) if line_key == key => Ok(value.trim().to_string()),
+                _ => Err(WeightsError::Parse(format!(
+                    "Expected {} header, got \"{}\"",
+                    key, line
+                ))),
+            }
+        };
+        let parse_number = |key: &str, value: String| {
+            value.parse::<usize>().map_err(|err| {
+                WeightsError::Parse(format!("Invalid {} \"{}\": {}", key, value, err))
+            })
+        };
+
+        let version = parse_number("version", header_line(MAGIC)?)?;
+        if version != WEIGHTS_FORMAT_VERSION as usize {
+            return Err(WeightsError::UnsupportedVersion(version as u32));
+        }
+        let kind: WeightsKind = header_line("kind")?.parse()?;
+        let size = parse_number("size", header_line("size")?)?;
+        let komi: Komi = header_line("komi")?.parse().map_err(WeightsError::Parse)?;
+        let num_features = parse_number("features", header_line("features")?)?;
+
+        let mut params = Vec::with_capacity(num_features);
+        for line in lines {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            params.push(line.parse::<f32>().map_err(|err| {
+                WeightsError::Parse(format!("Invalid parameter \"{}\": {}", line, err))
+            })?);
+        }
+        if params.len() != num_features {
+            return Err(WeightsError::Parse(format!(
+                "Header says {} features, but the file has {}",
+                num_features,
+                params.len()
+            )));
+        }
+
+        Ok(Weights {
+            kind,
+            size,
+            komi,
+            params,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{} {}", MAGIC, WEIGHTS_FORMAT_VERSION)?;
+        writeln!(writer, "kind {}", self.kind)?;
+        writeln!(writer, "size {}", self.size)?;
+        writeln!(writer, "komi {}", self.komi)?;
+        writeln!(writer, "features {}", self.params.len())?;
+        for param in self.params.iter() {
+            writeln!(writer, "{}", param)?;
+        }
+        writer.flush()
+    }
+
+    /// Read and validate a weights file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, WeightsError> {
+        let file = fs::File::open(path)?;
+        let weights = Self::read(io::BufReader::new(file))?;
+        weights.validate()?;
+        Ok(weights)
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(io::BufWriter::new(fs::File::create(path)?))
+    }
+
+    /// Check that the parameters match the feature layout for their size
+    pub fn validate(&self) -> Result<(), WeightsError> {
+        match self.size {
+            4 => self.validate_for_size::<4>(),
+            5 => self.validate_for_size::<5>(),
+            6 => self.validate_for_size::<6>(),
+            7 => self.validate_for_size::<7>(),
+            8 => self.validate_for_size::<8>(),
+            size => Err(WeightsError::UnsupportedSize(size)),
+        }
+    }
+
+    /// Check that the parameters can be used as `kind` parameters for size `S`
+    pub fn validate_as<const S: usize>(&self, kind: WeightsKind) -> Result<(), WeightsError> {
+        if self.kind != kind {
+            return Err(WeightsError::WrongKind {
+                expected: kind,
+                actual: self.kind,
+            });
+        }
+        self.validate_for_size::<S>()
+    }
+
+    fn validate_for_size<const S: usize>(&self) -> Result<(), WeightsError> {
+        if self.size != S {
+            return Err(WeightsError::WrongSize {
+                expected: S,
+                actual: self.size,
+            });
+        }
+        // The padding is the last feature in both layouts.
+        // The value parameters have one half for each color, with identical layouts
+        let num_features = match self.kind {
+            WeightsKind::Value => value_indexes::<S>().padding.range().end * 2,
+            WeightsKind::Policy => policy_indexes::<S>().padding.range().end,
+        };
+        debug_assert_eq!(
+            num_features,
+            match self.kind {
+                WeightsKind::Value => num_value_features::<S>(),
+                WeightsKind::Policy => num_policy_features::<S>(),
+            }
+        );
+        if self.params.len() != num_features {
+            return Err(WeightsError::WrongFeatureCount {
+                expected: num_features,
+                actual: self.params.len(),
+            });
+        }
+        if let Some(index) = self.params.iter().position(|param| !param.is_finite()) {
+            return Err(WeightsError::NonFinite { index });
+        }
+        Ok(())
+    }
+}
+
+/// Weights loaded from the command line, which replace the built-in parameters for their board size
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LoadedWeights {
+    value: Option<(usize, Komi, Arc<[f32]>)>,
+    policy: Option<(usize, Komi, Arc<[f32]>)>,
+}
+
+impl LoadedWeights {
+    /// Load and validate value and/or policy weights files
+    pub fn load(value_path: Option<&str>, policy_path: Option<&str>) -> Result<Self, WeightsError> {
+        let load = |path: &str, kind: WeightsKind| -> Result<_, WeightsError> {
+            let weights = Weights::from_file(path)?;
+            if weights.kind != kind {
+                return Err(WeightsError::WrongKind {
+                    expected: kind,
+                    actual: weights.kind,
+                });
+            }
+            Ok((weights.size, weights.komi, weights.params.into()))
+        };
+        Ok(LoadedWeights {
+            value: value_path
+                .map(|path| load(path, WeightsKind::Value))
+                .transpose()?,
+            policy: policy_path
+                .map(|path| load(path, WeightsKind::Policy))
+                .transpose()?,
+        })
+    }
+
+    /// The loaded value parameters, if they are for size `S`
+    pub fn value_params<const S: usize>(&self) -> Option<Arc<[f32]>> {
+        self.value
+            .as_ref()
+            .filter(|(size, _, _)| *size == S)
+            .map(|(_, _, params)| params.clone())
+    }
+
+    /// The loaded policy parameters, if they are for size `S`
+    pub fn policy_params<const S: usize>(&self) -> Option<Arc<[f32]>> {
+        self.policy
+            .as_ref()
+            .filter(|(size, _, _)| *size == S)
+            .map(|(_, _, params)| params.clone())
+    }
+
+    /// Check that the loaded weights for `size`, if any, were tuned for `komi`
+    pub fn check_komi(&self, size: usize, komi: Komi) -> Result<(), WeightsError> {
+        for (weights_size, weights_komi, _) in self.value.iter().chain(self.policy.iter()) {
+            if *weights_size == size && *weights_komi != komi {
+                return Err(WeightsError::WrongKomi {
+                    expected: komi,
+                    actual: *weights_komi,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// The sizes of the loaded value and policy weights
+    pub fn sizes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.value
+            .iter()
+            .chain(self.policy.iter())
+            .map(|(size, _, _)| *size)
+    }
+}
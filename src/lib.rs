@@ -32,18 +32,23 @@ extern crate pgn_traits;
 
 pub use search::mcts;
 
-#[cfg(feature = "aws-lambda-runtime")]
+#[cfg(any(feature = "aws-lambda-runtime", feature = "serve"))]
 pub mod aws;
 pub mod minmax;
 pub mod move_gen;
 #[cfg(feature = "sqlite")]
 pub mod policy_sqlite;
 pub mod position;
+pub mod puzzles;
 pub mod search;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
 #[cfg(test)]
 mod tests;
 #[cfg(feature = "constant-tuning")]
 pub mod tune;
 
+pub mod annotate;
+pub mod book;
 pub mod evaluation;
 pub mod ptn;
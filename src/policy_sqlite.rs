@@ -81,12 +81,12 @@ fn policy_finds_win(position: &Position<5>) -> Option<Move<5>> {
 
     let parameters = <Position<5>>::policy_params(position.komi());
 
-    let mut policies: Vec<IncrementalPolicy<5>> =
-        vec![IncrementalPolicy::new(parameters); moves.len()];
+    let mut policies: Vec<IncrementalPolicy<5>> = vec![IncrementalPolicy::new(); moves.len()];
 
     let simple_moves: Vec<Move<5>> = moves.iter().map(|(mv, _)| *mv).collect();
 
     position.features_for_moves(
+        parameters,
         &mut policies,
         &simple_moves,
         &mut fcd_per_move,
@@ -179,6 +179,8 @@ pub fn check_all_games() {
     }
 }
 
+/// Read every 5s game from `puzzles.db`, which only needs the `notation`, `komi` and `size` columns,
+/// unlike the full playtak.com schema read by the `sqlite` module
 fn read_all_games() -> Vec<Game<5>> {
     let conn = Connection::open("puzzles.db").unwrap();
 
@@ -74,6 +74,8 @@ pub(crate) const fn num_square_symmetries<const S: usize>() -> usize {
         4 => 3,
         5 => 6,
         6 => 6,
+        7 => 10,
+        8 => 10,
         _ => 0,
     }
 }
@@ -104,12 +106,16 @@ const fn generate_square_symmetries_table<const S: usize>() -> AbstractBoard<usi
 pub(crate) const SQUARE_SYMMETRIES_4S: AbstractBoard<usize, 4> = generate_square_symmetries_table();
 pub(crate) const SQUARE_SYMMETRIES_5S: AbstractBoard<usize, 5> = generate_square_symmetries_table();
 pub(crate) const SQUARE_SYMMETRIES_6S: AbstractBoard<usize, 6> = generate_square_symmetries_table();
+pub(crate) const SQUARE_SYMMETRIES_7S: AbstractBoard<usize, 7> = generate_square_symmetries_table();
+pub(crate) const SQUARE_SYMMETRIES_8S: AbstractBoard<usize, 8> = generate_square_symmetries_table();
 
 pub(crate) fn lookup_square_symmetries<const S: usize>(square: Square<S>) -> usize {
     match S {
         4 => SQUARE_SYMMETRIES_4S[square.downcast_size()],
         5 => SQUARE_SYMMETRIES_5S[square.downcast_size()],
         6 => SQUARE_SYMMETRIES_6S[square.downcast_size()],
+        7 => SQUARE_SYMMETRIES_7S[square.downcast_size()],
+        8 => SQUARE_SYMMETRIES_8S[square.downcast_size()],
         _ => unimplemented!("Unsupported size {}", S),
     }
 }
@@ -119,6 +125,8 @@ pub(crate) const fn num_line_symmetries<const S: usize>() -> usize {
         4 => 2,
         5 => 3,
         6 => 3,
+        7 => 4,
+        8 => 4,
         _ => 0,
     }
 }
@@ -128,6 +136,8 @@ pub(crate) const fn line_symmetries<const S: usize>() -> &'static [usize] {
         4 => &[0, 1, 1, 0],
         5 => &[0, 1, 2, 1, 0],
         6 => &[0, 1, 2, 2, 1, 0],
+        7 => &[0, 1, 2, 3, 2, 1, 0],
+        8 => &[0, 1, 2, 3, 3, 2, 1, 0],
         _ => &[],
     }
 }
@@ -694,7 +704,6 @@ impl<const S: usize> Position<S> {
         self.black_caps_left
     }
 
-    #[cfg(test)]
     pub fn zobrist_hash(&self) -> u64 {
         self.hash
     }
@@ -1080,8 +1089,7 @@ impl<const S: usize> Position<S> {
 
         // TODO: Include highest id?
         for id in 1..highest_component_id {
-            if (components.raw[0].iter().any(|&cell| cell == id)
-                && components.raw[S - 1].iter().any(|&cell| cell == id))
+            if (components.raw[0].contains(&id) && components.raw[S - 1].contains(&id))
                 || ((0..S).any(|y| components.raw[y][0] == id)
                     && (0..S).any(|y| components.raw[y][S - 1] == id))
             {
@@ -1101,7 +1109,7 @@ impl<const S: usize> Position<S> {
     pub(crate) fn static_eval_with_params_and_data(
         &self,
         group_data: &GroupData<S>,
-        params: &'static [f32],
+        params: &[f32],
     ) -> f32 {
         let (white_params, black_params) = params.split_at(params.len() / 2);
         let mut white_value_features: IncrementalValue<S> = IncrementalValue::new(white_params);
@@ -1115,19 +1123,29 @@ impl<const S: usize> Position<S> {
         white_value_features.finish() + black_value_features.finish()
     }
 
+    /// Value parameters for the given komi. Komi between 0 and 2 use an interpolation of the 0 and 2 komi parameters.
+    /// There are no trained parameters for komi below 0 or above 2, which extrapolate from those sets instead, and print a warning
     pub fn value_params(komi: Komi) -> &'static [f32] {
-        match komi.half_komi() {
-            0 => Self::value_params_0komi(),
-            4 => Self::value_params_2komi(),
-            _ => unimplemented!("{} komi not supported in {}s", komi, S),
+        match S {
+            4 => parameters::value_features_4s(komi),
+            5 => parameters::value_features_5s(komi),
+            6 => parameters::value_features_6s(komi),
+            7 => parameters::value_features_7s(komi),
+            8 => parameters::value_features_8s(komi),
+            _ => unimplemented!("{}s is not supported for {} komi.", S, komi),
         }
     }
 
+    /// Policy parameters for the given komi. Komi between 0 and 2 use an interpolation of the 0 and 2 komi parameters.
+    /// There are no trained parameters for komi below 0 or above 2, which extrapolate from those sets instead, and print a warning
     pub fn policy_params(komi: Komi) -> &'static [f32] {
-        match komi.half_komi() {
-            0 => Self::policy_params_0komi(),
-            4 => Self::policy_params_2komi(),
-            _ => unimplemented!("{} komi not supported in {}s", komi, S),
+        match S {
+            4 => parameters::policy_features_4s(komi),
+            5 => parameters::policy_features_5s(komi),
+            6 => parameters::policy_features_6s(komi),
+            7 => parameters::policy_features_7s(komi),
+            8 => parameters::policy_features_8s(komi),
+            _ => unimplemented!("{}s is not supported for {} komi.", S, komi),
         }
     }
 
@@ -1167,7 +1185,11 @@ impl<const S: usize> Position<S> {
         }
     }
 
-    pub fn static_eval_features<V: ValueApplier>(&self, white_value: &mut V, black_value: &mut V) {
+    pub fn static_eval_features<'a, V: ValueApplier<'a>>(
+        &self,
+        white_value: &mut V,
+        black_value: &mut V,
+    ) {
         debug_assert!(self.game_result().is_none());
 
         let group_data = self.group_data();
@@ -1178,7 +1200,7 @@ impl<const S: usize> Position<S> {
     #[allow(clippy::too_many_arguments)]
     pub fn generate_moves_with_params<P: PolicyApplier>(
         &self,
-        params: &'static [f32],
+        params: &[f32],
         group_data: &GroupData<S>,
         simple_moves: &mut Vec<<Self as PositionTrait>::Move>,
         moves: &mut Vec<(<Self as PositionTrait>::Move, f16)>,
@@ -1220,7 +1242,7 @@ impl<const S: usize> Position<S> {
         simple_moves: &mut Vec<Move<S>>,
         moves: &mut Vec<(Move<S>, f16)>,
         fcd_per_move: &mut Vec<i8>,
-        policy_params: &'static [f32],
+        policy_params: &[f32],
         policy_feature_sets: &mut Vec<P>,
     ) {
         self.generate_moves_with_params(
@@ -1282,7 +1304,7 @@ impl<const S: usize> Position<S> {
         }
     }
 
-    pub fn static_eval_with_params(&self, params: &'static [f32]) -> f32 {
+    pub fn static_eval_with_params(&self, params: &[f32]) -> f32 {
         let (white_params, black_params) = params.split_at(params.len() / 2);
         let mut white_value: IncrementalValue<S> = IncrementalValue::new(white_params);
         let mut black_value: IncrementalValue<S> = IncrementalValue::new(black_params);
@@ -1,18 +1,19 @@
 use board_game_traits::{GameResult, Position};
 use pgn_traits::PgnPosition;
 use std::error;
+use std::fmt;
 
 pub mod ptn_parser;
 pub mod ptn_writer;
 
-type ParseError = Box<dyn error::Error + Send + Sync>;
-
 #[derive(Debug, Clone, PartialEq)]
 pub struct Game<B: Position> {
     pub start_position: B,
     pub moves: Vec<PtnMove<B::Move>>,
     pub game_result_str: Option<&'static str>,
     pub tags: Vec<(String, String)>,
+    /// The contents of each `{ ... }` comment before the first move, which apply to the whole game
+    pub leading_comments: Vec<String>,
 }
 
 impl<B: PgnPosition> Game<B> {
@@ -30,6 +31,59 @@ impl<B: PgnPosition> Game<B> {
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct PtnMove<Move> {
     pub mv: Move,
-    pub annotations: Vec<&'static str>,
-    pub comment: String,
+    /// Annotations written directly after the move, such as `'` or `!?`
+    pub annotations: Vec<String>,
+    /// The contents of each `{ ... }` comment following the move
+    pub comments: Vec<String>,
+    /// Alternatives to this move, each starting from the position before it
+    pub variations: Vec<Vec<PtnMove<Move>>>,
+}
+
+impl<Move> PtnMove<Move> {
+    /// A move without annotations, comments or variations
+    pub fn new(mv: Move) -> Self {
+        PtnMove {
+            mv,
+            annotations: vec![],
+            comments: vec![],
+            variations: vec![],
+        }
+    }
+}
+
+/// An error in a PTN file, with the location of the token that caused it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PtnError {
+    /// Index of the game in the file, counting from 0, including games that failed to parse
+    pub game_index: usize,
+    /// Line of the token, counting from 1
+    pub line: usize,
+    /// Column of the token in characters, counting from 1
+    pub column: usize,
+    /// The offending token, or an empty string at the end of the input
+    pub token: String,
+    /// What the parser expected to find instead
+    pub expected: String,
+    pub message: String,
 }
+
+impl fmt::Display for PtnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Game {}, line {}, column {}: {}",
+            self.game_index + 1,
+            self.line,
+            self.column,
+            self.message
+        )?;
+        if self.token.is_empty() {
+            write!(f, " at end of input")?;
+        } else {
+            write!(f, " at \"{}\"", self.token)?;
+        }
+        write!(f, ", expected {}", self.expected)
+    }
+}
+
+impl error::Error for PtnError {}
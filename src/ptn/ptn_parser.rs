@@ -1,205 +1,439 @@
-use crate::ptn::{Game, ParseError, PtnMove};
+use crate::ptn::{Game, PtnError, PtnMove};
+use board_game_traits::Color;
 use pgn_traits::PgnPosition;
+use std::fmt::Display;
 use std::str::FromStr;
 
-pub fn parse_ptn<B: PgnPosition>(input: &str) -> Result<Vec<Game<B>>, ParseError> {
-    let mut parser = ParserData { input };
+const EXPECTED_MOVE_TEXT: &str = "a move, move number, comment, variation or game result";
+
+/// Parse all games in the input, failing on the first malformed game
+pub fn parse_ptn<B: PgnPosition>(input: &str) -> Result<Vec<Game<B>>, PtnError> {
+    let mut parser = ParserData::new(input);
     let mut games = vec![];
-    loop {
-        if parser.input.chars().any(|ch| !ch.is_whitespace()) {
-            match parse_game(&mut parser) {
-                Ok(game) => games.push(game),
-                Err(err) => {
-                    eprintln!("Couldn't parse game: {}", err);
-                    if games.is_empty() {
-                        return Err(err);
-                    } else {
-                        return Ok(games);
-                    }
-                }
+    while parser.input.chars().any(|ch| !ch.is_whitespace()) {
+        games.push(parse_game(&mut parser)?);
+        parser.game_index += 1;
+    }
+    Ok(games)
+}
+
+/// Parse all games in the input, skipping malformed games.
+/// Returns the games that were parsed, and an error for each game that was skipped.
+/// After an error, parsing resumes at the next line that starts a new tag section
+pub fn parse_ptn_lenient<B: PgnPosition>(input: &str) -> (Vec<Game<B>>, Vec<PtnError>) {
+    let mut parser = ParserData::new(input);
+    let mut games = vec![];
+    let mut errors = vec![];
+    while parser.input.chars().any(|ch| !ch.is_whitespace()) {
+        match parse_game(&mut parser) {
+            Ok(game) => games.push(game),
+            Err(err) => {
+                errors.push(err);
+                parser.skip_to_next_game();
             }
-        } else {
-            return Ok(games);
         }
+        parser.game_index += 1;
     }
+    (games, errors)
 }
 
-fn parse_game<B: PgnPosition>(input: &mut ParserData) -> Result<Game<B>, ParseError> {
+fn parse_game<B: PgnPosition>(input: &mut ParserData) -> Result<Game<B>, PtnError> {
     let mut tags = vec![];
+    let mut start_position_tag = None;
     input.skip_whitespaces();
     while input.peek() == Some('[') {
         let (tag, value) = parse_tag(input)?;
         input.skip_whitespaces();
+        if B::START_POSITION_TAG_NAME.is_some_and(|fen_tag| tag.eq_ignore_ascii_case(fen_tag)) {
+            start_position_tag = Some(tag);
+        }
         tags.push((tag.to_string(), value));
     }
 
     // Thunk to get the game's start position
     // It can't be a regular variable, because there is no `B: Clone` bound
-    let start_position = || {
-        if let Some(fen_tag) = B::START_POSITION_TAG_NAME {
-            if let Some((_, tps)) = tags
+    let start_position = || match start_position_tag {
+        Some(tag) => {
+            let (_, tps) = tags
                 .iter()
-                .find(|(name, _)| name.eq_ignore_ascii_case(fen_tag))
-            {
-                B::from_fen(tps)
-            } else {
-                Ok(B::start_position())
-            }
-        } else {
-            Ok(B::start_position())
+                .find(|(name, _)| name.eq_ignore_ascii_case(tag))
+                .unwrap();
+            B::from_fen(tps).map_err(|err| {
+                input.error(
+                    tag,
+                    "a valid start position",
+                    format!("Invalid {}: {}", tag, err),
+                )
+            })
         }
+        None => Ok(B::start_position()),
     };
 
-    let (moves, game_result_str) = parse_moves(input, start_position()?)?;
+    let first_position = start_position()?;
+    let start_position = start_position()?;
+    let ParsedLine {
+        leading_comments,
+        moves,
+        game_result_str,
+    } = parse_moves(input, first_position)?;
 
     Ok(Game {
-        start_position: start_position()?,
+        start_position,
         moves,
         game_result_str,
         tags,
+        leading_comments,
     })
 }
 
-fn parse_tag<'a>(input: &mut ParserData<'a>) -> Result<(&'a str, String), pgn_traits::Error> {
+fn parse_tag<'a>(input: &mut ParserData<'a>) -> Result<(&'a str, String), PtnError> {
     assert_eq!(input.take(), Some('['));
     let tag: &'a str = input.take_word();
 
     input.skip_whitespaces();
+    let quote = input.next_char();
     if input.take() != Some('"') {
-        return Err(pgn_traits::Error::new_parse_error(format!(
-            "Tag value for {} didn't start with \"",
-            tag
-        )));
+        return Err(input.error(
+            quote,
+            "\" to start the tag value",
+            format!("Tag value for {} didn't start with \"", tag),
+        ));
     }
 
     let mut value = String::new();
     loop {
         match input.take() {
             Some('"') => break,
-            Some('\\') => match input.take().unwrap() {
-                '"' => value.push('"'),
-                '\\' => value.push('\\'),
-                ch => {
+            Some('\\') => match input.take() {
+                Some('"') => value.push('"'),
+                Some('\\') => value.push('\\'),
+                Some(ch) => {
                     value.push('\\');
                     value.push(ch);
                 }
+                None => value.push('\\'),
             },
             Some(ch) => value.push(ch),
             None => {
-                return Err(pgn_traits::Error::new_parse_error(format!(
-                    "Unexpected EOF parsing tag value for {}",
-                    tag
-                )))
+                return Err(input.error(
+                    input.next_char(),
+                    "\" to end the tag value",
+                    format!("Unexpected EOF parsing tag value for {}", tag),
+                ))
             }
         }
     }
     input.skip_whitespaces();
+    let bracket = input.next_char();
     if input.take() == Some(']') {
         Ok((tag, value))
     } else {
-        Err(pgn_traits::Error::new_parse_error(format!(
-            "Unexpected EOF parsing tag value for {}",
-            tag
-        )))
+        Err(input.error(
+            bracket,
+            "] to end the tag",
+            format!("Tag {} wasn't closed", tag),
+        ))
     }
 }
 
-#[allow(clippy::type_complexity)]
 fn parse_moves<B: PgnPosition>(
     input: &mut ParserData,
     mut position: B,
-) -> Result<(Vec<PtnMove<B::Move>>, Option<&'static str>), ParseError> {
+) -> Result<ParsedLine<B::Move>, PtnError> {
+    let line = parse_line(input, &mut position, false)?;
+    if line.moves.is_empty() && line.game_result_str.is_none() {
+        // Return an error if we've read tags, but no moves
+        return Err(input.error(
+            input.next_char(),
+            "a move or a game result",
+            "Game has no moves",
+        ));
+    }
+    Ok(line)
+}
+
+/// The moves of the main line or a variation
+struct ParsedLine<Move> {
+    /// Comments before the first move
+    leading_comments: Vec<String>,
+    moves: Vec<PtnMove<Move>>,
+    /// The game result, which only the main line has
+    game_result_str: Option<&'static str>,
+}
+
+/// Parse moves until the end of the line, which is the game result for the main line, or `)` for a variation.
+/// Variations restore `position` to where it was before returning
+fn parse_line<B: PgnPosition>(
+    input: &mut ParserData,
+    position: &mut B,
+    is_variation: bool,
+) -> Result<ParsedLine<B::Move>, PtnError> {
     let mut moves: Vec<PtnMove<B::Move>> = vec![];
-    let mut _ply_counter = 0; // Last ply seen
-    loop {
+    let mut reverse_moves = vec![];
+    let mut leading_comments = vec![];
+    let game_result_str = loop {
         input.skip_whitespaces();
-        if input.peek().is_none() || input.peek() == Some('[') {
+        let next_char = input.next_char();
+        match input.peek() {
+            None | Some('[') if is_variation => {
+                return Err(input.error(
+                    next_char,
+                    ") to end the variation",
+                    "Unexpected end of game in variation",
+                ));
+            }
             // Games without a result aren't allowed by the spec,
             // but try to accept it anyway and return a `None` result
-            if !moves.is_empty() {
-                return Ok((moves, None));
+            None | Some('[') => break None,
+            Some(')') if is_variation => {
+                input.take();
+                break None;
+            }
+            Some(')') => {
+                return Err(input.error(
+                    next_char,
+                    EXPECTED_MOVE_TEXT,
+                    "Unexpected ) outside of a variation",
+                ));
+            }
+            Some('}') => {
+                return Err(input.error(
+                    next_char,
+                    EXPECTED_MOVE_TEXT,
+                    "Unexpected } outside of a comment",
+                ));
+            }
+            Some('{') => {
+                let comment = parse_comment(input)?;
+                match moves.last_mut() {
+                    Some(last_move) => last_move.comments.push(comment),
+                    None => leading_comments.push(comment),
+                }
+                continue;
             }
-            // Return an error if we've read tags, but no moves
-            return Err(Box::new(pgn_traits::Error::new_parse_error(
-                "Unexpected EOF, expected a move or a game result.".to_string(),
-            )));
+            Some('(') => {
+                input.take();
+                let (Some(last_move), Some(reverse_move)) = (moves.last_mut(), reverse_moves.pop())
+                else {
+                    return Err(input.error(
+                        next_char,
+                        "a move before the variation",
+                        "Variation must follow the move it is an alternative to",
+                    ));
+                };
+                position.reverse_move(reverse_move);
+                let ParsedLine {
+                    mut leading_comments,
+                    moves: mut variation,
+                    ..
+                } = parse_line(input, position, true)?;
+                // Variations have no place for leading comments, so they are kept with the first move
+                if let Some(first_move) = variation.first_mut() {
+                    leading_comments.append(&mut first_move.comments);
+                    first_move.comments = leading_comments;
+                    last_move.variations.push(variation);
+                }
+                reverse_moves.push(position.do_move(last_move.mv.clone()));
+                continue;
+            }
+            Some(_) => (),
         }
         let word = input.take_word();
-
-        assert!(!word.is_empty());
+        if word.is_empty() {
+            return Err(input.error(next_char, EXPECTED_MOVE_TEXT, "Unexpected character"));
+        }
 
         if let Some(num_string) = word.strip_suffix("...") {
-            let _num = u64::from_str(num_string)?;
-            _ply_counter = _num * 2 - 1;
+            check_move_number(input, position, word, num_string, true)?;
         } else if let Some(num_string) = word.strip_suffix('.') {
-            let _num = u64::from_str(num_string)?;
-            _ply_counter = _num * 2 - 2;
+            check_move_number(input, position, word, num_string, false)?;
         } else if let Some((result_str, _)) = B::POSSIBLE_GAME_RESULTS
             .iter()
             .find(|(s, _result)| *s == word)
         {
-            return Ok((moves, Some(*result_str)));
-        } else {
-            let mut move_string = word;
-            let mut annotations = vec![];
-            while let Some(annotation) = B::POSSIBLE_MOVE_ANNOTATIONS
-                .iter()
-                .find(|annotation| move_string.strip_suffix(*annotation).is_some())
-            {
-                move_string = move_string.strip_suffix(*annotation).unwrap();
-                annotations.insert(0, *annotation);
+            if is_variation {
+                return Err(input.error(
+                    word,
+                    ") to end the variation",
+                    "Unexpected game result in variation",
+                ));
             }
+            break Some(*result_str);
+        } else {
+            let (move_string, annotations) = split_annotations::<B>(word);
 
             match position.move_from_san(move_string) {
                 Ok(mv) => {
                     if !position.move_is_legal(mv.clone()) {
-                        return Err(Box::new(pgn_traits::Error::new(
-                            pgn_traits::ErrorKind::IllegalMove,
-                            word,
-                        )));
-                    }
-                    position.do_move(mv.clone());
-                    input.skip_whitespaces();
-                    if input.peek() == Some('{') {
-                        input.take();
-                        let comment = input.take_while(|ch| ch != '}');
-                        input.take();
-                        moves.push(PtnMove {
-                            mv,
-                            annotations,
-                            comment: comment.to_string(),
-                        })
-                    } else {
-                        moves.push(PtnMove {
-                            mv,
-                            annotations,
-                            comment: String::new(),
-                        });
+                        return Err(input.error(word, "a legal move", "Illegal move"));
                     }
+                    reverse_moves.push(position.do_move(mv.clone()));
+                    moves.push(PtnMove {
+                        annotations,
+                        ..PtnMove::new(mv)
+                    });
                 }
                 Err(err) => {
-                    return Err(Box::new(pgn_traits::Error::new_parse_error(format!(
-                        "Couldn't parse move {}: {}",
-                        word, err
-                    ))));
+                    return Err(input.error(
+                        word,
+                        EXPECTED_MOVE_TEXT,
+                        format!("Couldn't parse move: {}", err),
+                    ));
                 }
             }
         }
+    };
+
+    if is_variation {
+        for reverse_move in reverse_moves.into_iter().rev() {
+            position.reverse_move(reverse_move);
+        }
+    }
+    Ok(ParsedLine {
+        leading_comments,
+        moves,
+        game_result_str,
+    })
+}
+
+/// Check that a move number such as `3.` or `3...` matches the position, if the position knows its move number
+fn check_move_number<'a, B: PgnPosition>(
+    input: &ParserData<'a>,
+    position: &B,
+    word: &'a str,
+    num_string: &str,
+    is_second_player: bool,
+) -> Result<(), PtnError> {
+    let move_number = u32::from_str(num_string).map_err(|err| {
+        input.error(
+            word,
+            EXPECTED_MOVE_TEXT,
+            format!("Invalid move number: {}", err),
+        )
+    })?;
+    let is_second_player_to_move = position.side_to_move() == Color::Black;
+    match position.full_move_number() {
+        Some(full_move_number)
+            if full_move_number != move_number || is_second_player != is_second_player_to_move =>
+        {
+            Err(input.error(
+                word,
+                &format!(
+                    "move number {}{}",
+                    full_move_number,
+                    if is_second_player_to_move { "..." } else { "." }
+                ),
+                "Move number does not match the position",
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Split trailing annotations off a move string.
+/// Runs of `!` and `?` form a single annotation, any other annotation character only groups with itself
+fn split_annotations<B: PgnPosition>(word: &str) -> (&str, Vec<String>) {
+    let is_annotation_char = |ch: char| {
+        B::POSSIBLE_MOVE_ANNOTATIONS
+            .iter()
+            .any(|annotation| annotation.contains(ch))
+    };
+    let move_string = word.trim_end_matches(is_annotation_char);
+    let mut annotations: Vec<String> = vec![];
+    for ch in word[move_string.len()..].chars() {
+        let is_evaluation = |ch: char| ch == '!' || ch == '?';
+        match annotations.last_mut() {
+            Some(last)
+                if last.ends_with(ch) || (is_evaluation(ch) && last.chars().all(is_evaluation)) =>
+            {
+                last.push(ch)
+            }
+            _ => annotations.push(ch.to_string()),
+        }
     }
+    (move_string, annotations)
+}
+
+fn parse_comment(input: &mut ParserData) -> Result<String, PtnError> {
+    let start = input.next_char();
+    assert_eq!(input.take(), Some('{'));
+    let comment = input.take_while(|ch| ch != '}');
+    if input.take() != Some('}') {
+        return Err(input.error(
+            start,
+            "} to end the comment",
+            "Unexpected EOF parsing comment",
+        ));
+    }
+    Ok(comment.to_string())
 }
 
 struct ParserData<'a> {
+    /// The whole input, to find the line and column of errors
+    full_input: &'a str,
     input: &'a str,
+    game_index: usize,
 }
 
 impl<'a> ParserData<'a> {
+    fn new(input: &'a str) -> Self {
+        ParserData {
+            full_input: input,
+            input,
+            game_index: 0,
+        }
+    }
+
+    /// An error at `token`, which must be a slice of the input
+    fn error(&self, token: &'a str, expected: &str, message: impl Display) -> PtnError {
+        let offset = token.as_ptr() as usize - self.full_input.as_ptr() as usize;
+        let before = &self.full_input[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        PtnError {
+            game_index: self.game_index,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            token: token.to_string(),
+            expected: expected.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Skip to the first tag of the next game, which is the next line starting with `[` that doesn't follow another tag.
+    /// If the parser is already at the start of such a line, it stays there
+    fn skip_to_next_game(&mut self) {
+        let consumed = self.full_input.len() - self.input.len();
+        let line_start = self.full_input[..consumed].rfind('\n').map_or(0, |i| i + 1);
+        let mut previous_line_is_tag = self.full_input[..line_start]
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .is_some_and(|line| line.trim_start().starts_with('['));
+
+        let mut offset = line_start;
+        for line in self.full_input[line_start..].split_inclusive('\n') {
+            let trimmed = line.trim_start();
+            let trimmed_offset = offset + line.len() - trimmed.len();
+            if trimmed_offset >= consumed && trimmed.starts_with('[') && !previous_line_is_tag {
+                self.input = &self.full_input[trimmed_offset..];
+                return;
+            }
+            if !trimmed.is_empty() {
+                previous_line_is_tag = trimmed.starts_with('[');
+            }
+            offset += line.len();
+        }
+        self.input = &self.full_input[self.full_input.len()..];
+    }
+
     fn skip_whitespaces(&mut self) {
         self.input = self.input.trim_start_matches(char::is_whitespace);
     }
 
+    /// Take characters up to the next whitespace, or the next delimiter of comments and variations
     fn take_word(&mut self) -> &'a str {
         self.skip_whitespaces();
-        self.take_while(|ch| !ch.is_whitespace())
+        self.take_while(|ch| !ch.is_whitespace() && !"{}()".contains(ch))
     }
 
     fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'a str {
@@ -215,6 +449,12 @@ impl<'a> ParserData<'a> {
         output
     }
 
+    /// The next character as a slice of the input, or an empty slice at the end of the input
+    fn next_char(&self) -> &'a str {
+        let len = self.input.chars().next().map_or(0, char::len_utf8);
+        &self.input[..len]
+    }
+
     fn peek(&mut self) -> Option<char> {
         self.input.chars().next()
     }
@@ -1,7 +1,6 @@
 use crate::ptn::{Game, PtnMove};
 use board_game_traits::Color;
 use pgn_traits::PgnPosition;
-use std::fmt::Write as _;
 use std::io;
 use std::io::Write;
 
@@ -21,7 +20,7 @@ impl<B: PgnPosition + Clone> Game<B> {
             if let Some(position) = position {
                 let (_tag, value) = tags.remove(position);
                 // Write the tag with correct capitalization
-                writeln!(f, "[{} \"{}\"]", required_tag, value)?;
+                writeln!(f, "[{} \"{}\"]", required_tag, escape_tag_value(&value))?;
             } else {
                 // If the result tag is required, but not provided, manually write it
                 if required_tag.eq_ignore_ascii_case("Result") {
@@ -36,7 +35,7 @@ impl<B: PgnPosition + Clone> Game<B> {
 
         // Write any remaining tags
         for (tag, value) in tags.iter() {
-            writeln!(f, "[{} \"{}\"]", tag, value)?;
+            writeln!(f, "[{} \"{}\"]", tag, escape_tag_value(value))?;
         }
 
         // Write TPS tag, if starting position is non-standard
@@ -55,78 +54,92 @@ impl<B: PgnPosition + Clone> Game<B> {
 
         writeln!(f)?;
 
-        let mut position = self.start_position.clone();
+        let mut units: Vec<String> = self
+            .leading_comments
+            .iter()
+            .map(|comment| format!("{{{}}}", escape_comment(comment)))
+            .collect();
+        let start_move_number = self.start_position.full_move_number().unwrap_or(1);
+        write_moves(
+            self.start_position.clone(),
+            &self.moves,
+            start_move_number,
+            &mut units,
+        );
+        units.push(self.game_result_str.unwrap_or("*").to_string());
+
+        // Wrap lines between units, but never inside them
         let mut column_position = 0;
-        let mut buffer = String::new();
-
-        let start_move_number = position.full_move_number().unwrap_or(1) as usize;
-
-        for (
-            i,
-            PtnMove {
-                mv,
-                comment,
-                annotations,
-            },
-        ) in self.moves.iter().enumerate()
-        {
-            let move_string = position.move_to_san(mv);
-            if i == 0 && position.side_to_move() == Color::Black {
-                write!(buffer, "{}... {}", start_move_number, move_string).unwrap();
-            } else if position.side_to_move() == Color::White {
-                write!(
-                    buffer,
-                    "{}. {}",
-                    (i + 1) / 2 + start_move_number,
-                    move_string
-                )
-                .unwrap();
+        for unit in units {
+            if column_position == 0 {
+                write!(f, "{}", unit)?;
+                column_position = unit.len();
+            } else if column_position + unit.len() < LINE_WIDTH {
+                write!(f, " {}", unit)?;
+                column_position += unit.len() + 1;
             } else {
-                buffer.push_str(&move_string);
+                write!(f, "\n{}", unit)?;
+                column_position = unit.len();
             }
+        }
 
-            for annotation in annotations {
-                buffer.push_str(annotation);
-            }
+        writeln!(f)?;
+        writeln!(f)?;
+        Ok(())
+    }
+}
 
-            if !comment.is_empty() {
-                buffer.push_str(" {");
-                buffer.push_str(comment);
-                buffer.push('}');
+/// Split a line of moves into units of text that may not be broken across lines,
+/// such as a move with its move number, a comment, or the start and end of a variation
+fn write_moves<B: PgnPosition + Clone>(
+    mut position: B,
+    moves: &[PtnMove<B::Move>],
+    mut move_number: u32,
+    units: &mut Vec<String>,
+) {
+    // The second player's move only needs a move number at the start of a line, or after a variation
+    let mut needs_move_number = true;
+    for PtnMove {
+        mv,
+        annotations,
+        comments,
+        variations,
+    } in moves
+    {
+        let move_string = position.move_to_san(mv) + &annotations.concat();
+        match position.side_to_move() {
+            Color::White => units.push(format!("{}. {}", move_number, move_string)),
+            Color::Black if needs_move_number => {
+                units.push(format!("{}... {}", move_number, move_string))
             }
+            Color::Black => units.push(move_string),
+        }
+        needs_move_number = false;
 
-            if i == self.moves.len() - 1 {
-                buffer.push(' ');
-                buffer.push_str(self.game_result_str.unwrap_or("*"));
-            }
+        for comment in comments {
+            units.push(format!("{{{}}}", escape_comment(comment)));
+        }
 
-            if position.side_to_move() == Color::Black || i == self.moves.len() - 1 {
-                if column_position == 0 {
-                    write!(f, "{}", buffer)?;
-                    column_position = buffer.len();
-                } else if column_position + buffer.len() < LINE_WIDTH {
-                    write!(f, " {}", buffer)?;
-                    column_position += buffer.len() + 1;
-                } else {
-                    write!(f, "\n{}", buffer)?;
-                    column_position = buffer.len();
-                }
-                buffer.clear();
-            } else {
-                buffer.push(' ');
-            }
+        for variation in variations.iter().filter(|variation| !variation.is_empty()) {
+            let first_unit = units.len();
+            write_moves(position.clone(), variation, move_number, units);
+            units[first_unit].insert(0, '(');
+            units.last_mut().unwrap().push(')');
+            needs_move_number = true;
+        }
 
-            position.do_move(mv.clone());
+        if position.side_to_move() == Color::Black {
+            move_number += 1;
         }
+        position.do_move(mv.clone());
+    }
+}
 
-        assert!(
-            buffer.is_empty(),
-            "\"{}\" was not written to the ptn",
-            buffer
-        );
+/// Comments cannot contain `}`, so it is removed
+fn escape_comment(comment: &str) -> String {
+    comment.replace('}', "")
+}
 
-        writeln!(f)?;
-        writeln!(f)?;
-        Ok(())
-    }
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
 }
@@ -0,0 +1,308 @@
+//! Extract tactics puzzles from game collections.
+//!
+//! A puzzle is a position where the side to move can force a road win within a few plies, and only one move does it.
+//! A move that wins more slowly than the solution, but still within the ply limit, means the puzzle is not unique.
+//! Placing a flatstone or a capstone on the same square counts as the same move.
+//! Candidate positions are found quickly with the [`Solver`], which only considers threatening moves.
+//! Every candidate is then verified with an exhaustive search of all moves for both sides,
+//! and discarded if the search cannot finish within its node limit.
+
+use std::fmt::{self, Display};
+
+use board_game_traits::{Color, Position as PositionTrait};
+use pgn_traits::PgnPosition;
+
+use crate::position::{ExpMove, Move, Position, Role};
+use crate::ptn::{Game, PtnMove};
+use crate::search::solver::{Proof, Solver};
+
+/// A position with a unique forced road win
+#[derive(Clone, Debug, PartialEq)]
+pub struct Puzzle<const S: usize> {
+    pub position: Position<S>,
+    /// Number of plies until the road is completed, including the opponent's replies
+    pub plies: u8,
+    /// The winning line, where the opponent always plays the move that delays the road the longest.
+    /// Only the first move is unique, later moves for the winning side may have alternatives
+    pub solution: Vec<Move<S>>,
+    /// Where the puzzle was found, such as the players and the ply number
+    pub source: String,
+}
+
+impl<const S: usize> Display for Puzzle<S> {
+    /// Write the puzzle on a single line, as `<tps>;<plies>;<solution>;<source>`,
+    /// with the solution moves separated by spaces. Any `;` in the source is replaced by `,`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut position = self.position.clone();
+        let solution: Vec<String> = self
+            .solution
+            .iter()
+            .map(|mv| {
+                let move_string = position.move_to_san(mv);
+                position.do_move(*mv);
+                move_string
+            })
+            .collect();
+        write!(
+            f,
+            "{};{};{};{}",
+            self.position.to_fen(),
+            self.plies,
+            solution.join(" "),
+            self.source.replace(';', ",")
+        )
+    }
+}
+
+pub struct PuzzleFinder<const S: usize> {
+    solver: Solver<S>,
+    min_plies: u8,
+    max_plies: u8,
+    max_nodes: u64,
+    nodes: u64,
+}
+
+impl<const S: usize> PuzzleFinder<S> {
+    /// Find puzzles where the road is completed within `max_plies` plies, which must be odd
+    pub fn new(max_plies: u8) -> Self {
+        assert!(
+            max_plies % 2 == 1,
+            "Forced wins always take an odd number of plies"
+        );
+        PuzzleFinder {
+            solver: Solver::new(1_000_000),
+            min_plies: 1,
+            max_plies,
+            max_nodes: 1_000_000,
+            nodes: 0,
+        }
+    }
+
+    /// Skip puzzles that are solved in fewer than `min_plies` plies
+    pub fn min_plies(mut self, min_plies: u8) -> Self {
+        self.min_plies = min_plies;
+        self
+    }
+
+    /// Give up on a position after searching `max_nodes` nodes, both when looking for candidates and when verifying them
+    pub fn max_nodes(mut self, max_nodes: u64) -> Self {
+        self.solver.set_max_nodes(max_nodes);
+        self.max_nodes = max_nodes;
+        self
+    }
+
+    /// Find the puzzle in every position of the game, including the start position
+    pub fn puzzles_from_game(&mut self, game: &Game<Position<S>>, source: &str) -> Vec<Puzzle<S>> {
+        let mut position = game.start_position.clone();
+        let mut puzzles = vec![];
+        for (ply, PtnMove { mv, .. }) in game.moves.iter().enumerate() {
+            if position.game_result().is_some() {
+                break;
+            }
+            if let Some((plies, solution)) = self.find_puzzle(&mut position) {
+                puzzles.push(Puzzle {
+                    position: position.clone(),
+                    plies,
+                    solution,
+                    source: format!("{}, ply {}", source, ply),
+                });
+            }
+            position.do_move(*mv);
+        }
+        puzzles
+    }
+
+    /// If the side to move has a unique forced road win, return its length in plies and the winning line.
+    /// The win is only unique if no other move wins within `max_plies`, even if it takes longer
+    pub fn find_puzzle(&mut self, position: &mut Position<S>) -> Option<(u8, Vec<Move<S>>)> {
+        let Proof::Win { plies, .. } = self.solver.solve(position, self.max_plies) else {
+            return None;
+        };
+        if plies < self.min_plies {
+            return None;
+        }
+
+        // The solver only tries threatening moves, so check every move for a win that is as short or shorter
+        self.nodes = 0;
+        let mut winning_moves = vec![];
+        let mut winning_plies = 1;
+        for shorter_plies in (1..=plies).step_by(2) {
+            winning_moves = self.winning_moves(position, shorter_plies);
+            winning_plies = shorter_plies;
+            if !winning_moves.is_empty() || self.is_out_of_nodes() {
+                break;
+            }
+        }
+        remove_redundant_capstones(&mut winning_moves);
+
+        if self.is_out_of_nodes() || winning_moves.len() != 1 || winning_plies < self.min_plies {
+            return None;
+        }
+
+        // Another move may still win, just more slowly
+        if winning_plies < self.max_plies {
+            let mut slower_winning_moves = self.winning_moves(position, self.max_plies);
+            remove_redundant_capstones(&mut slower_winning_moves);
+            if self.is_out_of_nodes() || slower_winning_moves.len() != 1 {
+                return None;
+            }
+        }
+
+        let mut solution = vec![winning_moves[0]];
+        let reverse_move = position.do_move(winning_moves[0]);
+        self.extend_solution(position, winning_plies - 1, &mut solution);
+        position.reverse_move(reverse_move);
+        if self.is_out_of_nodes() {
+            return None;
+        }
+        Some((winning_plies, solution))
+    }
+
+    fn is_out_of_nodes(&self) -> bool {
+        self.nodes >= self.max_nodes
+    }
+
+    /// Extend the solution from a position where the losing side is to move, and loses within `plies` plies
+    fn extend_solution(
+        &mut self,
+        position: &mut Position<S>,
+        plies: u8,
+        solution: &mut Vec<Move<S>>,
+    ) {
+        if plies == 0 {
+            return;
+        }
+        // Find the defence that delays the road the longest, and the shortest win against it
+        let mut moves = vec![];
+        position.generate_moves(&mut moves);
+        let mut best_defence = None;
+        for defence in moves {
+            let reverse_move = position.do_move(defence);
+            if position.game_result().is_none() {
+                if let Some((winning_move, winning_plies)) = self.shortest_win(position, plies - 1)
+                {
+                    if best_defence
+                        .is_none_or(|(_, _, longest_plies)| winning_plies > longest_plies)
+                    {
+                        best_defence = Some((defence, winning_move, winning_plies));
+                    }
+                }
+            }
+            position.reverse_move(reverse_move);
+        }
+
+        let Some((defence, winning_move, winning_plies)) = best_defence else {
+            // Every defence immediately loses the game
+            return;
+        };
+        solution.push(defence);
+        solution.push(winning_move);
+        let defence_reverse_move = position.do_move(defence);
+        let winning_reverse_move = position.do_move(winning_move);
+        self.extend_solution(position, winning_plies - 1, solution);
+        position.reverse_move(winning_reverse_move);
+        position.reverse_move(defence_reverse_move);
+    }
+
+    /// The shortest road win for the side to move within `plies` plies
+    fn shortest_win(&mut self, position: &mut Position<S>, plies: u8) -> Option<(Move<S>, u8)> {
+        let mut moves = vec![];
+        position.generate_moves(&mut moves);
+        // A placement can only complete a road on a critical square, so the others can be skipped when winning in one
+        let us = position.side_to_move();
+        let group_data = position.group_data();
+        for shorter_plies in (1..=plies).step_by(2) {
+            for &mv in moves.iter() {
+                if self.is_out_of_nodes() {
+                    return None;
+                }
+                if shorter_plies == 1 {
+                    if let ExpMove::Place(role, square) = mv.expand() {
+                        if role == Role::Wall || !group_data.is_critical_square(square, us) {
+                            continue;
+                        }
+                    }
+                }
+                if self.move_wins(position, mv, shorter_plies) {
+                    return Some((mv, shorter_plies));
+                }
+            }
+        }
+        None
+    }
+
+    /// Every move that wins by road for the side to move within `plies` plies
+    fn winning_moves(&mut self, position: &mut Position<S>, plies: u8) -> Vec<Move<S>> {
+        let mut moves = vec![];
+        position.generate_moves(&mut moves);
+        moves.retain(|mv| self.move_wins(position, *mv, plies));
+        moves
+    }
+
+    /// Whether `mv` forces a road win for the side to move within `plies` plies
+    fn move_wins(&mut self, position: &mut Position<S>, mv: Move<S>, plies: u8) -> bool {
+        let us = position.side_to_move();
+        self.nodes += 1;
+        let reverse_move = position.do_move(mv);
+        let wins = if position.game_result().is_some() {
+            is_road_win_for(position, us)
+        } else {
+            plies >= 3 && self.all_moves_lose(position, plies - 1)
+        };
+        position.reverse_move(reverse_move);
+        wins
+    }
+
+    /// Whether every move for the side to move allows the opponent to win by road within `plies` plies
+    fn all_moves_lose(&mut self, position: &mut Position<S>, plies: u8) -> bool {
+        let them = !position.side_to_move();
+        let mut moves = vec![];
+        position.generate_moves(&mut moves);
+        // Try blocking the opponent's roads first, since that is the likeliest way to avoid losing
+        let group_data = position.group_data();
+        moves.sort_by_key(|mv| match mv.expand() {
+            ExpMove::Place(_, square) if group_data.is_critical_square(square, them) => 0,
+            ExpMove::Move(..) => 1,
+            ExpMove::Place(..) => 2,
+        });
+        for mv in moves {
+            if self.is_out_of_nodes() {
+                return false;
+            }
+            self.nodes += 1;
+            let reverse_move = position.do_move(mv);
+            let loses = if position.game_result().is_some() {
+                is_road_win_for(position, them)
+            } else {
+                self.shortest_win(position, plies - 1).is_some()
+            };
+            position.reverse_move(reverse_move);
+            if !loses {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Placing a flatstone or a capstone on the same square is the same solution
+fn remove_redundant_capstones<const S: usize>(winning_moves: &mut Vec<Move<S>>) {
+    let redundant_capstones: Vec<Move<S>> = winning_moves
+        .iter()
+        .copied()
+        .filter(|mv| match mv.expand() {
+            ExpMove::Place(Role::Cap, square) => {
+                winning_moves.contains(&Move::placement(Role::Flat, square))
+            }
+            _ => false,
+        })
+        .collect();
+    winning_moves.retain(|mv| !redundant_capstones.contains(mv));
+}
+
+fn is_road_win_for<const S: usize>(position: &Position<S>, color: Color) -> bool {
+    match color {
+        Color::White => position.pgn_game_result() == Some("R-0"),
+        Color::Black => position.pgn_game_result() == Some("0-R"),
+    }
+}
@@ -27,6 +27,11 @@ pub struct ArenaStats {
     pub padding_bytes: AtomicUsize,
 }
 
+// The arena only hands out references through indexes, and new elements are allocated atomically,
+// so it can be shared between threads. Avoiding data races on the elements themselves is up to the caller
+unsafe impl<const S: usize> Send for Arena<S> {}
+unsafe impl<const S: usize> Sync for Arena<S> {}
+
 impl<const S: usize> fmt::Debug for Arena<S> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Arena")
@@ -81,7 +86,7 @@ impl<T> Default for SliceIndex<T> {
 
 const fn raw_alignment(mut alignment: usize) -> usize {
     let mut raw_alignment = 1;
-    while alignment % 2 == 0 {
+    while alignment.is_multiple_of(2) {
         raw_alignment *= 2;
         alignment /= 2;
     }
@@ -141,7 +146,7 @@ impl<const S: usize> Arena<S> {
             }
 
             // Make sure the pointer is correctly aligned
-            if (ptr as usize) % S == 0 {
+            if (ptr as usize).is_multiple_of(S) {
                 (ptr, ptr)
             } else {
                 (ptr.add(S - (ptr as usize) % S), ptr)
@@ -298,7 +303,14 @@ impl<const S: usize> Arena<S> {
     }
 
     pub const fn supports_type<T>(&self) -> bool {
-        S % mem::align_of::<T>() == 0
+        S.is_multiple_of(mem::align_of::<T>())
+    }
+
+    /// Remove all elements from the arena, so that its memory can be reused.
+    /// Any indexes into the arena from before it was cleared are invalid afterwards
+    pub fn clear(&mut self) {
+        *self.next_index.get_mut() = 1;
+        self.stats = ArenaStats::default();
     }
 
     pub fn slots_used(&self) -> u32 {
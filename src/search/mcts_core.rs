@@ -1,4 +1,5 @@
 use std::ops;
+use std::sync::Arc;
 
 use board_game_traits::{Color, GameResult, Position as PositionTrait};
 use half::f16;
@@ -7,10 +8,12 @@ use rand::Rng;
 use rand_distr::Distribution;
 
 use crate::evaluation::parameters::IncrementalPolicy;
+use crate::position::GroupData;
 use crate::position::Move;
 /// This module contains the core of the MCTS search algorithm
 use crate::position::Position;
-use crate::search::{cp_to_win_percentage, MctsSetting};
+use crate::search::solver::{Proof, Solver};
+use crate::search::{cp_to_win_percentage, MctsSetting, TranspositionTable};
 
 use super::{arena, Arena, Error};
 
@@ -18,8 +21,17 @@ use super::{arena, Arena, Error};
 #[derive(PartialEq, Debug)]
 pub struct Tree<const S: usize> {
     pub total_action_value: f64,
+    /// Set if the game is over in this node, or if its result has been proven by the search
     pub game_result: Option<GameResultForUs>,
+    /// If `game_result` is set, the number of plies until the game ends with best play
+    pub proven_plies: u8,
     pub children: Option<arena::Index<TreeBridge<S>>>,
+    /// Set while a search thread is evaluating, expanding or solving this node.
+    /// Always false in single-threaded search
+    pub pending: bool,
+    /// Number of lines currently being searched through this node, which each add a virtual loss to its statistics.
+    /// Always 0 in single-threaded search
+    pub virtual_losses: u8,
 }
 
 #[derive(PartialEq, Debug)]
@@ -36,7 +48,8 @@ pub struct TreeEdge<const S: usize> {
     pub child: Option<arena::Index<Tree<S>>>,
 }
 
-/// Temporary vectors that are continually re-used during search to avoid unnecessary allocations
+/// Temporary vectors that are continually re-used during search to avoid unnecessary allocations.
+/// Also holds each search thread's handle to the shared transposition table
 #[derive(Debug)]
 pub struct TempVectors<const S: usize> {
     simple_moves: Vec<Move<S>>,
@@ -44,6 +57,8 @@ pub struct TempVectors<const S: usize> {
     fcd_per_move: Vec<i8>,
     policy_feature_sets: Vec<IncrementalPolicy<S>>,
     unpacked_heuristic_scores: Vec<f32>,
+    solver: Solver<S>,
+    pub transpositions: Option<Arc<TranspositionTable>>,
 }
 
 impl<const S: usize> Default for TempVectors<S> {
@@ -54,6 +69,8 @@ impl<const S: usize> Default for TempVectors<S> {
             fcd_per_move: vec![],
             policy_feature_sets: vec![],
             unpacked_heuristic_scores: vec![0.; 65536],
+            solver: Solver::new(0),
+            transpositions: None,
         }
     }
 }
@@ -141,6 +158,7 @@ impl<const S: usize> TreeBridge<S> {
         best_child_node_index as usize
     }
 
+    /// Select a child and search it, returning the result and the index of the child
     pub fn select(
         &mut self,
         position: &mut Position<S>,
@@ -148,7 +166,7 @@ impl<const S: usize> TreeBridge<S> {
         temp_vectors: &mut TempVectors<S>,
         arena: &Arena,
         our_visits: u32,
-    ) -> Result<f32, Error> {
+    ) -> Result<(f32, usize), Error> {
         assert_ne!(
             arena.get_slice(&self.children).len(),
             0,
@@ -178,6 +196,13 @@ impl<const S: usize> TreeBridge<S> {
             });
 
         position.do_move(child_move);
+        let child_hash = position.zobrist_hash();
+
+        if let Some(child) = child_edge.child.as_mut() {
+            if child_visits == settings.solver_visits {
+                solve_node(position, settings, temp_vectors, arena.get_mut(child));
+            }
+        }
 
         let result =
             1.0 - child_edge.select(position, settings, temp_vectors, arena, child_visits)?;
@@ -187,17 +212,66 @@ impl<const S: usize> TreeBridge<S> {
             .get_mut(best_child_node_index)
             .unwrap() += 1;
 
-        *arena
-            .get_slice_mut(&mut self.mean_action_values)
-            .get_mut(best_child_node_index)
-            .unwrap() = arena
-            .get(child_edge.child.as_ref().unwrap())
-            .total_action_value as f32
+        let child = arena.get(child_edge.child.as_ref().unwrap());
+        let mean_action_value = child.total_action_value as f32
             / *arena
                 .get_slice_mut(&mut self.visitss)
                 .get_mut(best_child_node_index)
                 .unwrap() as f32;
-        Ok(result)
+        *arena
+            .get_slice_mut(&mut self.mean_action_values)
+            .get_mut(best_child_node_index)
+            .unwrap() = mean_action_value;
+
+        if let Some(transpositions) = temp_vectors.transpositions.as_ref() {
+            // Finished games may be draws by repetition, which depends on the move order
+            if child.game_result.is_none() {
+                transpositions.insert(child_hash, mean_action_value, child_visits + 1);
+            }
+        }
+        Ok((result, best_child_node_index))
+    }
+
+    /// Check whether the results of the children prove the result of the node that owns this bridge,
+    /// after the child at `child_index` has been searched.
+    /// The node is a proven win if any child is a proven loss for the opponent,
+    /// and a proven loss if every child is a proven win for the opponent.
+    pub fn proven_result(
+        &self,
+        arena: &Arena,
+        child_index: usize,
+    ) -> Option<(GameResultForUs, u8)> {
+        let children = arena.get_slice(&self.children);
+        let child = arena.get(children[child_index].child.as_ref()?);
+        match child.game_result? {
+            GameResultForUs::Loss => {
+                Some((GameResultForUs::Win, child.proven_plies.saturating_add(1)))
+            }
+            GameResultForUs::Win | GameResultForUs::Draw => {
+                let mut result = GameResultForUs::Loss;
+                let mut proven_plies = 0;
+                for (edge, mv) in children.iter().zip(arena.get_slice(&self.moves)) {
+                    // Skip padding and excluded moves
+                    if mv.is_none() {
+                        continue;
+                    }
+                    let child = arena.get(edge.child.as_ref()?);
+                    match child.game_result? {
+                        GameResultForUs::Loss => {
+                            return Some((
+                                GameResultForUs::Win,
+                                child.proven_plies.saturating_add(1),
+                            ))
+                        }
+                        GameResultForUs::Draw => result = GameResultForUs::Draw,
+                        GameResultForUs::Win => (),
+                    }
+                    // The losing side delays the end of the game as long as possible
+                    proven_plies = proven_plies.max(child.proven_plies.saturating_add(1));
+                }
+                Some((result, proven_plies))
+            }
+        }
     }
 
     /// Apply Dirichlet noise to the heuristic scores of the child node
@@ -218,6 +292,33 @@ impl<const S: usize> TreeBridge<S> {
             *child_prior = f16::from_f32(child_prior.to_f32() * (1.0 - epsilon) + epsilon * eta);
         }
     }
+
+    /// Recursively copy this bridge and all its children into `new_arena`
+    pub fn copy_to(&self, arena: &Arena, new_arena: &Arena) -> Result<TreeBridge<S>, Error> {
+        let children = arena
+            .get_slice(&self.children)
+            .iter()
+            .map(|edge| edge.copy_to(arena, new_arena))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(TreeBridge {
+            children: new_arena
+                .add_slice(children.into_iter())
+                .ok_or(Error::OOM)?,
+            moves: new_arena
+                .add_slice(arena.get_slice(&self.moves).iter().copied())
+                .ok_or(Error::OOM)?,
+            mean_action_values: new_arena
+                .add_slice(arena.get_slice(&self.mean_action_values).iter().copied())
+                .ok_or(Error::OOM)?,
+            visitss: new_arena
+                .add_slice(arena.get_slice(&self.visitss).iter().copied())
+                .ok_or(Error::OOM)?,
+            heuristic_scores: new_arena
+                .add_slice(arena.get_slice(&self.heuristic_scores).iter().copied())
+                .ok_or(Error::OOM)?,
+        })
+    }
 }
 
 impl<const S: usize> TreeEdge<S> {
@@ -239,20 +340,56 @@ impl<const S: usize> TreeEdge<S> {
             );
         }
 
-        let (result, game_result) =
-            rollout(position, settings, settings.rollout_depth, temp_vectors);
+        let (result, game_result) = evaluate_new_node(position, settings, temp_vectors);
         self.child = Some(
             arena
                 .add(Tree {
                     total_action_value: result as f64,
                     game_result,
+                    proven_plies: 0,
                     children: None,
+                    pending: false,
+                    virtual_losses: 0,
                 })
                 .ok_or(Error::OOM)?,
         );
 
         Ok(result)
     }
+
+    /// If the child node is proven, the number of plies until the game ends when playing this edge's move.
+    /// Positive if the move wins for the side playing it, negative if it loses
+    pub fn proven_plies(&self, arena: &Arena) -> Option<i32> {
+        let child = arena.get(self.child.as_ref()?);
+        match child.game_result? {
+            GameResultForUs::Loss => Some(child.proven_plies as i32 + 1),
+            GameResultForUs::Win => Some(-(child.proven_plies as i32 + 1)),
+            GameResultForUs::Draw => None,
+        }
+    }
+
+    /// Ranking of this edge's move when choosing the best move after the search.
+    /// Proven wins are always preferred, the fastest first, and proven losses are avoided, the slowest first.
+    /// The remaining moves are ranked by visits
+    pub fn best_move_key(&self, arena: &Arena, visits: u32) -> (u8, u32) {
+        match self.proven_plies(arena) {
+            Some(plies) if plies > 0 => (2, u32::MAX - plies as u32),
+            Some(plies) => (0, plies.unsigned_abs()),
+            None => (1, visits),
+        }
+    }
+
+    /// Recursively copy the subtree under this edge into `new_arena`
+    pub fn copy_to(&self, arena: &Arena, new_arena: &Arena) -> Result<TreeEdge<S>, Error> {
+        let child = match self.child.as_ref() {
+            Some(child) => {
+                let tree = arena.get(child).copy_to(arena, new_arena)?;
+                Some(new_arena.add(tree).ok_or(Error::OOM)?)
+            }
+            None => None,
+        };
+        Ok(TreeEdge { child })
+    }
 }
 
 const SIMD_WIDTH: usize = 4;
@@ -281,13 +418,13 @@ impl<const S: usize> Tree<S> {
             return Ok(result);
         };
 
-        let result = arena.get_mut(children).select(
-            position,
-            settings,
-            temp_vectors,
-            arena,
-            parent_visits,
-        )?;
+        let bridge = arena.get_mut(children);
+        let (result, child_index) =
+            bridge.select(position, settings, temp_vectors, arena, parent_visits)?;
+        if let Some((game_result, proven_plies)) = bridge.proven_result(arena, child_index) {
+            self.game_result = Some(game_result);
+            self.proven_plies = proven_plies;
+        }
         self.total_action_value += result as f64;
         Ok(result)
     }
@@ -303,62 +440,159 @@ impl<const S: usize> Tree<S> {
         arena: &Arena,
     ) -> Result<f32, Error> {
         assert!(self.children.is_none());
-        let group_data = position.group_data();
-        assert!(temp_vectors.simple_moves.is_empty());
-        assert!(temp_vectors.moves.is_empty());
-        assert!(temp_vectors.fcd_per_move.is_empty());
-        position.generate_moves_with_params(
-            match settings.policy_params.as_ref() {
-                Some(params) => params,
-                None => <Position<S>>::policy_params(position.komi()),
-            },
-            &group_data,
-            &mut temp_vectors.simple_moves,
-            &mut temp_vectors.moves,
-            &mut temp_vectors.fcd_per_move,
-            &mut temp_vectors.policy_feature_sets,
-        );
-
-        let num_children = temp_vectors.moves.len();
-        let padding = (SIMD_WIDTH - (num_children % SIMD_WIDTH)) % SIMD_WIDTH;
-
-        let mut tree_edge = TreeBridge {
-            children: arena
-                .add_slice((0..(num_children + padding)).map(|_| TreeEdge { child: None }))
-                .ok_or(Error::OOM)?,
-            moves: arena
-                .add_slice(
-                    (0..(num_children + padding))
-                        .map(|i| temp_vectors.moves.get(i).map(|(mv, _)| *mv)),
-                )
-                .ok_or(Error::OOM)?,
-            mean_action_values: arena
-                .add_slice(
-                    (0..(num_children + padding)).map(|_| settings.initial_mean_action_value()),
-                )
-                .ok_or(Error::OOM)?,
-            visitss: arena
-                .add_slice((0..(num_children + padding)).map(|_| 0))
-                .ok_or(Error::OOM)?,
-            heuristic_scores: arena
-                .add_slice((0..(num_children + padding)).map(|i| {
-                    temp_vectors
-                        .moves
-                        .get(i)
-                        .map(|(_, score)| *score)
-                        .unwrap_or(f16::NEG_INFINITY) // Ensure that this move never actually gets selected
-                }))
-                .ok_or(Error::OOM)?,
-        };
-        temp_vectors.moves.clear();
+        let mut tree_edge = new_bridge(position, settings, temp_vectors, arena)?;
 
         // Select child edge before writing the child node into the tree, in case we OOM inside this call
-        let result = tree_edge.select(position, settings, temp_vectors, arena, 1)?;
+        let (result, child_index) = tree_edge.select(position, settings, temp_vectors, arena, 1)?;
+        if let Some((game_result, proven_plies)) = tree_edge.proven_result(arena, child_index) {
+            self.game_result = Some(game_result);
+            self.proven_plies = proven_plies;
+        }
 
         self.children = Some(arena.add(tree_edge).ok_or(Error::OOM)?);
 
         Ok(result)
     }
+
+    /// Recursively copy this node and all its children into `new_arena`
+    pub fn copy_to(&self, arena: &Arena, new_arena: &Arena) -> Result<Tree<S>, Error> {
+        let children = match self.children.as_ref() {
+            Some(children) => {
+                let bridge = arena.get(children).copy_to(arena, new_arena)?;
+                Some(new_arena.add(bridge).ok_or(Error::OOM)?)
+            }
+            None => None,
+        };
+        Ok(Tree {
+            total_action_value: self.total_action_value,
+            game_result: self.game_result,
+            proven_plies: self.proven_plies,
+            children,
+            pending: false,
+            virtual_losses: 0,
+        })
+    }
+}
+
+/// Run the tactics solver on a node, and mark it as won or lost if the solver finds a proof.
+/// The solver's node limit scales with the number of visits, to keep its cost proportional to the search
+pub fn solve_node<const S: usize>(
+    position: &mut Position<S>,
+    settings: &MctsSetting<S>,
+    temp_vectors: &mut TempVectors<S>,
+    node: &mut Tree<S>,
+) {
+    if node.game_result.is_some() {
+        return;
+    }
+    if let Some((game_result, plies)) = solve_position(position, settings, temp_vectors) {
+        node.game_result = Some(game_result);
+        node.proven_plies = plies;
+    }
+}
+
+/// Run the tactics solver on a position, and return the result and its length in plies, if the solver finds a proof
+pub fn solve_position<const S: usize>(
+    position: &mut Position<S>,
+    settings: &MctsSetting<S>,
+    temp_vectors: &mut TempVectors<S>,
+) -> Option<(GameResultForUs, u8)> {
+    if settings.solver_plies == 0 {
+        return None;
+    }
+    temp_vectors
+        .solver
+        .set_max_nodes(4 * settings.solver_visits as u64);
+    match temp_vectors.solver.solve(position, settings.solver_plies) {
+        Proof::Win { plies, .. } => Some((GameResultForUs::Win, plies)),
+        Proof::Loss { plies } => Some((GameResultForUs::Loss, plies)),
+        Proof::Unknown => None,
+    }
+}
+
+/// Evaluate a node that was just added to the tree.
+/// If the position has already been searched through a different move order, the mean action value from that search is used,
+/// without doing a static evaluation or rollout
+pub fn evaluate_new_node<const S: usize>(
+    position: &mut Position<S>,
+    settings: &MctsSetting<S>,
+    temp_vectors: &mut TempVectors<S>,
+) -> (f32, Option<GameResultForUs>) {
+    let group_data = position.group_data();
+    // Finished games are checked first, because they are never stored in the transposition table
+    if let Some(game_result) = game_result_for_us(position, &group_data) {
+        return (game_result.score(), Some(game_result));
+    }
+    if let Some((mean_action_value, _)) = temp_vectors
+        .transpositions
+        .as_ref()
+        .and_then(|transpositions| transpositions.get(position.zobrist_hash()))
+    {
+        return (mean_action_value, None);
+    }
+    rollout_with_group_data(
+        position,
+        &group_data,
+        settings,
+        settings.rollout_depth,
+        temp_vectors,
+    )
+}
+
+/// Generate the moves of a position, and allocate a bridge for them, without writing it into the tree
+pub fn new_bridge<const S: usize>(
+    position: &mut Position<S>,
+    settings: &MctsSetting<S>,
+    temp_vectors: &mut TempVectors<S>,
+    arena: &Arena,
+) -> Result<TreeBridge<S>, Error> {
+    let group_data = position.group_data();
+    assert!(temp_vectors.simple_moves.is_empty());
+    assert!(temp_vectors.moves.is_empty());
+    assert!(temp_vectors.fcd_per_move.is_empty());
+    position.generate_moves_with_params(
+        match settings.policy_params.as_deref() {
+            Some(params) => params,
+            None => <Position<S>>::policy_params(position.komi()),
+        },
+        &group_data,
+        &mut temp_vectors.simple_moves,
+        &mut temp_vectors.moves,
+        &mut temp_vectors.fcd_per_move,
+        &mut temp_vectors.policy_feature_sets,
+    );
+
+    let num_children = temp_vectors.moves.len();
+    let padding = (SIMD_WIDTH - (num_children % SIMD_WIDTH)) % SIMD_WIDTH;
+
+    let tree_edge = TreeBridge {
+        children: arena
+            .add_slice((0..(num_children + padding)).map(|_| TreeEdge { child: None }))
+            .ok_or(Error::OOM)?,
+        moves: arena
+            .add_slice(
+                (0..(num_children + padding)).map(|i| temp_vectors.moves.get(i).map(|(mv, _)| *mv)),
+            )
+            .ok_or(Error::OOM)?,
+        mean_action_values: arena
+            .add_slice((0..(num_children + padding)).map(|_| settings.initial_mean_action_value()))
+            .ok_or(Error::OOM)?,
+        visitss: arena
+            .add_slice((0..(num_children + padding)).map(|_| 0))
+            .ok_or(Error::OOM)?,
+        heuristic_scores: arena
+            .add_slice((0..(num_children + padding)).map(|i| {
+                temp_vectors
+                    .moves
+                    .get(i)
+                    .map(|(_, score)| *score)
+                    .unwrap_or(f16::NEG_INFINITY) // Ensure that this move never actually gets selected
+            }))
+            .ok_or(Error::OOM)?,
+    };
+    temp_vectors.moves.clear();
+
+    Ok(tree_edge)
 }
 
 pub struct Pv<'a, const S: usize> {
@@ -396,7 +630,7 @@ impl<'a, const S: usize> Iterator for Pv<'a, S> {
                             .zip(self.arena.get_slice(&bridge.children)),
                     )
                     .filter(|(_, (mv, _))| mv.is_some())
-                    .max_by_key(|(visits, _)| **visits)?;
+                    .max_by_key(|(visits, (_, child))| child.best_move_key(self.arena, **visits))?;
                 self.edge = child;
                 *mv
             })
@@ -415,21 +649,36 @@ pub fn rollout<const S: usize>(
     temp_vectors: &mut TempVectors<S>,
 ) -> (f32, Option<GameResultForUs>) {
     let group_data = position.group_data();
+    rollout_with_group_data(position, &group_data, settings, depth, temp_vectors)
+}
 
-    if let Some(game_result) = position.game_result_with_group_data(&group_data) {
-        let game_result_for_us = match (game_result, position.side_to_move()) {
-            (GameResult::Draw, _) => GameResultForUs::Draw,
-            (GameResult::WhiteWin, Color::Black) => GameResultForUs::Loss, // The side to move has lost
-            (GameResult::BlackWin, Color::White) => GameResultForUs::Loss, // The side to move has lost
-            (GameResult::WhiteWin, Color::White) => GameResultForUs::Win, // The side to move has lost
-            (GameResult::BlackWin, Color::Black) => GameResultForUs::Win, // The side to move has lost
-        };
+fn game_result_for_us<const S: usize>(
+    position: &Position<S>,
+    group_data: &GroupData<S>,
+) -> Option<GameResultForUs> {
+    let game_result = position.game_result_with_group_data(group_data)?;
+    Some(match (game_result, position.side_to_move()) {
+        (GameResult::Draw, _) => GameResultForUs::Draw,
+        (GameResult::WhiteWin, Color::Black) => GameResultForUs::Loss, // The side to move has lost
+        (GameResult::BlackWin, Color::White) => GameResultForUs::Loss, // The side to move has lost
+        (GameResult::WhiteWin, Color::White) => GameResultForUs::Win,  // The side to move has lost
+        (GameResult::BlackWin, Color::Black) => GameResultForUs::Win,  // The side to move has lost
+    })
+}
 
+fn rollout_with_group_data<const S: usize>(
+    position: &mut Position<S>,
+    group_data: &GroupData<S>,
+    settings: &MctsSetting<S>,
+    depth: u16,
+    temp_vectors: &mut TempVectors<S>,
+) -> (f32, Option<GameResultForUs>) {
+    if let Some(game_result_for_us) = game_result_for_us(position, group_data) {
         (game_result_for_us.score(), Some(game_result_for_us))
     } else if depth == 0 {
         let centipawn_score = position.static_eval_with_params_and_data(
-            &group_data,
-            match settings.value_params.as_ref() {
+            group_data,
+            match settings.value_params.as_deref() {
                 Some(params) => params,
                 None => <Position<S>>::value_params(position.komi()),
             },
@@ -448,11 +697,11 @@ pub fn rollout<const S: usize>(
         }
     } else {
         position.generate_moves_with_probabilities(
-            &group_data,
+            group_data,
             &mut temp_vectors.simple_moves,
             &mut temp_vectors.moves,
             &mut temp_vectors.fcd_per_move,
-            match settings.policy_params.as_ref() {
+            match settings.policy_params.as_deref() {
                 Some(params) => params,
                 None => <Position<S>>::policy_params(position.komi()),
             },
@@ -491,7 +740,7 @@ impl ops::Not for GameResultForUs {
 }
 
 impl GameResultForUs {
-    fn score(self) -> f32 {
+    pub fn score(self) -> f32 {
         match self {
             GameResultForUs::Win => 1.0,
             GameResultForUs::Loss => 0.0,
@@ -526,10 +775,10 @@ pub fn best_move<R: Rng, const S: usize>(
         }
         unreachable!()
     } else {
-        return move_scores
+        move_scores
             .iter()
             .max_by(|(_, score1), (_, score2)| score1.total_cmp(score2))
             .unwrap()
-            .0;
+            .0
     }
 }
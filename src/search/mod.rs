@@ -2,27 +2,40 @@
 //!
 //! This implementation does not use full Monte Carlo rollouts, relying on a heuristic evaluation when expanding new nodes instead.
 
+use board_game_traits::Position as PositionTrait;
 use half::f16;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::f32::consts::PI;
 use std::fmt::Display;
-use std::{mem, time};
+use std::sync::Arc;
+use std::{iter, mem, time};
 use std::{process, sync};
 
-use crate::position::Move;
+use crate::evaluation::weights::{LoadedWeights, WeightsError};
 use crate::position::Position;
+use crate::position::{Komi, Move};
 pub use crate::search::mcts_core::best_move;
-use crate::search::mcts_core::{TempVectors, Tree, TreeEdge};
+use crate::search::mcts_core::{GameResultForUs, TempVectors, Tree, TreeEdge};
+use crate::search::strength::StrengthLevel;
 
-use self::arena::ArenaError;
+pub use self::arena::ArenaError;
 use self::mcts_core::Pv;
+use self::time_management::{Deadlines, TimeManager};
 
 mod arena;
 /// This module contains the public-facing convenience API for the search.
 /// The implementation itself in in mcts_core.
 mod mcts_core;
+mod parallel;
+pub mod solver;
+pub mod strength;
+pub mod time_management;
+mod transposition_table;
+mod tree_export;
 pub use arena::Arena;
+pub use transposition_table::TranspositionTable;
+pub use tree_export::ExportedNode;
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, PartialEq, Clone)]
@@ -34,14 +47,19 @@ pub enum TimeControl {
 #[derive(Clone, PartialEq, Debug)]
 pub struct MctsSetting<const S: usize> {
     arena_size: u32,
-    value_params: Option<&'static [f32]>,
-    policy_params: Option<&'static [f32]>,
+    value_params: Option<Arc<[f32]>>,
+    policy_params: Option<Arc<[f32]>>,
     search_params: Box<[f32]>,
     dirichlet: Option<f32>,
     excluded_moves: Vec<Move<S>>,
     static_eval_variance: Option<f32>,
     rollout_depth: u16,
     rollout_temperature: Option<f64>,
+    num_threads: usize,
+    solver_plies: u8,
+    solver_visits: u32,
+    transpositions: bool,
+    max_nodes: Option<u64>,
 }
 
 impl<const S: usize> Default for MctsSetting<S> {
@@ -56,6 +74,11 @@ impl<const S: usize> Default for MctsSetting<S> {
             static_eval_variance: None,
             rollout_depth: 0,
             rollout_temperature: None,
+            num_threads: 1,
+            solver_plies: 0,
+            solver_visits: 0,
+            transpositions: false,
+            max_nodes: None,
         }
     }
 }
@@ -87,16 +110,33 @@ impl<const S: usize> MctsSetting<S> {
         self
     }
 
-    pub fn add_value_params(mut self, value_params: &'static [f32]) -> Self {
-        self.value_params = Some(value_params);
+    pub fn add_value_params(mut self, value_params: impl Into<Arc<[f32]>>) -> Self {
+        self.value_params = Some(value_params.into());
         self
     }
 
-    pub fn add_policy_params(mut self, policy_params: &'static [f32]) -> Self {
-        self.policy_params = Some(policy_params);
+    pub fn add_policy_params(mut self, policy_params: impl Into<Arc<[f32]>>) -> Self {
+        self.policy_params = Some(policy_params.into());
         self
     }
 
+    /// Use weights loaded from files, if they are for this board size.
+    /// Weights for this size must also be for the game's komi
+    pub fn add_loaded_weights(
+        mut self,
+        weights: &LoadedWeights,
+        komi: Komi,
+    ) -> Result<Self, WeightsError> {
+        weights.check_komi(S, komi)?;
+        if let Some(value_params) = weights.value_params::<S>() {
+            self.value_params = Some(value_params);
+        }
+        if let Some(policy_params) = weights.policy_params::<S>() {
+            self.policy_params = Some(policy_params);
+        }
+        Ok(self)
+    }
+
     pub fn add_search_params(mut self, search_params: Box<[f32]>) -> Self {
         self.search_params = search_params;
         self
@@ -130,6 +170,58 @@ impl<const S: usize> MctsSetting<S> {
         self
     }
 
+    /// Number of threads searching the tree in parallel. Defaults to 1.
+    /// With more than one thread, the threads share the same tree, and use virtual loss to spread out over different lines.
+    /// At most 255 threads are supported
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        assert!(num_threads > 0 && num_threads <= u8::MAX as usize);
+        self.num_threads = num_threads;
+        self
+    }
+
+    /// Run the tactics solver on every node that reaches `visits` visits, looking for forced wins up to `plies` plies deep.
+    /// Nodes that are proven wins or losses are scored as if the game was over
+    pub fn add_solver(mut self, plies: u8, visits: u32) -> Self {
+        self.solver_plies = plies;
+        self.solver_visits = visits;
+        self
+    }
+
+    /// Use a transposition table, to share search results between nodes that are reached by different move orders.
+    /// The table uses up to a quarter as much memory as the search tree, in addition to the search tree's memory.
+    pub fn use_transpositions(mut self, transpositions: bool) -> Self {
+        self.transpositions = transpositions;
+        self
+    }
+
+    /// Stop timed searches after searching `max_nodes` nodes, even if there is time left
+    pub fn max_nodes(mut self, max_nodes: u64) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    /// Play at a handicap level, by limiting the nodes searched and adding noise.
+    /// This replaces the node limit, the Dirichlet noise and the static evaluation noise with the level's settings,
+    /// and clears them at full strength. Apply the level before `add_dirichlet` or `add_static_eval_variance`,
+    /// to let those settings override the level's noise.
+    /// Other settings, such as the weights and the number of threads, are kept
+    pub fn strength(mut self, strength: StrengthLevel) -> Self {
+        self.max_nodes = strength.max_nodes;
+        self.dirichlet = strength.dirichlet;
+        self.static_eval_variance = strength.static_eval_variance;
+        self
+    }
+
+    /// The maximum nodes searched per move, if any
+    pub fn node_limit(&self) -> Option<u64> {
+        self.max_nodes
+    }
+
+    /// The maximum memory usage of the search tree
+    pub fn arena_size_bytes(&self) -> usize {
+        self.arena_size as usize * ARENA_ELEMENT_SIZE
+    }
+
     pub fn c_puct_init(&self) -> f32 {
         self.search_params[0]
     }
@@ -167,6 +259,52 @@ impl Display for Error {
 
 impl std::error::Error for Error {}
 
+/// Allocate an arena with `num_slots` slots, or as many slots as the system has memory for
+fn new_arena(num_slots: u32) -> Arena {
+    match Arena::new(num_slots) {
+        Ok(arena) => arena,
+        Err(ArenaError::AllocationFailed(num_bytes)) if !sysinfo::IS_SUPPORTED_SYSTEM => {
+            panic!(
+                "Fatal error: failed to allocate {}MB memory for search tree. Could not detect total system memory.",
+                num_bytes
+            )
+        }
+        Err(ArenaError::AllocationFailed(num_bytes)) => {
+            // The allocation may have failed because the system doesn't have enough memory
+            // Check the system's max memory, and try again
+
+            let mut sys = sysinfo::System::new_all();
+            sys.refresh_all();
+
+            if sys.total_memory() < num_bytes as u64 {
+                // Note: The actual memory allocation is two slots larger, to ensure correct alignment
+                let Some(max_num_slots) =
+                    ((sys.total_memory() / 16).min(u32::MAX as u64) as u32).checked_sub(2)
+                else {
+                    panic!(
+                        "Failed to allocated arena, system reports {} bytes total memory",
+                        sys.total_memory()
+                    );
+                };
+                eprintln!("Warning: failed to allocate {}MB memory for the search tree. Trying again with {}MB.", num_bytes / (1024 * 1024), sys.total_memory() / (1024 * 1024));
+
+                match <Arena<16>>::new(max_num_slots) {
+                    Ok(arena) => arena,
+                    Err(ArenaError::AllocationFailed(num_bytes)) => {
+                        eprintln!("Fatal error: failed to allocate {}MB memory for search tree. Try reducing the search time.", num_bytes / (1024 * 1024));
+                        process::exit(1)
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+            } else {
+                eprintln!("Fatal error: failed to allocate {}MB memory for search tree. Try reducing the search time.", num_bytes / (1024 * 1024));
+                process::exit(1)
+            }
+        }
+        Err(err) => panic!("{}", err),
+    }
+}
+
 pub struct MonteCarloTree<const S: usize> {
     tree: TreeEdge<S>, // Fake edge to the root node
     visits: u32,
@@ -175,150 +313,253 @@ pub struct MonteCarloTree<const S: usize> {
     settings: MctsSetting<S>,
     temp_vectors: TempVectors<S>,
     arena: Arena,
+    /// Whether Dirichlet noise has been applied to the current root's children
+    root_noised: bool,
 }
 
 impl<const S: usize> MonteCarloTree<S> {
+    /// Create a search tree for the position.
+    /// If the arena cannot be allocated, retry with as much memory as the system has, or exit the process if that fails too
     pub fn new(position: Position<S>, settings: MctsSetting<S>) -> MonteCarloTree<S> {
-        let arena = match Arena::new(settings.arena_size) {
-            Ok(arena) => arena,
-            Err(ArenaError::AllocationFailed(num_bytes)) if !sysinfo::IS_SUPPORTED_SYSTEM => {
-                panic!(
-                    "Fatal error: failed to allocate {}MB memory for search tree. Could not detect total system memory.",
-                    num_bytes
-                )
-            }
-            Err(ArenaError::AllocationFailed(num_bytes)) => {
-                // The allocation may have failed because the system doesn't have enough memory
-                // Check the system's max memory, and try again
-
-                let mut sys = sysinfo::System::new_all();
-                sys.refresh_all();
-
-                if sys.total_memory() < num_bytes as u64 {
-                    // Note: The actual memory allocation is two slots larger, to ensure correct alignment
-                    let Some(max_num_slots) =
-                        ((sys.total_memory() / 16).min(u32::MAX as u64) as u32).checked_sub(2)
-                    else {
-                        panic!(
-                            "Failed to allocated arena, system reports {} bytes total memory",
-                            sys.total_memory()
-                        );
-                    };
-                    eprintln!("Warning: failed to allocate {}MB memory for the search tree. Trying again with {}MB.", num_bytes / (1024 * 1024), sys.total_memory() / (1024 * 1024));
-
-                    match <Arena<16>>::new(max_num_slots) {
-                        Ok(arena) => arena,
-                        Err(ArenaError::AllocationFailed(num_bytes)) => {
-                            eprintln!("Fatal error: failed to allocate {}MB memory for search tree. Try reducing the search time.", num_bytes / (1024 * 1024));
-                            process::exit(1)
-                        }
-                        Err(err) => panic!("{}", err),
-                    }
-                } else {
-                    eprintln!("Fatal error: failed to allocate {}MB memory for search tree. Try reducing the search time.", num_bytes / (1024 * 1024));
-                    process::exit(1)
-                }
-            }
-            Err(err) => panic!("{}", err),
-        };
+        let arena = new_arena(settings.arena_size);
+        Self::with_arena(position, settings, arena)
+    }
+
+    /// Create a search tree for the position, or return an error if the arena cannot be allocated.
+    /// For long-running processes that must survive a failed allocation, such as a server
+    pub fn try_new(
+        position: Position<S>,
+        settings: MctsSetting<S>,
+    ) -> Result<MonteCarloTree<S>, ArenaError> {
+        let arena = Arena::new(settings.arena_size)?;
+        Ok(Self::with_arena(position, settings, arena))
+    }
 
-        let mut tree = TreeEdge { child: None };
+    fn with_arena(
+        position: Position<S>,
+        settings: MctsSetting<S>,
+        arena: Arena,
+    ) -> MonteCarloTree<S> {
         let mut temp_vectors = TempVectors::default();
+        if settings.transpositions {
+            temp_vectors.transpositions = Some(sync::Arc::new(TranspositionTable::for_arena_size(
+                settings.arena_size,
+            )));
+        }
+
+        let mut tree = MonteCarloTree {
+            tree: TreeEdge { child: None },
+            visits: 0,
+            position: position.clone(),
+            temp_position: position,
+            settings,
+            temp_vectors,
+            arena,
+            root_noised: false,
+        };
+        tree.initialize_root();
+        tree
+    }
 
+    /// Expand the root node if necessary, and apply dirichlet noise and excluded moves to its children.
+    /// The noise is only applied once to each root
+    fn initialize_root(&mut self) {
         // Applying dirichlet noise or excluding moves can only be done once the child edges of the root are initialized,
         // which is done on the 2nd select
-        tree.select(
-            &mut position.clone(),
-            &settings,
-            &mut temp_vectors,
-            &arena,
-            0,
-        )
-        .unwrap();
-        tree.select(
-            &mut position.clone(),
-            &settings,
-            &mut temp_vectors,
-            &arena,
-            1,
-        )
-        .unwrap();
-
-        if let Some(alpha) = settings.dirichlet {
-            arena
-                .get_mut(
-                    (arena.get_mut(tree.child.as_mut().unwrap()))
-                        .children
-                        .as_mut()
-                        .unwrap(),
+        let mut parent_visits = self.visits;
+        while self.tree.child.as_ref().is_none_or(|child| {
+            let root = self.arena.get(child);
+            root.children.is_none() && root.game_result.is_none()
+        }) {
+            self.tree
+                .select(
+                    &mut self.position.clone(),
+                    &self.settings,
+                    &mut self.temp_vectors,
+                    &self.arena,
+                    parent_visits,
                 )
-                .apply_dirichlet(&arena, 0.25, alpha);
+                .unwrap();
+            parent_visits += 1;
         }
 
-        if !settings.excluded_moves.is_empty() {
-            let bridge = arena.get_mut(
-                (arena.get_mut(tree.child.as_mut().unwrap()))
-                    .children
-                    .as_mut()
-                    .unwrap(),
-            );
-            for excluded_move in settings.excluded_moves.iter() {
-                let index = arena
-                    .get_slice(&bridge.moves)
-                    .iter()
-                    .enumerate()
-                    .find(|(_, mv)| **mv == Some(*excluded_move))
-                    .unwrap()
-                    .0;
-                let moves = arena.get_slice_mut(&mut bridge.moves);
-                let heuristic_scores = arena.get_slice_mut(&mut bridge.heuristic_scores);
-
-                moves[index] = None;
-                heuristic_scores[index] = f16::NEG_INFINITY; // TODO: Also set infinite visitss?
+        let Some(bridge) = self
+            .arena
+            .get_mut(self.tree.child.as_mut().unwrap())
+            .children
+            .as_mut()
+        else {
+            return;
+        };
+        let bridge = self.arena.get_mut(bridge);
+
+        if let Some(alpha) = self.settings.dirichlet {
+            if !self.root_noised {
+                bridge.apply_dirichlet(&self.arena, 0.25, alpha);
+                self.root_noised = true;
             }
         }
 
-        MonteCarloTree {
-            tree,
-            visits: 0,
-            position: position.clone(),
-            temp_position: position,
-            settings,
-            temp_vectors,
-            arena,
+        for excluded_move in self.settings.excluded_moves.iter() {
+            let index = self
+                .arena
+                .get_slice(&bridge.moves)
+                .iter()
+                .enumerate()
+                .find(|(_, mv)| **mv == Some(*excluded_move))
+                .unwrap()
+                .0;
+            let moves = self.arena.get_slice_mut(&mut bridge.moves);
+            let heuristic_scores = self.arena.get_slice_mut(&mut bridge.heuristic_scores);
+
+            moves[index] = None;
+            heuristic_scores[index] = f16::NEG_INFINITY; // TODO: Also set infinite visitss?
         }
     }
 
+    /// Play `mv` from the root position, and make the subtree under it the new root, keeping its search results.
+    /// The rest of the tree is freed, by temporarily copying the subtree out of the arena and clearing it.
+    /// If there is not enough memory for the copy, the tree is discarded instead, with a warning.
+    /// The new root gets its own Dirichlet noise, since the subtree was searched without noise at its root.
+    /// Excluded moves are cleared, since they only apply to the original position.
+    pub fn advance(&mut self, mv: Move<S>) {
+        let subtree = self.child_edge(mv).and_then(|(edge, visits)| {
+            let copy = Arena::new(self.arena.slots_used())
+                .map_err(|err| err.to_string())
+                .and_then(|scratch_arena| {
+                    let edge = edge
+                        .copy_to(&self.arena, &scratch_arena)
+                        .map_err(|err| err.to_string())?;
+                    Ok((edge, visits, scratch_arena))
+                });
+            if let Err(err) = &copy {
+                eprintln!(
+                    "Warning: Discarding the search tree after {}, because it could not be copied: {}",
+                    mv, err
+                );
+            }
+            copy.ok()
+        });
+
+        self.arena.clear();
+        self.position.do_move(mv);
+        self.temp_position.clone_from(&self.position);
+        self.settings.excluded_moves.clear();
+        self.root_noised = false;
+
+        if let Some((edge, visits, scratch_arena)) = subtree {
+            // The subtree fit in the arena before it was cleared, so this cannot fail
+            self.tree = edge.copy_to(&scratch_arena, &self.arena).unwrap();
+            self.visits = visits;
+        } else {
+            self.tree = TreeEdge { child: None };
+            self.visits = 0;
+        }
+        self.initialize_root();
+    }
+
+    /// The edge from the root to the child node reached by `mv`, and its visit count.
+    /// Returns `None` if that child has not been searched yet
+    fn child_edge(&self, mv: Move<S>) -> Option<(&TreeEdge<S>, u32)> {
+        let root = self.arena.get(self.tree.child.as_ref()?);
+        let bridge = self.arena.get(root.children.as_ref()?);
+        let index = self
+            .arena
+            .get_slice(&bridge.moves)
+            .iter()
+            .position(|child_move| *child_move == Some(mv))?;
+        let edge = &self.arena.get_slice(&bridge.children)[index];
+        edge.child.as_ref()?;
+        Some((edge, self.arena.get_slice(&bridge.visitss)[index]))
+    }
+
     pub fn search_for_time<F>(&mut self, max_time: time::Duration, callback: F)
     where
         F: Fn(&Self),
+    {
+        self.search_for_time_or_until_stopped(
+            max_time,
+            &sync::atomic::AtomicBool::new(false),
+            callback,
+        )
+    }
+
+    /// Like `search_for_time`, but also returns early if `stop` is set
+    pub fn search_for_time_or_until_stopped<F>(
+        &mut self,
+        max_time: time::Duration,
+        stop: &sync::atomic::AtomicBool,
+        callback: F,
+    ) where
+        F: Fn(&Self),
+    {
+        self.search_until_deadlines(Deadlines::fixed(max_time), stop, callback)
+    }
+
+    /// Search until around the soft deadline, until `stop` is set, or until the node limit in the settings is reached.
+    /// If the best move changed during the second half of the search, the search may continue until the hard deadline
+    pub fn search_until_deadlines<F>(
+        &mut self,
+        deadlines: Deadlines,
+        stop: &sync::atomic::AtomicBool,
+        callback: F,
+    ) where
+        F: Fn(&Self),
     {
         let start_time = time::Instant::now();
+        let mut best_move = None;
+        let mut best_move_changed_visits = 0;
+        let max_visits = self
+            .settings
+            .max_nodes
+            .map(|max_nodes| self.visits() as u64 + max_nodes);
 
         for i in 0.. {
-            let nodes = (50.0 * 2.0_f32.powf(0.125).powi(i)) as u64;
-            for _ in 0..nodes {
-                if let Err(err) = self.select() {
-                    eprintln!("Warning: {err}");
-                    callback(self);
-                    return;
-                };
+            let nodes =
+                (50.0 * 2.0_f32.powf(0.125).powi(i)) as u64 * self.settings.num_threads as u64;
+            let nodes = max_visits.map_or(nodes, |max_visits| {
+                nodes.min(max_visits.saturating_sub(self.visits() as u64))
+            });
+            if let Err(err) = self.search_nodes(nodes) {
+                eprintln!("Warning: {err}");
+                callback(self);
+                return;
+            }
+            // Further search cannot change the result of a proven position
+            if stop.load(sync::atomic::Ordering::Relaxed)
+                || self.proven_plies().is_some()
+                || max_visits.is_some_and(|max_visits| self.visits() as u64 >= max_visits)
+            {
+                callback(self);
+                return;
             }
 
             let mut shallow_edges = self.shallow_edges().unwrap();
+            shallow_edges.sort_by_key(|edge| edge.visits);
+            shallow_edges.reverse();
+
+            if let Some(edge) = shallow_edges
+                .first()
+                .filter(|edge| best_move != Some(edge.mv))
+            {
+                best_move = Some(edge.mv);
+                best_move_changed_visits = self.visits();
+            }
+            let max_time = if best_move_changed_visits > self.visits() / 2 {
+                deadlines.hard
+            } else {
+                deadlines.soft
+            };
 
             // Always return when we have less than 10ms left
             if max_time < (time::Duration::from_millis(10))
                 || start_time.elapsed() > max_time - (time::Duration::from_millis(10))
-                || shallow_edges.len() == 1
+                || shallow_edges.len() <= 1
             {
                 callback(self);
                 return;
             }
 
-            shallow_edges.sort_by_key(|edge| edge.visits);
-            shallow_edges.reverse();
-
             let node_ratio =
                 (1 + shallow_edges[1].visits) as f32 / (1 + shallow_edges[0].visits) as f32;
             let time_ratio = start_time.elapsed().as_secs_f32() / max_time.as_secs_f32();
@@ -330,7 +571,7 @@ impl<const S: usize> MonteCarloTree<S> {
                         / self.settings.c_puct_base(),
                 );
 
-            let best_edge = shallow_edges.iter().max_by_key(|edge| edge.visits).unwrap();
+            let best_edge = &shallow_edges[0];
 
             let best_exploration_value = best_edge.exploration_value(visits_sqrt, dynamic_cpuct);
 
@@ -351,6 +592,14 @@ impl<const S: usize> MonteCarloTree<S> {
         }
     }
 
+    pub fn position(&self) -> &Position<S> {
+        &self.position
+    }
+
+    pub fn settings(&self) -> &MctsSetting<S> {
+        &self.settings
+    }
+
     // TODO: Count up to u64 on root?
     pub fn visits(&self) -> u32 {
         self.visits
@@ -368,18 +617,72 @@ impl<const S: usize> MonteCarloTree<S> {
             .unwrap_or(self.settings.initial_mean_action_value())
     }
 
+    /// The move with the most visits, and its winning probability for the side to move.
+    /// A proven win is always chosen over unproven moves, and a proven loss is only chosen if every move loses
     pub fn best_move(&self) -> Option<(Move<S>, f32)> {
         let best_edge = self
             .shallow_edges()?
             .into_iter()
-            .max_by_key(|edge| edge.visits)?;
+            .max_by_key(|edge| edge.best_move_key(&self.arena))?;
         Some((best_edge.mv, 1.0 - best_edge.mean_action_value))
     }
 
+    /// If the search has proven the result of the root position, the number of plies until the game ends with best play.
+    /// Positive if the side to move wins, and negative if it loses.
+    pub fn proven_plies(&self) -> Option<i32> {
+        let root = self.arena.get(self.tree.child.as_ref()?);
+        match root.game_result? {
+            GameResultForUs::Win => Some(root.proven_plies as i32),
+            GameResultForUs::Loss => Some(-(root.proven_plies as i32)),
+            GameResultForUs::Draw => None,
+        }
+    }
+
     pub fn pv(&self) -> impl Iterator<Item = Move<S>> + '_ {
         Pv::new(&self.tree, &self.arena)
     }
 
+    /// The `num_lines` most visited moves from the root, each with its own principal variation.
+    /// The lines are sorted like `best_move`, so the first line is the same as `best_move` and `pv`
+    pub fn multi_pv(&self, num_lines: usize) -> Vec<PvLine<S>> {
+        let mut edges = self.shallow_edges().unwrap_or_default();
+        edges.sort_by_key(|edge| edge.best_move_key(&self.arena));
+        edges.reverse();
+
+        edges
+            .iter()
+            .filter(|edge| edge.visits > 0)
+            .take(num_lines)
+            .map(|edge| PvLine {
+                mv: edge.mv,
+                visits: edge.visits,
+                winning_probability: 1.0 - edge.mean_action_value,
+                proven_plies: edge.child.proven_plies(&self.arena),
+                policy: edge.policy.to_f32(),
+                pv: iter::once(edge.mv)
+                    .chain(Pv::new(edge.child, &self.arena))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Export the search tree from the root, for inspecting or visualising the search.
+    /// Only nodes with at least `min_visits` visits, and at most `max_depth` plies below the root, are included
+    pub fn export_tree(&self, max_depth: usize, min_visits: u32) -> ExportedNode<S> {
+        let root = self.arena.get(self.tree.child.as_ref().unwrap());
+        ExportedNode::from_tree(
+            root,
+            &self.arena,
+            None,
+            self.position.side_to_move(),
+            self.visits,
+            self.mean_action_value(),
+            1.0,
+            max_depth,
+            min_visits,
+        )
+    }
+
     /// Print human-readable information of the search's progress.
     pub fn print_info(&self) {
         let mut best_children: Vec<ShallowEdge<S>> = self.shallow_edges().unwrap_or_default();
@@ -434,6 +737,47 @@ impl<const S: usize> MonteCarloTree<S> {
         Ok(result)
     }
 
+    /// Search `nodes` more nodes, using as many threads as set in the settings.
+    pub fn search_nodes(&mut self, nodes: u64) -> Result<(), Error> {
+        if self.settings.num_threads > 1 {
+            parallel::search_nodes(self, nodes)
+        } else {
+            for _ in 0..nodes {
+                self.select()?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Search until `stop` is set, checking it after every small batch of nodes.
+    /// Useful for searching in a background thread, for example when pondering.
+    pub fn search_until_stopped(&mut self, stop: &sync::atomic::AtomicBool) -> Result<(), Error> {
+        while !stop.load(sync::atomic::Ordering::Relaxed) {
+            self.search_nodes(100 * self.settings.num_threads as u64)?;
+        }
+        Ok(())
+    }
+
+    /// The fraction of the root's visits that went to each move, used as policy training targets
+    pub fn training_move_scores(&self) -> Vec<(Move<S>, f16)> {
+        // Visit counts stop changing once the root is proven, so use the winning move as the target instead
+        if self.proven_plies().is_some_and(|plies| plies > 0) {
+            let (best_move, _) = self.best_move().unwrap();
+            return vec![(best_move, f16::ONE)];
+        }
+        let shallow_edges = self.shallow_edges().unwrap();
+        let child_visits: u32 = shallow_edges.iter().map(|edge| edge.visits).sum();
+        shallow_edges
+            .iter()
+            .map(|edge| {
+                (
+                    edge.mv,
+                    f16::from_f32(edge.visits as f32 / child_visits as f32),
+                )
+            })
+            .collect()
+    }
+
     pub fn shallow_edges(&self) -> Option<Vec<ShallowEdge<'_, S>>> {
         let child = self.arena.get(
             self.arena
@@ -469,16 +813,46 @@ impl<const S: usize> MonteCarloTree<S> {
         )
     }
 }
+
+/// A move from the root position, with its search results and principal variation
+#[derive(Clone, Debug, PartialEq)]
+pub struct PvLine<const S: usize> {
+    pub mv: Move<S>,
+    pub visits: u32,
+    /// Winning probability for the side to move in the root position
+    pub winning_probability: f32,
+    /// If the line is proven, the number of plies until the game ends.
+    /// Positive if the side to move in the root position wins, and negative if it loses
+    pub proven_plies: Option<i32>,
+    pub policy: f32,
+    /// The principal variation, starting with `mv`
+    pub pv: Vec<Move<S>>,
+}
+
 // More convenient edge representation, allowing them to be stored as array-of-structs rather than struct-of-arrays
 pub struct ShallowEdge<'a, const S: usize> {
-    visits: u32,
-    mv: Move<S>,
+    pub visits: u32,
+    pub mv: Move<S>,
     mean_action_value: f32,
     child: &'a TreeEdge<S>,
     policy: f16,
 }
 
 impl<'a, const S: usize> ShallowEdge<'a, S> {
+    fn best_move_key(&self, arena: &Arena) -> (u8, u32) {
+        self.child.best_move_key(arena, self.visits)
+    }
+
+    /// The policy's prior probability for the move
+    pub fn policy(&self) -> f32 {
+        self.policy.to_f32()
+    }
+
+    /// Winning probability for the side to move in the root position, or `None` if the move has not been searched
+    pub fn winning_probability(&self) -> Option<f32> {
+        (self.visits > 0).then_some(1.0 - self.mean_action_value)
+    }
+
     pub fn exploration_value(&self, parent_visits_sqrt: f32, dynamic_cpuct: f32) -> f32 {
         mcts_core::exploration_value(
             self.mean_action_value,
@@ -526,29 +900,19 @@ pub fn mcts_training<const S: usize>(
 
     match time_control {
         TimeControl::FixedNodes(nodes) => {
-            for _ in 0..*nodes {
-                if let Err(err) = tree.select() {
-                    eprintln!("Warning: {err}");
-                    break;
-                }
+            if let Err(err) = tree.search_nodes(*nodes) {
+                eprintln!("Warning: {err}");
             }
         }
-        TimeControl::Time(time, increment) => {
-            let max_time = *time / 5 + *increment / 2;
-            tree.search_for_time(max_time, |_| {});
+        TimeControl::Time(_, _) => {
+            let plies = tree.position().half_moves_played();
+            let deadlines = TimeManager::default()
+                .deadlines::<S>(time_control, plies)
+                .unwrap();
+            tree.search_until_deadlines(deadlines, &sync::atomic::AtomicBool::new(false), |_| {});
         }
     }
-    let shallow_edges = tree.shallow_edges().unwrap();
-    let child_visits: u32 = shallow_edges.iter().map(|edge| edge.visits).sum();
-    shallow_edges
-        .iter()
-        .map(|edge| {
-            (
-                edge.mv,
-                f16::from_f32(edge.visits as f32 / child_visits as f32),
-            )
-        })
-        .collect()
+    tree.training_move_scores()
 }
 
 /// Convert a static evaluation in centipawns to a winning probability between 0.0 and 1.0.
@@ -0,0 +1,420 @@
+//! Multi-threaded search, where several threads share the same tree and arena.
+//!
+//! The tree is protected by a single lock, which is only held while selecting a line and backing up its result.
+//! The expensive parts of each iteration, generating moves, evaluating positions and running the tactics solver, are done without holding the lock.
+//! Each thread applies a virtual loss to the line it selects, which steers the other threads towards different lines.
+//! A thread whose line runs into a node that another thread is working on waits until that work is done, instead of retrying immediately.
+
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+use board_game_traits::Position as PositionTrait;
+
+use crate::position::Position;
+use crate::search::arena::{self, Arena};
+use crate::search::mcts_core::{self, GameResultForUs, TempVectors, Tree, TreeBridge};
+use crate::search::{Error, MctsSetting, MonteCarloTree};
+
+/// Score added to every node in a line while a thread is searching it.
+/// Node scores are from the perspective of the side to move, so this counts as a loss for the side choosing the line.
+const VIRTUAL_LOSS: f32 = 1.0;
+
+/// Search state shared between all the threads. Must only be accessed while holding the lock
+struct SharedState {
+    root_visits: u32,
+    nodes_left: u64,
+    error: Option<Error>,
+}
+
+/// A raw pointer to a node in the arena.
+/// Must only be dereferenced while holding the lock on the shared state,
+/// or if the node is unreachable from the other threads
+struct NodePtr<T>(*mut T);
+
+impl<T> Clone for NodePtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for NodePtr<T> {}
+
+unsafe impl<T> Send for NodePtr<T> {}
+unsafe impl<T> Sync for NodePtr<T> {}
+
+/// One edge in the line currently being searched by a thread
+struct PathStep<const S: usize> {
+    bridge: NodePtr<TreeBridge<S>>,
+    child_index: usize,
+    child: NodePtr<Tree<S>>,
+    child_hash: u64,
+}
+
+/// The node reached at the end of a selected line
+enum Leaf {
+    /// The game is over, with the given score
+    Terminal(f32),
+    /// A new, unevaluated node was added to the tree
+    Evaluate,
+    /// An evaluated node without children, which must be expanded
+    Expand,
+    /// A node that another thread is currently evaluating, expanding or solving
+    Collision,
+    /// A node that must be checked by the tactics solver before the line continues.
+    /// Holds the node's visits before this line
+    Solve(u32),
+}
+
+/// Search `nodes` nodes, using the number of threads given by the tree's settings
+pub fn search_nodes<const S: usize>(tree: &mut MonteCarloTree<S>, nodes: u64) -> Result<(), Error> {
+    let root = NodePtr(tree.arena.get_mut(tree.tree.child.as_mut().unwrap()) as *mut Tree<S>);
+    let shared = Mutex::new(SharedState {
+        root_visits: tree.visits,
+        nodes_left: nodes,
+        error: None,
+    });
+
+    // Notified whenever a thread finishes working on a pending node, or stops with an error
+    let node_finished = Condvar::new();
+
+    let (position, settings, arena) = (&tree.position, &tree.settings, &tree.arena);
+    let shared_ref = &shared;
+    let node_finished_ref = &node_finished;
+
+    let transpositions = &tree.temp_vectors.transpositions;
+
+    thread::scope(|s| {
+        for _ in 0..settings.num_threads {
+            let mut temp_vectors = TempVectors::default();
+            temp_vectors.transpositions = transpositions.clone();
+            s.spawn(move || {
+                search_worker(
+                    root,
+                    position,
+                    settings,
+                    arena,
+                    shared_ref,
+                    node_finished_ref,
+                    temp_vectors,
+                )
+            });
+        }
+    });
+
+    let shared = shared.into_inner().unwrap();
+    tree.visits = shared.root_visits;
+    match shared.error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+fn search_worker<const S: usize>(
+    root: NodePtr<Tree<S>>,
+    root_position: &Position<S>,
+    settings: &MctsSetting<S>,
+    arena: &Arena,
+    shared: &Mutex<SharedState>,
+    node_finished: &Condvar,
+    mut temp_vectors: TempVectors<S>,
+) {
+    let mut position = root_position.clone();
+    let mut path: Vec<PathStep<S>> = vec![];
+
+    loop {
+        let mut state = shared.lock().unwrap();
+        if state.error.is_some() || state.nodes_left == 0 {
+            return;
+        }
+        if state.root_visits == u32::MAX {
+            state.error = Some(Error::MaxVisits);
+            return;
+        }
+        position.clone_from(root_position);
+
+        path.clear();
+        let root_visits = state.root_visits;
+        state.root_visits += 1;
+        let mut selection = select_leaf(
+            root,
+            root_visits,
+            &mut position,
+            settings,
+            &mut temp_vectors,
+            arena,
+            &mut path,
+        );
+
+        // Run the solver without holding the lock, and then continue the line from the solved node
+        while let Ok(Leaf::Solve(node_visits)) = selection {
+            drop(state);
+            let proof = mcts_core::solve_position(&mut position, settings, &mut temp_vectors);
+
+            state = shared.lock().unwrap();
+            let node = leaf_node(root, &path);
+            let tree = unsafe { &mut *node.0 };
+            tree.pending = false;
+            if let Some((game_result, proven_plies)) = proof {
+                tree.game_result = Some(game_result);
+                tree.proven_plies = proven_plies;
+            }
+            node_finished.notify_all();
+            selection = select_leaf(
+                node,
+                node_visits,
+                &mut position,
+                settings,
+                &mut temp_vectors,
+                arena,
+                &mut path,
+            );
+        }
+
+        let leaf = match selection {
+            Ok(leaf) => leaf,
+            Err(err) => {
+                undo_virtual_loss(&mut state, &path, settings, arena);
+                state.error = Some(err);
+                node_finished.notify_all();
+                return;
+            }
+        };
+
+        match leaf {
+            Leaf::Collision => {
+                undo_virtual_loss(&mut state, &path, settings, arena);
+                // Wait for the other thread, instead of selecting the same line again straight away
+                drop(node_finished.wait(state).unwrap());
+            }
+            Leaf::Solve(_) => unreachable!(),
+            Leaf::Terminal(result) => {
+                state.nodes_left -= 1;
+                backup(root, &path, arena, &temp_vectors, result);
+            }
+            Leaf::Evaluate => {
+                state.nodes_left -= 1;
+                drop(state);
+
+                let (result, game_result) =
+                    mcts_core::evaluate_new_node(&mut position, settings, &mut temp_vectors);
+
+                let _state = shared.lock().unwrap();
+                let node = unsafe { &mut *leaf_node(root, &path).0 };
+                node.game_result = game_result;
+                node.pending = false;
+                backup(root, &path, arena, &temp_vectors, result);
+                node_finished.notify_all();
+            }
+            Leaf::Expand => {
+                state.nodes_left -= 1;
+                drop(state);
+
+                let expansion = expand(&mut position, settings, &mut temp_vectors, arena);
+
+                let mut state = shared.lock().unwrap();
+                let node = unsafe { &mut *leaf_node(root, &path).0 };
+                node.pending = false;
+                node_finished.notify_all();
+                match expansion {
+                    Ok((children, result, proven_result)) => {
+                        node.children = Some(children);
+                        if let Some((game_result, proven_plies)) = proven_result {
+                            node.game_result = Some(game_result);
+                            node.proven_plies = proven_plies;
+                        }
+                        backup(root, &path, arena, &temp_vectors, result);
+                    }
+                    Err(err) => {
+                        undo_virtual_loss(&mut state, &path, settings, arena);
+                        state.error = Some(err);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Continue a line from `node`, which has been visited `node_visits` times, applying virtual loss to every edge added to `path`.
+/// Must only be called while holding the lock on the shared state.
+/// If this returns an error, the line in `path` still needs to have its virtual loss undone
+fn select_leaf<const S: usize>(
+    mut node: NodePtr<Tree<S>>,
+    mut node_visits: u32,
+    position: &mut Position<S>,
+    settings: &MctsSetting<S>,
+    temp_vectors: &mut TempVectors<S>,
+    arena: &Arena,
+    path: &mut Vec<PathStep<S>>,
+) -> Result<Leaf, Error> {
+    loop {
+        let tree = unsafe { &mut *node.0 };
+        if tree.pending {
+            return Ok(Leaf::Collision);
+        }
+        if let Some(game_result) = tree.game_result {
+            return Ok(Leaf::Terminal(game_result.score()));
+        }
+        let Some(children) = tree.children.as_mut() else {
+            tree.pending = true;
+            return Ok(Leaf::Expand);
+        };
+
+        let bridge = arena.get_mut(children);
+        let child_index = bridge.best_child(settings, temp_vectors, arena, node_visits);
+        let child_visits = arena.get_slice(&bridge.visitss)[child_index];
+        let child_move = arena.get_slice(&bridge.moves)[child_index].unwrap();
+
+        position.do_move(child_move);
+
+        let edge = &mut arena.get_slice_mut(&mut bridge.children)[child_index];
+        let needs_solver = edge.child.as_mut().is_some_and(|child| {
+            let child = arena.get_mut(child);
+            child_visits == settings.solver_visits
+                && settings.solver_plies > 0
+                && child.game_result.is_none()
+                && !child.pending
+        });
+        let is_new_node = edge.child.is_none();
+        if is_new_node {
+            edge.child = Some(
+                arena
+                    .add(Tree {
+                        total_action_value: 0.0,
+                        game_result: None,
+                        proven_plies: 0,
+                        children: None,
+                        pending: true,
+                        virtual_losses: 0,
+                    })
+                    .ok_or(Error::OOM)?,
+            );
+        }
+        let child = arena.get_mut(edge.child.as_mut().unwrap());
+
+        child.total_action_value += VIRTUAL_LOSS as f64;
+        child.virtual_losses += 1;
+        let visits = &mut arena.get_slice_mut(&mut bridge.visitss)[child_index];
+        *visits += 1;
+        arena.get_slice_mut(&mut bridge.mean_action_values)[child_index] =
+            child.total_action_value as f32 / *visits as f32;
+
+        let child = NodePtr(child as *mut Tree<S>);
+        path.push(PathStep {
+            bridge: NodePtr(bridge as *mut TreeBridge<S>),
+            child_index,
+            child,
+            child_hash: position.zobrist_hash(),
+        });
+
+        if is_new_node {
+            return Ok(Leaf::Evaluate);
+        }
+        if needs_solver {
+            unsafe { (*child.0).pending = true };
+            return Ok(Leaf::Solve(child_visits));
+        }
+        node = child;
+        node_visits = child_visits;
+    }
+}
+
+/// Generate the children of a node, and evaluate its first child.
+/// Also returns the node's result, if evaluating the child proved it.
+/// The children are not reachable from the other threads until they are written into the tree
+#[allow(clippy::type_complexity)]
+fn expand<const S: usize>(
+    position: &mut Position<S>,
+    settings: &MctsSetting<S>,
+    temp_vectors: &mut TempVectors<S>,
+    arena: &Arena,
+) -> Result<
+    (
+        arena::Index<TreeBridge<S>>,
+        f32,
+        Option<(GameResultForUs, u8)>,
+    ),
+    Error,
+> {
+    let mut bridge = mcts_core::new_bridge(position, settings, temp_vectors, arena)?;
+    let (result, child_index) = bridge.select(position, settings, temp_vectors, arena, 1)?;
+    let proven_result = bridge.proven_result(arena, child_index);
+    Ok((arena.add(bridge).ok_or(Error::OOM)?, result, proven_result))
+}
+
+/// Replace the virtual loss in a line with the real result, which is from the perspective of the final node.
+/// Also propagates proven results up the line
+fn backup<const S: usize>(
+    root: NodePtr<Tree<S>>,
+    path: &[PathStep<S>],
+    arena: &Arena,
+    temp_vectors: &TempVectors<S>,
+    mut result: f32,
+) {
+    for (i, step) in path.iter().enumerate().rev() {
+        let child = unsafe { &mut *step.child.0 };
+        let bridge = unsafe { &mut *step.bridge.0 };
+
+        child.total_action_value += (result - VIRTUAL_LOSS) as f64;
+        child.virtual_losses -= 1;
+        let visits = arena.get_slice(&bridge.visitss)[step.child_index];
+        let mean_action_value = child.total_action_value as f32 / visits as f32;
+        arena.get_slice_mut(&mut bridge.mean_action_values)[step.child_index] = mean_action_value;
+
+        if let Some(transpositions) = temp_vectors.transpositions.as_ref() {
+            // Other threads' lines through the node are still in the statistics as virtual losses,
+            // which must not be shared with other nodes. Store the statistics without them
+            let other_lines = child.virtual_losses as u32;
+            if child.game_result.is_none() && visits > other_lines {
+                let total_action_value =
+                    child.total_action_value - other_lines as f64 * VIRTUAL_LOSS as f64;
+                transpositions.insert(
+                    step.child_hash,
+                    total_action_value as f32 / (visits - other_lines) as f32,
+                    visits - other_lines,
+                );
+            }
+        }
+
+        if let Some((game_result, proven_plies)) = bridge.proven_result(arena, step.child_index) {
+            let parent = match i {
+                0 => root,
+                _ => path[i - 1].child,
+            };
+            let parent = unsafe { &mut *parent.0 };
+            parent.game_result = Some(game_result);
+            parent.proven_plies = proven_plies;
+        }
+
+        result = 1.0 - result;
+    }
+    unsafe { (*root.0).total_action_value += result as f64 };
+}
+
+/// Remove the virtual loss from a line, without adding any result
+fn undo_virtual_loss<const S: usize>(
+    state: &mut SharedState,
+    path: &[PathStep<S>],
+    settings: &MctsSetting<S>,
+    arena: &Arena,
+) {
+    for step in path.iter().rev() {
+        let child = unsafe { &mut *step.child.0 };
+        let bridge = unsafe { &mut *step.bridge.0 };
+
+        child.total_action_value -= VIRTUAL_LOSS as f64;
+        child.virtual_losses -= 1;
+        let visits = &mut arena.get_slice_mut(&mut bridge.visitss)[step.child_index];
+        *visits -= 1;
+        arena.get_slice_mut(&mut bridge.mean_action_values)[step.child_index] = if *visits == 0 {
+            settings.initial_mean_action_value()
+        } else {
+            child.total_action_value as f32 / *visits as f32
+        };
+    }
+    state.root_visits -= 1;
+}
+
+fn leaf_node<const S: usize>(root: NodePtr<Tree<S>>, path: &[PathStep<S>]) -> NodePtr<Tree<S>> {
+    path.last().map(|step| step.child).unwrap_or(root)
+}
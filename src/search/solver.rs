@@ -0,0 +1,200 @@
+//! An exact solver for short forced wins, used alongside MCTS to find tactics the search might miss.
+//!
+//! The solver proves that the side to move can force a win within a few plies, or that every move loses to a forced win.
+//! It is sound but incomplete: every proof it finds is correct, but to keep the search small,
+//! it only tries moves that threaten an immediate win when looking for wins longer than one move.
+//! It also gives up after a fixed number of nodes.
+
+use board_game_traits::{Color, GameResult, Position as PositionTrait};
+
+use crate::position::{ExpMove, Move, Position, Role};
+
+/// The outcome of solving a position, from the perspective of the side to move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Proof<const S: usize> {
+    /// The side to move wins with `mv`, at the latest on their move `plies` plies from now
+    Win { mv: Move<S>, plies: u8 },
+    /// Every move loses, at the latest `plies` plies from now
+    Loss { plies: u8 },
+    /// No forced win was found for either side, or the solver ran out of nodes
+    Unknown,
+}
+
+#[derive(Debug)]
+pub struct Solver<const S: usize> {
+    max_nodes: u64,
+    nodes: u64,
+    // One move list per ply, re-used between searches to avoid allocations
+    move_lists: Vec<Vec<Move<S>>>,
+}
+
+impl<const S: usize> Solver<S> {
+    /// Create a solver that gives up after visiting `max_nodes` nodes in a single search
+    pub fn new(max_nodes: u64) -> Self {
+        Solver {
+            max_nodes,
+            nodes: 0,
+            move_lists: vec![],
+        }
+    }
+
+    pub fn set_max_nodes(&mut self, max_nodes: u64) {
+        self.max_nodes = max_nodes;
+    }
+
+    /// Number of nodes visited in the last search
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Try to prove a win or loss for the side to move within `max_plies` plies.
+    /// Shorter wins are always preferred, and wins are preferred over losses.
+    /// The position is restored before returning
+    pub fn solve(&mut self, position: &mut Position<S>, max_plies: u8) -> Proof<S> {
+        self.nodes = 0;
+        if position.game_result().is_some() {
+            return Proof::Unknown;
+        }
+        for plies in 1..=max_plies {
+            if plies % 2 == 1 {
+                if let Some(mv) = self.win(position, plies, 0) {
+                    return Proof::Win { mv, plies };
+                }
+            } else if self.all_moves_lose(position, plies, 0) {
+                return Proof::Loss { plies };
+            }
+            if self.is_out_of_nodes() {
+                break;
+            }
+        }
+        Proof::Unknown
+    }
+
+    fn is_out_of_nodes(&self) -> bool {
+        self.nodes >= self.max_nodes
+    }
+
+    fn moves_at_ply(&mut self, position: &Position<S>, ply: usize) -> Vec<Move<S>> {
+        while self.move_lists.len() <= ply {
+            self.move_lists.push(vec![]);
+        }
+        let mut moves = std::mem::take(&mut self.move_lists[ply]);
+        moves.clear();
+        position.generate_moves(&mut moves);
+        moves
+    }
+
+    /// Find a move that wins for the side to move within `plies` plies, where `plies` is odd
+    fn win(&mut self, position: &mut Position<S>, plies: u8, ply: usize) -> Option<Move<S>> {
+        if let Some(mv) = self.win_in_one(position, ply) {
+            return Some(mv);
+        }
+        if plies < 3 {
+            return None;
+        }
+
+        let moves = self.moves_at_ply(position, ply);
+        let mut winning_move = None;
+
+        for &mv in moves.iter() {
+            if self.is_out_of_nodes() {
+                break;
+            }
+            self.nodes += 1;
+            let reverse_move = position.do_move(mv);
+            // Immediate wins were already checked, so any game result is a loss or a draw
+            if position.game_result().is_none()
+                && self.threatens_win(position, ply + 1)
+                && self.all_moves_lose(position, plies - 1, ply + 1)
+            {
+                winning_move = Some(mv);
+            }
+            position.reverse_move(reverse_move);
+            if winning_move.is_some() {
+                break;
+            }
+        }
+        self.move_lists[ply] = moves;
+        winning_move
+    }
+
+    /// Check whether every move for the side to move allows the opponent to win within `plies` plies, where `plies` is even
+    fn all_moves_lose(&mut self, position: &mut Position<S>, plies: u8, ply: usize) -> bool {
+        let moves = self.moves_at_ply(position, ply);
+        let us = position.side_to_move();
+        let mut all_lose = true;
+
+        for &mv in moves.iter() {
+            if self.is_out_of_nodes() {
+                all_lose = false;
+                break;
+            }
+            self.nodes += 1;
+            let reverse_move = position.do_move(mv);
+            let loses = match position.game_result() {
+                Some(game_result) => is_win_for(game_result, !us),
+                None => self.win(position, plies - 1, ply + 1).is_some(),
+            };
+            position.reverse_move(reverse_move);
+            if !loses {
+                all_lose = false;
+                break;
+            }
+        }
+        self.move_lists[ply] = moves;
+        all_lose
+    }
+
+    /// Check whether the player who just moved could win immediately, if they were allowed to move again
+    fn threatens_win(&mut self, position: &mut Position<S>, ply: usize) -> bool {
+        position.null_move();
+        let threat = self.win_in_one(position, ply).is_some();
+        position.null_move();
+        threat
+    }
+
+    /// Find a move that immediately wins the game for the side to move
+    fn win_in_one(&mut self, position: &mut Position<S>, ply: usize) -> Option<Move<S>> {
+        let us = position.side_to_move();
+        let group_data = position.group_data();
+        let empty_squares = S * S - group_data.all_pieces().count() as usize;
+        let our_pieces_left = match us {
+            Color::White => position.white_reserves_left() + position.white_caps_left(),
+            Color::Black => position.black_reserves_left() + position.black_caps_left(),
+        };
+        // Any placement may win on flats if it ends the game
+        let placement_ends_game = empty_squares == 1 || our_pieces_left == 1;
+
+        let moves = self.moves_at_ply(position, ply);
+        let mut winning_move = None;
+
+        for &mv in moves.iter() {
+            if let ExpMove::Place(role, square) = mv.expand() {
+                if !placement_ends_game
+                    && (role == Role::Wall || !group_data.is_critical_square(square, us))
+                {
+                    continue;
+                }
+            }
+            self.nodes += 1;
+            let reverse_move = position.do_move(mv);
+            let wins = position
+                .game_result()
+                .is_some_and(|game_result| is_win_for(game_result, us));
+            position.reverse_move(reverse_move);
+            if wins {
+                winning_move = Some(mv);
+                break;
+            }
+        }
+        self.move_lists[ply] = moves;
+        winning_move
+    }
+}
+
+fn is_win_for(game_result: GameResult, color: Color) -> bool {
+    matches!(
+        (game_result, color),
+        (GameResult::WhiteWin, Color::White) | (GameResult::BlackWin, Color::Black)
+    )
+}
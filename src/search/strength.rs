@@ -0,0 +1,122 @@
+//! Handicap levels for playing against humans, from level 1 (weakest) to level 10 (full strength).
+//! Levels are applied to the search with [`MctsSetting::strength`].
+//!
+//! Each level limits the number of nodes searched per move, and weaker levels also add noise to the policy and the static evaluation.
+//! The Elo ratings are measured with the `calibrate` command of the `tune` binary, by playing the levels against each other.
+//! The ratings are relative to level 1, which is rated 0.
+//! Level 10 has no node limit, so its strength depends on the time control. It is rated with 100 000 nodes per move.
+//!
+//! The current ratings come from a single run of
+//! ```text
+//! tune calibrate --size 5 --komi 2 --games 100
+//! ```
+//! with the default `--max-distance 2` and `--full-strength-nodes 100000`, and the default openings of two flat placements.
+//! Each level played the two levels above it, for 17 pairings and 1700 games in total.
+//! With 100 games per pairing, each difference between adjacent levels has a 95% confidence interval of at least ±70 Elo, and more for lopsided pairings.
+//!
+//! [`MctsSetting::strength`]: crate::search::MctsSetting::strength
+
+/// A calibrated combination of node limit and noise
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StrengthLevel {
+    pub level: u8,
+    /// Elo rating on 5s with 2 komi, relative to level 1
+    pub elo: i32,
+    /// Maximum nodes searched per move, or `None` for no limit
+    pub max_nodes: Option<u64>,
+    /// Dirichlet noise added to the policy of the root node
+    pub dirichlet: Option<f32>,
+    /// Random noise added to the static evaluation
+    pub static_eval_variance: Option<f32>,
+}
+
+impl StrengthLevel {
+    /// The level with the given number, from 1 to 10
+    pub fn from_level(level: u8) -> Option<StrengthLevel> {
+        STRENGTH_LEVELS
+            .iter()
+            .find(|strength| strength.level == level)
+            .copied()
+    }
+
+    /// The level whose rating is closest to `elo`
+    pub fn closest_to_elo(elo: i32) -> StrengthLevel {
+        *STRENGTH_LEVELS
+            .iter()
+            .min_by_key(|strength| (strength.elo - elo).abs())
+            .unwrap()
+    }
+}
+
+pub const STRENGTH_LEVELS: [StrengthLevel; 10] = [
+    StrengthLevel {
+        level: 1,
+        elo: 0,
+        max_nodes: Some(5),
+        dirichlet: Some(0.1),
+        static_eval_variance: Some(1.5),
+    },
+    StrengthLevel {
+        level: 2,
+        elo: 94,
+        max_nodes: Some(10),
+        dirichlet: Some(0.1),
+        static_eval_variance: Some(1.0),
+    },
+    StrengthLevel {
+        level: 3,
+        elo: 209,
+        max_nodes: Some(20),
+        dirichlet: Some(0.25),
+        static_eval_variance: Some(1.0),
+    },
+    StrengthLevel {
+        level: 4,
+        elo: 455,
+        max_nodes: Some(50),
+        dirichlet: Some(0.25),
+        static_eval_variance: Some(0.5),
+    },
+    StrengthLevel {
+        level: 5,
+        elo: 671,
+        max_nodes: Some(100),
+        dirichlet: Some(0.5),
+        static_eval_variance: Some(0.5),
+    },
+    StrengthLevel {
+        level: 6,
+        elo: 900,
+        max_nodes: Some(300),
+        dirichlet: Some(0.5),
+        static_eval_variance: None,
+    },
+    StrengthLevel {
+        level: 7,
+        elo: 1166,
+        max_nodes: Some(1000),
+        dirichlet: None,
+        static_eval_variance: None,
+    },
+    StrengthLevel {
+        level: 8,
+        elo: 1480,
+        max_nodes: Some(4000),
+        dirichlet: None,
+        static_eval_variance: None,
+    },
+    StrengthLevel {
+        level: 9,
+        elo: 1865,
+        max_nodes: Some(20_000),
+        dirichlet: None,
+        static_eval_variance: None,
+    },
+    StrengthLevel {
+        level: 10,
+        elo: 2235,
+        max_nodes: None,
+        dirichlet: None,
+        static_eval_variance: None,
+    },
+];
@@ -0,0 +1,143 @@
+//! Decide how much time to spend on each move when playing on a clock.
+//!
+//! A [`TimeManager`] turns the clock into two deadlines for a search. The search normally stops around the soft deadline,
+//! but may keep going until the hard deadline if the best move keeps changing, see [`MonteCarloTree::search_until_deadlines`].
+//!
+//! [`MonteCarloTree::search_until_deadlines`]: crate::search::MonteCarloTree::search_until_deadlines
+
+use std::time::Duration;
+
+use crate::search::TimeControl;
+
+/// Time reserved for communication and other overhead for each move, by default
+pub const DEFAULT_MOVE_OVERHEAD: Duration = Duration::from_millis(100);
+
+/// How much of the remaining time to use for a move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimePolicy {
+    /// Use `1/time_divisor` of the remaining time, plus `1/increment_divisor` of the increment
+    Fraction {
+        time_divisor: u32,
+        increment_divisor: u32,
+    },
+    /// Divide the remaining time evenly over the moves we expect to have left,
+    /// estimated from the board size and the number of plies played, plus half the increment
+    MovesLeft,
+}
+
+/// When a search should stop
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Deadlines {
+    /// Stop around here if the best move is stable
+    pub soft: Duration,
+    /// Never search for longer than this
+    pub hard: Duration,
+}
+
+impl Deadlines {
+    /// Deadlines that always stop the search after `time`
+    pub fn fixed(time: Duration) -> Self {
+        Deadlines {
+            soft: time,
+            hard: time,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeManager {
+    policy: TimePolicy,
+    opening_plies: usize,
+    max_move_time: Option<Duration>,
+    extension_factor: f32,
+    move_overhead: Duration,
+}
+
+impl Default for TimeManager {
+    fn default() -> Self {
+        TimeManager::new(TimePolicy::Fraction {
+            time_divisor: 5,
+            increment_divisor: 2,
+        })
+    }
+}
+
+impl TimeManager {
+    pub fn new(policy: TimePolicy) -> Self {
+        TimeManager {
+            policy,
+            opening_plies: 0,
+            max_move_time: None,
+            extension_factor: 2.0,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+        }
+    }
+
+    /// Use half as much time for the first `opening_plies` plies of the game
+    pub fn opening_plies(mut self, opening_plies: usize) -> Self {
+        self.opening_plies = opening_plies;
+        self
+    }
+
+    /// Never search a move for longer than `max_move_time`, even if the best move is unstable
+    pub fn max_move_time(mut self, max_move_time: Duration) -> Self {
+        self.max_move_time = Some(max_move_time);
+        self
+    }
+
+    /// Allow searching up to `extension_factor` times longer than the soft deadline when the best move is unstable.
+    /// A factor of 1.0 disables the extension
+    pub fn extension_factor(mut self, extension_factor: f32) -> Self {
+        assert!(extension_factor >= 1.0);
+        self.extension_factor = extension_factor;
+        self
+    }
+
+    /// Time to reserve for each move, which is not counted as time left when setting the deadlines.
+    /// Covers communication with the opponent or the GUI. Defaults to [`DEFAULT_MOVE_OVERHEAD`]
+    pub fn move_overhead(mut self, move_overhead: Duration) -> Self {
+        self.move_overhead = move_overhead;
+        self
+    }
+
+    /// The deadlines for a move on an `S`x`S` board after `plies` plies, or `None` if the search is not timed.
+    /// Neither deadline is ever more than a third of the time left, after subtracting the move overhead.
+    /// With a large increment and little time left, the increment can otherwise add up to more time than we have
+    pub fn deadlines<const S: usize>(
+        &self,
+        time_control: &TimeControl,
+        plies: usize,
+    ) -> Option<Deadlines> {
+        let TimeControl::Time(time_left, increment) = *time_control else {
+            return None;
+        };
+        let mut soft = match self.policy {
+            TimePolicy::Fraction {
+                time_divisor,
+                increment_divisor,
+            } => time_left / time_divisor + increment / increment_divisor,
+            TimePolicy::MovesLeft => {
+                time_left / expected_moves_left::<S>(plies) as u32 + increment / 2
+            }
+        };
+        if plies < self.opening_plies {
+            soft /= 2;
+        }
+        let max_time = time_left.saturating_sub(self.move_overhead) / 3;
+        soft = soft.min(max_time);
+
+        // The soft deadline is already within the cap, so this never shortens it
+        let mut hard = soft.mul_f32(self.extension_factor).min(max_time);
+        if let Some(max_move_time) = self.max_move_time {
+            soft = soft.min(max_move_time);
+            hard = hard.min(max_move_time);
+        }
+        Some(Deadlines { soft, hard })
+    }
+}
+
+/// Rough estimate of how many more moves we will play, from the length of typical games on each board size
+fn expected_moves_left<const S: usize>(plies: usize) -> usize {
+    let expected_game_plies = 2 * S * S;
+    (expected_game_plies.saturating_sub(plies) / 2).max(10)
+}
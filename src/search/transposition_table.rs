@@ -0,0 +1,87 @@
+//! A transposition table, which shares search results between nodes that are reached by different move orders.
+//!
+//! The search tree itself is still a pure tree, with one node per move order. Instead, the table stores the mean action value and visit count of recently searched positions.
+//! When the search reaches a position for the first time through a new move order, it uses the stored mean action value as the node's first evaluation,
+//! instead of the static evaluation.
+//!
+//! The table is a fixed-size hash table, allocated separately from the arena, and is never resized.
+//! Entries are replaced by entries with more visits, or by newer entries for the same position.
+//! It can be read and written by several search threads at the same time, without locking.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The maximum number of entries, to limit memory usage to 256MiB
+const MAX_ENTRIES: usize = 1 << 24;
+
+/// A stored search result for a position.
+/// `key` is the position's hash xor-ed with `data`, so that an entry that was torn by a concurrent write is detected as a miss
+struct Entry {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+pub struct TranspositionTable {
+    entries: Box<[Entry]>,
+}
+
+impl fmt::Debug for TranspositionTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TranspositionTable({} entries)", self.entries.len())
+    }
+}
+
+impl TranspositionTable {
+    /// Create a table for an arena with `arena_slots` slots.
+    /// The table uses at most a quarter as much memory as the arena
+    pub fn for_arena_size(arena_slots: u32) -> Self {
+        let max_entries = (arena_slots as usize / 4).clamp(1, MAX_ENTRIES);
+        // Round down to a power of two, so that the index is a bitmask of the hash
+        let num_entries = 1 << max_entries.ilog2();
+        TranspositionTable {
+            entries: (0..num_entries)
+                .map(|_| Entry {
+                    key: AtomicU64::new(0),
+                    data: AtomicU64::new(0),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn entry(&self, hash: u64) -> &Entry {
+        &self.entries[hash as usize & (self.entries.len() - 1)]
+    }
+
+    /// The stored mean action value and visit count for the position with the given Zobrist hash, if any
+    pub fn get(&self, hash: u64) -> Option<(f32, u32)> {
+        let entry = self.entry(hash);
+        let data = entry.data.load(Ordering::Relaxed);
+        let key = entry.key.load(Ordering::Relaxed);
+        let visits = data as u32;
+        if key ^ data != hash || visits == 0 {
+            return None;
+        }
+        Some((f32::from_bits((data >> 32) as u32), visits))
+    }
+
+    /// Store the mean action value of a position, unless its entry holds a different position with more visits
+    pub fn insert(&self, hash: u64, mean_action_value: f32, visits: u32) {
+        let entry = self.entry(hash);
+        let old_data = entry.data.load(Ordering::Relaxed);
+        let old_key = entry.key.load(Ordering::Relaxed);
+        if old_key ^ old_data != hash && old_data as u32 > visits {
+            return;
+        }
+        let data = ((mean_action_value.to_bits() as u64) << 32) | visits as u64;
+        entry.data.store(data, Ordering::Relaxed);
+        entry.key.store(hash ^ data, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,223 @@
+//! Export the search tree into a plain data structure, to inspect why the search preferred or ignored a line.
+//! The exported tree can be written as JSON, or as Graphviz DOT for visualisation.
+
+use std::fmt::{self, Write};
+
+use board_game_traits::{Color, GameResult};
+
+use crate::position::Move;
+use crate::search::mcts_core::{GameResultForUs, Tree};
+use crate::search::Arena;
+
+/// A node in the exported search tree
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportedNode<const S: usize> {
+    /// The move leading to this node, or `None` for the root
+    pub mv: Option<Move<S>>,
+    pub side_to_move: Color,
+    pub visits: u32,
+    /// Mean action value for the side to move in this node
+    pub mean_action_value: f32,
+    /// The heuristic policy score of the move leading to this node. Always 1.0 for the root
+    pub policy: f32,
+    /// Set if the game is over in this node
+    pub game_result: Option<GameResult>,
+    /// Children with enough visits, sorted by visits
+    pub children: Vec<ExportedNode<S>>,
+    /// Number of children that were not exported, because of their visit count or the depth limit
+    pub omitted_children: usize,
+}
+
+impl<const S: usize> ExportedNode<S> {
+    /// Recursively export the subtree under `tree`.
+    /// Children with fewer than `min_visits` visits, or more than `max_depth` plies below the root, are omitted
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn from_tree(
+        tree: &Tree<S>,
+        arena: &Arena,
+        mv: Option<Move<S>>,
+        side_to_move: Color,
+        visits: u32,
+        mean_action_value: f32,
+        policy: f32,
+        max_depth: usize,
+        min_visits: u32,
+    ) -> Self {
+        let game_result = tree.game_result.map(|result| match (result, side_to_move) {
+            (GameResultForUs::Draw, _) => GameResult::Draw,
+            (GameResultForUs::Win, Color::White) | (GameResultForUs::Loss, Color::Black) => {
+                GameResult::WhiteWin
+            }
+            (GameResultForUs::Win, Color::Black) | (GameResultForUs::Loss, Color::White) => {
+                GameResult::BlackWin
+            }
+        });
+
+        let mut children = vec![];
+        let mut omitted_children = 0;
+
+        if let Some(bridge) = tree.children.as_ref().map(|index| arena.get(index)) {
+            for (((edge, mv), (child_visits, child_mean_action_value)), child_policy) in arena
+                .get_slice(&bridge.children)
+                .iter()
+                .zip(arena.get_slice(&bridge.moves))
+                .zip(
+                    arena
+                        .get_slice(&bridge.visitss)
+                        .iter()
+                        .zip(arena.get_slice(&bridge.mean_action_values)),
+                )
+                .zip(arena.get_slice(&bridge.heuristic_scores))
+            {
+                // Padding and excluded moves have no move
+                let Some(mv) = mv else {
+                    continue;
+                };
+                match edge.child.as_ref() {
+                    Some(child) if max_depth > 0 && *child_visits >= min_visits => {
+                        children.push(Self::from_tree(
+                            arena.get(child),
+                            arena,
+                            Some(*mv),
+                            !side_to_move,
+                            *child_visits,
+                            *child_mean_action_value,
+                            child_policy.to_f32(),
+                            max_depth - 1,
+                            min_visits,
+                        ))
+                    }
+                    _ => omitted_children += 1,
+                }
+            }
+        }
+        children.sort_by_key(|child| u32::MAX - child.visits);
+
+        ExportedNode {
+            mv,
+            side_to_move,
+            visits,
+            mean_action_value,
+            policy,
+            game_result,
+            children,
+            omitted_children,
+        }
+    }
+
+    /// Total number of exported nodes, including this one
+    pub fn num_nodes(&self) -> usize {
+        1 + self.children.iter().map(Self::num_nodes).sum::<usize>()
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut output = String::new();
+        self.write_json(&mut output).unwrap();
+        output
+    }
+
+    pub fn write_json<W: Write>(&self, w: &mut W) -> fmt::Result {
+        write!(w, "{{\"move\":")?;
+        match self.mv {
+            Some(mv) => write!(w, "\"{}\"", mv)?,
+            None => write!(w, "null")?,
+        }
+        write!(
+            w,
+            ",\"side_to_move\":\"{}\",\"visits\":{},\"mean_action_value\":{},\"policy\":{},\"game_result\":",
+            color_name(self.side_to_move),
+            self.visits,
+            json_float(self.mean_action_value),
+            json_float(self.policy),
+        )?;
+        match self.game_result {
+            Some(result) => write!(w, "\"{}\"", result_string(result))?,
+            None => write!(w, "null")?,
+        }
+        write!(
+            w,
+            ",\"omitted_children\":{},\"children\":[",
+            self.omitted_children
+        )?;
+        for (i, child) in self.children.iter().enumerate() {
+            if i > 0 {
+                write!(w, ",")?;
+            }
+            child.write_json(w)?;
+        }
+        write!(w, "]}}")
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut output = String::new();
+        self.write_dot(&mut output).unwrap();
+        output
+    }
+
+    /// Write the tree as a Graphviz digraph. Terminal nodes are drawn as boxes
+    pub fn write_dot<W: Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(w, "digraph tree {{")?;
+        writeln!(w, "  node [fontname=\"monospace\"];")?;
+        let mut next_id = 0;
+        self.write_dot_node(w, &mut next_id)?;
+        writeln!(w, "}}")
+    }
+
+    fn write_dot_node<W: Write>(&self, w: &mut W, next_id: &mut usize) -> fmt::Result {
+        let id = *next_id;
+        *next_id += 1;
+
+        let mut label = match self.mv {
+            Some(mv) => mv.to_string(),
+            None => "root".to_string(),
+        };
+        write!(
+            label,
+            "\\n{} visits\\n{:.1}% for {}\\n{:.1}% policy",
+            self.visits,
+            self.mean_action_value * 100.0,
+            color_name(self.side_to_move),
+            self.policy * 100.0
+        )?;
+        if self.omitted_children > 0 {
+            write!(label, "\\n{} omitted", self.omitted_children)?;
+        }
+        match self.game_result {
+            Some(result) => {
+                write!(label, "\\n{}", result_string(result))?;
+                writeln!(w, "  n{} [shape=box, label=\"{}\"];", id, label)?;
+            }
+            None => writeln!(w, "  n{} [label=\"{}\"];", id, label)?,
+        }
+
+        for child in self.children.iter() {
+            writeln!(w, "  n{} -> n{};", id, *next_id)?;
+            child.write_dot_node(w, next_id)?;
+        }
+        Ok(())
+    }
+}
+
+fn color_name(color: Color) -> &'static str {
+    match color {
+        Color::White => "white",
+        Color::Black => "black",
+    }
+}
+
+fn result_string(result: GameResult) -> &'static str {
+    match result {
+        GameResult::WhiteWin => "1-0",
+        GameResult::BlackWin => "0-1",
+        GameResult::Draw => "1/2-1/2",
+    }
+}
+
+/// JSON has no representation of NaN or infinity
+fn json_float(value: f32) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
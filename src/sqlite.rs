@@ -0,0 +1,341 @@
+//! Read human games from the playtak.com games database.
+//!
+//! The database is a single `games` table, where each row is a game:
+//! ```text
+//! id, date, size, player_white, player_black, notation, result, timertime, timerinc,
+//! rating_white, rating_black, unrated, tournament, komi, pieces, capstones, ...
+//! ```
+//! `date` is in milliseconds since the Unix epoch, and `komi` is in half flats.
+//! Moves are stored in playtak's server notation, separated by commas, like `P A1,P E5,M A1 A2 1`.
+//! Moves separated by whitespace in PTN notation are also accepted.
+//!
+//! Games are filtered with a [`GameQuery`], and returned as regular PTN games.
+
+use std::fmt::{self, Display};
+use std::path::Path;
+
+use board_game_traits::{GameResult, Position as PositionTrait};
+use chrono::{DateTime, Utc};
+use pgn_traits::PgnPosition;
+use rusqlite::types::Value;
+use rusqlite::{Connection, OpenFlags};
+
+use crate::position::{starting_capstones, starting_stones, Komi, Move, Position};
+use crate::ptn::{Game, PtnMove};
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    Sqlite(rusqlite::Error),
+}
+
+impl Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::Sqlite(err) => write!(f, "SQLite error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+impl From<rusqlite::Error> for ArchiveError {
+    fn from(err: rusqlite::Error) -> Self {
+        ArchiveError::Sqlite(err)
+    }
+}
+
+/// Filters for reading games from the database. The board size is given when reading the games.
+/// Games with a non-standard number of pieces, and games that were aborted, are never read
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GameQuery {
+    komi: Option<Komi>,
+    min_rating: Option<i64>,
+    max_rating: Option<i64>,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+    player: Option<String>,
+    result: Option<GameResult>,
+    rated_only: bool,
+    min_plies: usize,
+    limit: Option<usize>,
+}
+
+impl GameQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn komi(mut self, komi: Komi) -> Self {
+        self.komi = Some(komi);
+        self
+    }
+
+    /// Only read games where both players are rated at least `min_rating`
+    pub fn min_rating(mut self, min_rating: i64) -> Self {
+        self.min_rating = Some(min_rating);
+        self
+    }
+
+    /// Only read games where both players are rated at most `max_rating`
+    pub fn max_rating(mut self, max_rating: i64) -> Self {
+        self.max_rating = Some(max_rating);
+        self
+    }
+
+    /// Only read games played at or after `after`
+    pub fn after(mut self, after: DateTime<Utc>) -> Self {
+        self.after = Some(after);
+        self
+    }
+
+    /// Only read games played before `before`
+    pub fn before(mut self, before: DateTime<Utc>) -> Self {
+        self.before = Some(before);
+        self
+    }
+
+    /// Only read games where `player` played either color
+    pub fn player(mut self, player: &str) -> Self {
+        self.player = Some(player.to_string());
+        self
+    }
+
+    pub fn result(mut self, result: GameResult) -> Self {
+        self.result = Some(result);
+        self
+    }
+
+    pub fn rated_only(mut self) -> Self {
+        self.rated_only = true;
+        self
+    }
+
+    /// Only read games with at least `min_plies` moves
+    pub fn min_plies(mut self, min_plies: usize) -> Self {
+        self.min_plies = min_plies;
+        self
+    }
+
+    /// Read at most `limit` games, newest first
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Build the SQL query for size `size`, along with its parameters
+    fn to_sql(&self, size: usize) -> (String, Vec<Value>) {
+        let mut sql = "SELECT id, date, player_white, player_black, notation, result, komi, rating_white, rating_black \
+            FROM games WHERE size = ? \
+            AND (pieces = -1 OR pieces = ?) AND (capstones = -1 OR capstones = ?)"
+            .to_string();
+        let mut params = vec![
+            Value::Integer(size as i64),
+            Value::Integer(starting_stones(size) as i64),
+            Value::Integer(starting_capstones(size) as i64),
+        ];
+        let mut add_filter = |filter: &str, new_params: &[Value]| {
+            sql.push_str(" AND ");
+            sql.push_str(filter);
+            params.extend_from_slice(new_params);
+        };
+
+        if let Some(komi) = self.komi {
+            add_filter("komi = ?", &[Value::Integer(komi.half_komi() as i64)]);
+        }
+        if let Some(min_rating) = self.min_rating {
+            add_filter(
+                "rating_white >= ? AND rating_black >= ?",
+                &[Value::Integer(min_rating), Value::Integer(min_rating)],
+            );
+        }
+        if let Some(max_rating) = self.max_rating {
+            add_filter(
+                "rating_white <= ? AND rating_black <= ?",
+                &[Value::Integer(max_rating), Value::Integer(max_rating)],
+            );
+        }
+        if let Some(after) = self.after {
+            add_filter("date >= ?", &[Value::Integer(after.timestamp_millis())]);
+        }
+        if let Some(before) = self.before {
+            add_filter("date < ?", &[Value::Integer(before.timestamp_millis())]);
+        }
+        if let Some(player) = &self.player {
+            add_filter(
+                "(player_white = ? OR player_black = ?)",
+                &[Value::Text(player.clone()), Value::Text(player.clone())],
+            );
+        }
+        match self.result {
+            Some(GameResult::WhiteWin) => add_filter("result IN ('R-0', 'F-0', '1-0')", &[]),
+            Some(GameResult::BlackWin) => add_filter("result IN ('0-R', '0-F', '0-1')", &[]),
+            Some(GameResult::Draw) => add_filter("result = '1/2-1/2'", &[]),
+            None => add_filter(
+                "result IN ('R-0', 'F-0', '1-0', '0-R', '0-F', '0-1', '1/2-1/2')",
+                &[],
+            ),
+        }
+        if self.rated_only {
+            add_filter("unrated = 0", &[]);
+        }
+
+        sql.push_str(" ORDER BY date DESC");
+        (sql, params)
+    }
+
+    /// Read all matching games of size `S` from the database at `path`.
+    /// Games with illegal moves are skipped, since a few old games in the database were recorded incorrectly
+    pub fn read_games<const S: usize, P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<Vec<Game<Position<S>>>, ArchiveError> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        self.read_games_from_connection(&conn)
+    }
+
+    pub fn read_games_from_connection<const S: usize>(
+        &self,
+        conn: &Connection,
+    ) -> Result<Vec<Game<Position<S>>>, ArchiveError> {
+        let (sql, params) = self.to_sql(S);
+        let mut stmt = conn.prepare(&sql)?;
+        let mut rows = stmt.query(rusqlite::params_from_iter(params))?;
+
+        let mut games = vec![];
+        while let Some(row) = rows.next()? {
+            let row = ArchiveRow {
+                id: row.get(0)?,
+                date: row.get(1)?,
+                player_white: row.get(2)?,
+                player_black: row.get(3)?,
+                notation: row.get(4)?,
+                result: row.get(5)?,
+                half_komi: row.get(6)?,
+                rating_white: row.get(7)?,
+                rating_black: row.get(8)?,
+            };
+            if let Some(game) = row.to_game() {
+                if game.moves.len() >= self.min_plies {
+                    games.push(game);
+                }
+            }
+            if self.limit.is_some_and(|limit| games.len() >= limit) {
+                break;
+            }
+        }
+        Ok(games)
+    }
+}
+
+struct ArchiveRow {
+    id: i64,
+    date: i64,
+    player_white: String,
+    player_black: String,
+    notation: String,
+    result: String,
+    half_komi: i64,
+    // Ratings are missing for some old games
+    rating_white: Option<i64>,
+    rating_black: Option<i64>,
+}
+
+impl ArchiveRow {
+    /// Convert the row to a PTN game, or `None` if it contains illegal moves
+    fn to_game<const S: usize>(&self) -> Option<Game<Position<S>>> {
+        let komi = Komi::from_half_komi(self.half_komi.try_into().ok()?)?;
+        let start_position = <Position<S>>::start_position_with_komi(komi);
+        let mut position = start_position.clone();
+        let mut moves = vec![];
+
+        for mv in parse_notation::<S>(&self.notation)? {
+            if position.game_result().is_some() || !position.move_is_legal(mv) {
+                return None;
+            }
+            position.do_move(mv);
+            moves.push(PtnMove::new(mv));
+        }
+
+        let game_result_str = <Position<S>>::POSSIBLE_GAME_RESULTS
+            .iter()
+            .find(|(result_str, _)| *result_str == self.result)
+            .map(|(result_str, _)| *result_str);
+        let date = DateTime::from_timestamp_millis(self.date)?;
+
+        let mut tags = vec![
+            ("Site".to_string(), "PlayTak.com".to_string()),
+            ("Event".to_string(), format!("Game #{}", self.id)),
+            ("Player1".to_string(), self.player_white.clone()),
+            ("Player2".to_string(), self.player_black.clone()),
+            ("Size".to_string(), S.to_string()),
+            ("Date".to_string(), date.format("%Y.%m.%d").to_string()),
+            ("Komi".to_string(), komi.to_string()),
+        ];
+        if let Some(rating) = self.rating_white {
+            tags.push(("Rating1".to_string(), rating.to_string()));
+        }
+        if let Some(rating) = self.rating_black {
+            tags.push(("Rating2".to_string(), rating.to_string()));
+        }
+
+        Some(Game {
+            start_position,
+            moves,
+            game_result_str,
+            tags,
+            leading_comments: vec![],
+        })
+    }
+}
+
+/// Parse moves in playtak's server notation, separated by commas, or PTN moves separated by whitespace
+fn parse_notation<const S: usize>(notation: &str) -> Option<Vec<Move<S>>> {
+    if notation.contains(',') || notation.starts_with("P ") || notation.starts_with("M ") {
+        notation
+            .split(',')
+            .map(str::trim)
+            .filter(|move_string| !move_string.is_empty())
+            .map(parse_playtak_move)
+            .collect()
+    } else {
+        notation
+            .split_whitespace()
+            .map(|move_string| Move::from_string(move_string).ok())
+            .collect()
+    }
+}
+
+/// Parse a move in playtak's server notation, without panicking on malformed moves
+fn parse_playtak_move<const S: usize>(move_string: &str) -> Option<Move<S>> {
+    let words: Vec<&str> = move_string.split_whitespace().collect();
+    // The file and rank of a square, like `A1`
+    let square = |word: &str| match word.as_bytes() {
+        [file @ b'A'..=b'H', rank @ b'1'..=b'8']
+            if ((file - b'A') as usize) < S && ((rank - b'1') as usize) < S =>
+        {
+            Some((*file as i32, *rank as i32))
+        }
+        _ => None,
+    };
+    let is_valid = match words.as_slice() {
+        ["P", target] | ["P", target, "C" | "W"] => square(target).is_some(),
+        ["M", start, end, drops @ ..] => match (square(start), square(end)) {
+            (Some((start_file, start_rank)), Some((end_file, end_rank))) => {
+                let drops: Option<Vec<u8>> = drops
+                    .iter()
+                    .map(|drop| drop.parse::<u8>().ok().filter(|drop| *drop > 0))
+                    .collect();
+                // Moves go in a straight line, and drop pieces on every square they pass
+                (start_file == end_file) != (start_rank == end_rank)
+                    && drops.is_some_and(|drops| {
+                        drops.len() as i32
+                            == (start_file - end_file).abs() + (start_rank - end_rank).abs()
+                            && drops.iter().map(|drop| *drop as usize).sum::<usize>() <= S
+                    })
+            }
+            _ => false,
+        },
+        _ => false,
+    };
+    is_valid.then(|| Move::from_string_playtak(move_string))
+}
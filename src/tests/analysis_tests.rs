@@ -0,0 +1,176 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use board_game_traits::Position as PositionTrait;
+use pgn_traits::PgnPosition;
+
+use crate::aws::analysis::analyze;
+use crate::aws::Event;
+use crate::position::{Komi, Position};
+use crate::search::TimeControl;
+
+fn event(size: usize, moves: &[&str], time_control: TimeControl) -> Event {
+    Event {
+        size,
+        tps: None,
+        moves: moves.iter().map(|mv| mv.to_string()).collect(),
+        time_control,
+        komi: 2.0,
+        eval_komi: None,
+        dirichlet_noise: None,
+        rollout_depth: 0,
+        rollout_temperature: 0.25,
+        excluded_moves: vec![],
+        multi_pv: None,
+        static_eval_variance: None,
+        value_params: None,
+        policy_params: None,
+    }
+}
+
+#[test]
+fn analyze_fixed_nodes_test() {
+    let infos = Cell::new(0);
+    let output = analyze(
+        event(5, &["a1", "e5"], TimeControl::FixedNodes(5000)),
+        64 * 1024 * 1024,
+        &AtomicBool::new(false),
+        |info| {
+            assert!(info.nodes < 5000);
+            infos.set(infos.get() + 1);
+        },
+    )
+    .unwrap();
+    assert_eq!(output.nodes, 5000);
+    assert!(!output.pv.is_empty());
+    assert!(infos.get() > 0);
+
+    // Every legal move is listed, most visited first, with a policy prior
+    let mut legal_moves = vec![];
+    let mut position = <Position<5>>::start_position_with_komi(Komi::from_half_komi(4).unwrap());
+    for mv in ["a1", "e5"] {
+        position.do_move(position.move_from_san(mv).unwrap());
+    }
+    position.generate_moves(&mut legal_moves);
+    assert_eq!(output.moves.len(), legal_moves.len());
+    assert_eq!(output.moves[0].mv, output.pv[0]);
+    assert!(output
+        .moves
+        .windows(2)
+        .all(|moves| moves[0].visits >= moves[1].visits));
+    assert!(output.moves.iter().all(|mv| mv.policy > 0.0));
+    assert_eq!(output.lines.len(), 1);
+    assert_eq!(output.lines[0].pv, output.pv);
+}
+
+#[test]
+fn analyze_multi_pv_and_excluded_moves_test() {
+    let mut multi_pv_event = event(5, &["a1", "e5"], TimeControl::FixedNodes(5000));
+    multi_pv_event.multi_pv = Some(3);
+    let output = analyze(
+        multi_pv_event,
+        64 * 1024 * 1024,
+        &AtomicBool::new(false),
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!(output.lines.len(), 3);
+    let best_move = output.pv[0].clone();
+
+    let mut excluded_event = event(5, &["a1", "e5"], TimeControl::FixedNodes(5000));
+    excluded_event.excluded_moves = vec![best_move.clone()];
+    let output = analyze(
+        excluded_event,
+        64 * 1024 * 1024,
+        &AtomicBool::new(false),
+        |_| {},
+    )
+    .unwrap();
+    assert_ne!(output.pv[0], best_move);
+    assert!(output
+        .moves
+        .iter()
+        .all(|mv| mv.mv != best_move || mv.visits == 0));
+}
+
+#[test]
+fn analyze_with_custom_params_test() {
+    let komi = Komi::from_half_komi(4).unwrap();
+    let mut custom_event = event(4, &[], TimeControl::FixedNodes(1000));
+    custom_event.value_params = Some(<Position<4>>::value_params(komi).to_vec());
+    custom_event.policy_params = Some(<Position<4>>::policy_params(komi).to_vec());
+    let output = analyze(
+        custom_event.clone(),
+        16 * 1024 * 1024,
+        &AtomicBool::new(false),
+        |_| {},
+    )
+    .unwrap();
+    let built_in_output = analyze(
+        event(4, &[], TimeControl::FixedNodes(1000)),
+        16 * 1024 * 1024,
+        &AtomicBool::new(false),
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!(output.pv, built_in_output.pv);
+
+    // Parameters for the wrong board size are rejected
+    custom_event.value_params = Some(vec![0.0; 10]);
+    assert!(analyze(
+        custom_event,
+        16 * 1024 * 1024,
+        &AtomicBool::new(false),
+        |_| {}
+    )
+    .is_err());
+}
+
+#[test]
+fn analyze_stops_early_test() {
+    let stop = AtomicBool::new(false);
+    let output = analyze(
+        event(5, &[], TimeControl::FixedNodes(1_000_000)),
+        256 * 1024 * 1024,
+        &stop,
+        |_| stop.store(true, Ordering::Relaxed),
+    )
+    .unwrap();
+    assert!(output.nodes < 1_000_000);
+    assert!(!output.pv.is_empty());
+}
+
+#[test]
+fn analyze_invalid_events_test() {
+    let nodes = TimeControl::FixedNodes(100);
+    let stop = AtomicBool::new(false);
+    assert!(analyze(event(9, &[], nodes.clone()), 1024 * 1024, &stop, |_| {}).is_err());
+    assert!(analyze(
+        event(5, &["a1", "a1"], nodes.clone()),
+        1024 * 1024,
+        &stop,
+        |_| {}
+    )
+    .is_err());
+    assert!(analyze(event(5, &["a6"], nodes.clone()), 1024 * 1024, &stop, |_| {}).is_err());
+
+    let mut excluded_event = event(5, &["a1"], nodes);
+    excluded_event.excluded_moves = vec!["a1".to_string()];
+    assert!(analyze(excluded_event, 1024 * 1024, &stop, |_| {}).is_err());
+}
+
+#[test]
+fn analyze_without_memory_for_tree_test() {
+    // The search tree cannot be allocated, which fails the analysis instead of exiting the process
+    let stop = AtomicBool::new(false);
+    let result = analyze(
+        event(5, &[], TimeControl::FixedNodes(100)),
+        0,
+        &stop,
+        |_| {},
+    );
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .starts_with("Failed to allocate the search tree"));
+}
@@ -0,0 +1,65 @@
+use crate::annotate::{Annotator, Judgement, SearchBudget};
+use crate::position::{Move, Position};
+use crate::ptn::{ptn_parser, Game};
+
+// White has a road win with e1 on move 5, but plays a2 instead
+const MISSED_WIN: &str =
+    "[Size \"5\"]\n\n1. a5 a1 2. b1 e4 3. c1 e3 4. d1 e2 5. a2 {Missed it} e1 *";
+
+#[test]
+fn missed_road_win_is_blunder_test() {
+    let games: Vec<Game<Position<5>>> = ptn_parser::parse_ptn(MISSED_WIN).unwrap();
+    let annotator = Annotator::<5>::new(SearchBudget::Nodes(10_000));
+    let mut num_analyzed = 0;
+    let analysis = annotator.analyze_game(&games[0], |_| num_analyzed += 1);
+    assert_eq!(analysis.len(), 10);
+    assert_eq!(num_analyzed, 10);
+
+    let missed_win = &analysis[8];
+    assert_eq!(missed_win.best_move, <Move<5>>::from_string("e1").unwrap());
+    assert_eq!(missed_win.best_score, 1.0);
+    assert!(missed_win.score_loss() >= 0.2);
+    assert_eq!(missed_win.judgement, Some(Judgement::Blunder));
+
+    // Black's block is the only move that doesn't lose immediately
+    assert_eq!(analysis[9].judgement, Some(Judgement::Good));
+}
+
+#[test]
+fn annotate_game_test() {
+    let games: Vec<Game<Position<5>>> = ptn_parser::parse_ptn(MISSED_WIN).unwrap();
+    let annotator = Annotator::<5>::new(SearchBudget::Nodes(10_000)).max_line_length(3);
+    let analysis = annotator.analyze_game(&games[0], |_| ());
+    let annotated_game = annotator.annotate_game(&games[0], &analysis);
+
+    let missed_win = &annotated_game.moves[8];
+    assert_eq!(missed_win.annotations, vec!["??"]);
+    assert_eq!(missed_win.comments[0], "Missed it");
+    assert!(missed_win.comments[1].starts_with("Blunder. "));
+    assert!(missed_win.comments[1].contains("best e1"));
+    assert_eq!(missed_win.variations.len(), 1);
+    assert_eq!(missed_win.variations[0][0].mv, analysis[8].best_move);
+    assert!(annotated_game
+        .moves
+        .iter()
+        .all(|ptn_move| !ptn_move.comments.is_empty()));
+
+    // The annotated game can be written and read back
+    let mut ptn = vec![];
+    annotated_game.game_to_ptn(&mut ptn).unwrap();
+    let reparsed_games: Vec<Game<Position<5>>> =
+        ptn_parser::parse_ptn(&String::from_utf8(ptn).unwrap()).unwrap();
+    assert_eq!(reparsed_games[0].moves, annotated_game.moves);
+}
+
+#[test]
+fn opening_move_scored_like_best_move_test() {
+    let games: Vec<Game<Position<5>>> = ptn_parser::parse_ptn(MISSED_WIN).unwrap();
+    let annotator = Annotator::<5>::new(SearchBudget::Nodes(10_000));
+    let analysis = annotator.analyze_game(&games[0], |_| ());
+
+    // Every first move in a corner is equally good, and searched well enough to be scored from the same search
+    let first_move = &analysis[0];
+    assert!(first_move.score_loss() < 0.05, "{:?}", first_move);
+    assert_eq!(first_move.judgement, None);
+}
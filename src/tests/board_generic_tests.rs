@@ -27,6 +27,16 @@ fn play_random_6s_games_test() {
     play_random_games_prop::<6>(200)
 }
 
+#[test]
+fn play_random_7s_games_test() {
+    play_random_games_prop::<7>(200)
+}
+
+#[test]
+fn play_random_8s_games_test() {
+    play_random_games_prop::<8>(200)
+}
+
 #[test]
 fn play_random_3s_games_no_eval_test() {
     play_random_games_no_eval_prop::<3>(1000)
@@ -75,6 +85,18 @@ fn play_random_6s_games_test_long() {
     play_random_games_prop::<6>(10_000)
 }
 
+#[test]
+#[ignore]
+fn play_random_7s_games_test_long() {
+    play_random_games_prop::<7>(10_000)
+}
+
+#[test]
+#[ignore]
+fn play_random_8s_games_test_long() {
+    play_random_games_prop::<8>(10_000)
+}
+
 fn play_random_games_prop<const S: usize>(num_games: usize) {
     let mut white_wins = 0;
     let mut black_wins = 0;
@@ -130,8 +152,14 @@ fn play_random_games_prop<const S: usize>(num_games: usize) {
             let parameters = <Position<S>>::policy_params(position.komi());
 
             let mut policies: Vec<IncrementalPolicy<S>> =
-                vec![IncrementalPolicy::new(parameters); moves.len()];
-            position.features_for_moves(&mut policies, &moves, &mut vec![], &group_data);
+                vec![IncrementalPolicy::new(); moves.len()];
+            position.features_for_moves(
+                parameters,
+                &mut policies,
+                &moves,
+                &mut vec![],
+                &group_data,
+            );
 
             // If the decline_win value is set, check that there really is a winning move
             if policies.iter().any(|policy| policy.has_immediate_win()) {
@@ -466,7 +494,7 @@ fn bitboard_full_board_file_rank_prop<const S: usize>() {
         &mut position,
         &(move_strings.iter().map(AsRef::as_ref).collect::<Vec<_>>()),
     );
-    if S % 2 == 0 {
+    if S.is_multiple_of(2) {
         assert_eq!(position.game_result(), Some(BlackWin));
     } else {
         assert_eq!(position.game_result(), Some(WhiteWin));
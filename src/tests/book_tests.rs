@@ -0,0 +1,183 @@
+use board_game_traits::Position as PositionTrait;
+use pgn_traits::PgnPosition;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::book::{BookError, BookMove, OpeningBook};
+use crate::position::{Komi, Position};
+use crate::ptn::ptn_parser;
+
+const GAMES: &str = r#"
+[Size "5"]
+[Result "R-0"]
+
+1. a1 e5 2. c3 d3 3. c4 d4 R-0
+
+[Size "5"]
+[Result "0-R"]
+
+1. a1 e5 2. c3 c2 0-R
+
+[Size "5"]
+[Result "1/2-1/2"]
+
+1. e1 a5 2. c3 d3 1/2-1/2
+"#;
+
+fn book_from_games() -> OpeningBook {
+    let games = ptn_parser::parse_ptn::<Position<5>>(GAMES).unwrap();
+    let mut book = OpeningBook::new(5, Komi::default());
+    for game in games.iter() {
+        book.add_game(game, 3);
+    }
+    book
+}
+
+#[test]
+fn add_games_test() {
+    let book = book_from_games();
+    let start_position = <Position<5>>::start_position();
+    assert_eq!(
+        book.moves(&start_position),
+        &[
+            BookMove {
+                mv: "a1".to_string(),
+                weight: 2,
+                score: Some(0.5)
+            },
+            BookMove {
+                mv: "e1".to_string(),
+                weight: 1,
+                score: Some(0.5)
+            }
+        ]
+    );
+
+    let mut position = start_position.clone();
+    for mv in ["a1", "e5"] {
+        position.do_move(position.move_from_san(mv).unwrap());
+    }
+    assert_eq!(
+        book.moves(&position),
+        &[BookMove {
+            mv: "c3".to_string(),
+            weight: 2,
+            score: Some(0.5)
+        }]
+    );
+    // Only the first 3 plies are added
+    position.do_move(position.move_from_san("c3").unwrap());
+    assert!(book.moves(&position).is_empty());
+
+    // The book is not used for other komis
+    let komi_position = <Position<5>>::start_position_with_komi(Komi::from_half_komi(4).unwrap());
+    assert!(book.moves(&komi_position).is_empty());
+}
+
+#[test]
+fn analysis_score_test() {
+    let mut book = book_from_games();
+    // Black has a 70% chance to win after 1. a1 e5 2. c3, so c3 scores 30% for white
+    book.add_analysis_line::<5>("a1 e5 c3: 0.7000, 1.5s, d3 c4 d4 ")
+        .unwrap();
+    book.add_analysis_line::<5>("a1 e5 d3: 0.4000, 1.5s, c3 c4 d4 ")
+        .unwrap();
+
+    let mut position = <Position<5>>::start_position();
+    for mv in ["a1", "e5"] {
+        position.do_move(position.move_from_san(mv).unwrap());
+    }
+    let moves = book.moves(&position);
+    assert_eq!(moves.len(), 2);
+    assert_eq!((moves[0].weight, moves[0].score), (2, Some(0.3)));
+    assert_eq!((moves[1].weight, moves[1].score), (1, Some(0.6)));
+
+    // c3 scores much worse than d3, so it is never chosen
+    let mut rng = StdRng::seed_from_u64(0);
+    let d3 = position.move_from_san("d3").unwrap();
+    for _ in 0..20 {
+        assert_eq!(book.choose_move(&position, &mut rng), Some(d3));
+    }
+
+    assert!(matches!(
+        book.add_analysis_line::<5>("a1 e5 a1: 0.5, 1.5s"),
+        Err(BookError::Parse(_))
+    ));
+    assert!(matches!(
+        book.add_analysis_line::<6>("a1 f6: 0.5, 1.5s"),
+        Err(BookError::Parse(_))
+    ));
+}
+
+#[test]
+fn choose_move_test() {
+    let book = book_from_games();
+    let mut rng = StdRng::seed_from_u64(0);
+    let start_position = <Position<5>>::start_position();
+    let a1 = start_position.move_from_san("a1").unwrap();
+    let e1 = start_position.move_from_san("e1").unwrap();
+
+    let chosen: Vec<_> = (0..300)
+        .map(|_| book.choose_move(&start_position, &mut rng).unwrap())
+        .collect();
+    assert!(chosen.iter().all(|mv| *mv == a1 || *mv == e1));
+    let num_a1 = chosen.iter().filter(|mv| **mv == a1).count();
+    assert!((150..250).contains(&num_a1), "a1 chosen {} times", num_a1);
+
+    let mut position = start_position;
+    position.do_move(e1);
+    assert_eq!(
+        book.choose_move(&position, &mut rng),
+        Some(position.move_from_san("a5").unwrap())
+    );
+    position.do_move(position.move_from_san("a5").unwrap());
+    position.do_move(position.move_from_san("c3").unwrap());
+    assert_eq!(book.choose_move(&position, &mut rng), None);
+}
+
+#[test]
+fn book_round_trip_test() {
+    let mut book = book_from_games();
+    book.add_analysis_line::<5>("a1 e5 d3: 0.4, 1.5s, c3")
+        .unwrap();
+    book.sort_moves();
+
+    let mut buffer = vec![];
+    book.write(&mut buffer).unwrap();
+    assert_eq!(OpeningBook::read(buffer.as_slice()).unwrap(), book);
+
+    let text = String::from_utf8(buffer).unwrap();
+    assert!(text.starts_with("tiltak-book 1\nsize 5\nkomi 0\n"));
+    assert!(matches!(
+        OpeningBook::read(
+            text.replacen("tiltak-book 1", "tiltak-book 2", 1)
+                .as_bytes()
+        ),
+        Err(BookError::UnsupportedVersion(2))
+    ));
+    assert!(matches!(
+        OpeningBook::read(format!("{}x5/x5/x5/x5/x5 1 1;a1;1\n", text).as_bytes()),
+        Err(BookError::Parse(_))
+    ));
+
+    book.prune(2);
+    let start_position = <Position<5>>::start_position();
+    assert_eq!(book.moves(&start_position).len(), 1);
+    assert_eq!(book.len(), 3);
+}
+
+#[test]
+fn book_lines_test() {
+    let book = book_from_games();
+    let line_strings = |max_plies| {
+        book.lines::<5>(max_plies)
+            .iter()
+            .map(|line| line.iter().map(|mv| mv.to_string()).collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        line_strings(3),
+        vec![vec!["a1", "e5", "c3"], vec!["e1", "a5", "c3"]]
+    );
+    assert_eq!(line_strings(1), vec![vec!["a1"], vec!["e1"]]);
+}
@@ -0,0 +1,253 @@
+use std::collections::HashSet;
+
+use board_game_traits::{Color, GameResult, Position as PositionTrait};
+
+use crate::position::{Komi, Move, Position};
+use crate::ptn::ptn_parser;
+use crate::search::strength::StrengthLevel;
+use crate::search::{MctsSetting, TimeControl};
+use crate::tune::calibration::{self, fit_ratings, Pairing};
+use crate::tune::engine_match::{
+    elo_to_score, random_openings, run_match, score_to_elo, MatchPlayer, MatchScore, Sprt,
+    SprtResult, RANDOM_OPENING_PLIES,
+};
+
+#[test]
+fn elo_conversion_test() {
+    assert_eq!(score_to_elo(0.5), 0.0);
+    assert!((score_to_elo(0.75) - 190.8).abs() < 0.1);
+    assert!((score_to_elo(0.25) + 190.8).abs() < 0.1);
+    for elo in [-300.0, -20.0, 0.0, 5.0, 150.0] {
+        assert!((score_to_elo(elo_to_score(elo)) - elo).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn match_score_test() {
+    let mut score = MatchScore::default();
+    score.add_game(Some(GameResult::WhiteWin), Color::White);
+    score.add_game(Some(GameResult::WhiteWin), Color::Black);
+    score.add_game(Some(GameResult::BlackWin), Color::Black);
+    score.add_game(Some(GameResult::Draw), Color::White);
+    score.add_game(None, Color::Black);
+    assert_eq!(
+        score,
+        MatchScore {
+            wins: 2,
+            draws: 2,
+            losses: 1
+        }
+    );
+    assert_eq!(score.games(), 5);
+    assert!((score.score() - 0.6).abs() < 1e-9);
+    assert!(score.elo() > 0.0);
+    assert!(score.elo_error() > 0.0);
+
+    // More games with the same score give a smaller error bar
+    let large_score = MatchScore {
+        wins: 200,
+        draws: 200,
+        losses: 100,
+    };
+    assert!((large_score.elo() - score.elo()).abs() < 1e-9);
+    assert!(large_score.elo_error() < score.elo_error());
+}
+
+#[test]
+fn sprt_test() {
+    let sprt = Sprt::new(0.0, 10.0);
+    let (lower, upper) = sprt.bounds();
+    assert!((lower + 2.944).abs() < 0.001);
+    assert!((upper - 2.944).abs() < 0.001);
+
+    let even = MatchScore {
+        wins: 10,
+        draws: 0,
+        losses: 10,
+    };
+    assert_eq!(sprt.result(&even), SprtResult::Continue);
+
+    let winning = MatchScore {
+        wins: 700,
+        draws: 100,
+        losses: 500,
+    };
+    assert!(sprt.llr(&winning) > upper);
+    assert_eq!(sprt.result(&winning), SprtResult::AcceptH1);
+
+    let losing = MatchScore {
+        wins: 500,
+        draws: 100,
+        losses: 700,
+    };
+    assert!(sprt.llr(&losing) < lower);
+    assert_eq!(sprt.result(&losing), SprtResult::AcceptH0);
+}
+
+#[test]
+fn play_short_match_test() {
+    let player = |name: &str| MatchPlayer::<4> {
+        name: name.to_string(),
+        settings: MctsSetting::default().arena_size_for_nodes(100),
+        time_control: TimeControl::FixedNodes(100),
+    };
+    let openings: Vec<Vec<Move<4>>> = vec![vec![Move::from_string("a1").unwrap()]];
+    let mut output = vec![];
+
+    let score = run_match(
+        &player("first"),
+        &player("second"),
+        Komi::default(),
+        &openings,
+        4,
+        None,
+        &mut output,
+    )
+    .unwrap();
+    assert_eq!(score.games(), 4);
+
+    let games = ptn_parser::parse_ptn::<Position<4>>(&String::from_utf8(output).unwrap()).unwrap();
+    assert_eq!(games.len(), 4);
+    for game in games {
+        assert_eq!(game.moves[0].mv, openings[0][0]);
+        assert!(game
+            .tags
+            .iter()
+            .any(|(tag, value)| tag == "Player1" && (value == "first" || value == "second")));
+    }
+}
+
+#[test]
+fn fit_ratings_test() {
+    let score = |wins, draws, losses| MatchScore {
+        wins,
+        draws,
+        losses,
+    };
+    // Player 1 scores 75% against player 0, and player 2 scores 75% against player 1
+    let pairings = [
+        Pairing {
+            player1: 0,
+            player2: 1,
+            score: score(249, 2, 749),
+        },
+        Pairing {
+            player1: 2,
+            player2: 1,
+            score: score(749, 2, 249),
+        },
+    ];
+    let ratings = fit_ratings(4, &pairings);
+    assert_eq!(ratings[0], 0.0);
+    assert!((ratings[1] - 190.8).abs() < 1.0, "{:?}", ratings);
+    assert!((ratings[2] - 381.6).abs() < 2.0, "{:?}", ratings);
+    assert_eq!(ratings[3], 0.0);
+
+    // Winning every game still gives a finite rating
+    let ratings = fit_ratings(
+        2,
+        &[Pairing {
+            player1: 0,
+            player2: 1,
+            score: score(0, 0, 20),
+        }],
+    );
+    assert!(ratings[1] > 400.0 && ratings[1] < 1000.0, "{:?}", ratings);
+}
+
+#[test]
+fn match_without_openings_test() {
+    let player = MatchPlayer::<4> {
+        name: "player".to_string(),
+        settings: MctsSetting::default().arena_size_for_nodes(100),
+        time_control: TimeControl::FixedNodes(100),
+    };
+    let mut output = vec![];
+    run_match(&player, &player, Komi::default(), &[], 4, None, &mut output).unwrap();
+
+    // Each pair of games starts from a different random opening
+    let games = ptn_parser::parse_ptn::<Position<4>>(&String::from_utf8(output).unwrap()).unwrap();
+    let openings: HashSet<Vec<String>> = games
+        .iter()
+        .map(|game| {
+            game.moves[..RANDOM_OPENING_PLIES]
+                .iter()
+                .map(|ptn_move| ptn_move.mv.to_string())
+                .collect()
+        })
+        .collect();
+    assert_eq!(openings.len(), 2);
+}
+
+#[test]
+fn random_openings_test() {
+    let openings = random_openings::<4, _>(
+        50,
+        RANDOM_OPENING_PLIES,
+        Komi::default(),
+        &mut rand::thread_rng(),
+    );
+    assert_eq!(openings.len(), 50);
+
+    let mut seen_positions = HashSet::new();
+    for opening in openings {
+        assert_eq!(opening.len(), RANDOM_OPENING_PLIES);
+        let mut position = <Position<4>>::start_position();
+        for mv in opening {
+            position.do_move(mv);
+        }
+        assert!(position
+            .symmetries()
+            .iter()
+            .all(|symmetry| !seen_positions.contains(symmetry)));
+        seen_positions.insert(position);
+    }
+}
+
+#[test]
+fn two_ply_openings_test() {
+    let openings = calibration::two_ply_openings::<4>();
+    assert!(openings.len() * 8 >= 16 * 15 && openings.len() < 16 * 15);
+
+    let mut seen_positions = HashSet::new();
+    for opening in openings {
+        let mut position = <Position<4>>::start_position();
+        for mv in opening {
+            position.do_move(mv);
+        }
+        assert!(position
+            .symmetries()
+            .iter()
+            .all(|symmetry| !seen_positions.contains(symmetry)));
+        seen_positions.insert(position);
+    }
+}
+
+#[test]
+fn calibrate_levels_test() {
+    let levels = [
+        StrengthLevel::from_level(1).unwrap(),
+        StrengthLevel::from_level(2).unwrap(),
+        StrengthLevel::from_level(3).unwrap(),
+    ];
+    let mut output = vec![];
+    let ratings = calibration::calibrate_levels::<4, _>(
+        &levels,
+        100,
+        Komi::default(),
+        &calibration::two_ply_openings(),
+        2,
+        1,
+        &mut output,
+    )
+    .unwrap();
+    assert_eq!(ratings.len(), 3);
+    assert_eq!(ratings[0], 0.0);
+
+    // Only adjacent levels play each other, so level 2 plays every game
+    let games = ptn_parser::parse_ptn::<Position<4>>(&String::from_utf8(output).unwrap()).unwrap();
+    assert_eq!(games.len(), 4);
+    assert!(games
+        .iter()
+        .all(|game| game.tags.iter().any(|(_, value)| value == "level2")));
+}
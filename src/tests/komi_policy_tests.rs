@@ -1,6 +1,9 @@
 use std::convert::TryFrom;
 
+use board_game_traits::Position as PositionTrait;
+
 use crate::position::{ExpMove, Komi, Position, Role};
+use crate::search;
 use crate::tests::moves_sorted_by_policy;
 
 #[test]
@@ -81,3 +84,50 @@ fn place_to_allow_komi_loss() {
         top_score.to_f32() * 100.0
     );
 }
+
+#[test]
+fn interpolated_komi_params_test() {
+    let komi_0 = <Position<5>>::value_params(Komi::from_half_komi(0).unwrap());
+    let komi_1 = <Position<5>>::value_params(Komi::from_half_komi(2).unwrap());
+    let komi_2 = <Position<5>>::value_params(Komi::from_half_komi(4).unwrap());
+    for ((param_0, param_1), param_2) in komi_0.iter().zip(komi_1).zip(komi_2) {
+        assert!((param_1 - (param_0 + param_2) / 2.0).abs() < 0.0001);
+    }
+}
+
+#[test]
+fn extrapolated_komi_params_test() {
+    for half_komi in [5, 6] {
+        let komi = Komi::from_half_komi(half_komi).unwrap();
+        assert_ne!(
+            <Position<5>>::value_params(komi),
+            <Position<5>>::value_params(Komi::from_half_komi(4).unwrap())
+        );
+        assert_ne!(
+            <Position<6>>::policy_params(komi),
+            <Position<6>>::policy_params(Komi::from_half_komi(4).unwrap())
+        );
+        assert_ne!(
+            <Position<7>>::value_params(komi),
+            <Position<7>>::value_params(Komi::from_half_komi(4).unwrap())
+        );
+    }
+
+    // 2.5 komi continues the line from 0 to 2 komi
+    let komi_0 = <Position<5>>::policy_params(Komi::from_half_komi(0).unwrap());
+    let komi_2 = <Position<5>>::policy_params(Komi::from_half_komi(4).unwrap());
+    let komi_2_5 = <Position<5>>::policy_params(Komi::from_half_komi(5).unwrap());
+    for ((param_0, param_2), param_2_5) in komi_0.iter().zip(komi_2).zip(komi_2_5) {
+        assert!((param_2_5 - (param_2 + (param_2 - param_0) / 4.0)).abs() < 0.0001);
+    }
+}
+
+#[test]
+fn search_with_half_komi_test() {
+    for komi in [0.5, 1.5, 2.5] {
+        let position: Position<6> =
+            Position::start_position_with_komi(Komi::try_from(komi).unwrap());
+        let (mv, _) = search::mcts(position.clone(), 1000);
+        assert!(position.move_is_legal(mv));
+    }
+}
@@ -1,10 +1,12 @@
 use crate::position::{Move, Position};
-use crate::search::MctsSetting;
 use crate::search::{self, MonteCarloTree};
+use crate::search::{ExportedNode, MctsSetting, TranspositionTable};
 use crate::tests::TestPosition;
-use board_game_traits::Position as PositionTrait;
+use board_game_traits::{GameResult, Position as PositionTrait};
 use half::f16;
 use pgn_traits::PgnPosition;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
 use std::time::Duration;
 
 #[test]
@@ -36,6 +38,227 @@ fn play_on_low_time() {
     search::play_move_time(position, time, MctsSetting::default());
 }
 
+#[test]
+fn parallel_search_test() {
+    let position =
+        TestPosition::from_move_strings(&["e5", "c3", "c2", "d5", "c1", "c5", "d3", "a4", "e3"])
+            .position::<5>();
+    let settings = MctsSetting::default()
+        .arena_size_for_nodes(20_000)
+        .num_threads(4);
+    let mut tree = MonteCarloTree::new(position.clone(), settings);
+    let visits_before = tree.visits();
+
+    tree.search_nodes(10_000).unwrap();
+    assert_eq!(tree.visits(), visits_before + 10_000);
+
+    let (best_move, _score) = tree.best_move().unwrap();
+    assert!(
+        ["b4", "b5", "Cb4", "Cb5"]
+            .iter()
+            .any(|mv| position.move_from_san(mv).unwrap() == best_move),
+        "Played {}",
+        position.move_to_san(&best_move)
+    );
+}
+
+#[test]
+fn transposition_search_test() {
+    let position =
+        TestPosition::from_move_strings(&["e5", "c3", "c2", "d5", "c1", "c5", "d3", "a4", "e3"])
+            .position::<5>();
+    for num_threads in [1, 4] {
+        let settings = MctsSetting::default()
+            .arena_size_for_nodes(20_000)
+            .num_threads(num_threads)
+            .use_transpositions(true);
+        let mut tree = MonteCarloTree::new(position.clone(), settings);
+        tree.search_nodes(10_000).unwrap();
+
+        let (best_move, _score) = tree.best_move().unwrap();
+        assert!(
+            ["b4", "b5", "Cb4", "Cb5"]
+                .iter()
+                .any(|mv| position.move_from_san(mv).unwrap() == best_move),
+            "Played {} with {} threads",
+            position.move_to_san(&best_move),
+            num_threads
+        );
+    }
+}
+
+#[test]
+fn transposition_table_test() {
+    let table = TranspositionTable::for_arena_size(4096);
+    assert_eq!(table.len(), 1024);
+    assert_eq!(table.get(5), None);
+
+    table.insert(5, 0.25, 10);
+    assert_eq!(table.get(5), Some((0.25, 10)));
+    // The same position is always updated
+    table.insert(5, 0.5, 2);
+    assert_eq!(table.get(5), Some((0.5, 2)));
+
+    // A different position in the same entry only replaces it with more visits
+    table.insert(5 + 1024, 0.75, 1);
+    assert_eq!(table.get(5 + 1024), None);
+    assert_eq!(table.get(5), Some((0.5, 2)));
+    table.insert(5 + 1024, 0.75, 3);
+    assert_eq!(table.get(5 + 1024), Some((0.75, 3)));
+    assert_eq!(table.get(5), None);
+}
+
+#[test]
+fn tree_node_fits_in_arena_element_test() {
+    assert!(search::node_mem_usage::<4>() <= search::ARENA_ELEMENT_SIZE);
+    assert!(search::node_mem_usage::<6>() <= search::ARENA_ELEMENT_SIZE);
+    assert!(search::node_mem_usage::<8>() <= search::ARENA_ELEMENT_SIZE);
+}
+
+#[test]
+fn advance_tree_test() {
+    let mut position = <Position<5>>::start_position();
+    let settings = MctsSetting::default().arena_size_for_nodes(20_000);
+    let mut tree = MonteCarloTree::new(position.clone(), settings.clone());
+    tree.search_nodes(10_000).unwrap();
+
+    let (best_move, _score) = tree.best_move().unwrap();
+    let best_edge_visits = tree
+        .shallow_edges()
+        .unwrap()
+        .iter()
+        .find(|edge| edge.mv == best_move)
+        .unwrap()
+        .visits;
+
+    let mem_usage = tree.mem_usage();
+    tree.advance(best_move);
+    position.do_move(best_move);
+
+    assert_eq!(*tree.position(), position);
+    assert_eq!(tree.visits(), best_edge_visits);
+    assert!(tree.mem_usage() < mem_usage);
+
+    tree.search_nodes(1000).unwrap();
+    assert_eq!(tree.visits(), best_edge_visits + 1000);
+    let (reply, _score) = tree.best_move().unwrap();
+    assert!(position.move_is_legal(reply));
+
+    // Advancing with a move that hasn't been searched starts a new tree
+    let mut tree = MonteCarloTree::new(position.clone(), settings);
+    let unsearched_move = tree
+        .shallow_edges()
+        .unwrap()
+        .iter()
+        .find(|edge| edge.visits == 0)
+        .unwrap()
+        .mv;
+    tree.advance(unsearched_move);
+    position.do_move(unsearched_move);
+    assert_eq!(*tree.position(), position);
+    tree.search_nodes(100).unwrap();
+}
+
+#[test]
+fn search_until_stopped_test() {
+    let settings = MctsSetting::default().arena_size_for_nodes(100_000);
+    let mut tree = MonteCarloTree::new(<Position<5>>::start_position(), settings);
+    let stop = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            thread::sleep(Duration::from_millis(50));
+            stop.store(true, Ordering::Relaxed);
+        });
+        tree.search_until_stopped(&stop).unwrap();
+    });
+    assert!(tree.visits() > 0);
+    assert!(tree.best_move().is_some());
+}
+
+#[test]
+fn multi_pv_test() {
+    let settings = MctsSetting::default().arena_size_for_nodes(20_000);
+    let mut tree = MonteCarloTree::new(<Position<5>>::start_position(), settings);
+    tree.search_nodes(10_000).unwrap();
+
+    let lines = tree.multi_pv(4);
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0].mv, tree.best_move().unwrap().0);
+    assert_eq!(lines[0].pv, tree.pv().collect::<Vec<_>>());
+    for (line, next_line) in lines.iter().zip(lines.iter().skip(1)) {
+        assert!(line.visits >= next_line.visits);
+        assert_ne!(line.mv, next_line.mv);
+        assert_eq!(line.pv[0], line.mv);
+    }
+}
+
+#[test]
+fn export_tree_test() {
+    let settings = MctsSetting::default().arena_size_for_nodes(5_000);
+    let mut tree = MonteCarloTree::new(<Position<5>>::start_position(), settings);
+    tree.search_nodes(5_000).unwrap();
+
+    let exported = tree.export_tree(2, 50);
+    assert_eq!(exported.mv, None);
+    assert_eq!(exported.visits, tree.visits());
+    assert_eq!(exported.children[0].mv, tree.best_move().map(|(mv, _)| mv));
+    assert_eq!(exported.children.len() + exported.omitted_children, 25);
+
+    fn check_node(node: &ExportedNode<5>, depth: usize) {
+        assert!(depth <= 2);
+        // The root's visits don't include the visits used to initialize it
+        if depth > 0 {
+            assert!(node.children.iter().map(|child| child.visits).sum::<u32>() <= node.visits);
+        }
+        for (child, next_child) in node.children.iter().zip(node.children.iter().skip(1)) {
+            assert!(child.visits >= next_child.visits);
+        }
+        for child in node.children.iter() {
+            assert!(child.visits >= 50);
+            assert_ne!(child.side_to_move, node.side_to_move);
+            check_node(child, depth + 1);
+        }
+    }
+    check_node(&exported, 0);
+
+    let json = exported.to_json();
+    assert!(json.starts_with("{\"move\":null,"));
+    assert_eq!(json.matches("\"move\":").count(), exported.num_nodes());
+    let dot = exported.to_dot();
+    assert!(dot.starts_with("digraph"));
+    assert_eq!(dot.matches(" -> ").count(), exported.num_nodes() - 1);
+}
+
+#[test]
+fn export_tree_game_result_test() {
+    let position: Position<5> = Position::from_fen("2,2,2,2,x/x5/x5/x5/1,1,1,1,x 1 5").unwrap();
+    let settings = MctsSetting::default().arena_size_for_nodes(1_000);
+    let mut tree = MonteCarloTree::new(position, settings);
+    tree.search_nodes(1_000).unwrap();
+
+    let exported = tree.export_tree(1, 1);
+    let winning_child = exported
+        .children
+        .iter()
+        .find(|child| child.mv == Some(Move::from_string("e1").unwrap()))
+        .unwrap();
+    assert_eq!(winning_child.game_result, Some(GameResult::WhiteWin));
+    assert!(winning_child.children.is_empty());
+    assert!(exported.to_dot().contains("shape=box"));
+}
+
+#[test]
+fn search_7s_and_8s_test() {
+    let (mv, score) = search::mcts(<Position<7>>::start_position(), 5000);
+    assert!(<Position<7>>::start_position().move_is_legal(mv));
+    assert!(score > 0.0 && score < 1.0);
+
+    let (mv, score) = search::mcts(<Position<8>>::start_position(), 5000);
+    assert!(<Position<8>>::start_position().move_is_legal(mv));
+    assert!(score > 0.0 && score < 1.0);
+}
+
 #[test]
 fn win_in_two_moves_test() {
     let test_position =
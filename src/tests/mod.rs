@@ -1,19 +1,35 @@
+#[cfg(any(feature = "aws-lambda-runtime", feature = "serve"))]
+mod analysis_tests;
+mod annotate_tests;
 mod arena_tests;
 mod blunder_tests;
 mod board_generic_tests;
 mod board_tests;
+mod book_tests;
+#[cfg(feature = "constant-tuning")]
+mod engine_match_tests;
 mod komi_policy_tests;
 mod mcts_tests;
 mod move_gen_5s_tests;
 mod move_gen_generic_tests;
 mod policy_tests;
 mod ptn_tests;
+mod puzzle_tests;
+mod solver_tests;
+#[cfg(feature = "sqlite")]
+mod sqlite_tests;
+mod strength_tests;
 mod tactics_tests_5s;
 mod tactics_tests_6s;
+mod time_management_tests;
+#[cfg(feature = "constant-tuning")]
+mod training_tests;
+mod weights_tests;
 
 use crate::evaluation::parameters::{IncrementalPolicy, PolicyApplier};
 use crate::position::{Komi, Move, Position};
 use crate::search;
+use crate::search::solver::{Proof, Solver};
 use board_game_traits::Position as PositionTrait;
 use half::f16;
 use pgn_traits::PgnPosition;
@@ -80,6 +96,27 @@ impl TestPosition {
         );
     }
 
+    /// Check that the solver proves a win with one of the correct moves, within `max_plies` plies
+    pub fn solves_correct_move<const S: usize>(&self, correct_moves: &[&str], max_plies: u8) {
+        let mut position: Position<S> = self.position();
+        let candidate_moves = check_candidate_moves(&position, correct_moves);
+
+        let mut solver = Solver::new(10_000_000);
+        match solver.solve(&mut position, max_plies) {
+            Proof::Win { mv, .. } => assert!(
+                candidate_moves.contains(&mv),
+                "Solver found {} instead of one of the correct moves {:?} in position:\n{:?}",
+                position.move_to_san(&mv),
+                correct_moves,
+                position
+            ),
+            proof => panic!(
+                "Solver didn't find a win in {} plies, got {:?} in position:\n{:?}",
+                max_plies, proof, position
+            ),
+        }
+    }
+
     pub fn avoid_move_short_prop<const S: usize>(&self, avoid_moves: &[&str]) {
         let position: Position<S> = self.position();
         let candidate_moves = check_candidate_moves(&position, avoid_moves);
@@ -133,10 +170,9 @@ impl TestPosition {
 
         let parameters = <Position<S>>::policy_params(position.komi());
 
-        let mut policies: Vec<IncrementalPolicy<S>> =
-            vec![IncrementalPolicy::new(parameters); moves.len()];
+        let mut policies: Vec<IncrementalPolicy<S>> = vec![IncrementalPolicy::new(); moves.len()];
 
-        position.features_for_moves(&mut policies, &moves, &mut vec![], &group_data);
+        position.features_for_moves(parameters, &mut policies, &moves, &mut vec![], &group_data);
 
         policies.iter().any(|policy| policy.has_immediate_win())
     }
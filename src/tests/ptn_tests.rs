@@ -15,11 +15,7 @@ pub fn write_and_read_ptn_test() {
     let moves = position
         .moves()
         .iter()
-        .map(|mv| PtnMove {
-            mv: *mv,
-            annotations: vec![],
-            comment: "".to_string(),
-        })
+        .map(|mv| PtnMove::new(*mv))
         .collect();
 
     let game: Game<Position<6>> = Game {
@@ -34,6 +30,7 @@ pub fn write_and_read_ptn_test() {
             ("Result".to_string(), "1-0".to_string()),
             ("Round".to_string(), "1".to_string()),
         ],
+        leading_comments: vec![],
     };
 
     let mut ptn_writer = Cursor::new(vec![]);
@@ -54,11 +51,7 @@ pub fn custom_start_position_test() {
 
     let tps = position.to_fen();
 
-    let moves = vec![PtnMove {
-        mv: position.move_from_san("e6").unwrap(),
-        annotations: vec![],
-        comment: String::new(),
-    }];
+    let moves = vec![PtnMove::new(position.move_from_san("e6").unwrap())];
 
     let mut game: Game<Position<6>> = Game {
         start_position: position,
@@ -72,6 +65,7 @@ pub fn custom_start_position_test() {
             ("Result".to_string(), "1-0".to_string()),
             ("Round".to_string(), "1".to_string()),
         ],
+        leading_comments: vec![],
     };
 
     let mut ptn_writer = Cursor::new(vec![]);
@@ -112,3 +106,169 @@ fn parse_ptn_without_result2() {
 fn parse_bad_direction_test() {
     assert!(<Move<6>>::from_string("a1d").is_err())
 }
+
+#[test]
+fn parse_variations_and_comments_test() {
+    let ptn = "[Size \"5\"]\n\n{Leading comment} 1. a1 e5 {Corner} {Second comment} (1... a5 {Other corner} 2. e1 (2. b1'!?)) 2. c3!? (2. b2 c4 (2... d4)) d4 1-0";
+
+    let games: Vec<Game<Position<5>>> = ptn_parser::parse_ptn(ptn).unwrap();
+    let moves = &games[0].moves;
+    assert_eq!(moves.len(), 4);
+    assert_eq!(games[0].leading_comments, vec!["Leading comment"]);
+    assert!(moves[0].comments.is_empty());
+    assert_eq!(moves[1].comments, vec!["Corner", "Second comment"]);
+    assert_eq!(moves[2].annotations, vec!["!?"]);
+
+    let variation = &moves[1].variations[0];
+    assert_eq!(variation.len(), 2);
+    assert_eq!(variation[0].mv, <Move<5>>::from_string("a5").unwrap());
+    assert_eq!(variation[0].comments, vec!["Other corner"]);
+    assert_eq!(variation[1].variations[0][0].annotations, vec!["'", "!?"]);
+
+    let nested_variation = &moves[2].variations[0][1].variations[0];
+    assert_eq!(
+        nested_variation[0].mv,
+        <Move<5>>::from_string("d4").unwrap()
+    );
+
+    // Writing and reading the game again keeps all the moves, and writing it again gives the same ptn
+    let written_ptn = game_to_ptn_string(&games[0]);
+    let reparsed_games: Vec<Game<Position<5>>> = ptn_parser::parse_ptn(&written_ptn).unwrap();
+    assert_eq!(
+        reparsed_games[0].moves, games[0].moves,
+        "ptn:\n{}",
+        written_ptn
+    );
+    assert_eq!(reparsed_games[0].game_result_str, Some("1-0"));
+    assert_eq!(game_to_ptn_string(&reparsed_games[0]), written_ptn);
+}
+
+#[test]
+fn leading_comments_round_trip_test() {
+    let ptn = "[Size \"5\"]\n\n{Intro} {More intro} 1. a1 e5 0-1";
+    let games: Vec<Game<Position<5>>> = ptn_parser::parse_ptn(ptn).unwrap();
+    assert_eq!(games[0].leading_comments, vec!["Intro", "More intro"]);
+
+    let written_ptn = game_to_ptn_string(&games[0]);
+    assert!(
+        written_ptn.contains("{Intro} {More intro} 1. a1 e5 0-1"),
+        "ptn:\n{}",
+        written_ptn
+    );
+    let reparsed_games: Vec<Game<Position<5>>> = ptn_parser::parse_ptn(&written_ptn).unwrap();
+    assert_eq!(
+        reparsed_games[0].leading_comments,
+        games[0].leading_comments
+    );
+    assert_eq!(reparsed_games[0].moves, games[0].moves);
+
+    // Games without moves keep their comments too
+    let games: Vec<Game<Position<5>>> =
+        ptn_parser::parse_ptn("[Size \"5\"]\n\n{Nothing happened} 1/2-1/2").unwrap();
+    assert_eq!(games[0].leading_comments, vec!["Nothing happened"]);
+    let reparsed_games: Vec<Game<Position<5>>> =
+        ptn_parser::parse_ptn(&game_to_ptn_string(&games[0])).unwrap();
+    assert_eq!(
+        reparsed_games[0].leading_comments,
+        games[0].leading_comments
+    );
+    assert!(reparsed_games[0].moves.is_empty());
+}
+
+#[test]
+fn closing_brace_in_comment_is_removed_test() {
+    let mut games: Vec<Game<Position<5>>> =
+        ptn_parser::parse_ptn("[Size \"5\"]\n\n1. a1 e5 0-1").unwrap();
+    games[0].leading_comments.push("Intro }".to_string());
+    games[0].moves[0].comments.push("Corner} a1".to_string());
+
+    let reparsed_games: Vec<Game<Position<5>>> =
+        ptn_parser::parse_ptn(&game_to_ptn_string(&games[0])).unwrap();
+    assert_eq!(reparsed_games[0].leading_comments, vec!["Intro "]);
+    assert_eq!(reparsed_games[0].moves[0].comments, vec!["Corner a1"]);
+}
+
+#[test]
+fn escaped_tag_values_round_trip_test() {
+    let ptn = "[Size \"5\"]\n[Event \"The \\\"big\\\" one \\\\ final\"]\n\n1. a1 e5 0-1";
+    let games: Vec<Game<Position<5>>> = ptn_parser::parse_ptn(ptn).unwrap();
+    assert!(games[0]
+        .tags
+        .contains(&("Event".to_string(), "The \"big\" one \\ final".to_string())));
+
+    let written_ptn = game_to_ptn_string(&games[0]);
+    let reparsed_games: Vec<Game<Position<5>>> = ptn_parser::parse_ptn(&written_ptn).unwrap();
+    assert!(reparsed_games[0]
+        .tags
+        .contains(&("Event".to_string(), "The \"big\" one \\ final".to_string())));
+}
+
+#[test]
+fn invalid_move_numbers_and_variations_test() {
+    for ptn in [
+        "1. a1 e5 3. c3 1-0",
+        "1. a1 1. e5 1-0",
+        "1. a1 e5 (2... b2) 1-0",
+        "(1. a1) 1. a1 e5 1-0",
+        "1. a1 e5 (1... a5 1-0",
+        "1. a1 e5) 1-0",
+        "1. a1 e5 {Unfinished comment",
+        "1. a1 } e5 1-0",
+        "1. a1 e5 (1... a5 }) 1-0",
+    ] {
+        assert!(
+            ptn_parser::parse_ptn::<Position<5>>(ptn).is_err(),
+            "Parsed invalid ptn {}",
+            ptn
+        );
+    }
+}
+
+#[test]
+fn ptn_error_location_test() {
+    let ptn = "[Size \"5\"]\n\n1. a1 e5\n2. c3 a6 1-0\n\n[Size \"5\"]\n\n1. a1 e5 0-1";
+    let err = ptn_parser::parse_ptn::<Position<5>>(ptn).unwrap_err();
+    assert_eq!(err.game_index, 0);
+    assert_eq!(err.line, 4);
+    assert_eq!(err.column, 7);
+    assert_eq!(err.token, "a6");
+
+    let ptn = "[Size \"5\"]\n\n1. a1 e5 (1... a5 {Unfinished";
+    let err = ptn_parser::parse_ptn::<Position<5>>(ptn).unwrap_err();
+    assert_eq!((err.line, err.column), (3, 19));
+    assert_eq!(err.token, "{");
+    assert_eq!(err.expected, "} to end the comment");
+
+    let ptn = "[Size \"5\"]\n\n1. a1 } e5 1-0";
+    let err = ptn_parser::parse_ptn::<Position<5>>(ptn).unwrap_err();
+    assert_eq!((err.line, err.column), (3, 7));
+    assert_eq!(err.token, "}");
+}
+
+#[test]
+fn parse_ptn_lenient_test() {
+    let ptn = "[Size \"5\"]\n\n1. a1 e5 1-0\n\n\
+        [Size \"5\"]\n[Player1 \"broken\"\n[Player2 \"?\"]\n\n1. a1 e5 0-1\n\n\
+        [Size \"5\"]\n\n1. a1 e5 2. a1 1-0\n\n\
+        [Size \"5\"]\n\n1. a1 e5 (1... a5\n\n\
+        [Size \"5\"]\n\n1. a1 e5 2. c3 R-0\n";
+
+    let (games, errors): (Vec<Game<Position<5>>>, _) = ptn_parser::parse_ptn_lenient(ptn);
+    assert_eq!(games.len(), 2);
+    assert_eq!(games[0].game_result_str, Some("1-0"));
+    assert_eq!(games[1].game_result_str, Some("R-0"));
+    assert_eq!(
+        errors.iter().map(|err| err.game_index).collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    assert_eq!(errors[1].token, "a1");
+    assert_eq!(errors[2].expected, ") to end the variation");
+
+    assert!(ptn_parser::parse_ptn::<Position<5>>(ptn).is_err());
+}
+
+fn game_to_ptn_string(game: &Game<Position<5>>) -> String {
+    let mut ptn_writer = Cursor::new(vec![]);
+    game.game_to_ptn(&mut ptn_writer).unwrap();
+    String::from_utf8(ptn_writer.into_inner()).unwrap()
+}
@@ -0,0 +1,134 @@
+use board_game_traits::Position as PositionTrait;
+use pgn_traits::PgnPosition;
+
+use crate::position::Position;
+use crate::ptn::{Game, PtnMove};
+use crate::puzzles::{Puzzle, PuzzleFinder};
+
+use super::TestPosition;
+
+// Same position as solver_tests::solver_proves_loss_test, where black wins with 3e6-111
+const TINUE_3PLY_MOVES: &[&str] = &[
+    "b6", "a6", "a5", "b3", "b5", "c3", "c5", "d3", "e5", "d5", "f5", "d4", "d6", "d5>", "e6",
+    "Cd5", "c6", "b6>", "Cc4", "d2", "c5+", "d1", "c4>", "a3", "f6", "d5+", "d5", "Sc5", "c2",
+    "e1", "f1", "f2", "2d4-", "e2", "f3", "b1", "f4", "c1", "f3-", "2e5>", "f4+", "Sf4", "b2",
+    "e3", "f3", "f4+", "d4", "5f5-122", "3d3>12", "3f2-", "3f3-", "e3>", "f4-", "e3>", "e3",
+    "5f3<32", "Sf3", "2d6>", "f5", "a1", "f4",
+];
+
+const WIN_IN_ONE_MOVES: &[&str] = &["e5", "a1", "b1", "d5", "c1", "c5", "d1", "a5"];
+
+#[test]
+fn finds_win_in_one_test() {
+    let mut position: Position<5> = TestPosition::from_move_strings(WIN_IN_ONE_MOVES).position();
+    let tps = position.to_fen();
+
+    // Placing the capstone on e1 also wins, but is the same solution
+    let mut finder = PuzzleFinder::new(3);
+    assert_eq!(
+        finder.find_puzzle(&mut position),
+        Some((1, vec![position.move_from_san("e1").unwrap()]))
+    );
+    assert_eq!(position.to_fen(), tps);
+
+    // Wins in one move are skipped with a higher minimum
+    let mut finder = PuzzleFinder::new(3).min_plies(3);
+    assert_eq!(finder.find_puzzle(&mut position), None);
+}
+
+#[test]
+fn skips_non_unique_win_test() {
+    // White wins with either e5 or e1
+    let mut position: Position<5> =
+        TestPosition::from_tps("1,1,1,1,x/2,2,x3/x5/2,2,x3/1,1,1,1,x 1 5").position();
+
+    let mut finder = PuzzleFinder::new(3);
+    assert_eq!(finder.find_puzzle(&mut position), None);
+}
+
+#[test]
+fn skips_win_with_slower_alternative_test() {
+    // White wins immediately with e1, but d3 also wins in 3 plies, by threatening both e1 and e3
+    let tps = "2,2,x,2,x/x,2,x2,2/1,1,1,x2/2,x,2,x2/1,1,1,1,x 1 8";
+    let mut position: Position<5> = TestPosition::from_tps(tps).position();
+
+    let mut finder = PuzzleFinder::new(3);
+    assert_eq!(finder.find_puzzle(&mut position), None);
+
+    let mut finder = PuzzleFinder::new(1);
+    assert_eq!(
+        finder.find_puzzle(&mut position),
+        Some((1, vec![position.move_from_san("e1").unwrap()]))
+    );
+}
+
+#[test]
+fn finds_3ply_tinue_test() {
+    let mut position: Position<6> = TestPosition::from_move_strings(TINUE_3PLY_MOVES).position();
+
+    let mut finder = PuzzleFinder::new(5).min_plies(3).max_nodes(10_000_000);
+    let (plies, solution) = finder.find_puzzle(&mut position).unwrap();
+    assert_eq!(plies, 3);
+    assert_eq!(solution.len(), 3);
+    assert_eq!(solution[0], position.move_from_san("3e6-111").unwrap());
+
+    // The solution must end with a road win for black
+    for mv in solution {
+        position.do_move(mv);
+    }
+    assert_eq!(position.pgn_game_result(), Some("0-R"));
+}
+
+#[test]
+fn gives_up_when_out_of_nodes_test() {
+    let mut position: Position<6> = TestPosition::from_move_strings(TINUE_3PLY_MOVES).position();
+
+    let mut finder = PuzzleFinder::new(5).min_plies(3).max_nodes(100);
+    assert_eq!(finder.find_puzzle(&mut position), None);
+}
+
+#[test]
+fn puzzles_from_game_test() {
+    let position: Position<5> = TestPosition::from_move_strings(WIN_IN_ONE_MOVES).position();
+    let winning_move = position.move_from_san("e1").unwrap();
+    let game = Game {
+        start_position: position.clone(),
+        moves: vec![PtnMove::new(winning_move)],
+        game_result_str: Some("R-0"),
+        tags: vec![],
+        leading_comments: vec![],
+    };
+
+    let puzzles = PuzzleFinder::new(3).puzzles_from_game(&game, "Test game");
+    assert_eq!(
+        puzzles,
+        vec![Puzzle {
+            position: position.clone(),
+            plies: 1,
+            solution: vec![winning_move],
+            source: "Test game, ply 0".to_string(),
+        }]
+    );
+    assert_eq!(
+        puzzles[0].to_string(),
+        format!("{};1;e1;Test game, ply 0", position.to_fen())
+    );
+
+    // Semicolons in the source would break the format
+    let puzzles = PuzzleFinder::new(3).puzzles_from_game(&game, "White; Black");
+    assert_eq!(
+        puzzles[0].to_string(),
+        format!("{};1;e1;White, Black, ply 0", position.to_fen())
+    );
+}
+
+#[test]
+fn finds_3ply_tinue_with_default_settings_test() {
+    let mut position: Position<6> = TestPosition::from_move_strings(TINUE_3PLY_MOVES).position();
+
+    // The puzzles binary's default settings
+    let mut finder = PuzzleFinder::new(3).min_plies(3).max_nodes(1_000_000);
+    let (plies, solution) = finder.find_puzzle(&mut position).unwrap();
+    assert_eq!(plies, 3);
+    assert_eq!(solution[0], position.move_from_san("3e6-111").unwrap());
+}
@@ -0,0 +1,138 @@
+use board_game_traits::{Color, GameResult, Position as PositionTrait};
+use pgn_traits::PgnPosition;
+
+use crate::position::Position;
+use crate::search::solver::{Proof, Solver};
+use crate::search::{MctsSetting, MonteCarloTree};
+
+use super::TestPosition;
+
+// Same position as tactics_tests_6s::tinue_3ply_test, where black wins with 3e6-111
+const TINUE_3PLY_MOVES: &[&str] = &[
+    "b6", "a6", "a5", "b3", "b5", "c3", "c5", "d3", "e5", "d5", "f5", "d4", "d6", "d5>", "e6",
+    "Cd5", "c6", "b6>", "Cc4", "d2", "c5+", "d1", "c4>", "a3", "f6", "d5+", "d5", "Sc5", "c2",
+    "e1", "f1", "f2", "2d4-", "e2", "f3", "b1", "f4", "c1", "f3-", "2e5>", "f4+", "Sf4", "b2",
+    "e3", "f3", "f4+", "d4", "5f5-122", "3d3>12", "3f2-", "3f3-", "e3>", "f4-", "e3>", "e3",
+    "5f3<32", "Sf3", "2d6>", "f5", "a1", "f4",
+];
+
+#[test]
+fn solver_proves_loss_test() {
+    let mut position: Position<6> = TestPosition::from_move_strings(TINUE_3PLY_MOVES).position();
+    position.do_move(position.move_from_san("3e6-111").unwrap());
+
+    let mut solver = Solver::new(10_000_000);
+    assert_eq!(solver.solve(&mut position, 1), Proof::Unknown);
+    assert_eq!(solver.solve(&mut position, 5), Proof::Loss { plies: 2 });
+}
+
+#[test]
+fn solver_finds_win_in_one_test() {
+    let mut position: Position<5> =
+        TestPosition::from_move_strings(&["e5", "a1", "b1", "d5", "c1", "c5", "d1"]).position();
+
+    let mut solver = Solver::new(10_000);
+    // Black has to block white's road, and cannot win in three plies
+    assert_eq!(solver.solve(&mut position, 3), Proof::Unknown);
+    position.do_move(position.move_from_san("a5").unwrap());
+    assert_eq!(
+        solver.solve(&mut position, 3),
+        Proof::Win {
+            mv: position.move_from_san("e1").unwrap(),
+            plies: 1
+        }
+    );
+}
+
+#[test]
+fn solver_restores_position_test() {
+    let mut position: Position<6> = TestPosition::from_move_strings(TINUE_3PLY_MOVES).position();
+    let tps = position.to_fen();
+
+    let mut solver = Solver::new(100);
+    assert_eq!(solver.solve(&mut position, 5), Proof::Unknown);
+    assert!(solver.nodes() >= 100);
+    assert_eq!(position.to_fen(), tps);
+}
+
+#[test]
+fn mcts_marks_solved_nodes_test() {
+    for num_threads in [1, 4] {
+        let position: Position<6> = TestPosition::from_move_strings(TINUE_3PLY_MOVES).position();
+        let winning_move = position.move_from_san("3e6-111").unwrap();
+
+        let settings = MctsSetting::default()
+            .arena_size_for_nodes(30_000)
+            .num_threads(num_threads)
+            .add_solver(3, 2000);
+        let mut tree = MonteCarloTree::new(position, settings);
+        tree.search_nodes(30_000).unwrap();
+        assert_eq!(tree.visits(), 30_000);
+
+        assert_eq!(tree.best_move().unwrap().0, winning_move);
+
+        let exported = tree.export_tree(1, 0);
+        let winning_child = exported
+            .children
+            .iter()
+            .find(|child| child.mv == Some(winning_move))
+            .unwrap();
+        assert_eq!(winning_child.side_to_move, Color::White);
+        assert_eq!(winning_child.game_result, Some(GameResult::BlackWin));
+    }
+}
+
+#[test]
+fn mcts_proves_win_test() {
+    for num_threads in [1, 2] {
+        let position: Position<6> = TestPosition::from_move_strings(TINUE_3PLY_MOVES).position();
+        let winning_move = position.move_from_san("3e6-111").unwrap();
+
+        let settings = MctsSetting::default()
+            .arena_size_for_nodes(50_000)
+            .num_threads(num_threads);
+        let mut tree = MonteCarloTree::new(position, settings);
+        tree.search_nodes(50_000).unwrap();
+
+        assert_eq!(tree.proven_plies(), Some(3));
+        assert_eq!(tree.best_move().unwrap().0, winning_move);
+        assert_eq!(tree.pv().next(), Some(winning_move));
+
+        let lines = tree.multi_pv(2);
+        assert_eq!(lines[0].mv, winning_move);
+        assert_eq!(lines[0].proven_plies, Some(3));
+    }
+}
+
+#[test]
+fn mcts_proves_loss_test() {
+    let mut position: Position<6> = TestPosition::from_move_strings(TINUE_3PLY_MOVES).position();
+    position.do_move(position.move_from_san("3e6-111").unwrap());
+
+    let settings = MctsSetting::default().arena_size_for_nodes(20_000);
+    let mut tree = MonteCarloTree::new(position, settings);
+    tree.search_nodes(20_000).unwrap();
+
+    assert_eq!(tree.proven_plies(), Some(-2));
+    assert!(tree
+        .multi_pv(usize::MAX)
+        .iter()
+        .all(|line| line.proven_plies.is_none_or(|plies| plies < 0)));
+}
+
+#[test]
+fn mcts_proves_win_in_one_test() {
+    let position: Position<5> =
+        TestPosition::from_move_strings(&["e5", "a1", "b1", "d5", "c1", "c5", "d1", "a5"])
+            .position();
+
+    let settings = MctsSetting::default().arena_size_for_nodes(1000);
+    let mut tree = MonteCarloTree::new(position.clone(), settings);
+    tree.search_nodes(1000).unwrap();
+
+    assert_eq!(tree.proven_plies(), Some(1));
+    assert_eq!(
+        tree.best_move().unwrap().0,
+        position.move_from_san("e1").unwrap()
+    );
+}
@@ -0,0 +1,116 @@
+use board_game_traits::{GameResult, Position as PositionTrait};
+use chrono::{TimeZone, Utc};
+use pgn_traits::PgnPosition;
+use rusqlite::Connection;
+
+use crate::position::Komi;
+use crate::sqlite::GameQuery;
+
+fn test_database() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(
+        "CREATE TABLE games (id INTEGER PRIMARY KEY, date INTEGER, size INTEGER, player_white TEXT, player_black TEXT, \
+            notation TEXT, result TEXT, timertime INTEGER, timerinc INTEGER, rating_white INTEGER, rating_black INTEGER, \
+            unrated INTEGER, tournament INTEGER, komi INTEGER, pieces INTEGER, capstones INTEGER);
+        INSERT INTO games VALUES (1, 1500000000000, 5, 'alice', 'bob', 'P A1,P E5,P C3,P D3,M C3 C4 1', 'R-0', 900, 10, 1700, 1500, 0, 0, 0, -1, -1);
+        INSERT INTO games VALUES (2, 1600000000000, 5, 'bob', 'carol', 'P A1,P E5,P B2 W,P C3 C,M E5 E4 1', '0-F', 900, 10, 1550, 1800, 1, 0, 4, 21, 1);
+        INSERT INTO games VALUES (3, 1700000000000, 5, 'carol', 'alice', 'a1 e5 c3', '1/2-1/2', 900, 10, 1800, NULL, 0, 0, 4, -1, -1);
+        INSERT INTO games VALUES (4, 1700000000000, 6, 'alice', 'bob', 'P A1,P F6', '0-R', 900, 10, 1700, 1500, 0, 0, 4, -1, -1);
+        INSERT INTO games VALUES (5, 1700000000000, 5, 'alice', 'bob', 'P A1,P E5', '0-0', 900, 10, 1700, 1500, 0, 0, 0, -1, -1);
+        INSERT INTO games VALUES (6, 1700000000000, 5, 'alice', 'bob', 'P A1,P E5', 'R-0', 900, 10, 1700, 1500, 0, 0, 0, 30, -1);
+        INSERT INTO games VALUES (7, 1700000000000, 5, 'alice', 'bob', 'P A1,P A1', 'R-0', 900, 10, 1700, 1500, 0, 0, 0, -1, -1);
+        INSERT INTO games VALUES (8, 1700000000000, 5, 'alice', 'bob', 'P A1,M A1 A4 1', 'R-0', 900, 10, 1700, 1500, 0, 0, 0, -1, -1);",
+    )
+    .unwrap();
+    conn
+}
+
+fn game_ids(query: &GameQuery) -> Vec<String> {
+    query
+        .read_games_from_connection::<5>(&test_database())
+        .unwrap()
+        .iter()
+        .map(|game| {
+            game.tags
+                .iter()
+                .find(|(tag, _)| tag == "Event")
+                .unwrap()
+                .1
+                .clone()
+        })
+        .collect()
+}
+
+#[test]
+fn read_games_test() {
+    let games = GameQuery::new()
+        .read_games_from_connection::<5>(&test_database())
+        .unwrap();
+    // Aborted games, games with custom piece counts and games with illegal moves are skipped
+    assert_eq!(games.len(), 3);
+
+    let game = &games[2];
+    assert_eq!(game.game_result(), Some(GameResult::WhiteWin));
+    let mut position = game.start_position.clone();
+    for ptn_move in game.moves.iter().take(4) {
+        position.do_move(ptn_move.mv);
+    }
+    assert_eq!(position.move_to_san(&game.moves[4].mv), "c3+");
+    assert!(game
+        .tags
+        .contains(&("Player1".to_string(), "alice".to_string())));
+    assert!(game
+        .tags
+        .contains(&("Date".to_string(), "2017.07.14".to_string())));
+
+    let game = &games[0];
+    assert_eq!(game.moves.len(), 3);
+    assert_eq!(game.start_position.komi(), Komi::from_half_komi(4).unwrap());
+    assert_eq!(game.game_result(), Some(GameResult::Draw));
+    assert!(!game.tags.iter().any(|(tag, _)| tag == "Rating2"));
+
+    let games = GameQuery::new()
+        .read_games_from_connection::<6>(&test_database())
+        .unwrap();
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0].game_result(), Some(GameResult::BlackWin));
+}
+
+#[test]
+fn game_query_filters_test() {
+    assert_eq!(
+        game_ids(&GameQuery::new()),
+        ["Game #3", "Game #2", "Game #1"]
+    );
+    assert_eq!(
+        game_ids(&GameQuery::new().komi(Komi::from_half_komi(4).unwrap())),
+        ["Game #3", "Game #2"]
+    );
+    assert_eq!(game_ids(&GameQuery::new().min_rating(1520)), ["Game #2"]);
+    assert_eq!(game_ids(&GameQuery::new().max_rating(1750)), ["Game #1"]);
+    assert_eq!(
+        game_ids(&GameQuery::new().after(Utc.with_ymd_and_hms(2018, 1, 1, 0, 0, 0).unwrap())),
+        ["Game #3", "Game #2"]
+    );
+    assert_eq!(
+        game_ids(&GameQuery::new().before(Utc.with_ymd_and_hms(2018, 1, 1, 0, 0, 0).unwrap())),
+        ["Game #1"]
+    );
+    assert_eq!(
+        game_ids(&GameQuery::new().player("alice")),
+        ["Game #3", "Game #1"]
+    );
+    assert_eq!(
+        game_ids(&GameQuery::new().result(GameResult::BlackWin)),
+        ["Game #2"]
+    );
+    assert_eq!(
+        game_ids(&GameQuery::new().rated_only()),
+        ["Game #3", "Game #1"]
+    );
+    assert_eq!(
+        game_ids(&GameQuery::new().min_plies(4)),
+        ["Game #2", "Game #1"]
+    );
+    assert_eq!(game_ids(&GameQuery::new().limit(1)), ["Game #3"]);
+}
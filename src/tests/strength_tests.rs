@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+
+use board_game_traits::Position as PositionTrait;
+
+use crate::position::Position;
+use crate::search::strength::{StrengthLevel, STRENGTH_LEVELS};
+use crate::search::{MctsSetting, MonteCarloTree};
+
+#[test]
+fn levels_get_stronger_test() {
+    assert_eq!(StrengthLevel::from_level(0), None);
+    assert_eq!(StrengthLevel::from_level(11), None);
+    for (i, strength) in STRENGTH_LEVELS.iter().enumerate() {
+        assert_eq!(strength.level as usize, i + 1);
+        assert_eq!(StrengthLevel::from_level(strength.level), Some(*strength));
+    }
+    assert_eq!(STRENGTH_LEVELS[0].elo, 0);
+    assert_eq!(STRENGTH_LEVELS[9].max_nodes, None);
+
+    for window in STRENGTH_LEVELS.windows(2) {
+        assert!(window[0].elo < window[1].elo);
+        assert!(window[1]
+            .max_nodes
+            .is_none_or(|max_nodes| window[0].max_nodes.unwrap() < max_nodes));
+    }
+}
+
+#[test]
+fn closest_to_elo_test() {
+    assert_eq!(StrengthLevel::closest_to_elo(-1000).level, 1);
+    assert_eq!(StrengthLevel::closest_to_elo(100_000).level, 10);
+    for strength in STRENGTH_LEVELS {
+        assert_eq!(StrengthLevel::closest_to_elo(strength.elo), strength);
+    }
+}
+
+#[test]
+fn strength_sets_node_limit_test() {
+    let level1 = StrengthLevel::from_level(1).unwrap();
+    let level10 = StrengthLevel::from_level(10).unwrap();
+
+    let settings = <MctsSetting<5>>::default().num_threads(2).strength(level1);
+    assert_eq!(settings.node_limit(), level1.max_nodes);
+    assert_eq!(
+        settings,
+        MctsSetting::default().num_threads(2).strength(level1)
+    );
+
+    // Full strength clears the handicap
+    let settings = settings.strength(level10);
+    assert_eq!(settings.node_limit(), None);
+    assert_eq!(settings, MctsSetting::default().num_threads(2));
+}
+
+#[test]
+fn explicit_noise_overrides_strength_test() {
+    let level1 = StrengthLevel::from_level(1).unwrap();
+    let settings = <MctsSetting<5>>::default()
+        .strength(level1)
+        .add_dirichlet(0.3)
+        .add_static_eval_variance(2.0);
+    assert_eq!(
+        settings,
+        MctsSetting::default()
+            .max_nodes(level1.max_nodes.unwrap())
+            .add_dirichlet(0.3)
+            .add_static_eval_variance(2.0)
+    );
+}
+
+#[test]
+fn node_limit_stops_timed_search_test() {
+    let strength = StrengthLevel::from_level(3).unwrap();
+    let max_nodes = strength.max_nodes.unwrap();
+    let settings = MctsSetting::default()
+        .arena_size_for_nodes(10_000)
+        .strength(strength);
+
+    let mut tree = MonteCarloTree::new(<Position<5>>::start_position(), settings);
+    let start_time = Instant::now();
+    tree.search_for_time(Duration::from_secs(60), |_| {});
+    assert!(start_time.elapsed() < Duration::from_secs(10));
+    assert_eq!(tree.visits() as u64, max_nodes);
+
+    // The limit applies to each search, even if the tree is reused
+    let (best_move, _) = tree.best_move().unwrap();
+    tree.advance(best_move);
+    let start_visits = tree.visits() as u64;
+    tree.search_for_time(Duration::from_secs(60), |_| {});
+    assert_eq!(tree.visits() as u64, start_visits + max_nodes);
+}
@@ -12,6 +12,7 @@ fn tinue_5ply_test() {
     ]);
 
     test_position.plays_correct_move_long_prop::<6>(&["2f4-11"]);
+    test_position.solves_correct_move::<6>(&["2f4-11"], 5);
 }
 
 #[test]
@@ -24,6 +25,7 @@ fn tinue_7ply_test() {
     ]);
 
     test_position.plays_correct_move_long_prop::<6>(&["4b5<"]);
+    test_position.solves_correct_move::<6>(&["4b5<"], 7);
 }
 
 #[test]
@@ -39,6 +41,7 @@ fn tinue_3ply_test() {
     ]);
 
     test_position.plays_correct_move_long_prop::<6>(&["3e6-111"]);
+    test_position.solves_correct_move::<6>(&["3e6-111"], 3);
 }
 
 #[test]
@@ -52,6 +55,7 @@ fn tinue_3ply_test2() {
     ]);
 
     test_position.plays_correct_move_long_prop::<6>(&["2e3-11"]);
+    test_position.solves_correct_move::<6>(&["2e3-11"], 3);
 }
 
 #[test]
@@ -0,0 +1,115 @@
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+
+use board_game_traits::Position as PositionTrait;
+
+use crate::position::Position;
+use crate::search::time_management::{Deadlines, TimeManager, TimePolicy, DEFAULT_MOVE_OVERHEAD};
+use crate::search::{MctsSetting, MonteCarloTree, TimeControl};
+
+#[test]
+fn fraction_policy_test() {
+    let time_control = TimeControl::Time(Duration::from_secs(60), Duration::from_secs(2));
+    let deadlines = TimeManager::default()
+        .deadlines::<5>(&time_control, 10)
+        .unwrap();
+    assert_eq!(deadlines.soft, Duration::from_secs(13));
+    // The extension is capped at a third of the remaining time, after the move overhead
+    assert_eq!(
+        deadlines.hard,
+        (Duration::from_secs(60) - DEFAULT_MOVE_OVERHEAD) / 3
+    );
+}
+
+#[test]
+fn low_time_with_large_increment_test() {
+    // Half the increment alone is more than the time left
+    let time_control = TimeControl::Time(Duration::from_secs(1), Duration::from_secs(10));
+    for time_manager in [
+        TimeManager::default(),
+        TimeManager::new(TimePolicy::MovesLeft),
+        TimeManager::default().move_overhead(Duration::from_millis(400)),
+    ] {
+        let deadlines = time_manager.deadlines::<5>(&time_control, 30).unwrap();
+        assert!(deadlines.soft <= deadlines.hard);
+        assert!(
+            deadlines.hard <= Duration::from_millis(300),
+            "{:?}",
+            deadlines
+        );
+    }
+
+    // No time left after the overhead
+    let time_control = TimeControl::Time(Duration::from_millis(50), Duration::from_secs(10));
+    assert_eq!(
+        TimeManager::default().deadlines::<5>(&time_control, 30),
+        Some(Deadlines::fixed(Duration::ZERO))
+    );
+}
+
+#[test]
+fn fixed_nodes_have_no_deadlines_test() {
+    assert_eq!(
+        TimeManager::default().deadlines::<5>(&TimeControl::FixedNodes(1000), 10),
+        None
+    );
+}
+
+#[test]
+fn opening_plies_and_max_move_time_test() {
+    let time_manager = TimeManager::new(TimePolicy::Fraction {
+        time_divisor: 40,
+        increment_divisor: 3,
+    })
+    .opening_plies(4)
+    .max_move_time(Duration::from_secs(40));
+
+    let time_control = TimeControl::Time(Duration::from_secs(400), Duration::from_secs(3));
+    assert_eq!(
+        time_manager.deadlines::<6>(&time_control, 2).unwrap().soft,
+        Duration::from_millis(5500)
+    );
+    assert_eq!(
+        time_manager.deadlines::<6>(&time_control, 4).unwrap().soft,
+        Duration::from_secs(11)
+    );
+
+    let long_time_control = TimeControl::Time(Duration::from_secs(4000), Duration::ZERO);
+    assert_eq!(
+        time_manager.deadlines::<6>(&long_time_control, 10),
+        Some(Deadlines::fixed(Duration::from_secs(40)))
+    );
+}
+
+#[test]
+fn moves_left_policy_test() {
+    let time_manager = TimeManager::new(TimePolicy::MovesLeft).extension_factor(1.0);
+    let time_control = TimeControl::Time(Duration::from_secs(100), Duration::ZERO);
+
+    // Larger boards have longer games, so each move gets less time
+    let small_board = time_manager.deadlines::<4>(&time_control, 0).unwrap();
+    let large_board = time_manager.deadlines::<8>(&time_control, 0).unwrap();
+    assert!(small_board.soft > large_board.soft);
+    assert_eq!(small_board.soft, small_board.hard);
+
+    // Deep into the game, we still expect to play a few more moves
+    let late_game = time_manager.deadlines::<4>(&time_control, 100).unwrap();
+    assert_eq!(late_game.soft, Duration::from_secs(10));
+}
+
+#[test]
+fn search_respects_hard_deadline_test() {
+    let position = <Position<5>>::start_position();
+    let mut tree = MonteCarloTree::new(
+        position,
+        MctsSetting::default().arena_size_for_nodes(1_000_000),
+    );
+    let deadlines = Deadlines {
+        soft: Duration::from_millis(100),
+        hard: Duration::from_millis(300),
+    };
+    let start_time = Instant::now();
+    tree.search_until_deadlines(deadlines, &AtomicBool::new(false), |_| {});
+    assert!(start_time.elapsed() < Duration::from_millis(600));
+    assert!(tree.best_move().is_some());
+}
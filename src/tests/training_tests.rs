@@ -0,0 +1,110 @@
+use std::{env, fs};
+
+use board_game_traits::Position as PositionTrait;
+use half::f16;
+
+use crate::position::{Komi, Position};
+use crate::tune::training::{label_human_games, read_games_from_file, read_human_games_from_ptn};
+
+const GAMES: &str = r#"
+[Size "4"]
+[Komi "0"]
+[Rating1 "1600"]
+[Rating2 "1500"]
+[Result "R-0"]
+
+1. d4 a4 2. a1 c4 3. b1 c3 4. c1 b3 5. d1 R-0
+
+[Size "4"]
+[Komi "0"]
+[Rating1 "1200"]
+[Rating2 "1500"]
+[Result "0-R"]
+
+1. a1 d4 2. b2 a2 3. c2 a3 4. d2 a4 0-R
+
+[Size "4"]
+[Komi "2"]
+[Result "1/2-1/2"]
+
+1. a1 d4 1/2-1/2
+
+[Size "4"]
+[Komi "seven"]
+[Result "R-0"]
+
+1. d4 a4 2. a1 c4 3. b1 c3 4. c1 b3 5. d1 R-0
+"#;
+
+fn read_games(komi: Komi, min_rating: Option<i64>) -> Vec<crate::ptn::Game<Position<4>>> {
+    let path = env::temp_dir().join(format!("tiltak_training_test_{}.ptn", std::process::id()));
+    fs::write(&path, GAMES).unwrap();
+    let games = read_human_games_from_ptn::<4>(path.to_str().unwrap(), komi, min_rating).unwrap();
+    fs::remove_file(&path).unwrap();
+    games
+}
+
+#[test]
+fn read_games_with_bad_komi_test() {
+    let path = env::temp_dir().join(format!(
+        "tiltak_training_komi_test_{}.ptn",
+        std::process::id()
+    ));
+    fs::write(&path, GAMES).unwrap();
+    let games = read_games_from_file::<4>(path.to_str().unwrap(), Komi::default()).unwrap();
+    fs::remove_file(&path).unwrap();
+    // The game with 2 komi and the game with a bad Komi tag are skipped
+    assert_eq!(games.len(), 2);
+}
+
+#[test]
+fn read_human_games_test() {
+    assert_eq!(read_games(Komi::default(), None).len(), 2);
+    assert_eq!(read_games(Komi::default(), Some(1400)).len(), 1);
+    let games = read_games(Komi::from_half_komi(4).unwrap(), None);
+    assert_eq!(games.len(), 1);
+    assert_eq!(
+        games[0].start_position.komi(),
+        Komi::from_half_komi(4).unwrap()
+    );
+}
+
+#[test]
+fn label_human_games_test() {
+    let games = read_games(Komi::default(), None);
+    let value_params = <Position<4>>::value_params(Komi::default());
+    let policy_params = <Position<4>>::policy_params(Komi::default());
+
+    let (positions, value_results, move_scoress) =
+        label_human_games(&games, None, value_params, policy_params);
+    assert_eq!(positions.len(), 9 + 8);
+    assert_eq!(value_results[0], f16::ONE);
+    assert_eq!(value_results[9], f16::ZERO);
+    assert_eq!(move_scoress.len(), 2);
+    // The played move gets all the policy score
+    let first_move_scores = &move_scoress[0][0];
+    assert_eq!(first_move_scores.len(), 16);
+    for (mv, score) in first_move_scores {
+        let expected = if *mv == games[0].moves[0].mv {
+            f16::ONE
+        } else {
+            f16::ZERO
+        };
+        assert_eq!(*score, expected);
+    }
+
+    let (positions, value_results, move_scoress) =
+        label_human_games(&games, Some(300), value_params, policy_params);
+    assert_eq!(positions.len(), 9 + 8);
+    // White can win immediately in the last position of the first game
+    assert!(value_results[8] > f16::from_f32(0.9));
+    let last_move_scores = &move_scoress[0][8];
+    let total: f32 = last_move_scores
+        .iter()
+        .map(|(_, score)| score.to_f32())
+        .sum();
+    assert!((total - 1.0).abs() < 0.01);
+    assert!(positions
+        .iter()
+        .all(|position| position.game_result().is_none()));
+}
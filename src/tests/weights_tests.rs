@@ -0,0 +1,164 @@
+use std::{env, fs};
+
+use crate::evaluation::parameters::{num_policy_features, num_value_features};
+use crate::evaluation::weights::{LoadedWeights, Weights, WeightsError, WeightsKind};
+use crate::position::{Komi, Position};
+use crate::search::{MctsSetting, MonteCarloTree};
+
+fn built_in_weights<const S: usize>(kind: WeightsKind, komi: Komi) -> Weights {
+    let params = match kind {
+        WeightsKind::Value => <Position<S>>::value_params(komi),
+        WeightsKind::Policy => <Position<S>>::policy_params(komi),
+    };
+    Weights::new(kind, S, komi, params.to_vec())
+}
+
+fn round_trip(weights: &Weights) -> Result<Weights, WeightsError> {
+    let mut buffer = vec![];
+    weights.write(&mut buffer).unwrap();
+    Weights::read(buffer.as_slice())
+}
+
+#[test]
+fn built_in_params_round_trip_test() {
+    for half_komi in [0, 3, 4] {
+        let komi = Komi::from_half_komi(half_komi).unwrap();
+        for kind in [WeightsKind::Value, WeightsKind::Policy] {
+            let all_weights = [
+                built_in_weights::<4>(kind, komi),
+                built_in_weights::<5>(kind, komi),
+                built_in_weights::<6>(kind, komi),
+                built_in_weights::<7>(kind, komi),
+                built_in_weights::<8>(kind, komi),
+            ];
+            for weights in all_weights {
+                let read_weights = round_trip(&weights).unwrap();
+                assert_eq!(read_weights, weights);
+                read_weights.validate().unwrap();
+            }
+        }
+    }
+}
+
+#[test]
+fn reject_invalid_weights_test() {
+    let komi = Komi::default();
+    let weights = built_in_weights::<6>(WeightsKind::Value, komi);
+
+    assert!(matches!(
+        weights.validate_as::<5>(WeightsKind::Value),
+        Err(WeightsError::WrongSize {
+            expected: 5,
+            actual: 6
+        })
+    ));
+    assert!(matches!(
+        weights.validate_as::<6>(WeightsKind::Policy),
+        Err(WeightsError::WrongKind { .. })
+    ));
+
+    let mut truncated = weights.clone();
+    truncated.params.pop();
+    assert!(matches!(
+        truncated.validate(),
+        Err(WeightsError::WrongFeatureCount { .. })
+    ));
+
+    let mut policy_as_value = built_in_weights::<6>(WeightsKind::Policy, komi);
+    policy_as_value.kind = WeightsKind::Value;
+    assert!(matches!(
+        policy_as_value.validate(),
+        Err(WeightsError::WrongFeatureCount {
+            expected,
+            actual,
+        }) if expected == num_value_features::<6>() && actual == num_policy_features::<6>()
+    ));
+
+    let mut not_finite = weights.clone();
+    not_finite.params[0] = f32::NAN;
+    assert!(matches!(
+        not_finite.validate(),
+        Err(WeightsError::NonFinite { index: 0 })
+    ));
+}
+
+#[test]
+fn reject_malformed_weights_file_test() {
+    let weights = built_in_weights::<5>(WeightsKind::Policy, Komi::default());
+    let mut buffer = vec![];
+    weights.write(&mut buffer).unwrap();
+    let text = String::from_utf8(buffer).unwrap();
+
+    let future_version = text.replacen("tiltak-weights 1", "tiltak-weights 2", 1);
+    assert!(matches!(
+        Weights::read(future_version.as_bytes()),
+        Err(WeightsError::UnsupportedVersion(2))
+    ));
+
+    let missing_param = text.trim_end().rsplit_once('\n').unwrap().0;
+    assert!(matches!(
+        Weights::read(missing_param.as_bytes()),
+        Err(WeightsError::Parse(_))
+    ));
+
+    assert!(matches!(
+        Weights::read("0.1\n0.2\n".as_bytes()),
+        Err(WeightsError::Parse(_))
+    ));
+}
+
+#[test]
+fn search_with_loaded_weights_test() {
+    let komi = Komi::from_half_komi(4).unwrap();
+    let dir = env::temp_dir().join(format!("tiltak_weights_test_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let value_path = dir.join("value.weights");
+    let policy_path = dir.join("policy.weights");
+    built_in_weights::<6>(WeightsKind::Value, komi)
+        .to_file(&value_path)
+        .unwrap();
+    built_in_weights::<6>(WeightsKind::Policy, komi)
+        .to_file(&policy_path)
+        .unwrap();
+
+    let loaded = LoadedWeights::load(value_path.to_str(), policy_path.to_str()).unwrap();
+    assert!(matches!(
+        LoadedWeights::load(policy_path.to_str(), None),
+        Err(WeightsError::WrongKind { .. })
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(loaded.sizes().collect::<Vec<_>>(), vec![6, 6]);
+    assert!(loaded.value_params::<5>().is_none());
+
+    // Searching with loaded copies of the built-in parameters gives the same result as the built-in parameters
+    let position = <Position<6>>::start_position_with_komi(komi);
+    let built_in_settings = MctsSetting::default()
+        .arena_size_for_nodes(1000)
+        .add_value_params(<Position<6>>::value_params(komi))
+        .add_policy_params(<Position<6>>::policy_params(komi));
+    let loaded_settings = MctsSetting::default()
+        .arena_size_for_nodes(1000)
+        .add_loaded_weights(&loaded, komi)
+        .unwrap();
+
+    // Weights for this size must be for the game's komi, but weights for other sizes are ignored
+    assert!(matches!(
+        <MctsSetting<6>>::default().add_loaded_weights(&loaded, Komi::default()),
+        Err(WeightsError::WrongKomi { .. })
+    ));
+    assert!(<MctsSetting<5>>::default()
+        .add_loaded_weights(&loaded, Komi::default())
+        .is_ok());
+
+    let mut built_in_tree = MonteCarloTree::new(position.clone(), built_in_settings);
+    let mut loaded_tree = MonteCarloTree::new(position, loaded_settings);
+    built_in_tree.search_nodes(1000).unwrap();
+    loaded_tree.search_nodes(1000).unwrap();
+    assert_eq!(
+        built_in_tree.pv().collect::<Vec<_>>(),
+        loaded_tree.pv().collect::<Vec<_>>()
+    );
+    assert_eq!(built_in_tree.best_move(), loaded_tree.best_move());
+}
+
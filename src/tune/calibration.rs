@@ -0,0 +1,155 @@
+//! Measure the strength of the handicap levels in [`strength`], by playing them against each other.
+//!
+//! Every pair of levels that are close enough plays a match, and Elo ratings are fitted to all the match results at once.
+//! This gives each level a rating relative to the weakest level, even if it never played it directly.
+//!
+//! [`strength`]: crate::search::strength
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use board_game_traits::Position as PositionTrait;
+
+use crate::position::{Komi, Move, Position};
+use crate::search::strength::StrengthLevel;
+use crate::search::{MctsSetting, TimeControl};
+use crate::tune::engine_match::{self, elo_to_score, MatchPlayer, MatchScore};
+
+/// A match between two levels, with the score from `player1`'s perspective
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pairing {
+    pub player1: usize,
+    pub player2: usize,
+    pub score: MatchScore,
+}
+
+/// A match player for the level. Levels without a node limit search `full_strength_nodes` nodes per move
+pub fn level_player<const S: usize>(
+    strength: StrengthLevel,
+    full_strength_nodes: u64,
+) -> MatchPlayer<S> {
+    let nodes = strength.max_nodes.unwrap_or(full_strength_nodes);
+    MatchPlayer {
+        name: format!("level{}", strength.level),
+        settings: MctsSetting::default()
+            .strength(strength)
+            // Very small searches still need room to expand the root and its children
+            .arena_size_for_nodes(nodes.max(1000) as u32),
+        time_control: TimeControl::FixedNodes(nodes),
+    }
+}
+
+/// Play `games_per_pairing` games between every pair of levels that are at most `max_distance` levels apart,
+/// and return the fitted Elo rating of each level, relative to the first one.
+/// Every game is written to `ptn_output`
+pub fn calibrate_levels<const S: usize, W: Write + Send>(
+    levels: &[StrengthLevel],
+    full_strength_nodes: u64,
+    komi: Komi,
+    openings: &[Vec<Move<S>>],
+    games_per_pairing: u64,
+    max_distance: usize,
+    mut ptn_output: W,
+) -> io::Result<Vec<f64>> {
+    let players: Vec<MatchPlayer<S>> = levels
+        .iter()
+        .map(|strength| level_player(*strength, full_strength_nodes))
+        .collect();
+
+    let mut pairings = vec![];
+    for player1 in 0..players.len() {
+        for player2 in player1 + 1..players.len().min(player1 + max_distance + 1) {
+            let score = engine_match::run_match(
+                &players[player1],
+                &players[player2],
+                komi,
+                openings,
+                games_per_pairing,
+                None,
+                &mut ptn_output,
+            )?;
+            println!(
+                "{} vs {}: {}",
+                players[player1].name, players[player2].name, score
+            );
+            pairings.push(Pairing {
+                player1,
+                player2,
+                score,
+            });
+        }
+    }
+    Ok(fit_ratings(players.len(), &pairings))
+}
+
+/// Fit Elo ratings to the results of several matches, relative to the first player.
+/// Each pairing counts an extra draw, so that players who won or lost every game still get finite ratings.
+/// Players without any games are rated 0
+pub fn fit_ratings(num_players: usize, pairings: &[Pairing]) -> Vec<f64> {
+    let mut ratings = vec![0.0; num_players];
+    for _ in 0..1000 {
+        let mut max_change: f64 = 0.0;
+        // The first player's rating is fixed at 0
+        for player in 1..num_players {
+            // One Newton step on the log-likelihood of `player`'s results, keeping the other ratings fixed
+            let mut points_above_expected = 0.0;
+            let mut variance = 0.0;
+            for pairing in pairings {
+                let (opponent, points) = if pairing.player1 == player {
+                    (
+                        pairing.player2,
+                        pairing.score.wins as f64 + pairing.score.draws as f64 / 2.0,
+                    )
+                } else if pairing.player2 == player {
+                    (
+                        pairing.player1,
+                        pairing.score.losses as f64 + pairing.score.draws as f64 / 2.0,
+                    )
+                } else {
+                    continue;
+                };
+                let games = pairing.score.games() as f64 + 1.0;
+                let expected = elo_to_score(ratings[player] - ratings[opponent]);
+                points_above_expected += points + 0.5 - games * expected;
+                variance += games * expected * (1.0 - expected);
+            }
+            if variance > 0.0 {
+                let change = points_above_expected / variance * 400.0 / 10.0_f64.ln();
+                ratings[player] += change;
+                max_change = max_change.max(change.abs());
+            }
+        }
+        if max_change < 0.01 {
+            break;
+        }
+    }
+    ratings
+}
+
+/// Every opening of two flat placements, skipping openings that are symmetrical to an earlier one
+pub fn two_ply_openings<const S: usize>() -> Vec<Vec<Move<S>>> {
+    let mut openings = vec![];
+    let mut seen_positions = HashSet::new();
+    let mut position = <Position<S>>::start_position();
+    let mut first_moves = vec![];
+    position.generate_moves(&mut first_moves);
+    for first_move in first_moves {
+        let first_reverse_move = position.do_move(first_move);
+        let mut second_moves = vec![];
+        position.generate_moves(&mut second_moves);
+        for second_move in second_moves {
+            let second_reverse_move = position.do_move(second_move);
+            if position
+                .symmetries()
+                .iter()
+                .all(|symmetry| !seen_positions.contains(symmetry))
+            {
+                seen_positions.insert(position.clone());
+                openings.push(vec![first_move, second_move]);
+            }
+            position.reverse_move(second_reverse_move);
+        }
+        position.reverse_move(first_reverse_move);
+    }
+    openings
+}
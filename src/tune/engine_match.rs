@@ -0,0 +1,398 @@
+//! Play matches between two engine configurations, to check whether a change makes the engine stronger.
+//!
+//! Each opening is played twice, once with each engine as white, to cancel out unbalanced openings.
+//! The match reports the Elo difference with a 95% confidence interval,
+//! and can stop early with a sequential probability ratio test (SPRT), once the result is statistically clear.
+
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use board_game_traits::{Color, GameResult, Position as PositionTrait};
+use chrono::Datelike;
+use pgn_traits::PgnPosition;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rayon::prelude::*;
+
+use crate::position::{ExpMove, Komi, Move, Position, Role};
+use crate::ptn::{Game, PtnMove};
+use crate::search::time_management::TimeManager;
+use crate::search::{MctsSetting, MonteCarloTree, TimeControl};
+
+/// Games are adjudicated as draws after this many plies
+const MAX_PLIES: usize = 400;
+
+/// Length of the random openings used when a match has no openings
+pub const RANDOM_OPENING_PLIES: usize = 4;
+
+/// One side of a match
+#[derive(Clone, Debug)]
+pub struct MatchPlayer<const S: usize> {
+    pub name: String,
+    pub settings: MctsSetting<S>,
+    pub time_control: TimeControl,
+}
+
+/// Wins, draws and losses from the first player's perspective
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: u64,
+    pub draws: u64,
+    pub losses: u64,
+}
+
+impl MatchScore {
+    pub fn games(&self) -> u64 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Add a game result, where `first_player_color` is the color the first player had in the game.
+    /// Unfinished games count as draws
+    pub fn add_game(&mut self, game_result: Option<GameResult>, first_player_color: Color) {
+        match (game_result, first_player_color) {
+            (Some(GameResult::WhiteWin), Color::White)
+            | (Some(GameResult::BlackWin), Color::Black) => self.wins += 1,
+            (Some(GameResult::WhiteWin), Color::Black)
+            | (Some(GameResult::BlackWin), Color::White) => self.losses += 1,
+            (Some(GameResult::Draw), _) | (None, _) => self.draws += 1,
+        }
+    }
+
+    /// The first player's average score per game, from 0.0 to 1.0
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// Variance of the score of a single game
+    fn variance(&self) -> f64 {
+        let score = self.score();
+        let games = self.games() as f64;
+        (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / games
+    }
+
+    /// The first player's Elo advantage
+    pub fn elo(&self) -> f64 {
+        score_to_elo(self.score())
+    }
+
+    /// Half the width of the 95% confidence interval of `elo`
+    pub fn elo_error(&self) -> f64 {
+        let score = self.score();
+        let error = 1.96 * (self.variance() / self.games() as f64).sqrt();
+        (score_to_elo(score + error) - score_to_elo(score - error)) / 2.0
+    }
+}
+
+impl Display for MatchScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "+{}-{}={}, {:.1}%, {:.1} +/- {:.1} Elo",
+            self.wins,
+            self.losses,
+            self.draws,
+            self.score() * 100.0,
+            self.elo(),
+            self.elo_error()
+        )
+    }
+}
+
+/// Convert an average score to an Elo difference, clamped to avoid infinities
+pub fn score_to_elo(score: f64) -> f64 {
+    let score = score.clamp(0.001, 0.999);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+pub fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10.0_f64.powf(-elo / 400.0))
+}
+
+/// A sequential probability ratio test, of whether the first player is `elo0` or `elo1` Elo stronger.
+/// `alpha` and `beta` are the probabilities of accepting `elo1` when `elo0` is true, and the reverse
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SprtResult {
+    /// The first player is `elo0` Elo stronger or worse
+    AcceptH0,
+    /// The first player is `elo1` Elo stronger or better
+    AcceptH1,
+    Continue,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64) -> Self {
+        Sprt {
+            elo0,
+            elo1,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+
+    /// The log-likelihood ratio of the score, using a normal approximation of the score distribution.
+    /// This is 0.0 until the games have had different results, since the variance is unknown until then
+    pub fn llr(&self, score: &MatchScore) -> f64 {
+        let variance = score.variance();
+        if score.games() == 0 || variance <= 0.0 {
+            return 0.0;
+        }
+        let score0 = elo_to_score(self.elo0);
+        let score1 = elo_to_score(self.elo1);
+        score.games() as f64 * (score1 - score0) * (2.0 * score.score() - score0 - score1)
+            / (2.0 * variance)
+    }
+
+    /// The LLR bounds for accepting H0 and H1
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    pub fn result(&self, score: &MatchScore) -> SprtResult {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr <= lower {
+            SprtResult::AcceptH0
+        } else if llr >= upper {
+            SprtResult::AcceptH1
+        } else {
+            SprtResult::Continue
+        }
+    }
+}
+
+/// Play a single game, where each player searches with its own settings and time control.
+/// Each player always plays the move with the most visits
+pub fn play_match_game<const S: usize>(
+    white: &MatchPlayer<S>,
+    black: &MatchPlayer<S>,
+    komi: Komi,
+    opening: &[Move<S>],
+) -> Game<Position<S>> {
+    let mut position = <Position<S>>::start_position_with_komi(komi);
+    let mut moves: Vec<PtnMove<Move<S>>> = vec![];
+    for mv in opening {
+        position.do_move(*mv);
+        moves.push(PtnMove {
+            comments: vec!["book".to_string()],
+            ..PtnMove::new(*mv)
+        });
+    }
+
+    let time_left = |time_control: &TimeControl| match time_control {
+        TimeControl::FixedNodes(_) => Duration::MAX,
+        TimeControl::Time(time, _) => *time,
+    };
+    let mut white_time_left = time_left(&white.time_control);
+    let mut black_time_left = time_left(&black.time_control);
+
+    while position.game_result().is_none() && moves.len() < MAX_PLIES {
+        let (player, time_left) = match position.side_to_move() {
+            Color::White => (white, &mut white_time_left),
+            Color::Black => (black, &mut black_time_left),
+        };
+        let start_time = Instant::now();
+        let tree = match player.time_control {
+            TimeControl::FixedNodes(nodes) => {
+                let mut tree = MonteCarloTree::new(position.clone(), player.settings.clone());
+                if let Err(err) = tree.search_nodes(nodes) {
+                    eprintln!("Warning: {err}");
+                }
+                tree
+            }
+            TimeControl::Time(_, increment) => {
+                let deadlines = TimeManager::default()
+                    .deadlines::<S>(
+                        &TimeControl::Time(*time_left, increment),
+                        position.half_moves_played(),
+                    )
+                    .unwrap();
+                // Give enough memory for roughly 200K nps
+                let max_nodes =
+                    (deadlines.hard.as_secs_f64().ceil() as u32).saturating_mul(200_000);
+                let mut tree = MonteCarloTree::new(
+                    position.clone(),
+                    player.settings.clone().arena_size(
+                        max_nodes
+                            .saturating_mul((S * S) as u32 * 3)
+                            .min(2_u32.pow(31)),
+                    ),
+                );
+                tree.search_until_deadlines(deadlines, &AtomicBool::new(false), |_| {});
+                tree
+            }
+        };
+        let (best_move, score) = tree.best_move().unwrap();
+
+        if let TimeControl::Time(_, increment) = player.time_control {
+            *time_left = time_left.saturating_sub(start_time.elapsed()) + increment;
+        }
+        position.do_move(best_move);
+        moves.push(PtnMove {
+            comments: vec![format!("{:.3}", score)],
+            ..PtnMove::new(best_move)
+        });
+    }
+
+    let date = chrono::Local::now();
+    let tags = vec![
+        ("Event".to_string(), "Tiltak match".to_string()),
+        ("Site".to_string(), "Tiltak".to_string()),
+        ("Player1".to_string(), white.name.clone()),
+        ("Player2".to_string(), black.name.clone()),
+        ("Size".to_string(), S.to_string()),
+        (
+            "Date".to_string(),
+            format!("{}.{:0>2}.{:0>2}", date.year(), date.month(), date.day()),
+        ),
+        ("Komi".to_string(), komi.to_string()),
+    ];
+
+    Game {
+        start_position: <Position<S>>::start_position_with_komi(komi),
+        moves,
+        game_result_str: position.pgn_game_result(),
+        tags,
+        leading_comments: vec![],
+    }
+}
+
+/// Up to `count` random openings of `plies` flat placements each, where no two openings are symmetrical to each other.
+/// Returns fewer openings if it fails to find enough different ones
+pub fn random_openings<const S: usize, R: Rng>(
+    count: usize,
+    plies: usize,
+    komi: Komi,
+    rng: &mut R,
+) -> Vec<Vec<Move<S>>> {
+    let mut openings = vec![];
+    let mut seen_positions = HashSet::new();
+    for _ in 0..count.saturating_mul(10) {
+        if openings.len() >= count {
+            break;
+        }
+        let mut position = <Position<S>>::start_position_with_komi(komi);
+        let mut opening = vec![];
+        for _ in 0..plies {
+            let mut moves = vec![];
+            position.generate_moves(&mut moves);
+            moves.retain(|mv| matches!(mv.expand(), ExpMove::Place(Role::Flat, _)));
+            let Some(mv) = moves.choose(rng) else {
+                break;
+            };
+            position.do_move(*mv);
+            opening.push(*mv);
+        }
+        if position.game_result().is_none()
+            && position
+                .symmetries()
+                .iter()
+                .all(|symmetry| !seen_positions.contains(symmetry))
+        {
+            seen_positions.insert(position);
+            openings.push(opening);
+        }
+    }
+    openings
+}
+
+/// Play up to `num_games` games between `player1` and `player2` on rayon threads, and return the score from `player1`'s perspective.
+/// Each opening is played with both colors, and the openings are repeated if there are too few of them.
+/// Without any openings, every pair of games starts from a different random opening of `RANDOM_OPENING_PLIES` flat placements,
+/// because players that search a fixed number of nodes would otherwise repeat the same game.
+/// Every game is written to `ptn_output` as it finishes.
+/// With `sprt`, no new games are started once the test has a result, but games that are already running are finished
+pub fn run_match<const S: usize, W: Write + Send>(
+    player1: &MatchPlayer<S>,
+    player2: &MatchPlayer<S>,
+    komi: Komi,
+    openings: &[Vec<Move<S>>],
+    num_games: u64,
+    sprt: Option<Sprt>,
+    ptn_output: W,
+) -> io::Result<MatchScore> {
+    let random_openings = if openings.is_empty() {
+        random_openings(
+            num_games.div_ceil(2) as usize,
+            RANDOM_OPENING_PLIES,
+            komi,
+            &mut rand::thread_rng(),
+        )
+    } else {
+        vec![]
+    };
+    let openings = if openings.is_empty() {
+        &random_openings[..]
+    } else {
+        openings
+    };
+    let score = Mutex::new(MatchScore::default());
+    let ptn_output = Mutex::new(ptn_output);
+    let is_finished = AtomicBool::new(false);
+    let start_time = Instant::now();
+
+    (0..num_games.div_ceil(2))
+        .into_par_iter()
+        .try_for_each(|pair_id| -> io::Result<()> {
+            let opening = &openings[pair_id as usize % openings.len()];
+            for player1_color in [Color::White, Color::Black] {
+                if is_finished.load(Ordering::SeqCst) {
+                    return Ok(());
+                }
+                let game = match player1_color {
+                    Color::White => play_match_game(player1, player2, komi, opening),
+                    Color::Black => play_match_game(player2, player1, komi, opening),
+                };
+                game.game_to_ptn(&mut *ptn_output.lock().unwrap())?;
+
+                let mut score = score.lock().unwrap();
+                score.add_game(game.game_result(), player1_color);
+                let sprt_result = sprt.map(|sprt| sprt.result(&score));
+                match sprt {
+                    Some(sprt) => println!(
+                        "{} vs {}: {}, LLR {:.2} ({:.2}, {:.2}), {}s",
+                        player1.name,
+                        player2.name,
+                        *score,
+                        sprt.llr(&score),
+                        sprt.bounds().0,
+                        sprt.bounds().1,
+                        start_time.elapsed().as_secs()
+                    ),
+                    None => println!(
+                        "{} vs {}: {}, {}s",
+                        player1.name,
+                        player2.name,
+                        *score,
+                        start_time.elapsed().as_secs()
+                    ),
+                }
+                if score.games() >= num_games
+                    || sprt_result.is_some_and(|result| result != SprtResult::Continue)
+                {
+                    is_finished.store(true, Ordering::SeqCst);
+                }
+            }
+            Ok(())
+        })?;
+
+    ptn_output.into_inner().unwrap().flush()?;
+    Ok(score.into_inner().unwrap())
+}
@@ -1,5 +1,7 @@
+pub mod calibration;
+pub mod engine_match;
 pub mod gradient_descent;
-mod openings;
+pub mod openings;
 pub mod play_match;
 pub mod spsa;
 pub mod training;
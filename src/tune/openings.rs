@@ -1,3 +1,6 @@
+//! Read opening lines for tuning matches, one opening per line, with moves in PTN notation separated by whitespace.
+//! Opening book files, as written by `OpeningBook`, can also be used, and give every line in the book.
+
 use std::fs;
 use std::io;
 use std::io::BufRead;
@@ -5,12 +8,19 @@ use std::io::BufRead;
 use board_game_traits::Position as PositionTrait;
 use pgn_traits::PgnPosition;
 
+use crate::book::{self, OpeningBook};
 use crate::position::Komi;
 use crate::position::Move;
 use crate::position::Position;
 
+/// Lines from opening books are cut off after this many plies
+pub const MAX_BOOK_OPENING_PLIES: usize = 6;
+
 pub fn openings_from_file<const S: usize>(path: &str, komi: Komi) -> io::Result<Vec<Vec<Move<S>>>> {
-    let reader = io::BufReader::new(fs::File::open(path)?);
+    let mut reader = io::BufReader::new(fs::File::open(path)?);
+    if reader.fill_buf()?.starts_with(book::MAGIC.as_bytes()) {
+        return openings_from_book(reader, komi);
+    }
     let mut openings = vec![];
 
     for line in reader.lines() {
@@ -35,3 +45,21 @@ pub fn openings_from_file<const S: usize>(path: &str, komi: Komi) -> io::Result<
     }
     Ok(openings)
 }
+
+fn openings_from_book<const S: usize, R: BufRead>(
+    reader: R,
+    komi: Komi,
+) -> io::Result<Vec<Vec<Move<S>>>> {
+    let book =
+        OpeningBook::read(reader).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    if book.size != S || book.komi != komi {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Opening book is for {}s with {} komi, expected {}s with {} komi",
+                book.size, book.komi, S, komi
+            ),
+        ));
+    }
+    Ok(book.lines(MAX_BOOK_OPENING_PLIES))
+}
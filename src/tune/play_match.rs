@@ -62,7 +62,7 @@ pub fn play_game<const S: usize>(
             (TimeControl::Time(_, _), Color::Black) => search::mcts_training::<S>(
                 position.clone(),
                 &TimeControl::Time(black_time_left, increment),
-                white_settings.clone(),
+                black_settings.clone(),
             ),
         };
 
@@ -117,16 +117,10 @@ pub fn play_game<const S: usize>(
     (
         Game {
             start_position: Position::start_position_with_komi(komi),
-            moves: game_moves
-                .into_iter()
-                .map(|mv| PtnMove {
-                    mv,
-                    annotations: vec![],
-                    comment: String::new(),
-                })
-                .collect::<Vec<_>>(),
+            moves: game_moves.into_iter().map(PtnMove::new).collect::<Vec<_>>(),
             game_result_str: position.pgn_game_result(),
             tags,
+            leading_comments: vec![],
         },
         move_scores,
     )
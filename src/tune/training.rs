@@ -1,9 +1,9 @@
-use std::convert::TryFrom;
 use std::io::Read;
 use std::io::Write;
 use std::mem;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time;
 use std::{error, fs, io};
 
@@ -14,10 +14,11 @@ use crate::evaluation::parameters::ValueApplier;
 use crate::evaluation::policy_eval;
 use crate::evaluation::policy_eval::policy_offset;
 use crate::evaluation::value_eval;
+use crate::evaluation::weights::{Weights, WeightsKind};
 use crate::position::Komi;
 use crate::search::TimeControl;
-use board_game_traits::GameResult;
 use board_game_traits::Position as PositionTrait;
+use board_game_traits::{Color, GameResult};
 use half::f16;
 use rand::prelude::*;
 use rayon::prelude::*;
@@ -26,7 +27,7 @@ use crate::position::Move;
 use crate::position::Position;
 use crate::ptn::Game;
 use crate::ptn::{ptn_parser, PtnMove};
-use crate::search::MctsSetting;
+use crate::search::{MctsSetting, MonteCarloTree};
 use crate::tune::gradient_descent;
 use crate::tune::gradient_descent::TrainingSample;
 use crate::tune::play_match::play_game;
@@ -68,6 +69,8 @@ pub fn train_from_scratch<const S: usize, const N: usize, const M: usize>(
 pub fn continue_training<const S: usize, const N: usize, const M: usize>(
     options: TrainingOptions,
     komi: Komi,
+    initial_value_params: [f32; N],
+    initial_policy_params: [f32; M],
 ) -> Result<(), DynError> {
     let mut games = vec![];
     let mut move_scores = vec![];
@@ -104,15 +107,11 @@ pub fn continue_training<const S: usize, const N: usize, const M: usize>(
         move_scores.iter().map(Vec::len).sum::<usize>()
     );
 
-    let value_params = <Position<S>>::value_params(komi);
-
-    let policy_params = <Position<S>>::policy_params(komi);
-
     train_perpetually::<S, N, M>(
         options,
         komi,
-        <[f32; N]>::try_from(value_params).unwrap(),
-        <[f32; M]>::try_from(policy_params).unwrap(),
+        initial_value_params,
+        initial_policy_params,
         games,
         move_scores,
         batch_id,
@@ -128,11 +127,11 @@ pub fn train_perpetually<const S: usize, const N: usize, const M: usize>(
     mut all_move_scores: Vec<MoveScoresForGame<S>>,
     mut batch_id: usize,
 ) -> Result<(), DynError> {
-    let mut last_value_params: &'static [f32; N] = Box::leak(Box::new(initial_value_params));
-    let mut last_policy_params: &'static [f32; M] = Box::leak(Box::new(initial_policy_params));
+    let mut last_value_params: Arc<[f32]> = Arc::new(initial_value_params);
+    let mut last_policy_params: Arc<[f32]> = Arc::new(initial_policy_params);
 
-    let mut value_params: &'static [f32; N] = last_value_params;
-    let mut policy_params: &'static [f32; M] = last_policy_params;
+    let mut value_params = last_value_params.clone();
+    let mut policy_params = last_policy_params.clone();
 
     let start_time = time::Instant::now();
     let mut playing_time = time::Duration::default();
@@ -148,10 +147,10 @@ pub fn train_perpetually<const S: usize, const N: usize, const M: usize>(
             .map(|i| {
                 play_game_pair::<S>(
                     komi,
-                    last_value_params,
-                    last_policy_params,
-                    value_params,
-                    policy_params,
+                    &last_value_params,
+                    &last_policy_params,
+                    &value_params,
+                    &policy_params,
                     &current_params_wins,
                     &last_params_wins,
                     i,
@@ -239,15 +238,21 @@ pub fn train_perpetually<const S: usize, const N: usize, const M: usize>(
             &games_in_training_batch,
             &move_scores_in_training_batch,
             komi,
-            value_params,
-            policy_params,
+            value_params[..].try_into().unwrap(),
+            policy_params[..].try_into().unwrap(),
         )?;
 
-        last_value_params = value_params;
-        last_policy_params = policy_params;
+        last_value_params = mem::replace(&mut value_params, Arc::new(new_value_params));
+        last_policy_params = mem::replace(&mut policy_params, Arc::new(new_policy_params));
 
-        value_params = Box::leak(Box::new(new_value_params));
-        policy_params = Box::leak(Box::new(new_policy_params));
+        Weights::new(WeightsKind::Value, S, komi, value_params.to_vec()).to_file(format!(
+            "value{}_{}s_batch{}.weights",
+            options.training_id, S, batch_id
+        ))?;
+        Weights::new(WeightsKind::Policy, S, komi, policy_params.to_vec()).to_file(format!(
+            "policy{}_{}s_batch{}.weights",
+            options.training_id, S, batch_id
+        ))?;
 
         tuning_time += value_tuning_start_time.elapsed();
 
@@ -264,23 +269,23 @@ pub fn train_perpetually<const S: usize, const N: usize, const M: usize>(
 #[allow(clippy::too_many_arguments)]
 fn play_game_pair<const S: usize>(
     komi: Komi,
-    last_value_params: &'static [f32],
-    last_policy_params: &'static [f32],
-    value_params: &'static [f32],
-    policy_params: &'static [f32],
+    last_value_params: &Arc<[f32]>,
+    last_policy_params: &Arc<[f32]>,
+    value_params: &Arc<[f32]>,
+    policy_params: &Arc<[f32]>,
     current_params_wins: &AtomicU64,
     last_params_wins: &AtomicU64,
     i: usize,
 ) -> (Game<Position<S>>, MoveScoresForGame<S>) {
     let settings = MctsSetting::default()
-        .add_value_params(value_params)
-        .add_policy_params(policy_params)
+        .add_value_params(value_params.clone())
+        .add_policy_params(policy_params.clone())
         .add_dirichlet(0.2);
     let last_settings = MctsSetting::default()
-        .add_value_params(last_value_params)
-        .add_policy_params(last_policy_params)
+        .add_value_params(last_value_params.clone())
+        .add_policy_params(last_policy_params.clone())
         .add_dirichlet(0.2);
-    if i % 2 == 0 {
+    if i.is_multiple_of(2) {
         let game = play_game::<S>(
             &settings,
             &last_settings,
@@ -352,17 +357,30 @@ pub fn read_games_from_file<const S: usize>(
     let mut file = fs::File::open(file_name)?;
     let mut input = String::new();
     file.read_to_string(&mut input)?;
-    let mut games = ptn_parser::parse_ptn::<Position<S>>(&input)?;
-    for game in games.iter_mut() {
-        if let Some((_, komi_str)) = game
-            .tags
-            .iter()
-            .find(|(tag, _)| tag.eq_ignore_ascii_case("Komi"))
-        {
-            game.start_position
-                .set_komi(Komi::from_str(komi_str).unwrap());
+    let mut games = read_ptn_lenient::<S>(file_name, &input);
+    let num_games = games.len();
+    games.retain_mut(|game| match set_komi_from_tag(game) {
+        Ok(()) if game.start_position.komi() == komi => true,
+        Ok(()) => {
+            eprintln!(
+                "Warning: Skipped game in {} with {} komi, expected {} komi",
+                file_name,
+                game.start_position.komi(),
+                komi
+            );
+            false
+        }
+        Err(err) => {
+            eprintln!("Warning: Skipped game in {}: {}", file_name, err);
+            false
         }
-        assert_eq!(komi, game.start_position.komi());
+    });
+    if games.len() < num_games {
+        eprintln!(
+            "Skipped {} games with a bad or different komi in {}",
+            num_games - games.len(),
+            file_name
+        );
     }
     println!(
         "Read {} games from PTN in {:.1}s",
@@ -450,15 +468,36 @@ pub fn tune_value_and_policy<const S: usize, const N: usize, const M: usize>(
     initial_value_params: &[f32; N],
     initial_policy_params: &[f32; M],
 ) -> Result<([f32; N], [f32; M]), DynError> {
-    let mut rng = rand::rngs::StdRng::from_seed(Default::default());
-
     let (positions, results) = positions_and_results_from_games(games, komi);
+    let value_results: Vec<f16> = results.into_iter().map(game_result_to_f16).collect();
+
+    tune_value_and_policy_with_value_results(
+        &positions,
+        &value_results,
+        games,
+        move_scoress,
+        initial_value_params,
+        initial_policy_params,
+    )
+}
+
+/// Tune value parameters on `positions`, labelled with `value_results` from white's perspective,
+/// and policy parameters on the moves of `games`, labelled with `move_scoress`
+fn tune_value_and_policy_with_value_results<const S: usize, const N: usize, const M: usize>(
+    positions: &[Position<S>],
+    value_results: &[f16],
+    games: &[Game<Position<S>>],
+    move_scoress: &[MoveScoresForGame<S>],
+    initial_value_params: &[f32; N],
+    initial_policy_params: &[f32; M],
+) -> Result<([f32; N], [f32; M]), DynError> {
+    let mut rng = rand::rngs::StdRng::from_seed(Default::default());
 
     let start_time = time::Instant::now();
     let mut value_training_samples = positions
         .par_iter()
-        .zip(results)
-        .map(|(position, game_result)| {
+        .zip(value_results)
+        .map(|(position, result)| {
             let mut white_features: Value<S> = Value::new(&[]);
             let mut black_features: Value<S> = Value::new(&[]);
             position.static_eval_features(&mut white_features, &mut black_features);
@@ -471,15 +510,10 @@ pub fn tune_value_and_policy<const S: usize, const N: usize, const M: usize>(
                 .try_into()
                 .unwrap();
 
-            let result = match game_result {
-                GameResult::WhiteWin => f16::ONE,
-                GameResult::Draw => f16::ONE / (f16::ONE + f16::ONE),
-                GameResult::BlackWin => f16::ZERO,
-            };
             TrainingSample {
                 features,
                 offset: 0.0,
-                result,
+                result: *result,
             }
         })
         .collect::<Vec<_>>();
@@ -512,10 +546,12 @@ pub fn tune_value_and_policy<const S: usize, const N: usize, const M: usize>(
                 .flat_map(move |(mv, move_scores)| {
                     let group_data = position.group_data();
 
-                    let mut policies: Vec<Policy<S>> = vec![Policy::new(&[]); move_scores.len()];
+                    let mut policies: Vec<Policy<S>> = vec![Policy::new(); move_scores.len()];
                     let moves: Vec<Move<S>> = move_scores.iter().map(|(mv, _score)| *mv).collect();
 
+                    // The features are used directly, so no parameters are needed
                     position.features_for_moves(
+                        &[],
                         &mut policies,
                         &moves,
                         &mut Vec::with_capacity(moves.len()),
@@ -599,6 +635,206 @@ pub fn tune_value_and_policy_from_file<const S: usize, const N: usize, const M:
 
 type DynError = Box<dyn error::Error + Send + Sync>;
 
+/// Parse the games in a PTN file, skipping malformed games with a warning
+fn read_ptn_lenient<const S: usize>(file_name: &str, input: &str) -> Vec<Game<Position<S>>> {
+    let (games, errors) = ptn_parser::parse_ptn_lenient::<Position<S>>(input);
+    for err in errors.iter() {
+        eprintln!("Warning: Skipped game in {}: {}", file_name, err);
+    }
+    if !errors.is_empty() {
+        eprintln!(
+            "Skipped {} malformed games in {}, read {} games",
+            errors.len(),
+            file_name,
+            games.len()
+        );
+    }
+    games
+}
+
+/// Set the game's komi from its `Komi` tag, if it has one
+fn set_komi_from_tag<const S: usize>(game: &mut Game<Position<S>>) -> Result<(), String> {
+    if let Some((_, komi_str)) = game
+        .tags
+        .iter()
+        .find(|(tag, _)| tag.eq_ignore_ascii_case("Komi"))
+    {
+        let komi = Komi::from_str(komi_str).map_err(|err| format!("Bad Komi tag: {}", err))?;
+        game.start_position.set_komi(komi);
+    }
+    Ok(())
+}
+
+/// Read human games from a PTN file, such as a playtak.com PTN export.
+/// Unlike `read_games_from_file`, games with a different komi are skipped without a warning, because exports usually mix several komi.
+/// Unfinished games are also skipped, and if `min_rating` is set,
+/// games where either player is rated lower, or that have no `Rating1` and `Rating2` tags
+pub fn read_human_games_from_ptn<const S: usize>(
+    file_name: &str,
+    komi: Komi,
+    min_rating: Option<i64>,
+) -> Result<Vec<Game<Position<S>>>, DynError> {
+    let input = fs::read_to_string(file_name)?;
+    let mut games = read_ptn_lenient::<S>(file_name, &input);
+    let num_games = games.len();
+    games.retain_mut(|game| {
+        set_komi_from_tag(game)
+            .inspect_err(|err| eprintln!("Warning: Skipped game in {}: {}", file_name, err))
+            .is_ok()
+    });
+    let rating = |game: &Game<Position<S>>, tag_name: &str| {
+        game.tags
+            .iter()
+            .find(|(tag, _)| tag.eq_ignore_ascii_case(tag_name))
+            .and_then(|(_, rating)| rating.parse::<i64>().ok())
+    };
+    games.retain(|game| {
+        game.start_position.komi() == komi
+            && game.game_result().is_some()
+            && min_rating.is_none_or(|min_rating| {
+                rating(game, "Rating1").is_some_and(|rating| rating >= min_rating)
+                    && rating(game, "Rating2").is_some_and(|rating| rating >= min_rating)
+            })
+    });
+    println!(
+        "Read {} games from {}, and kept {} games with {} komi",
+        num_games,
+        file_name,
+        games.len(),
+        komi
+    );
+    Ok(games)
+}
+
+/// Label every position in the games for training, and return the positions,
+/// their value labels from white's perspective, and the move scores for each move.
+///
+/// By default, positions are labelled with the game result, and the played move gets all the policy score.
+/// With `relabel_nodes`, each position is instead searched for that many nodes with the given parameters,
+/// and labelled with the search's winning probability and visit distribution.
+/// If a search fails, that position falls back to the game result labels
+pub fn label_human_games<const S: usize>(
+    games: &[Game<Position<S>>],
+    relabel_nodes: Option<u32>,
+    value_params: &[f32],
+    policy_params: &[f32],
+) -> (Vec<Position<S>>, Vec<f16>, Vec<MoveScoresForGame<S>>) {
+    let value_params: Arc<[f32]> = value_params.into();
+    let policy_params: Arc<[f32]> = policy_params.into();
+    let start_time = time::Instant::now();
+    let labelled_positions = AtomicU64::new(0);
+
+    let labelled_games: Vec<(Vec<Position<S>>, Vec<f16>, MoveScoresForGame<S>)> = games
+        .par_iter()
+        .map(|game| {
+            let game_result = game_result_to_f16(game.game_result().unwrap_or(GameResult::Draw));
+            let mut position = game.start_position.clone();
+            let mut positions = vec![];
+            let mut value_results = vec![];
+            let mut move_scores = vec![];
+
+            for PtnMove { mv, .. } in game.moves.iter() {
+                if position.game_result().is_some() {
+                    break;
+                }
+                let search_labels = relabel_nodes.and_then(|nodes| {
+                    let settings = MctsSetting::default()
+                        .arena_size_for_nodes(nodes)
+                        .add_value_params(value_params.clone())
+                        .add_policy_params(policy_params.clone());
+                    let mut tree = MonteCarloTree::new(position.clone(), settings);
+                    if let Err(err) = tree.search_nodes(nodes as u64) {
+                        eprintln!("Warning: {err}, using the game result instead");
+                        return None;
+                    }
+                    let (_, score) = tree.best_move()?;
+                    let white_score = match position.side_to_move() {
+                        Color::White => score,
+                        Color::Black => 1.0 - score,
+                    };
+                    Some((f16::from_f32(white_score), tree.training_move_scores()))
+                });
+                let (value_result, scores) = match search_labels {
+                    Some(labels) => labels,
+                    None => {
+                        let mut legal_moves = vec![];
+                        position.generate_moves(&mut legal_moves);
+                        let scores = legal_moves
+                            .into_iter()
+                            .map(|legal_move| {
+                                let score = if legal_move == *mv {
+                                    f16::ONE
+                                } else {
+                                    f16::ZERO
+                                };
+                                (legal_move, score)
+                            })
+                            .collect();
+                        (game_result, scores)
+                    }
+                };
+                positions.push(position.clone());
+                value_results.push(value_result);
+                move_scores.push(scores);
+                position.do_move(*mv);
+            }
+
+            let total = labelled_positions.fetch_add(positions.len() as u64, Ordering::Relaxed);
+            if relabel_nodes.is_some()
+                && total / 10_000 != (total + positions.len() as u64) / 10_000
+            {
+                println!(
+                    "Relabelled {} positions in {:.1}s",
+                    total + positions.len() as u64,
+                    start_time.elapsed().as_secs_f32()
+                );
+            }
+            (positions, value_results, move_scores)
+        })
+        .collect();
+
+    let mut positions = vec![];
+    let mut value_results = vec![];
+    let mut move_scoress = vec![];
+    for (game_positions, game_value_results, game_move_scores) in labelled_games {
+        positions.extend(game_positions);
+        value_results.extend(game_value_results);
+        move_scoress.push(game_move_scores);
+    }
+    println!(
+        "Labelled {} positions from {} games in {:.1}s",
+        positions.len(),
+        games.len(),
+        start_time.elapsed().as_secs_f32()
+    );
+    (positions, value_results, move_scoress)
+}
+
+/// Tune value and policy parameters from human games. See `label_human_games` for how the positions are labelled.
+/// Relabelling searches use the initial parameters
+pub fn tune_from_human_games<const S: usize, const N: usize, const M: usize>(
+    games: &[Game<Position<S>>],
+    relabel_nodes: Option<u32>,
+    initial_value_params: &[f32; N],
+    initial_policy_params: &[f32; M],
+) -> Result<([f32; N], [f32; M]), DynError> {
+    let (positions, value_results, move_scoress) = label_human_games(
+        games,
+        relabel_nodes,
+        initial_value_params,
+        initial_policy_params,
+    );
+
+    tune_value_and_policy_with_value_results(
+        &positions,
+        &value_results,
+        games,
+        &move_scoress,
+        initial_value_params,
+        initial_policy_params,
+    )
+}
+
 pub fn games_and_move_scoress_from_file<const S: usize>(
     value_file_name: &str,
     policy_file_name: &str,
@@ -620,6 +856,14 @@ pub fn games_and_move_scoress_from_file<const S: usize>(
             move_scoress.truncate(12000);
             games.truncate(12000);
         }
+        7 => {
+            move_scoress.truncate(9000);
+            games.truncate(9000);
+        }
+        8 => {
+            move_scoress.truncate(7000);
+            games.truncate(7000);
+        }
         _ => (),
     }
 
@@ -735,6 +979,14 @@ pub fn positions_and_results_from_games<const S: usize>(
         .unzip()
 }
 
+fn game_result_to_f16(game_result: GameResult) -> f16 {
+    match game_result {
+        GameResult::WhiteWin => f16::ONE,
+        GameResult::Draw => f16::ONE / (f16::ONE + f16::ONE),
+        GameResult::BlackWin => f16::ZERO,
+    }
+}
+
 fn array_from_fn<F, T, const N: usize>(mut f: F) -> [T; N]
 where
     F: FnMut() -> T,